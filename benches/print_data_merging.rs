@@ -4,6 +4,9 @@ pub const ESC: u8 = 0x1B;
 pub const GS: u8 = 0x1D;
 pub const LF: u8 = 0x0A;
 
+/// Characters per line on 80mm paper, see `Profile::columns`
+pub const COLUMNS: usize = 48;
+
 pub const JUSTIFY_LEFT: &[u8; 3] = &[ESC, b'a', 0x0];
 pub const JUSTIFY_CENTER: &[u8; 3] = &[ESC, b'a', 0x1];
 pub const JUSTIFY_RIGHT: &[u8; 3] = &[ESC, b'a', 0x2];
@@ -26,7 +29,7 @@ fn human_extend_from_slice() -> Vec<u8> {
     out.extend_from_slice("Repo: angeloanan/notifi-printer".as_bytes()); // Send subtitle
     out.extend_from_slice(&[LF]); // Print
 
-    out.extend_from_slice([b'-'].repeat(64).as_slice()); // Send line
+    out.extend_from_slice([b'-'].repeat(COLUMNS).as_slice()); // Send line
     out.extend_from_slice(&[LF]); // Print
 
     out
@@ -46,7 +49,7 @@ fn minimize_extend_from_slice() -> Vec<u8> {
     out.extend_from_slice("Repo: angeloanan/notifi-printer".as_bytes()); // Send subtitle
     out.extend_from_slice(&[LF]); // Print
 
-    out.extend_from_slice([b'-'].repeat(64).as_slice()); // Send line
+    out.extend_from_slice([b'-'].repeat(COLUMNS).as_slice()); // Send line
     out.extend_from_slice(&[LF]); // Print
 
     out
@@ -101,8 +104,8 @@ fn vec_push() {
         .for_each(|c| out.push(*c));
     out.push(LF);
 
-    out.resize(out.len() + 64, b'-');
-    // for _ in 0..64 {
+    out.resize(out.len() + COLUMNS, b'-');
+    // for _ in 0..COLUMNS {
     //     out.push(b'-');
     // }
     out.push(LF);
@@ -0,0 +1,155 @@
+use std::{collections::BTreeMap, sync::LazyLock};
+
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::{printer::PrintData, state};
+
+/// Acks a service can fall behind on before missing some, see [`subscribe`]
+const CAPACITY: usize = 256;
+
+/// Events in flight this long are given up on & fetched again, e.g. ones held in a digest when
+/// the daemon crashed
+const IN_FLIGHT_TTL: TimeDelta = TimeDelta::days(1);
+
+/// Events of prints that made it to the printer or never will, see [`printed`] & [`released`]
+static PRINTED: LazyLock<broadcast::Sender<Acked>> =
+    LazyLock::new(|| broadcast::channel(CAPACITY).0);
+
+/// Event of a service that made it to the printer, or was dropped on the way
+#[derive(Debug, Clone)]
+pub struct Acked {
+    /// See [`PrintData::service`]
+    pub service: String,
+    pub event_id: String,
+    /// False if the print was dropped, e.g. by a full queue; Rate limited prints count as printed,
+    /// being reported on a receipt of their own
+    pub printed: bool,
+}
+
+/// Acks of every print from now on
+pub fn subscribe() -> broadcast::Receiver<Acked> {
+    PRINTED.subscribe()
+}
+
+/// Acks the events a print stands for to their services, once it's printed or turned out to be
+/// printed already
+pub fn printed(data: &PrintData) {
+    send(data, true);
+}
+
+/// Lets services stop waiting on the events of a print that was dropped, leaving them to be
+/// fetched again rather than acknowledged at the source
+pub fn released(data: &PrintData) {
+    send(data, false);
+}
+
+fn send(data: &PrintData, printed: bool) {
+    for (service, event_id) in events(data) {
+        // Only fails without subscribers, i.e. no service is waiting on acks
+        let _ = PRINTED.send(Acked {
+            service,
+            event_id,
+            printed,
+        });
+    }
+}
+
+/// Events a print stands for, as (service, event ID); Its own & those of the prints merged into
+/// it, e.g. by a digest
+pub fn events(data: &PrintData) -> Vec<(String, String)> {
+    let own = data.event_id.as_ref().map(|event_id| {
+        let service = data.service.clone().unwrap_or_default();
+        (service, event_id.clone())
+    });
+    own.into_iter()
+        .chain(data.merged_events.iter().cloned())
+        .collect()
+}
+
+/// Events a service sent to print that haven't printed yet, with what it needs to acknowledge
+/// them at the source once they have, e.g. the GitHub thread to mark read
+///
+/// Persisted in the state when `PRINT_JOURNAL` is set, as in flight prints are replayed from it
+/// after a restart; Without it they're lost, so they're forgotten to be fetched again.
+pub struct InFlight {
+    service: &'static str,
+    state_key: String,
+    persist: bool,
+    events: BTreeMap<String, Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    sent_at: DateTime<Local>,
+    value: String,
+}
+
+impl InFlight {
+    /// Events in flight of `service`, kept in the state under `state_key`, e.g. per account
+    pub fn load(service: &'static str, state_key: String) -> Self {
+        let persist = std::env::var_os("PRINT_JOURNAL").is_some();
+        let events = if persist {
+            state::get(&state_key, "in_flight")
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default()
+        } else {
+            // Cleared, so they're not skipped forever
+            state::set(&state_key, "in_flight", None);
+            BTreeMap::new()
+        };
+        Self {
+            service,
+            state_key,
+            persist,
+            events,
+        }
+    }
+
+    /// Whether the event is still waiting for the printer; Ones waiting for longer than a day
+    /// are given up on
+    pub fn contains(&mut self, event_id: &str) -> bool {
+        let now = Local::now();
+        let len = self.events.len();
+        self.events
+            .retain(|_, entry| now - entry.sent_at < IN_FLIGHT_TTL);
+        if self.events.len() != len {
+            warn!("Giving up on prints of {} stuck in flight", self.service);
+            self.save();
+        }
+        self.events.contains_key(event_id)
+    }
+
+    pub fn insert(&mut self, event_id: String, value: String) {
+        let sent_at = Local::now();
+        self.events.insert(event_id, Entry { sent_at, value });
+        self.save();
+    }
+
+    /// Stops waiting for an acked event of this service; Returns its value if it was in flight
+    /// & printed
+    pub fn acked(&mut self, acked: &Acked) -> Option<String> {
+        if acked.service != self.service {
+            return None;
+        }
+        let entry = self.events.remove(&acked.event_id)?;
+        self.save();
+        acked.printed.then_some(entry.value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    fn save(&self) {
+        if !self.persist {
+            return;
+        }
+        match serde_json::to_string(&self.events) {
+            Ok(json) => state::set(&self.state_key, "in_flight", Some(&json)),
+            Err(e) => warn!("Unable to save prints of {} in flight: {e}", self.service),
+        }
+    }
+}
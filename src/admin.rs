@@ -0,0 +1,81 @@
+use serde::Serialize;
+use tokio::sync::oneshot;
+use tracing::info;
+
+use crate::{
+    printer::{PrintData, Priority},
+    queue::PrintQueue,
+    schedule::QuietHours,
+    status,
+};
+
+/// Requests to the print loop, sent by the admin API & config reloads
+pub enum Command {
+    /// Holds queued prints until resumed; The print in progress is finished
+    Pause,
+    Resume,
+    /// Replies with the prints waiting for the printer, next one first
+    Pending(oneshot::Sender<Vec<Job>>),
+    /// Drops a queued print by ID; Replies whether it was still queued
+    Cancel(u64, oneshot::Sender<bool>),
+    SetQuietHours(Option<QuietHours>),
+}
+
+/// Summary of a queued print
+#[derive(Debug, Serialize)]
+pub struct Job {
+    pub id: u64,
+    pub title: String,
+    pub logo: Option<String>,
+    pub priority: Priority,
+}
+
+impl Job {
+    fn new(id: u64, data: &PrintData) -> Self {
+        Self {
+            id,
+            title: data.title.clone(),
+            logo: data.logo.clone(),
+            priority: data.priority,
+        }
+    }
+}
+
+impl Command {
+    pub fn apply(self, queue: &mut PrintQueue) {
+        match self {
+            Self::Pause => {
+                info!("Pausing prints");
+                queue.set_paused(true);
+                status::set_paused(true);
+            }
+            Self::Resume => {
+                info!("Resuming prints");
+                queue.set_paused(false);
+                status::set_paused(false);
+            }
+            Self::Pending(reply) => {
+                let jobs = queue.jobs().map(|(id, data)| Job::new(id, data)).collect();
+                let _ = reply.send(jobs);
+            }
+            Self::Cancel(id, reply) => {
+                let cancelled = queue.cancel(id);
+                if cancelled {
+                    info!("Cancelled print {id}");
+                }
+                let _ = reply.send(cancelled);
+            }
+            Self::SetQuietHours(quiet_hours) => queue.set_quiet_hours(quiet_hours),
+        }
+    }
+}
+
+/// Compares tokens in constant time, so they can't be guessed byte by byte
+pub fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
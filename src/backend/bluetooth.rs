@@ -0,0 +1,85 @@
+use bluer::{
+    agent::Agent,
+    rfcomm::{SocketAddr, Stream},
+    Address,
+};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, instrument};
+
+use super::PrinterBackend;
+
+/// Portable printers exposing a Bluetooth Serial Port Profile (RFCOMM) channel
+pub struct BluetoothBackend {
+    addr: SocketAddr,
+    stream: Stream,
+}
+
+impl BluetoothBackend {
+    /// Pairs with the printer through BlueZ if it isn't already, answering PIN requests with
+    /// `pin`, then connects to its RFCOMM `channel`
+    #[instrument(skip(pin))]
+    pub async fn open(address: Address, channel: u8, pin: Option<String>) -> bluer::Result<Self> {
+        let session = bluer::Session::new().await?;
+        let adapter = session.default_adapter().await?;
+        adapter.set_powered(true).await?;
+
+        let device = adapter.device(address)?;
+        if !device.is_paired().await? {
+            // Legacy printers ask for a fixed PIN, usually 0000 or 1234
+            let _agent = match pin {
+                Some(pin) => Some(
+                    session
+                        .register_agent(Agent {
+                            request_pin_code: Some(Box::new(move |_| {
+                                let pin = pin.clone();
+                                Box::pin(async move { Ok(pin) })
+                            })),
+                            ..Default::default()
+                        })
+                        .await?,
+                ),
+                None => None,
+            };
+            info!("Pairing with Bluetooth printer {address}...");
+            device.pair().await?;
+        }
+        device.set_trusted(true).await?;
+
+        let addr = SocketAddr::new(address, channel);
+        let stream = Stream::connect(addr).await?;
+        debug!("Opened RFCOMM channel {channel} @ {address}");
+
+        Ok(Self { addr, stream })
+    }
+
+    /// Opens `PRINTER_BT_ADDR` on `PRINTER_BT_CHANNEL` (default 1), pairing with
+    /// `PRINTER_BT_PIN` when set
+    pub async fn from_env() -> Self {
+        let address = std::env::var("PRINTER_BT_ADDR")
+            .expect("Env `PRINTER_BT_ADDR` not set!")
+            .parse::<Address>()
+            .expect("Invalid PRINTER_BT_ADDR! Expected an address, e.g. 86:67:7A:12:34:56");
+        let channel = std::env::var("PRINTER_BT_CHANNEL").map_or(1, |c| {
+            c.parse()
+                .expect("Invalid PRINTER_BT_CHANNEL! Expected a number")
+        });
+        let pin = std::env::var("PRINTER_BT_PIN").ok();
+
+        Self::open(address, channel, pin)
+            .await
+            .unwrap_or_else(|e| panic!("Unable to open Bluetooth printer {address}: {e}"))
+    }
+}
+
+impl PrinterBackend for BluetoothBackend {
+    async fn write_job(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await
+    }
+
+    /// Retried until the printer is back in range
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        self.stream = Stream::connect(self.addr).await?;
+        Ok(())
+    }
+}
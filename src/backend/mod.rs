@@ -0,0 +1,17 @@
+use std::future::Future;
+
+pub mod bluetooth;
+pub mod png;
+pub mod serial;
+pub mod tcp;
+pub mod terminal;
+pub mod usb;
+
+/// Transport the rendered printer commands are sent through
+pub trait PrinterBackend: Send + 'static {
+    /// Writes one complete print job, including the trailing feed & cut
+    fn write_job(&mut self, bytes: &[u8]) -> impl Future<Output = std::io::Result<()>> + Send;
+
+    /// Re-establishes the connection after a failed write; Retried with backoff by the caller
+    fn reconnect(&mut self) -> impl Future<Output = std::io::Result<()>> + Send;
+}
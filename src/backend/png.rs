@@ -0,0 +1,331 @@
+use std::{io::Cursor, path::PathBuf};
+
+use chrono::Local;
+use font8x8::legacy::{BASIC_LEGACY, BLOCK_LEGACY, BOX_LEGACY, LATIN_LEGACY};
+use image::{GrayImage, ImageFormat, ImageResult};
+
+use super::PrinterBackend;
+use crate::{
+    decode::{self, Block, Content, Segment},
+    escpos::{Justify, Style},
+    profile::{Cut, Profile},
+};
+
+/// Dots the printer's fonts are tall, the regular & the smaller one
+const CHAR_HEIGHT: u32 = 24;
+const SMALL_CHAR_HEIGHT: u32 = 17;
+/// Dots fed between lines, on top of the tallest character
+const LINE_GAP: u32 = 6;
+/// Dots fed per blank line
+const LINE_HEIGHT: u32 = CHAR_HEIGHT + LINE_GAP;
+
+const WHITE: u8 = 0xFF;
+const BLACK: u8 = 0x00;
+
+/// Virtual printer, rendering receipts to a PNG file each in `PRINTER_PNG_DIR` instead of
+/// printing them; See [`render`]
+pub struct PngBackend {
+    profile: Profile,
+    dir: PathBuf,
+}
+
+impl PngBackend {
+    /// Renders receipts as the profile's printer would print them; Needs the profile to be
+    /// [`Profile::for_preview`]
+    pub fn from_env(profile: &Profile) -> Self {
+        let dir = std::env::var("PRINTER_PNG_DIR").expect("Env `PRINTER_PNG_DIR` not set!");
+        Self {
+            profile: profile.clone(),
+            dir: dir.into(),
+        }
+    }
+}
+
+impl PrinterBackend for PngBackend {
+    async fn write_job(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let png = render_png(bytes, &self.profile).map_err(std::io::Error::other)?;
+        // Named by when it was printed, so they list in order
+        let name = Local::now().format("%Y%m%d-%H%M%S%.6f").to_string();
+        let path = self.dir.join(format!("{name}.png"));
+        // Written under another name first, so watchers never pick up half an image
+        let tmp = self.dir.join(format!(".{name}.tmp"));
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&tmp, png).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders printer commands to the receipt they print, a dot per pixel, e.g. to preview a print
+/// or compare layouts before & after a change; Needs a [`Profile::for_preview`]
+///
+/// Text is drawn with an 8x8 bitmap font stretched to the printer's character size, so it
+/// only approximates the printer's own font. QR codes & barcodes are shown as their data.
+pub fn render(bytes: &[u8], profile: &Profile) -> GrayImage {
+    let mut canvas = Canvas::new(profile);
+    for block in decode::decode(bytes, profile.code_page) {
+        match block {
+            Block::Line { justify, segments } => canvas.line(justify, &segments),
+            Block::Feed(n) => canvas.feed(u32::from(n) * LINE_HEIGHT),
+            Block::Raster {
+                justify,
+                width,
+                height,
+                data,
+            } => canvas.raster(justify, width, height, &data),
+            Block::QrCode { justify, data } => {
+                canvas.label(
+                    justify,
+                    &format!("[QR: {}]", String::from_utf8_lossy(&data)),
+                );
+            }
+            Block::Barcode { justify, data } => {
+                let label = format!("[Barcode: {}]", String::from_utf8_lossy(&data));
+                canvas.label(justify, &label);
+            }
+            Block::Cut(cut) => canvas.cut(cut),
+            // Not on paper
+            Block::Beep | Block::KickDrawer => {}
+        }
+    }
+    canvas.into_image()
+}
+
+/// [`render`]s printer commands into a PNG
+pub fn render_png(bytes: &[u8], profile: &Profile) -> ImageResult<Vec<u8>> {
+    let mut png = Vec::new();
+    render(bytes, profile).write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Paper being printed on, growing as it's fed
+struct Canvas {
+    width: u32,
+    /// Dots wide the characters of the regular & the smaller font are
+    char_width: u32,
+    small_char_width: u32,
+    /// Luma of each dot, row by row
+    pixels: Vec<u8>,
+    /// Row printed at next
+    y: u32,
+}
+
+impl Canvas {
+    fn new(profile: &Profile) -> Self {
+        let width = profile.dots.max(1);
+        let columns = |columns: usize| u32::try_from(columns).unwrap_or(u32::MAX).max(1);
+        Self {
+            width,
+            char_width: (width / columns(profile.columns)).max(1),
+            small_char_width: (width / columns(profile.small_columns)).max(1),
+            pixels: Vec::new(),
+            y: 0,
+        }
+    }
+
+    /// Feeds the paper by `height` dots
+    fn feed(&mut self, height: u32) {
+        self.y += height;
+        self.pixels.resize((self.y * self.width) as usize, WHITE);
+    }
+
+    fn set(&mut self, x: u32, y: u32, luma: u8) {
+        if x < self.width && y < self.y {
+            self.pixels[(y * self.width + x) as usize] = luma;
+        }
+    }
+
+    /// Column a `width` dots wide block starts at
+    const fn left(&self, justify: Justify, width: u32) -> u32 {
+        let free = self.width.saturating_sub(width);
+        match justify {
+            Justify::Left => 0,
+            Justify::Center => free / 2,
+            Justify::Right => free,
+        }
+    }
+
+    /// Dots a segment takes up, across & down
+    fn size(&self, segment: &Segment) -> (u32, u32) {
+        let (char_width, char_height) = if segment.small {
+            (self.small_char_width, SMALL_CHAR_HEIGHT)
+        } else {
+            (self.char_width, CHAR_HEIGHT)
+        };
+        let (width, height) = match &segment.content {
+            Content::Text(text) => {
+                let chars = u32::try_from(text.chars().count()).unwrap_or(u32::MAX);
+                (chars * char_width, char_height)
+            }
+            Content::Glyph { width, .. } => (u32::from(*width), CHAR_HEIGHT),
+        };
+        (
+            width * u32::from(segment.size.0),
+            height * u32::from(segment.size.1),
+        )
+    }
+
+    fn line(&mut self, justify: Justify, segments: &[Segment]) {
+        let sizes = segments
+            .iter()
+            .map(|segment| self.size(segment))
+            .collect::<Vec<_>>();
+        let width = sizes.iter().map(|(width, _)| width).sum();
+        let height = sizes
+            .iter()
+            .map(|(_, height)| *height)
+            .max()
+            .unwrap_or(CHAR_HEIGHT);
+
+        let top = self.y;
+        self.feed(height + LINE_GAP);
+        let mut x = self.left(justify, width);
+        for (segment, (width, segment_height)) in segments.iter().zip(sizes) {
+            // Segments of a line share their baseline
+            let y = top + height - segment_height;
+            self.segment(segment, x, y, width, segment_height);
+            x += width;
+        }
+    }
+
+    /// Draws a segment into the `width` by `height` dots at (x, y)
+    fn segment(&mut self, segment: &Segment, x: u32, y: u32, width: u32, height: u32) {
+        let Style {
+            bold,
+            underline,
+            invert,
+        } = segment.style;
+        let (ink, paper) = if invert {
+            (WHITE, BLACK)
+        } else {
+            (BLACK, WHITE)
+        };
+        if invert {
+            for dy in 0..height {
+                for dx in 0..width {
+                    self.set(x + dx, y + dy, paper);
+                }
+            }
+        }
+
+        match &segment.content {
+            Content::Text(text) => {
+                let chars = u32::try_from(text.chars().count())
+                    .unwrap_or(u32::MAX)
+                    .max(1);
+                let char_width = width / chars;
+                for (i, c) in (0..).zip(text.chars()) {
+                    let bitmap = bitmap(c);
+                    let left = x + i * char_width;
+                    for dy in 0..height {
+                        let row = bitmap[(dy * 8 / height) as usize];
+                        for dx in 0..char_width {
+                            if row & (1 << (dx * 8 / char_width)) != 0 {
+                                self.set(left + dx, y + dy, ink);
+                                // Double strike, as the printer does
+                                if bold {
+                                    self.set(left + dx + 1, y + dy, ink);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Content::Glyph {
+                width: columns,
+                data,
+            } => {
+                let columns = u32::from(*columns).max(1);
+                for dy in 0..height {
+                    let dot = dy * CHAR_HEIGHT / height;
+                    for dx in 0..width {
+                        let column = (dx * columns / width) as usize;
+                        let byte = data.get(column * 3 + (dot / 8) as usize);
+                        if byte.is_some_and(|byte| byte & (0x80 >> (dot % 8)) != 0) {
+                            self.set(x + dx, y + dy, ink);
+                        }
+                    }
+                }
+            }
+        }
+
+        if underline {
+            for dy in height.saturating_sub(2)..height {
+                for dx in 0..width {
+                    self.set(x + dx, y + dy, ink);
+                }
+            }
+        }
+    }
+
+    /// Image of `width` bytes by `height` dots, rows MSB first
+    fn raster(&mut self, justify: Justify, width: u16, height: u16, data: &[u8]) {
+        let (width, height) = (u32::from(width) * 8, u32::from(height));
+        let top = self.y;
+        self.feed(height);
+        let left = self.left(justify, width);
+        for dy in 0..height {
+            for dx in 0..width {
+                let byte = data.get(((dy * width + dx) / 8) as usize);
+                if byte.is_some_and(|byte| byte & (0x80 >> (dx % 8)) != 0) {
+                    self.set(left + dx, top + dy, BLACK);
+                }
+            }
+        }
+    }
+
+    /// Line of plain text standing in for something the font can't draw
+    fn label(&mut self, justify: Justify, text: &str) {
+        let columns = (self.width / self.char_width) as usize;
+        let segment = Segment {
+            content: Content::Text(text.chars().take(columns).collect()),
+            style: Style::default(),
+            size: (1, 1),
+            small: false,
+        };
+        self.line(justify, &[segment]);
+    }
+
+    /// Dashed line where the paper is cut, sparser for partial cuts
+    fn cut(&mut self, cut: Cut) {
+        let (dash, gap) = if cut == Cut::Partial { (4, 8) } else { (8, 4) };
+        self.feed(LINE_GAP);
+        let y = self.y;
+        self.feed(1);
+        for x in (0..self.width).filter(|x| x % (dash + gap) < dash) {
+            self.set(x, y, BLACK);
+        }
+        self.feed(LINE_GAP);
+    }
+
+    fn into_image(mut self) -> GrayImage {
+        // Images can't be empty
+        if self.y == 0 {
+            self.feed(1);
+        }
+        GrayImage::from_raw(self.width, self.y, self.pixels)
+            .expect("Canvas should be as big as its pixels")
+    }
+}
+
+/// 8x8 bitmap of a character, rows top to bottom, LSB first; Characters missing from the font
+/// are transliterated to ASCII, or drawn as `?`
+fn bitmap(c: char) -> [u8; 8] {
+    let code = c as usize;
+    match code {
+        0x00..=0x7F => BASIC_LEGACY[code],
+        0xA0..=0xFF => LATIN_LEGACY[code - 0xA0],
+        0x2500..=0x257F => BOX_LEGACY[code - 0x2500],
+        0x2580..=0x259F => BLOCK_LEGACY[code - 0x2580],
+        _ => {
+            let ascii = deunicode::deunicode_char(c)
+                .and_then(|s| s.chars().next())
+                .filter(char::is_ascii)
+                .unwrap_or('?');
+            BASIC_LEGACY[ascii as usize]
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use tokio::io::AsyncWriteExt;
+use tokio_serial::{FlowControl, SerialPortBuilderExt, SerialStream};
+use tracing::{debug, instrument};
+
+use super::PrinterBackend;
+
+/// Printers connected through RS-232 or a USB-serial adapter
+pub struct SerialBackend {
+    path: String,
+    baud_rate: u32,
+    flow_control: FlowControl,
+    stream: SerialStream,
+}
+
+impl SerialBackend {
+    #[instrument]
+    pub fn open(
+        path: &str,
+        baud_rate: u32,
+        flow_control: FlowControl,
+    ) -> tokio_serial::Result<Self> {
+        let stream = tokio_serial::new(path, baud_rate)
+            .flow_control(flow_control)
+            .open_native_async()?;
+        debug!("Opened serial port {path} @ {baud_rate} baud");
+
+        Ok(Self {
+            path: path.to_string(),
+            baud_rate,
+            flow_control,
+            stream,
+        })
+    }
+
+    /// Opens `PRINTER_SERIAL_PATH`, with `PRINTER_SERIAL_BAUD` (default 9600) and
+    /// `PRINTER_SERIAL_FLOW_CONTROL` (`none` by default, `software` or `hardware`)
+    pub fn from_env() -> Self {
+        let path =
+            std::env::var("PRINTER_SERIAL_PATH").expect("Env `PRINTER_SERIAL_PATH` not set!");
+        let baud_rate = std::env::var("PRINTER_SERIAL_BAUD").map_or(9600, |b| {
+            b.parse()
+                .expect("Invalid PRINTER_SERIAL_BAUD! Expected a number")
+        });
+        let flow_control = match std::env::var("PRINTER_SERIAL_FLOW_CONTROL").as_deref() {
+            Err(_) | Ok("none") => FlowControl::None,
+            Ok("software") => FlowControl::Software,
+            Ok("hardware") => FlowControl::Hardware,
+            Ok(other) => panic!(
+                "Invalid PRINTER_SERIAL_FLOW_CONTROL `{other}`! Expected none, software or hardware"
+            ),
+        };
+
+        Self::open(&path, baud_rate, flow_control)
+            .unwrap_or_else(|e| panic!("Unable to open serial port {path}: {e}"))
+    }
+}
+
+impl PrinterBackend for SerialBackend {
+    async fn write_job(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        // USB-serial adapters come back under the same path once replugged; The old port is
+        // still held exclusively until replaced though
+        self.stream.set_exclusive(false).ok();
+        *self = Self::open(&self.path, self.baud_rate, self.flow_control)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+use tracing::{debug, instrument};
+
+use super::PrinterBackend;
+
+/// Network printers listening on a raw socket, usually port 9100
+pub struct TcpBackend {
+    addr: String,
+    stream: TcpStream,
+}
+
+impl TcpBackend {
+    #[instrument]
+    pub async fn connect(addr: &str) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        debug!("Opened a TCP Stream @ {addr}");
+
+        Ok(Self {
+            addr: addr.to_string(),
+            stream,
+        })
+    }
+
+    /// Connects to `PRINTER_ADDR`
+    pub async fn from_env() -> Self {
+        let addr = std::env::var("PRINTER_ADDR").expect("Env `PRINTER_ADDR` not set!");
+        Self::connect(&addr)
+            .await
+            .unwrap_or_else(|e| panic!("Unable to connect to {addr}: {e}"))
+    }
+}
+
+impl PrinterBackend for TcpBackend {
+    async fn write_job(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        self.stream = TcpStream::connect(&self.addr).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::{fmt::Write, io::IsTerminal};
+
+use tokio::io::AsyncWriteExt;
+
+use super::PrinterBackend;
+use crate::{
+    decode::{self, Block, Content, Segment},
+    escpos::{Justify, Style},
+    profile::{Cut, Profile},
+};
+
+/// Prints receipts to stdout as text instead of on a printer, e.g. to work on a service's
+/// formatting without one; See `--dry-run`
+///
+/// Justification, width & styles are laid out as text, the latter as ANSI escapes when stdout
+/// is a terminal. Images are drawn with half blocks, while QR codes & barcodes only show their
+/// data.
+pub struct TerminalBackend {
+    profile: Profile,
+    ansi: bool,
+}
+
+impl TerminalBackend {
+    /// Lays out receipts as the profile's printer would; Needs the profile to be
+    /// [`Profile::for_preview`]
+    pub fn new(profile: &Profile) -> Self {
+        Self {
+            profile: profile.clone(),
+            ansi: std::io::stdout().is_terminal(),
+        }
+    }
+
+    fn render(&self, blocks: &[Block]) -> String {
+        let columns = self.profile.columns;
+        let mut out = String::new();
+        for block in blocks {
+            match block {
+                Block::Line { justify, segments } => {
+                    let small = !segments.is_empty() && segments.iter().all(|s| s.small);
+                    let width = if small {
+                        self.profile.small_columns
+                    } else {
+                        columns
+                    };
+                    let used = segments.iter().map(segment_width).sum();
+                    out.push_str(&padding(*justify, width, used));
+                    for segment in segments {
+                        self.segment(segment, &mut out);
+                    }
+                    out.push('\n');
+                }
+                Block::Feed(n) => out.push_str(&"\n".repeat(usize::from(*n))),
+                Block::Raster {
+                    justify,
+                    width,
+                    height,
+                    data,
+                } => {
+                    for line in self.raster(*width, *height, data) {
+                        out.push_str(&padding(*justify, columns, line.chars().count()));
+                        out.push_str(&line);
+                        out.push('\n');
+                    }
+                }
+                Block::QrCode { justify, data } => {
+                    let line = format!("[QR: {}]", String::from_utf8_lossy(data));
+                    out.push_str(&padding(*justify, columns, line.chars().count()));
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+                Block::Barcode { justify, data } => {
+                    let line = format!("[Barcode: {}]", String::from_utf8_lossy(data));
+                    out.push_str(&padding(*justify, columns, line.chars().count()));
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+                Block::Cut(cut) => {
+                    let dash = if *cut == Cut::Partial { "- " } else { "-" };
+                    let line = format!("✂{}", dash.repeat(columns / dash.len()));
+                    out.push_str(&line.chars().take(columns).collect::<String>());
+                    out.push('\n');
+                }
+                Block::Beep => out.push_str("(beep)\n"),
+                Block::KickDrawer => out.push_str("(drawer kicked)\n"),
+            }
+        }
+        out
+    }
+
+    fn segment(&self, segment: &Segment, out: &mut String) {
+        let Style {
+            bold,
+            underline,
+            invert,
+        } = segment.style;
+        let codes = [(bold, "1"), (underline, "4"), (invert, "7")]
+            .into_iter()
+            .filter_map(|(on, code)| on.then_some(code))
+            .collect::<Vec<_>>();
+        let styled = self.ansi && !codes.is_empty();
+        if styled {
+            let _ = write!(out, "\x1b[{}m", codes.join(";"));
+        }
+
+        // Wider text is spaced out, as terminals can't widen characters
+        let gap = " ".repeat(usize::from(segment.size.0) - 1);
+        match &segment.content {
+            Content::Text(text) => {
+                for c in text.chars() {
+                    out.push(c);
+                    out.push_str(&gap);
+                }
+            }
+            Content::Glyph { .. } => {
+                out.push('▣');
+                out.push_str(&gap);
+            }
+        }
+
+        if styled {
+            out.push_str("\x1b[0m");
+        }
+    }
+
+    /// Image drawn with half blocks, a character cell per `dots / columns` dots across & twice
+    /// that down, as terminal cells are about twice as tall as they're wide
+    fn raster(&self, width: u16, height: u16, data: &[u8]) -> Vec<String> {
+        let (width, height) = (usize::from(width) * 8, usize::from(height));
+        let dots = usize::try_from(self.profile.dots).unwrap_or_default();
+        let cell = (dots / self.profile.columns.max(1)).max(1);
+        let row_bytes = width / 8;
+        // Whether most dots of the cell-sized square at (x, y) are black
+        let dark = |x: usize, y: usize| {
+            let (mut black, mut total) = (0, 0);
+            for dy in y..(y + cell).min(height) {
+                for dx in x..(x + cell).min(width) {
+                    total += 1;
+                    if data
+                        .get(dy * row_bytes + dx / 8)
+                        .copied()
+                        .unwrap_or_default()
+                        & (0x80 >> (dx % 8))
+                        != 0
+                    {
+                        black += 1;
+                    }
+                }
+            }
+            black * 2 > total
+        };
+
+        (0..height)
+            .step_by(cell * 2)
+            .map(|y| {
+                (0..width)
+                    .step_by(cell)
+                    .map(|x| match (dark(x, y), dark(x, y + cell)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+}
+
+impl PrinterBackend for TerminalBackend {
+    async fn write_job(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let blocks = decode::decode(bytes, self.profile.code_page);
+        let mut stdout = tokio::io::stdout();
+        stdout.write_all(self.render(&blocks).as_bytes()).await?;
+        stdout.flush().await
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Columns a segment takes up
+fn segment_width(segment: &Segment) -> usize {
+    let chars = match &segment.content {
+        Content::Text(text) => text.chars().count(),
+        Content::Glyph { .. } => 1,
+    };
+    chars * usize::from(segment.size.0)
+}
+
+/// Spaces justifying `used` columns within `width`
+fn padding(justify: Justify, width: usize, used: usize) -> String {
+    let free = width.saturating_sub(used);
+    let n = match justify {
+        Justify::Left => 0,
+        Justify::Center => free / 2,
+        Justify::Right => free,
+    };
+    " ".repeat(n)
+}
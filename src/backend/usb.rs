@@ -0,0 +1,98 @@
+use std::{sync::Arc, time::Duration};
+
+use rusb::{DeviceHandle, Direction, GlobalContext, TransferType};
+use tracing::{debug, instrument};
+
+use super::PrinterBackend;
+
+/// How long a single bulk transfer may take before giving up, e.g. when the paper ran out
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// USB printer class devices, written to through their bulk OUT endpoint
+pub struct UsbBackend {
+    vendor_id: u16,
+    product_id: u16,
+    handle: Arc<DeviceHandle<GlobalContext>>,
+    interface: u8,
+    endpoint: u8,
+}
+
+impl UsbBackend {
+    /// Opens the first device matching the vendor & product ID and claims its printer interface
+    #[instrument]
+    pub fn open(vendor_id: u16, product_id: u16) -> rusb::Result<Self> {
+        let handle =
+            rusb::open_device_with_vid_pid(vendor_id, product_id).ok_or(rusb::Error::NotFound)?;
+
+        let config = handle.device().active_config_descriptor()?;
+        let (interface, endpoint) = config
+            .interfaces()
+            .flat_map(|i| i.descriptors())
+            .find_map(|descriptor| {
+                descriptor
+                    .endpoint_descriptors()
+                    .find(|e| {
+                        e.direction() == Direction::Out && e.transfer_type() == TransferType::Bulk
+                    })
+                    .map(|e| (descriptor.interface_number(), e.address()))
+            })
+            .ok_or(rusb::Error::NotFound)?;
+
+        // Linux binds `usblp` to printers; Not supported on every platform, hence ignored
+        handle.set_auto_detach_kernel_driver(true).ok();
+        handle.claim_interface(interface)?;
+        debug!("Claimed USB interface {interface}, bulk OUT endpoint {endpoint:#04x}");
+
+        Ok(Self {
+            vendor_id,
+            product_id,
+            handle: Arc::new(handle),
+            interface,
+            endpoint,
+        })
+    }
+
+    /// Opens the device identified by the hex `PRINTER_USB_VENDOR_ID` & `PRINTER_USB_PRODUCT_ID`
+    pub fn from_env() -> Self {
+        let usb_id = |key: &str| {
+            let value = std::env::var(key).unwrap_or_else(|_| panic!("Env `{key}` not set!"));
+            u16::from_str_radix(value.trim().trim_start_matches("0x"), 16)
+                .unwrap_or_else(|_| panic!("Invalid {key}! Expected a hex ID, e.g. 04b8"))
+        };
+        let (vendor_id, product_id) = (
+            usb_id("PRINTER_USB_VENDOR_ID"),
+            usb_id("PRINTER_USB_PRODUCT_ID"),
+        );
+
+        Self::open(vendor_id, product_id).unwrap_or_else(|e| {
+            panic!("Unable to open USB printer {vendor_id:04x}:{product_id:04x}: {e}")
+        })
+    }
+}
+
+impl PrinterBackend for UsbBackend {
+    async fn write_job(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let handle = Arc::clone(&self.handle);
+        let endpoint = self.endpoint;
+        let bytes = bytes.to_vec();
+
+        // libusb transfers are blocking
+        tokio::task::spawn_blocking(move || {
+            let mut written = 0;
+            while written < bytes.len() {
+                written += handle
+                    .write_bulk(endpoint, &bytes[written..], WRITE_TIMEOUT)
+                    .map_err(std::io::Error::other)?;
+            }
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        // Fails when the printer was unplugged, the handle is stale by then anyway
+        self.handle.release_interface(self.interface).ok();
+        *self = Self::open(self.vendor_id, self.product_id).map_err(std::io::Error::other)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use chrono::{Local, TimeDelta};
+use clap::{Parser, Subcommand};
+use reqwest::StatusCode;
+
+use crate::{
+    history::{self, History},
+    http, secrets,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Prints notifications on a thermal receipt printer")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Print receipts to the terminal instead of the printer, e.g. to work on a service's
+    /// formatting without one; Same as `PRINTER_TRANSPORT=terminal`
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// One-shot commands, mostly talking to an already running daemon through its HTTP server
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print an ad-hoc note, e.g. a shopping list or a message for your housemates; Needs
+    /// `ADMIN_TOKEN`
+    Note {
+        /// Text of the note
+        text: String,
+    },
+    /// Print a test page showing off every font, size, style & code, e.g. to check a new
+    /// printer; Needs `ADMIN_TOKEN`
+    TestPage,
+    /// Print notifications from the history again, e.g. when the paper jammed or faded; Needs
+    /// `ADMIN_TOKEN`
+    Reprint {
+        /// IDs of the prints, as shown by `history`
+        ids: Vec<i64>,
+        /// Reprint the latest prints, this many of them
+        #[arg(long, required_unless_present = "ids")]
+        last: Option<usize>,
+    },
+    /// Search the history of printed notifications, latest first; Needs `HISTORY_DB`
+    History {
+        /// Text to look for in the title, subtitle or body
+        text: Option<String>,
+        /// Only show prints of this service, e.g. `github`
+        #[arg(long)]
+        service: Option<String>,
+        /// Only show prints from the last while, e.g. `30m`, `12h`, `2d` or `1w`
+        #[arg(long, value_parser = parse_age)]
+        since: Option<TimeDelta>,
+        /// Most prints to show
+        #[arg(long, default_value_t = history::DEFAULT_LIMIT)]
+        limit: usize,
+    },
+    /// Store a secret in the OS keyring, read from stdin, for when its env var isn't set
+    #[cfg(feature = "keyring")]
+    Secret {
+        /// Env var the secret stands in for, e.g. `GITHUB_PAT`
+        var: String,
+    },
+}
+
+/// Runs a one-shot command against the daemon at `HTTP_ADDR`, or on the history at `HISTORY_DB`
+pub async fn run(command: Command) {
+    let addr = || std::env::var("HTTP_ADDR").expect("Env `HTTP_ADDR` not set!");
+    let client = http::client();
+
+    match command {
+        Command::Note { text } => {
+            let token = secrets::var("ADMIN_TOKEN").expect("Env `ADMIN_TOKEN` not set!");
+            let res = client
+                .post(format!("http://{}/note", addr()))
+                .bearer_auth(token)
+                .body(text)
+                .send()
+                .await
+                .expect("Unable to reach notifi-printer daemon; Is it running?");
+
+            if res.status().is_success() {
+                println!("Note queued for printing");
+            } else {
+                eprintln!("Daemon refused the note: {}", res.status());
+            }
+        }
+        Command::TestPage => {
+            let token = secrets::var("ADMIN_TOKEN").expect("Env `ADMIN_TOKEN` not set!");
+            let res = client
+                .post(format!("http://{}/test-page", addr()))
+                .bearer_auth(token)
+                .send()
+                .await
+                .expect("Unable to reach notifi-printer daemon; Is it running?");
+
+            if res.status().is_success() {
+                println!("Test page queued for printing");
+            } else {
+                eprintln!("Daemon refused the test page: {}", res.status());
+            }
+        }
+        Command::Reprint { ids, last } => {
+            let token = secrets::var("ADMIN_TOKEN").expect("Env `ADMIN_TOKEN` not set!");
+            let res = client
+                .post(format!("http://{}/admin/reprint", addr()))
+                .bearer_auth(token)
+                .json(&serde_json::json!({ "last": last.unwrap_or(0), "ids": ids }))
+                .send()
+                .await
+                .expect("Unable to reach notifi-printer daemon; Is it running?");
+
+            match res.status() {
+                StatusCode::NOT_FOUND => eprintln!("Nothing to reprint; Is `HISTORY_DB` set?"),
+                status if status.is_success() => println!("Reprints queued for printing"),
+                status => eprintln!("Daemon refused the reprint: {status}"),
+            }
+        }
+        Command::History {
+            text,
+            service,
+            since,
+            limit,
+        } => search_history(&history::Query {
+            service,
+            since: since.map(|age| Local::now() - age),
+            text,
+            limit,
+        }),
+        #[cfg(feature = "keyring")]
+        Command::Secret { var } => {
+            let mut value = String::new();
+            std::io::stdin()
+                .read_line(&mut value)
+                .expect("Unable to read the secret from stdin");
+            match secrets::store(&var, value.trim_end_matches(['\r', '\n'])) {
+                Ok(()) => println!("Stored {var} in the keyring"),
+                Err(e) => eprintln!("Unable to store {var} in the keyring: {e}"),
+            }
+        }
+    }
+}
+
+/// Prints the matching entries of the history at `HISTORY_DB`
+fn search_history(query: &history::Query) {
+    let path = std::env::var("HISTORY_DB").expect("Env `HISTORY_DB` not set!");
+    let entries = History::open(&path)
+        .and_then(|history| history.search(query))
+        .unwrap_or_else(|e| panic!("Unable to read history {path}: {e}"));
+
+    for entry in entries {
+        println!(
+            "#{} {} [{}] {}",
+            entry.id,
+            entry.printed_at.format("%Y-%m-%d %H:%M"),
+            entry.service.as_deref().unwrap_or("-"),
+            entry.title
+        );
+        for text in [entry.subtitle, entry.body].into_iter().flatten() {
+            for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                println!("    {line}");
+            }
+        }
+    }
+}
+
+/// Parses an age like `30m`, `12h`, `2d` or `1w`
+fn parse_age(s: &str) -> Result<TimeDelta, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let n = n
+        .parse()
+        .map_err(|_| format!("Expected an age like 2d, got `{s}`"))?;
+    let age = match unit {
+        "s" => TimeDelta::try_seconds(n),
+        "m" => TimeDelta::try_minutes(n),
+        "h" | "" => TimeDelta::try_hours(n),
+        "d" => TimeDelta::try_days(n),
+        "w" => TimeDelta::try_weeks(n),
+        other => return Err(format!("Unknown unit `{other}`; expected s, m, h, d or w")),
+    };
+    age.ok_or_else(|| format!("Age `{s}` is too long"))
+}
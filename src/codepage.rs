@@ -0,0 +1,286 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::protocol::Protocol;
+
+/// Character table the printer reads bytes above 0x7F from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodePage {
+    /// US; Box drawing & block elements, used for bar charts
+    #[default]
+    Pc437,
+    /// Western European
+    Pc850,
+    /// Central European
+    Pc852,
+    /// Western European with the euro sign
+    Pc858,
+    /// Cyrillic
+    Pc866,
+    /// Windows Western European
+    Wpc1252,
+}
+
+impl FromStr for CodePage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "pc437" => Ok(Self::Pc437),
+            "pc850" => Ok(Self::Pc850),
+            "pc852" => Ok(Self::Pc852),
+            "pc858" => Ok(Self::Pc858),
+            "pc866" => Ok(Self::Pc866),
+            "wpc1252" => Ok(Self::Wpc1252),
+            other => Err(format!(
+                "Unknown code page `{other}`; expected pc437, pc850, pc852, pc858, pc866 or wpc1252"
+            )),
+        }
+    }
+}
+
+impl CodePage {
+    const ALL: [Self; 6] = [
+        Self::Pc437,
+        Self::Pc850,
+        Self::Pc852,
+        Self::Pc858,
+        Self::Pc866,
+        Self::Wpc1252,
+    ];
+
+    /// Code page selected by table number `n`, see [`Self::number`]
+    pub fn from_number(n: u8, protocol: Protocol) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|code_page| code_page.number(protocol) == n)
+    }
+
+    /// Table number to select this code page with, `ESC t n` or `ESC GS t n` on Star
+    pub const fn number(self, protocol: Protocol) -> u8 {
+        match protocol {
+            Protocol::EscPos => match self {
+                Self::Pc437 => 0,
+                Self::Pc850 => 2,
+                Self::Wpc1252 => 16,
+                Self::Pc866 => 17,
+                Self::Pc852 => 18,
+                Self::Pc858 => 19,
+            },
+            Protocol::StarLine => match self {
+                Self::Pc437 => 1,
+                Self::Pc858 => 4,
+                Self::Pc852 => 5,
+                Self::Pc866 => 10,
+                Self::Pc850 => 11,
+                Self::Wpc1252 => 32,
+            },
+        }
+    }
+
+    /// Chars of bytes 0x80 to 0xFF, 16 per row; `\0` where the byte is undefined
+    const fn table(self) -> &'static [&'static str; 8] {
+        match self {
+            Self::Pc437 => &PC437,
+            Self::Pc850 => &PC850,
+            Self::Pc852 => &PC852,
+            Self::Pc858 => &PC858,
+            Self::Pc866 => &PC866,
+            Self::Wpc1252 => &WPC1252,
+        }
+    }
+
+    /// Whether `c` prints as is in this code page
+    pub fn contains(self, c: char) -> bool {
+        matches!(u8::try_from(c), Ok(b'\n' | b' '..=b'~')) || self.index_of(c).is_some()
+    }
+
+    /// Offset of `c` from 0x80, if it's in this code page
+    fn index_of(self, c: char) -> Option<u8> {
+        let index = self
+            .table()
+            .iter()
+            .flat_map(|row| row.chars())
+            .position(|t| t == c)?;
+        u8::try_from(index).ok()
+    }
+
+    /// Char a byte of text prints as, e.g. when reading printer commands back; `?` where the
+    /// byte is undefined
+    pub fn decode(self, byte: u8) -> char {
+        if byte < 0x80 {
+            return char::from(byte);
+        }
+        self.table()
+            .iter()
+            .flat_map(|row| row.chars())
+            .nth(usize::from(byte - 0x80))
+            .filter(|c| *c != '\0')
+            .unwrap_or('?')
+    }
+
+    /// Encodes text into this code page
+    ///
+    /// Chars missing from the code page are transliterated to ASCII (`ł` -> `l`, `“` -> `"`),
+    /// or printed as `?` if there's no sensible transliteration. Control characters other than
+    /// line feeds are dropped, so text can't sneak in printer commands.
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        let mut out = Vec::with_capacity(text.len());
+        for c in text.chars() {
+            if let Ok(byte @ (b'\n' | b' '..=b'~')) = u8::try_from(c) {
+                out.push(byte);
+            } else if c.is_control() {
+                // Dropped
+            } else if let Some(index) = self.index_of(c) {
+                out.push(0x80 + index);
+            } else if let Some(ascii) = deunicode::deunicode_char(c).filter(|a| !a.is_empty()) {
+                out.extend(ascii.bytes().filter(|b| b.is_ascii_graphic() || *b == b' '));
+            } else {
+                out.push(b'?');
+            }
+        }
+        out
+    }
+}
+
+/// Multi-byte encoding of a printer's CJK font, printed in kanji mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CjkEncoding {
+    /// Simplified Chinese; Most Chinese market printers
+    Gbk,
+    /// Traditional Chinese
+    Big5,
+    /// Japanese
+    ShiftJis,
+    /// Korean
+    EucKr,
+}
+
+impl FromStr for CjkEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "gbk" => Ok(Self::Gbk),
+            "big5" => Ok(Self::Big5),
+            "shift_jis" => Ok(Self::ShiftJis),
+            "euc_kr" => Ok(Self::EucKr),
+            other => Err(format!(
+                "Unknown CJK encoding `{other}`; expected gbk, big5, shift_jis or euc_kr"
+            )),
+        }
+    }
+}
+
+impl CjkEncoding {
+    /// Double-byte code of `c`, if the font has a glyph for it
+    pub fn encode(self, c: char) -> Option<Vec<u8>> {
+        let encoding = match self {
+            Self::Gbk => encoding_rs::GBK,
+            Self::Big5 => encoding_rs::BIG5,
+            Self::ShiftJis => encoding_rs::SHIFT_JIS,
+            Self::EucKr => encoding_rs::EUC_KR,
+        };
+        let mut buf = [0; 4];
+        let (bytes, _, unmappable) = encoding.encode(c.encode_utf8(&mut buf));
+        (!unmappable && bytes.len() == 2).then(|| bytes.into_owned())
+    }
+}
+
+const PC437: [&str; 8] = [
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜ¢£¥₧ƒ",
+    "áíóúñÑªº¿⌐¬½¼¡«»",
+    "░▒▓│┤╡╢╖╕╣║╗╝╜╛┐",
+    "└┴┬├─┼╞╟╚╔╩╦╠═╬╧",
+    "╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀",
+    "αßΓπΣσµτΦΘΩδ∞φε∩",
+    "≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}",
+];
+
+const PC850: [&str; 8] = [
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜø£Ø×ƒ",
+    "áíóúñÑªº¿®¬½¼¡«»",
+    "░▒▓│┤ÁÂÀ©╣║╗╝¢¥┐",
+    "└┴┬├─┼ãÃ╚╔╩╦╠═╬¤",
+    "ðÐÊËÈıÍÎÏ┘┌█▄¦Ì▀",
+    "ÓßÔÒõÕµþÞÚÛÙýÝ¯´",
+    "\u{ad}±‗¾¶§÷¸°¨·¹³²■\u{a0}",
+];
+
+const PC852: [&str; 8] = [
+    "ÇüéâäůćçłëŐőîŹÄĆ",
+    "ÉĹĺôöĽľŚśÖÜŤťŁ×č",
+    "áíóúĄąŽžĘę¬źČş«»",
+    "░▒▓│┤ÁÂĚŞ╣║╗╝Żż┐",
+    "└┴┬├─┼Ăă╚╔╩╦╠═╬¤",
+    "đĐĎËďŇÍÎě┘┌█▄ŢŮ▀",
+    "ÓßÔŃńňŠšŔÚŕŰýÝţ´",
+    "\u{ad}˝˛ˇ˘§÷¸°¨˙űŘř■\u{a0}",
+];
+
+const PC858: [&str; 8] = [
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜø£Ø×ƒ",
+    "áíóúñÑªº¿®¬½¼¡«»",
+    "░▒▓│┤ÁÂÀ©╣║╗╝¢¥┐",
+    "└┴┬├─┼ãÃ╚╔╩╦╠═╬¤",
+    "ðÐÊËÈ€ÍÎÏ┘┌█▄¦Ì▀",
+    "ÓßÔÒõÕµþÞÚÛÙýÝ¯´",
+    "\u{ad}±‗¾¶§÷¸°¨·¹³²■\u{a0}",
+];
+
+const PC866: [&str; 8] = [
+    "АБВГДЕЖЗИЙКЛМНОП",
+    "РСТУФХЦЧШЩЪЫЬЭЮЯ",
+    "абвгдежзийклмноп",
+    "░▒▓│┤╡╢╖╕╣║╗╝╜╛┐",
+    "└┴┬├─┼╞╟╚╔╩╦╠═╬╧",
+    "╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀",
+    "рстуфхцчшщъыьэюя",
+    "ЁёЄєЇїЎў°∙·√№¤■\u{a0}",
+];
+
+const WPC1252: [&str; 8] = [
+    "€\0‚ƒ„…†‡ˆ‰Š‹Œ\0Ž\0",
+    "\0‘’“”•–—˜™š›œ\0žŸ",
+    "\u{a0}¡¢£¤¥¦§¨©ª«¬\u{ad}®¯",
+    "°±²³´µ¶·¸¹º»¼½¾¿",
+    "ÀÁÂÃÄÅÆÇÈÉÊËÌÍÎÏ",
+    "ÐÑÒÓÔÕÖ×ØÙÚÛÜÝÞß",
+    "àáâãäåæçèéêëìíîï",
+    "ðñòóôõö÷øùúûüýþÿ",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode() {
+        let code_page = CodePage::Pc437;
+        assert_eq!(code_page.encode("Café ½"), b"Caf\x82 \xAB");
+        // Transliterated, control characters dropped
+        assert_eq!(code_page.encode("Łodź “hi”\u{1b}@\t"), b"Lodz \"hi\"@");
+        assert_eq!(
+            CodePage::Pc866.encode("Привет"),
+            b"\x8F\xE0\xA8\xA2\xA5\xE2"
+        );
+    }
+
+    #[test]
+    fn cjk_encode() {
+        assert_eq!(CjkEncoding::Gbk.encode('中'), Some(vec![0xD6, 0xD0]));
+        assert_eq!(CjkEncoding::Big5.encode('中'), Some(vec![0xA4, 0xA4]));
+        assert_eq!(CjkEncoding::ShiftJis.encode('日'), Some(vec![0x93, 0xFA]));
+        assert_eq!(CjkEncoding::EucKr.encode('한'), Some(vec![0xC7, 0xD1]));
+        // Missing from the font, or single byte
+        assert_eq!(CjkEncoding::ShiftJis.encode('한'), None);
+        assert_eq!(CjkEncoding::Gbk.encode('a'), None);
+        assert_eq!(CjkEncoding::ShiftJis.encode('ｱ'), None);
+    }
+}
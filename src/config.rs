@@ -0,0 +1,610 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env::VarError,
+    sync::{Mutex, RwLock},
+};
+
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::service;
+
+/// Where the config is read from when `CONFIG_FILE` isn't set
+const DEFAULT_PATH: &str = "config.toml";
+
+/// Env vars set from the config file rather than the environment, so reloads may change them
+static FROM_FILE: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Values of config keys as of the last reload, by env var; None for keys it removed. See
+/// [`Config::reload`]
+static RELOADED: RwLock<BTreeMap<String, Option<String>>> = RwLock::new(BTreeMap::new());
+
+/// Value of a config key, as its env var would hold it
+trait EnvValue {
+    fn to_env(&self) -> String;
+}
+
+impl EnvValue for String {
+    fn to_env(&self) -> String {
+        self.clone()
+    }
+}
+
+impl EnvValue for bool {
+    fn to_env(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl EnvValue for u64 {
+    fn to_env(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Lists are comma separated, e.g. `PRINTER_COMPACT_SERVICES`
+impl EnvValue for Vec<String> {
+    fn to_env(&self) -> String {
+        self.join(",")
+    }
+}
+
+/// Declares a config table, each key standing in for the env var it's mapped to
+///
+/// Tables of services running several accounts, see [`service::Account`], also take labelled
+/// ones, e.g. `[services.github.accounts.work]`, their keys standing in for the env vars
+/// suffixed with the label, e.g. `GITHUB_PAT_WORK`.
+macro_rules! table {
+    ($(#[$meta:meta])* $name:ident { $($key:ident: $ty:ty => $var:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Default, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct $name {
+            $(pub $key: Option<$ty>,)*
+        }
+
+        impl $name {
+            fn vars(&self) -> Vec<(String, Option<String>)> {
+                vec![$(($var.to_string(), self.$key.as_ref().map(EnvValue::to_env)),)*]
+            }
+        }
+    };
+    (
+        $(#[$meta:meta])* $name:ident accounts $accounts:literal {
+            $($key:ident: $ty:ty => $var:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Default, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct $name {
+            $(pub $key: Option<$ty>,)*
+            /// Labelled accounts; Their own `accounts` are ignored
+            pub accounts: BTreeMap<String, $name>,
+        }
+
+        impl $name {
+            fn vars(&self) -> Vec<(String, Option<String>)> {
+                let labels = self.accounts.keys().cloned().collect::<Vec<String>>();
+                let mut vars = vec![
+                    $(($var.to_string(), self.$key.as_ref().map(EnvValue::to_env)),)*
+                    ($accounts.to_string(), (!labels.is_empty()).then(|| labels.to_env())),
+                ];
+                for (label, account) in &self.accounts {
+                    vars.extend([$((
+                        service::account_var($var, label),
+                        account.$key.as_ref().map(EnvValue::to_env),
+                    ),)*]);
+                }
+                vars
+            }
+        }
+    };
+}
+
+table! {
+    /// `[printer]`; Transport, address & profile overrides
+    Printer {
+        transport: String => "PRINTER_TRANSPORT",
+        addr: String => "PRINTER_ADDR",
+        serial_path: String => "PRINTER_SERIAL_PATH",
+        serial_baud: u64 => "PRINTER_SERIAL_BAUD",
+        serial_flow_control: String => "PRINTER_SERIAL_FLOW_CONTROL",
+        usb_vendor_id: String => "PRINTER_USB_VENDOR_ID",
+        usb_product_id: String => "PRINTER_USB_PRODUCT_ID",
+        bt_addr: String => "PRINTER_BT_ADDR",
+        bt_channel: u64 => "PRINTER_BT_CHANNEL",
+        bt_pin: String => "PRINTER_BT_PIN",
+        png_dir: String => "PRINTER_PNG_DIR",
+        profile: String => "PRINTER_PROFILE",
+        protocol: String => "PRINTER_PROTOCOL",
+        paper_width: String => "PRINTER_PAPER_WIDTH",
+        code_page: String => "PRINTER_CODE_PAGE",
+        cjk_encoding: String => "PRINTER_CJK_ENCODING",
+        emoji: String => "PRINTER_EMOJI",
+        upside_down: bool => "PRINTER_UPSIDE_DOWN",
+        compact: bool => "PRINTER_COMPACT",
+        compact_services: Vec<String> => "PRINTER_COMPACT_SERVICES",
+        max_message_length: u64 => "PRINTER_MAX_MESSAGE_LENGTH",
+        markdown_links: String => "PRINTER_MARKDOWN_LINKS",
+        cut: String => "PRINTER_CUT",
+        cut_feed: u64 => "PRINTER_CUT_FEED",
+        cut_digests_only: bool => "PRINTER_CUT_DIGESTS_ONLY",
+        header: String => "PRINTER_HEADER",
+        footer: String => "PRINTER_FOOTER",
+        beep: String => "PRINTER_BEEP",
+        beep_services: Vec<String> => "PRINTER_BEEP_SERVICES",
+        drawer_kick: String => "PRINTER_DRAWER_KICK",
+        drawer_kick_services: Vec<String> => "PRINTER_DRAWER_KICK_SERVICES",
+        drawer_pin: u64 => "PRINTER_DRAWER_PIN",
+        drawer_pulse: u64 => "PRINTER_DRAWER_PULSE",
+    }
+}
+
+table! {
+    /// `[services.github]`
+    GitHub accounts "GITHUB_ACCOUNTS" {
+        pat: String => "GITHUB_PAT",
+        app_id: String => "GITHUB_APP_ID",
+        app_private_key: String => "GITHUB_APP_PRIVATE_KEY",
+        app_installation_id: String => "GITHUB_APP_INSTALLATION_ID",
+        webhook_secret: String => "GITHUB_WEBHOOK_SECRET",
+        mark_read: String => "GITHUB_MARK_READ",
+        repos: Vec<String> => "GITHUB_REPOS",
+        ignore_repos: Vec<String> => "GITHUB_IGNORE_REPOS",
+        watch_repos: Vec<String> => "GITHUB_WATCH_REPOS",
+    }
+}
+
+table! {
+    /// `[services.gitlab]`
+    GitLab accounts "GITLAB_ACCOUNTS" {
+        token: String => "GITLAB_TOKEN",
+        url: String => "GITLAB_URL",
+    }
+}
+
+table! {
+    /// `[services.gitea]`; Forgejo instances too, e.g. Codeberg
+    Gitea accounts "GITEA_ACCOUNTS" {
+        token: String => "GITEA_TOKEN",
+        url: String => "GITEA_URL",
+    }
+}
+
+table! {
+    /// `[services.jira]`
+    Jira {
+        url: String => "JIRA_URL",
+        email: String => "JIRA_EMAIL",
+        api_token: String => "JIRA_API_TOKEN",
+    }
+}
+
+table! {
+    /// `[services.linear]`
+    Linear {
+        api_key: String => "LINEAR_API_KEY",
+        team: String => "LINEAR_TEAM",
+    }
+}
+
+table! {
+    /// `[services.twitch]`
+    Twitch {
+        oauth_token: String => "TWITCH_OAUTH_TOKEN",
+        client_id: String => "TWITCH_CLIENT_ID",
+        client_secret: String => "TWITCH_CLIENT_SECRET",
+        broadcaster_ids: Vec<String> => "TWITCH_BROADCASTER_IDS",
+        channel_id: String => "TWITCH_CHANNEL_ID",
+        channel_events: Vec<String> => "TWITCH_CHANNEL_EVENTS",
+        keepalive_margin: u64 => "TWITCH_KEEPALIVE_MARGIN",
+        transport: String => "TWITCH_TRANSPORT",
+        webhook_callback: String => "TWITCH_WEBHOOK_CALLBACK",
+        webhook_secret: String => "TWITCH_WEBHOOK_SECRET",
+    }
+}
+
+table! {
+    /// `[services.twitch_chat]`
+    TwitchChat {
+        channels: Vec<String> => "TWITCH_CHAT_CHANNELS",
+        username: String => "TWITCH_CHAT_USERNAME",
+        keywords: Vec<String> => "TWITCH_CHAT_KEYWORDS",
+    }
+}
+
+table! {
+    /// `[services.bsky]`
+    Bsky accounts "BSKY_ACCOUNTS" {
+        identifier: String => "BSKY_IDENTIFIER",
+        password: String => "BSKY_PASSWORD",
+        poll_interval: u64 => "BSKY_POLL_INTERVAL",
+    }
+}
+
+table! {
+    /// `[services.email]`
+    Email {
+        domain: String => "IMAP_DOMAIN",
+        port: u64 => "IMAP_PORT",
+        user: String => "IMAP_USER",
+        password: String => "IMAP_PASSWORD",
+    }
+}
+
+table! {
+    /// `[services.football]`
+    Football {
+        token: String => "FOOTBALL_DATA_TOKEN",
+        team_ids: Vec<String> => "FOOTBALL_TEAM_IDS",
+    }
+}
+
+table! {
+    /// `[services.chess]`
+    Chess {
+        lichess_token: String => "LICHESS_TOKEN",
+        chesscom_username: String => "CHESSCOM_USERNAME",
+    }
+}
+
+table! {
+    /// `[services.arxiv]`
+    Arxiv {
+        categories: String => "ARXIV_CATEGORIES",
+        keywords: String => "ARXIV_KEYWORDS",
+        print_time: String => "ARXIV_PRINT_TIME",
+        max_results: u64 => "ARXIV_MAX_RESULTS",
+    }
+}
+
+table! {
+    /// `[services.caldav]`
+    CalDav {
+        url: String => "CALDAV_URL",
+        user: String => "CALDAV_USER",
+        password: String => "CALDAV_PASSWORD",
+        lead_minutes: u64 => "CALDAV_LEAD_MINUTES",
+    }
+}
+
+table! {
+    /// `[services.google_calendar]`
+    GoogleCalendar {
+        client_id: String => "GOOGLE_CLIENT_ID",
+        client_secret: String => "GOOGLE_CLIENT_SECRET",
+        refresh_token: String => "GOOGLE_REFRESH_TOKEN",
+        calendar_id: String => "GOOGLE_CALENDAR_ID",
+        print_time: String => "AGENDA_PRINT_TIME",
+    }
+}
+
+table! {
+    /// `[services.todoist]`
+    Todoist {
+        token: String => "TODOIST_TOKEN",
+        print_time: String => "TODOIST_PRINT_TIME",
+    }
+}
+
+table! {
+    /// `[services.carddav]`
+    CardDav {
+        url: String => "CARDDAV_URL",
+        user: String => "CARDDAV_USER",
+        password: String => "CARDDAV_PASSWORD",
+        evening_time: String => "BIRTHDAY_EVENING_TIME",
+        morning_time: String => "BIRTHDAY_MORNING_TIME",
+    }
+}
+
+table! {
+    /// `[services.reminders]`
+    Reminders {
+        file: String => "REMINDERS_FILE",
+    }
+}
+
+table! {
+    /// `[services.bandcamp]`
+    Bandcamp {
+        feeds: Vec<String> => "BANDCAMP_FEEDS",
+    }
+}
+
+table! {
+    /// `[services.lastfm]`
+    LastFm {
+        api_key: String => "LASTFM_API_KEY",
+        user: String => "LASTFM_USER",
+        print_time: String => "LASTFM_PRINT_TIME",
+    }
+}
+
+table! {
+    /// `[services.now_playing]`
+    NowPlaying {
+        gpio: u64 => "NOW_PLAYING_GPIO",
+        gpio_active_high: bool => "NOW_PLAYING_GPIO_ACTIVE_HIGH",
+        mpd_addr: String => "MPD_ADDR",
+        spotify_client_id: String => "SPOTIFY_CLIENT_ID",
+        spotify_client_secret: String => "SPOTIFY_CLIENT_SECRET",
+        spotify_refresh_token: String => "SPOTIFY_REFRESH_TOKEN",
+    }
+}
+
+table! {
+    /// `[services.strava]`
+    Strava {
+        client_id: String => "STRAVA_CLIENT_ID",
+        client_secret: String => "STRAVA_CLIENT_SECRET",
+        refresh_token: String => "STRAVA_REFRESH_TOKEN",
+        verify_token: String => "STRAVA_VERIFY_TOKEN",
+    }
+}
+
+table! {
+    /// `[services.heartbeat]`
+    Heartbeat {
+        interval: u64 => "HEARTBEAT_INTERVAL",
+    }
+}
+
+table! {
+    /// `[services.summary]`
+    Summary {
+        print_time: String => "SUMMARY_PRINT_TIME",
+        top: u64 => "SUMMARY_TOP",
+    }
+}
+
+table! {
+    /// `[queue]`; Buffering, dedup, digests, rate limits & redaction of prints on their way to the
+    /// printer
+    Queue {
+        capacity: u64 => "PRINT_QUEUE_CAPACITY",
+        overflow: String => "PRINT_QUEUE_OVERFLOW",
+        dedup_ttl: u64 => "DEDUP_TTL",
+        dedup_file: String => "DEDUP_FILE",
+        digest_interval: u64 => "DIGEST_INTERVAL",
+        digest_max_items: u64 => "DIGEST_MAX_ITEMS",
+        rate_limit_per_minute: u64 => "RATE_LIMIT_PER_MINUTE",
+        rate_limit_burst: u64 => "RATE_LIMIT_BURST",
+        redact: Vec<String> => "REDACT",
+        redact_file: String => "REDACT_FILE",
+        journal: String => "PRINT_JOURNAL",
+        shutdown_drain_timeout: u64 => "SHUTDOWN_DRAIN_TIMEOUT",
+    }
+}
+
+table! {
+    /// `[sinks]`; Where prints go besides the printer, each getting those of `<sink>_priority` &
+    /// up or of `<sink>_services` only if set
+    Sinks {
+        file_path: String => "SINK_FILE_PATH",
+        file_priority: String => "SINK_FILE_PRIORITY",
+        file_services: Vec<String> => "SINK_FILE_SERVICES",
+        spool_dir: String => "SINK_SPOOL_DIR",
+        spool_priority: String => "SINK_SPOOL_PRIORITY",
+        spool_services: Vec<String> => "SINK_SPOOL_SERVICES",
+        ntfy_server: String => "SINK_NTFY_SERVER",
+        ntfy_topic: String => "SINK_NTFY_TOPIC",
+        ntfy_token: String => "SINK_NTFY_TOKEN",
+        ntfy_priority: String => "SINK_NTFY_PRIORITY",
+        ntfy_services: Vec<String> => "SINK_NTFY_SERVICES",
+        telegram_token: String => "SINK_TELEGRAM_TOKEN",
+        telegram_chat_id: String => "SINK_TELEGRAM_CHAT_ID",
+        telegram_priority: String => "SINK_TELEGRAM_PRIORITY",
+        telegram_services: Vec<String> => "SINK_TELEGRAM_SERVICES",
+    }
+}
+
+/// `[services]`; Which services run & their options
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Services {
+    /// Services to run, by name; All of them if unset
+    pub enabled: Option<Vec<String>>,
+    /// Services not to run, by name
+    pub disabled: Option<Vec<String>>,
+    pub crash_receipts: Option<bool>,
+    pub github: GitHub,
+    pub gitlab: GitLab,
+    pub gitea: Gitea,
+    pub jira: Jira,
+    pub linear: Linear,
+    pub twitch: Twitch,
+    pub twitch_chat: TwitchChat,
+    pub bsky: Bsky,
+    pub email: Email,
+    pub football: Football,
+    pub chess: Chess,
+    pub arxiv: Arxiv,
+    pub caldav: CalDav,
+    pub google_calendar: GoogleCalendar,
+    pub todoist: Todoist,
+    pub carddav: CardDav,
+    pub reminders: Reminders,
+    pub bandcamp: Bandcamp,
+    pub lastfm: LastFm,
+    pub now_playing: NowPlaying,
+    pub strava: Strava,
+    pub heartbeat: Heartbeat,
+    pub summary: Summary,
+}
+
+/// Settings read from `config.toml`, e.g.
+///
+/// ```toml
+/// device_name = "kitchen"
+///
+/// [printer]
+/// transport = "tcp"
+/// addr = "192.168.1.24:9100"
+/// profile = "tm-t20"
+///
+/// [queue]
+/// dedup_ttl = 3600
+///
+/// [services]
+/// disabled = ["email", "strava"]
+///
+/// [services.twitch]
+/// oauth_token = "..."
+/// broadcaster_ids = ["88547576", "57220741"]
+///
+/// [services.github.accounts.work]
+/// pat = "..."
+/// ```
+///
+/// Every key stands in for an env var, e.g. `printer.addr` for `PRINTER_ADDR`; Env vars that are
+/// set take priority over the file. Sending the daemon SIGHUP reloads it, restarting services.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub device_name: Option<String>,
+    pub http_addr: Option<String>,
+    pub admin_token: Option<String>,
+    pub template_dir: Option<String>,
+    pub logo_dir: Option<String>,
+    pub emoji_dir: Option<String>,
+    pub quiet_hours: Option<String>,
+    pub quiet_hours_bypass: Option<String>,
+    pub log_dir: Option<String>,
+    pub log_filter: Option<String>,
+    pub log_file_filter: Option<String>,
+    pub history_db: Option<String>,
+    pub state_file: Option<String>,
+    pub printer: Printer,
+    pub queue: Queue,
+    pub sinks: Sinks,
+    pub services: Services,
+}
+
+impl Config {
+    /// Reads `CONFIG_FILE` (`config.toml` if unset); No config if it doesn't exist
+    pub fn load() -> Self {
+        Self::read().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like [`Config::load`], but returns errors for reloads to keep the previous config
+    pub fn read() -> Result<Self, String> {
+        let (path, required) = std::env::var("CONFIG_FILE")
+            .map_or_else(|_| (DEFAULT_PATH.to_string(), false), |path| (path, true));
+        let file = match std::fs::read_to_string(&path) {
+            Ok(file) => file,
+            Err(e) if required => return Err(format!("Unable to read CONFIG_FILE {path}: {e}")),
+            Err(_) => {
+                debug!("No {path}, using env vars only");
+                return Ok(Self::default());
+            }
+        };
+
+        info!("Loaded config from {path}");
+        toml::from_str(&file).map_err(|e| format!("Config {path} is malformed: {e}"))
+    }
+
+    /// Every config key, with the env var it stands in for
+    fn vars(&self) -> Vec<(String, Option<String>)> {
+        let services = &self.services;
+        let vars = vec![
+            ("DEVICE_NAME", self.device_name.clone()),
+            ("HTTP_ADDR", self.http_addr.clone()),
+            ("ADMIN_TOKEN", self.admin_token.clone()),
+            ("TEMPLATE_DIR", self.template_dir.clone()),
+            ("LOGO_DIR", self.logo_dir.clone()),
+            ("EMOJI_DIR", self.emoji_dir.clone()),
+            ("QUIET_HOURS", self.quiet_hours.clone()),
+            ("QUIET_HOURS_BYPASS", self.quiet_hours_bypass.clone()),
+            ("LOG_DIR", self.log_dir.clone()),
+            ("LOG_FILTER", self.log_filter.clone()),
+            ("LOG_FILE_FILTER", self.log_file_filter.clone()),
+            ("HISTORY_DB", self.history_db.clone()),
+            ("STATE_FILE", self.state_file.clone()),
+            ("SERVICES", services.enabled.as_ref().map(EnvValue::to_env)),
+            (
+                "SERVICES_DISABLED",
+                services.disabled.as_ref().map(EnvValue::to_env),
+            ),
+            (
+                "SERVICE_CRASH_RECEIPTS",
+                services.crash_receipts.as_ref().map(EnvValue::to_env),
+            ),
+        ];
+        let mut vars = vars
+            .into_iter()
+            .map(|(var, value)| (var.to_string(), value))
+            .collect::<Vec<_>>();
+        for table in [
+            self.printer.vars(),
+            self.queue.vars(),
+            self.sinks.vars(),
+            services.github.vars(),
+            services.gitlab.vars(),
+            services.gitea.vars(),
+            services.jira.vars(),
+            services.linear.vars(),
+            services.twitch.vars(),
+            services.twitch_chat.vars(),
+            services.bsky.vars(),
+            services.email.vars(),
+            services.football.vars(),
+            services.chess.vars(),
+            services.arxiv.vars(),
+            services.caldav.vars(),
+            services.google_calendar.vars(),
+            services.todoist.vars(),
+            services.carddav.vars(),
+            services.reminders.vars(),
+            services.bandcamp.vars(),
+            services.lastfm.vars(),
+            services.now_playing.vars(),
+            services.strava.vars(),
+            services.heartbeat.vars(),
+            services.summary.vars(),
+        ] {
+            vars.extend(table);
+        }
+        vars
+    }
+
+    /// Sets the env vars of configured keys, leaving those set by the environment as overrides
+    ///
+    /// Must run before anything reads them, i.e. first thing in `main`; The env is left alone
+    /// once other threads run, so reloads go through [`Self::reload`] instead.
+    pub fn apply(&self) {
+        let mut from_file = FROM_FILE.lock().unwrap();
+        for (var, value) in self.vars() {
+            if let Some(value) = value.filter(|_| std::env::var_os(&var).is_none()) {
+                std::env::set_var(&var, value);
+                from_file.insert(var);
+            }
+        }
+    }
+
+    /// Overrides the values [`Self::apply`] set with this config's, as read by
+    /// [`crate::secrets::var`]; Removed keys are unset, and those set by the environment are
+    /// still left alone
+    pub fn reload(&self) {
+        let from_file = FROM_FILE.lock().unwrap();
+        // Keys no longer listed at all, e.g. of removed accounts, are unset too
+        let mut reloaded = from_file
+            .iter()
+            .map(|var| (var.clone(), None))
+            .collect::<BTreeMap<_, _>>();
+        for (var, value) in self.vars() {
+            if from_file.contains(&var) || std::env::var_os(&var).is_none() {
+                reloaded.insert(var, value);
+            }
+        }
+        *RELOADED.write().unwrap() = reloaded;
+    }
+}
+
+/// Value a reload gave the env var, or an error if it unset it, see [`Config::reload`]; None if
+/// no reload touched it
+pub fn reloaded(var: &str) -> Option<Result<String, VarError>> {
+    let value = RELOADED.read().unwrap().get(var).cloned()?;
+    Some(value.ok_or(VarError::NotPresent))
+}
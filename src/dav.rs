@@ -0,0 +1,119 @@
+//! Minimal DAV helpers for the calendar / contacts services
+
+use reqwest::{
+    header::{HeaderValue, CONTENT_TYPE},
+    Client, Method,
+};
+
+use crate::{error::Result, http::SendRetrying};
+
+pub const CALDAV_NS: &str = "urn:ietf:params:xml:ns:caldav";
+pub const CARDDAV_NS: &str = "urn:ietf:params:xml:ns:carddav";
+
+/// Sends a `REPORT` request and returns the text of every `data_element` (in `namespace`) in the
+/// multistatus response, e.g. all `calendar-data` iCalendar objects
+pub async fn report(
+    client: &Client,
+    url: &str,
+    username: &str,
+    password: &str,
+    body: String,
+    (namespace, data_element): (&str, &str),
+) -> Result<Vec<String>> {
+    let method = Method::from_bytes(b"REPORT").expect("REPORT is a valid method");
+    let text = client
+        .request(method, url)
+        .basic_auth(username, Some(password))
+        .header("Depth", "1")
+        .header(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/xml; charset=utf-8"),
+        )
+        .body(body)
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let document = roxmltree::Document::parse(&text)?;
+    Ok(document
+        .descendants()
+        .filter(|n| n.has_tag_name((namespace, data_element)))
+        .filter_map(|n| n.text())
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// A single `NAME;PARAM=VALUE:value` line of an iCalendar / vCard object
+#[derive(Debug)]
+pub struct ContentLine {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl ContentLine {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Unfolds and splits an iCalendar (RFC 5545) / vCard (RFC 6350) object into content lines
+///
+/// Property groups (`item1.BDAY`) are stripped, names are uppercased and escaped text values
+/// (`\n`, `\,`, `\;`) are unescaped.
+pub fn parse_content_lines(data: &str) -> Vec<ContentLine> {
+    // Lines starting with whitespace are continuations of the previous line
+    let mut unfolded: Vec<String> = Vec::new();
+    for line in data.lines() {
+        if let Some(continuation) = line.strip_prefix([' ', '\t']) {
+            if let Some(last) = unfolded.last_mut() {
+                last.push_str(continuation);
+                continue;
+            }
+        }
+        unfolded.push(line.to_string());
+    }
+
+    unfolded
+        .iter()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let mut key_parts = key.split(';');
+            let name = key_parts.next()?;
+            let name = name.rsplit('.').next().unwrap_or(name).to_uppercase();
+            let params = key_parts
+                .filter_map(|p| p.split_once('='))
+                .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+                .collect();
+
+            Some(ContentLine {
+                name,
+                params,
+                value: unescape(value),
+            })
+        })
+        .collect()
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+
+    out
+}
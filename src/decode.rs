@@ -0,0 +1,354 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    codepage::CodePage,
+    escpos::{Justify, Style, BEL, ESC, FF, FS, GS, LF},
+    profile::Cut,
+    protocol::Protocol,
+};
+
+/// What a print job prints, read back from its ESC/POS commands by [`decode`]
+#[derive(Debug, Clone)]
+pub enum Block {
+    Line {
+        justify: Justify,
+        segments: Vec<Segment>,
+    },
+    /// Blank lines fed after the line before
+    Feed(u8),
+    /// Image of `width` bytes by `height` dots, rows MSB first
+    Raster {
+        justify: Justify,
+        width: u16,
+        height: u16,
+        data: Vec<u8>,
+    },
+    QrCode {
+        justify: Justify,
+        data: Vec<u8>,
+    },
+    Barcode {
+        justify: Justify,
+        data: Vec<u8>,
+    },
+    Cut(Cut),
+    Beep,
+    KickDrawer,
+}
+
+/// Part of a line printed with the same settings
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub content: Content,
+    pub style: Style,
+    /// Width & height multipliers, see [`CommandBuffer::char_size`](crate::escpos::CommandBuffer)
+    pub size: (u8, u8),
+    /// Whether it's in the smaller font
+    pub small: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Content {
+    Text(String),
+    /// User-defined character, e.g. an emoji; `width` columns of 3 bytes, top to bottom, MSB
+    /// first
+    Glyph {
+        width: u8,
+        data: Vec<u8>,
+    },
+}
+
+/// Reads ESC/POS commands back into the receipt they print, e.g. to preview it without a
+/// printer; Text is decoded from `code_page` until another one is selected
+///
+/// Covers the commands [`CommandBuffer`](crate::escpos::CommandBuffer) sends. Others are
+/// skipped, assuming a single parameter; Kanji are read as `□` & upside down printing is
+/// ignored.
+pub fn decode(bytes: &[u8], code_page: CodePage) -> Vec<Block> {
+    Decoder {
+        bytes,
+        pos: 0,
+        default_code_page: code_page,
+        code_page,
+        justify: Justify::Left,
+        style: Style::default(),
+        size: (1, 1),
+        small: false,
+        kanji: false,
+        user_defined: false,
+        glyphs: BTreeMap::new(),
+        qr_code: Vec::new(),
+        segments: Vec::new(),
+        blocks: Vec::new(),
+    }
+    .run()
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    default_code_page: CodePage,
+    code_page: CodePage,
+    justify: Justify,
+    style: Style,
+    size: (u8, u8),
+    small: bool,
+    kanji: bool,
+    /// Whether user-defined characters print instead of the built-in ones
+    user_defined: bool,
+    /// User-defined characters by code, as width & data
+    glyphs: BTreeMap<u8, (u8, Vec<u8>)>,
+    /// QR code data stored, printed by its own command
+    qr_code: Vec<u8>,
+    /// Line being built
+    segments: Vec<Segment>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Decoder<'a> {
+    fn run(mut self) -> Vec<Block> {
+        while self.pos < self.bytes.len() {
+            match self.byte() {
+                ESC => self.esc(),
+                GS => self.gs(),
+                FS => self.fs(),
+                LF => self.line(),
+                FF => self.flush(),
+                BEL => self.blocks.push(Block::Beep),
+                byte @ 0x20.. => self.text(byte),
+                _ => {}
+            }
+        }
+        self.flush();
+        self.blocks
+    }
+
+    /// Next `n` bytes, fewer if the job ends first
+    fn take(&mut self, n: usize) -> &'a [u8] {
+        let end = (self.pos + n).min(self.bytes.len());
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        bytes
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.take(1).first().copied().unwrap_or_default()
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.byte(), self.byte()])
+    }
+
+    /// Ends the line being built, blank if nothing was printed on it
+    fn line(&mut self) {
+        let segments = std::mem::take(&mut self.segments);
+        self.blocks.push(Block::Line {
+            justify: self.justify,
+            segments,
+        });
+    }
+
+    /// Ends the line being built, if anything was printed on it
+    fn flush(&mut self) {
+        if !self.segments.is_empty() {
+            self.line();
+        }
+    }
+
+    fn push(&mut self, content: Content) {
+        let (style, size, small) = (self.style, self.size, self.small);
+        if let (Content::Text(text), Some(last)) = (&content, self.segments.last_mut()) {
+            if let Content::Text(last_text) = &mut last.content {
+                if (last.style, last.size, last.small) == (style, size, small) {
+                    last_text.push_str(text);
+                    return;
+                }
+            }
+        }
+        self.segments.push(Segment {
+            content,
+            style,
+            size,
+            small,
+        });
+    }
+
+    fn text(&mut self, byte: u8) {
+        if self.kanji {
+            // Second byte of the character
+            self.byte();
+            self.push(Content::Text("□".to_string()));
+            return;
+        }
+        if let Some((width, data)) = self.glyphs.get(&byte).filter(|_| self.user_defined) {
+            let (width, data) = (*width, data.clone());
+            self.push(Content::Glyph { width, data });
+            return;
+        }
+        let c = self.code_page.decode(byte);
+        self.push(Content::Text(c.to_string()));
+    }
+
+    fn esc(&mut self) {
+        match self.byte() {
+            b'@' => {
+                self.flush();
+                self.code_page = self.default_code_page;
+                self.justify = Justify::Left;
+                self.style = Style::default();
+                self.size = (1, 1);
+                self.small = false;
+                self.kanji = false;
+                self.user_defined = false;
+            }
+            b't' => {
+                let n = self.byte();
+                self.code_page =
+                    CodePage::from_number(n, Protocol::EscPos).unwrap_or(self.code_page);
+            }
+            b'a' => {
+                self.justify = match self.byte() {
+                    1 | b'1' => Justify::Center,
+                    2 | b'2' => Justify::Right,
+                    _ => Justify::Left,
+                };
+            }
+            b'E' => self.style.bold = self.byte() & 1 == 1,
+            b'-' => self.style.underline = matches!(self.byte(), 1 | 2 | b'1' | b'2'),
+            b'M' => self.small = matches!(self.byte(), 1 | b'1'),
+            b'%' => self.user_defined = self.byte() & 1 == 1,
+            b'd' => {
+                let n = self.byte();
+                self.flush();
+                if n > 0 {
+                    self.blocks.push(Block::Feed(n));
+                }
+            }
+            b'i' => {
+                self.flush();
+                self.blocks.push(Block::Cut(Cut::Full));
+            }
+            b'm' => {
+                self.flush();
+                self.blocks.push(Block::Cut(Cut::Partial));
+            }
+            b'B' => {
+                self.take(2);
+                self.blocks.push(Block::Beep);
+            }
+            b'p' => {
+                self.take(3);
+                self.blocks.push(Block::KickDrawer);
+            }
+            // ESC & y c1 c2 [x d1...d(y * x)]...
+            b'&' => {
+                let height = self.byte();
+                let (first, last) = (self.byte(), self.byte());
+                for code in first..=last {
+                    let width = self.byte();
+                    let data = self.take(usize::from(width) * usize::from(height));
+                    self.glyphs.insert(code, (width, data.to_vec()));
+                }
+            }
+            // No parameters
+            b'2' | b'<' => {}
+            _ => {
+                self.byte();
+            }
+        }
+    }
+
+    fn gs(&mut self) {
+        match self.byte() {
+            b'!' => {
+                let n = self.byte();
+                self.size = ((n >> 4) + 1, (n & 0x0F) + 1);
+            }
+            b'B' => self.style.invert = self.byte() & 1 == 1,
+            // GS v 0 m xL xH yL yH d1...dk
+            b'v' => {
+                self.take(2);
+                let (width, height) = (self.u16(), self.u16());
+                let data = self.take(usize::from(width) * usize::from(height)).to_vec();
+                self.flush();
+                self.blocks.push(Block::Raster {
+                    justify: self.justify,
+                    width,
+                    height,
+                    data,
+                });
+            }
+            // GS ( k pL pH cn fn ...; Only QR codes' store & print functions matter
+            b'(' => {
+                let function = self.byte();
+                let len = self.u16();
+                let params = self.take(usize::from(len));
+                match (function, params) {
+                    (b'k', [0x31, 0x50, _, data @ ..]) => self.qr_code = data.to_vec(),
+                    (b'k', [0x31, 0x51, ..]) => {
+                        self.flush();
+                        let data = std::mem::take(&mut self.qr_code);
+                        self.blocks.push(Block::QrCode {
+                            justify: self.justify,
+                            data,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            // GS k m n d1...dn, or NUL terminated for m < 65
+            b'k' => {
+                let system = self.byte();
+                let data = if system >= 65 {
+                    let n = self.byte();
+                    self.take(usize::from(n)).to_vec()
+                } else {
+                    let end = self.bytes[self.pos..]
+                        .iter()
+                        .position(|byte| *byte == 0)
+                        .unwrap_or(self.bytes.len() - self.pos);
+                    let data = self.take(end).to_vec();
+                    self.byte();
+                    data
+                };
+                // CODE128 data starts by selecting its code set, e.g. `{B`
+                let data = match data.as_slice() {
+                    [b'{', _, rest @ ..] => rest.to_vec(),
+                    _ => data,
+                };
+                self.flush();
+                self.blocks.push(Block::Barcode {
+                    justify: self.justify,
+                    data,
+                });
+            }
+            // GS V m [n]
+            b'V' => {
+                let m = self.byte();
+                if m >= 65 {
+                    self.byte();
+                }
+                self.flush();
+                let cut = if matches!(m, 0 | 48 | 65) {
+                    Cut::Full
+                } else {
+                    Cut::Partial
+                };
+                self.blocks.push(Block::Cut(cut));
+            }
+            _ => {
+                self.byte();
+            }
+        }
+    }
+
+    fn fs(&mut self) {
+        match self.byte() {
+            b'&' => self.kanji = true,
+            b'.' => self.kanji = false,
+            _ => {
+                self.byte();
+            }
+        }
+    }
+}
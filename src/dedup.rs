@@ -0,0 +1,116 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, TimeDelta};
+use tracing::warn;
+
+use crate::{ack, printer::PrintData};
+
+/// How long printed events are remembered for by default
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Events printed recently, so that reconnects & overlapping polls don't print them twice
+///
+/// Keyed on the print's service & event ID, see [`PrintData::event_id`]. Events are only
+/// remembered once printed, see [`Self::printed`]; Until then, prints of them are skipped while
+/// one is waiting for the printer. Optionally persisted to a JSON file, so that restarts don't
+/// reprint them either.
+pub struct Dedup {
+    ttl: TimeDelta,
+    seen: BTreeMap<String, DateTime<Local>>,
+    /// Events of prints waiting for the printer
+    pending: BTreeSet<String>,
+    path: Option<PathBuf>,
+}
+
+impl Dedup {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl: TimeDelta::from_std(ttl).unwrap_or_else(|_| TimeDelta::max_value()),
+            seen: BTreeMap::new(),
+            pending: BTreeSet::new(),
+            path: None,
+        }
+    }
+
+    /// Remembers printed events in the file at `path`, loading the ones from last run
+    pub fn with_file(mut self, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        match std::fs::read(&path) {
+            Ok(bytes) => match serde_json::from_slice(&bytes) {
+                Ok(seen) => self.seen = seen,
+                Err(e) => warn!("Ignoring unreadable dedup file {}: {e}", path.display()),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Whether the print's event was already printed within the TTL, or is waiting for the
+    /// printer; Counts it as waiting otherwise
+    pub fn is_duplicate(&mut self, data: &PrintData) -> bool {
+        let Some(event_id) = &data.event_id else {
+            return false;
+        };
+        let key = key(data.service.as_deref().unwrap_or_default(), event_id);
+
+        let now = Local::now();
+        let ttl = self.ttl;
+        self.seen.retain(|_, seen| now - *seen < ttl);
+        if self.seen.contains_key(&key) || self.pending.contains(&key) {
+            return true;
+        }
+
+        self.pending.insert(key);
+        false
+    }
+
+    /// Remembers the events of a print that made it to the printer, see [`ack::events`]
+    pub fn printed(&mut self, data: &PrintData) {
+        let events = ack::events(data);
+        if events.is_empty() {
+            return;
+        }
+
+        let now = Local::now();
+        for (service, event_id) in events {
+            let key = key(&service, &event_id);
+            self.pending.remove(&key);
+            self.seen.insert(key, now);
+        }
+        self.save();
+    }
+
+    /// Forgets the events of a print that was dropped rather than printed, so they may be
+    /// printed later
+    pub fn dropped(&mut self, data: &PrintData) {
+        for (service, event_id) in ack::events(data) {
+            self.pending.remove(&key(&service, &event_id));
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let tmp = path.with_extension("tmp");
+        let saved = serde_json::to_vec(&self.seen)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(&tmp, json))
+            .and_then(|()| std::fs::rename(&tmp, path));
+        if let Err(e) = saved {
+            warn!("Unable to write dedup file {}: {e}", path.display());
+        }
+    }
+}
+
+fn key(service: &str, event_id: &str) -> String {
+    format!("{service}:{event_id}")
+}
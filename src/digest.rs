@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use chrono::Local;
+use tokio::time::Instant;
+
+use crate::{
+    ack,
+    printer::{PrintData, Span},
+};
+
+pub const DEFAULT_MAX_ITEMS: usize = 20;
+
+/// Service digests are printed as, see [`PrintData::service`]
+const SERVICE: &str = "digest";
+
+/// Whether the print is a digest of several prints
+pub fn is_digest(data: &PrintData) -> bool {
+    data.service.as_deref() == Some(SERVICE)
+}
+
+/// Prints held back & printed together as one compact receipt, so that a night's worth of
+/// notifications doesn't take a receipt each
+///
+/// Held prints are let go once `interval` has passed since the first one, or once there are
+/// `max_items` of them.
+pub struct Digest {
+    interval: Duration,
+    max_items: usize,
+    items: Vec<PrintData>,
+    /// When the first held print is due
+    due: Option<Instant>,
+}
+
+impl Digest {
+    pub fn new(interval: Duration, max_items: usize) -> Self {
+        Self {
+            interval,
+            max_items: max_items.max(1),
+            items: Vec::new(),
+            due: None,
+        }
+    }
+
+    /// Holds a print back; Returns the digest once it's full
+    pub fn add(&mut self, data: PrintData) -> Option<PrintData> {
+        self.due
+            .get_or_insert_with(|| Instant::now() + self.interval);
+        self.items.push(data);
+        if self.items.len() >= self.max_items {
+            return self.take();
+        }
+        None
+    }
+
+    /// When the held prints are due, if there are any
+    pub const fn due(&self) -> Option<Instant> {
+        self.due
+    }
+
+    /// Combines the held prints into one; A single print is passed on as is
+    pub fn take(&mut self) -> Option<PrintData> {
+        self.due = None;
+        let mut items = std::mem::take(&mut self.items);
+        if items.len() <= 1 {
+            return items.pop();
+        }
+
+        let since = items[0].timestamp.format("%H:%M");
+        let mut message = Vec::new();
+        let mut qr_codes = Vec::new();
+        let mut merged_events = Vec::new();
+        for item in &mut items {
+            merged_events.extend(ack::events(item));
+            let time = item.timestamp.format("%H:%M");
+            message.push(Span::bold(format!("[{time}] {}\n", item.title)));
+            if let Some(subtitle) = item.subtitle.take() {
+                message.push(Span::plain(format!("{subtitle}\n")));
+            }
+            if let Some(text) = item.message.take() {
+                message.extend(text.into_spans());
+                message.push(Span::plain("\n"));
+            }
+            message.push(Span::plain("\n"));
+            qr_codes.append(&mut item.qr_codes);
+        }
+
+        Some(PrintData {
+            service: Some(SERVICE.to_string()),
+            logo: Some(SERVICE.to_string()),
+            title: "Digest".to_string(),
+            subtitle: Some(format!("{} notifications since {since}", items.len())),
+            message: Some(message.into()),
+            qr_codes,
+            merged_events,
+            timestamp: Local::now(),
+            ..Default::default()
+        })
+    }
+}
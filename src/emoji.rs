@@ -0,0 +1,140 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use emojis::Emoji;
+use serde::Deserialize;
+use tracing::{debug, warn};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{codepage::CodePage, profile::Profile, protocol::Protocol, raster::Raster};
+
+/// Width in dots emoji images are kept at; Scaled down to the font's cell when printed
+const EMOJI_WIDTH: u32 = 24;
+
+/// Emoji images by emoji, decoded once on first use; `None` when there's no usable image file
+static IMAGES: Mutex<BTreeMap<&'static str, Option<Arc<Raster>>>> = Mutex::new(BTreeMap::new());
+
+/// How emoji are printed, as no printer font has glyphs for them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmojiStrategy {
+    /// Left out entirely
+    Strip,
+    /// Replaced with their `:shortcode:`, e.g. `:wave:`
+    #[default]
+    Shortcode,
+    /// Printed inline as user-defined characters, see [`image`]; ESC/POS only, emoji without
+    /// an image fall back to their shortcode
+    Image,
+}
+
+impl FromStr for EmojiStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "strip" => Ok(Self::Strip),
+            "shortcode" => Ok(Self::Shortcode),
+            "image" => Ok(Self::Image),
+            other => Err(format!(
+                "Unknown emoji strategy `{other}`; expected strip, shortcode or image"
+            )),
+        }
+    }
+}
+
+/// What an emoji is printed as
+pub enum Glyph {
+    Text(String),
+    Image(Arc<Raster>),
+}
+
+impl EmojiStrategy {
+    pub fn render(self, emoji: &'static Emoji, protocol: Protocol) -> Glyph {
+        match self {
+            Self::Strip => Glyph::Text(String::new()),
+            Self::Image if protocol == Protocol::EscPos => {
+                image(emoji).map_or_else(|| Glyph::Text(shortcode(emoji)), Glyph::Image)
+            }
+            Self::Shortcode | Self::Image => Glyph::Text(shortcode(emoji)),
+        }
+    }
+}
+
+/// Emoji a grapheme stands for, unless the code page prints it as is, e.g. `©`
+pub fn find(grapheme: &str, code_page: CodePage) -> Option<&'static Emoji> {
+    let mut chars = grapheme.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii() || code_page.contains(c) {
+            return None;
+        }
+    }
+    emojis::get(grapheme).or_else(|| emojis::get(grapheme.trim_end_matches('\u{FE0F}')))
+}
+
+/// Applies the profile's emoji strategy ahead of wrapping, so lines are measured as printed
+///
+/// Emoji printed as images are left in place, for
+/// [`CommandBuffer::text`](crate::escpos::CommandBuffer::text) to print.
+pub fn replace<'a>(text: &'a str, profile: &Profile) -> Cow<'a, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    for grapheme in text.graphemes(true) {
+        match find(grapheme, profile.code_page).map(|e| profile.emoji.render(e, profile.protocol)) {
+            Some(Glyph::Text(text)) => out.push_str(&text),
+            Some(Glyph::Image(_)) | None => out.push_str(grapheme),
+        }
+    }
+    Cow::Owned(out)
+}
+
+fn shortcode(emoji: &Emoji) -> String {
+    let code = emoji
+        .shortcode()
+        .map_or_else(|| emoji.name().replace(' ', "_"), str::to_string);
+    format!(":{code}:")
+}
+
+/// Image of an emoji, read from `<EMOJI_DIR>/<codepoints>.png`
+///
+/// Files are named after the emoji's codepoints in lowercase hex joined by `-`, leaving out
+/// variation selectors, like Twemoji's (e.g. `1f44b.png`). `EMOJI_DIR` defaults to `emoji`.
+pub fn image(emoji: &'static Emoji) -> Option<Arc<Raster>> {
+    let mut images = IMAGES.lock().unwrap();
+    images
+        .entry(emoji.as_str())
+        .or_insert_with(|| load(emoji))
+        .clone()
+}
+
+fn load(emoji: &Emoji) -> Option<Arc<Raster>> {
+    let name = emoji
+        .as_str()
+        .chars()
+        .filter(|&c| c != '\u{FE0F}')
+        .map(|c| format!("{:x}", u32::from(c)))
+        .collect::<Vec<_>>()
+        .join("-");
+    let dir = std::env::var("EMOJI_DIR").unwrap_or_else(|_| "emoji".to_string());
+    let path = PathBuf::from(dir).join(format!("{name}.png"));
+    let Ok(bytes) = std::fs::read(&path) else {
+        debug!("No image for {} at {}", emoji.as_str(), path.display());
+        return None;
+    };
+
+    match Raster::from_bytes(&bytes, EMOJI_WIDTH) {
+        Ok(image) => Some(Arc::new(image)),
+        Err(e) => {
+            warn!("Unable to decode emoji {}: {e}", path.display());
+            None
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use reqwest::StatusCode;
+use serde_json::Value;
+use tokio::sync::mpsc::error::SendError;
+
+use crate::secrets;
+
+/// Errors services run into talking to their APIs
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Env `{0}` not set")]
+    MissingEnv(String),
+    /// An env var set to something the service can't use, e.g. a malformed time
+    #[error("Invalid env `{0}`: {1}")]
+    InvalidEnv(String, String),
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// Boxed, as it's several times the size of the other errors
+    #[error("Websocket error: {0}")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    #[error("Invalid URL: {0}")]
+    Url(#[from] tokio_tungstenite::tungstenite::http::uri::InvalidUri),
+    #[error("TLS error: {0}")]
+    Tls(#[from] native_tls::Error),
+    #[error("IMAP error: {0}")]
+    Imap(#[from] imap::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Malformed JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Malformed timestamp: {0}")]
+    Timestamp(#[from] chrono::ParseError),
+    #[error("Malformed XML: {0}")]
+    Xml(#[from] roxmltree::Error),
+    /// Signing in as a GitHub App failed, e.g. with a malformed private key
+    #[error("JWT error: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    /// A field missing from, or of the wrong type in, an API response
+    #[error("Response is missing `{0}`")]
+    MissingField(String),
+    #[error("Unexpected response status {0}")]
+    Status(StatusCode),
+    /// A reply the service can't make sense of, e.g. an error from MPD or an unknown feed format
+    #[error("Unexpected response: {0}")]
+    Response(String),
+    /// The API's rate limit is used up, for this long
+    #[error("Rate limited for {0:?}")]
+    RateLimited(Duration),
+    /// Credentials were rejected or have expired
+    #[error("Unauthorized")]
+    Unauthorized,
+    /// The printer loop is gone, i.e. the daemon is shutting down
+    #[error("Print queue closed")]
+    QueueClosed,
+    /// The service was stopped while waiting, e.g. for a login
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(e))
+    }
+}
+
+impl<T> From<SendError<T>> for Error {
+    fn from(_: SendError<T>) -> Self {
+        Self::QueueClosed
+    }
+}
+
+impl Error {
+    /// Whether trying again later may succeed, e.g. network hiccups & server errors; Other
+    /// errors need fixing first, like bad configuration
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Http(e) => e.status().is_none_or(is_transient_status),
+            // Malformed responses are usually a hiccup on the API's end
+            Self::WebSocket(_)
+            | Self::Imap(_)
+            | Self::Io(_)
+            | Self::Json(_)
+            | Self::Timestamp(_)
+            | Self::Xml(_)
+            | Self::MissingField(_)
+            | Self::Response(_)
+            | Self::RateLimited(_) => true,
+            Self::Status(status) => is_transient_status(*status),
+            Self::MissingEnv(_)
+            | Self::InvalidEnv(..)
+            | Self::Url(_)
+            | Self::Tls(_)
+            | Self::Jwt(_)
+            | Self::Unauthorized
+            | Self::QueueClosed
+            | Self::Cancelled => false,
+        }
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// String at a JSON pointer, e.g. `/subject/title`
+pub fn str_at<'a>(value: &'a Value, pointer: &str) -> Result<&'a str> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::MissingField(pointer.to_string()))
+}
+
+/// Reads an env var a service can't do without, or its secret, see [`secrets::var`]
+pub fn env(var: &str) -> Result<String> {
+    secrets::var(var).map_err(|_| Error::MissingEnv(var.to_string()))
+}
+
+/// Parses an env var a service can't do without, e.g. a number, see [`env`]
+pub fn env_parse<T: FromStr>(var: &str) -> Result<T>
+where
+    T::Err: Display,
+{
+    env(var)?
+        .trim()
+        .parse()
+        .map_err(|e| Error::InvalidEnv(var.to_string(), format!("{e}")))
+}
+
+/// Parses an env var a service has a default for, see [`env_parse`]; `default` if it isn't set
+pub fn env_or<T: FromStr>(var: &str, default: T) -> Result<T>
+where
+    T::Err: Display,
+{
+    match env_parse(var) {
+        Err(Error::MissingEnv(_)) => Ok(default),
+        parsed => parsed,
+    }
+}
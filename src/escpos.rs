@@ -0,0 +1,654 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    codepage::{CjkEncoding, CodePage},
+    emoji::{self, EmojiStrategy, Glyph},
+    profile::{Cut, Profile},
+    protocol::Protocol,
+    raster::Raster,
+};
+
+pub const BEL: u8 = 0x07;
+pub const ESC: u8 = 0x1B;
+pub const FS: u8 = 0x1C;
+pub const GS: u8 = 0x1D;
+pub const RS: u8 = 0x1E;
+pub const LF: u8 = 0x0A;
+pub const SUB: u8 = 0x1A;
+pub const FF: u8 = 0x0C;
+pub const SI: u8 = 0x0F;
+
+pub const JUSTIFY_LEFT: &[u8; 3] = &[ESC, b'a', 0x0];
+pub const JUSTIFY_CENTER: &[u8; 3] = &[ESC, b'a', 0x1];
+pub const JUSTIFY_RIGHT: &[u8; 3] = &[ESC, b'a', 0x2];
+
+/// Emphasis of printed text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub bold: bool,
+    pub underline: bool,
+    /// White text on black
+    pub invert: bool,
+}
+
+/// Range of character codes user-defined characters are stored at
+const FIRST_GLYPH: u8 = 0x20;
+const LAST_GLYPH: u8 = 0x7E;
+
+/// Printer settings that apply to every line printed after them, until changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Setting {
+    CodePage,
+    Smoothing,
+    Kanji,
+    Justify,
+    Style,
+    Size,
+    Font,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Justify {
+    Left,
+    Center,
+    Right,
+}
+
+/// Typed builder for printer commands, encoded for either ESC/POS or Star line mode
+///
+/// ```ignore
+/// let mut out = CommandBuffer::new(&Profile::default());
+/// out.justify(Justify::Center).char_size(2, 2).text("Hello").line().cut(Cut::Full, None);
+/// ```
+pub struct CommandBuffer {
+    protocol: Protocol,
+    code_page: CodePage,
+    cjk: Option<CjkEncoding>,
+    emoji: EmojiStrategy,
+    /// Whether the smaller font is selected, which user-defined characters are sized for
+    small: bool,
+    /// Character code the next user-defined character is stored at
+    next_glyph: u8,
+    /// Lines are printed rotated & last to first, see [`Profile::upside_down`]
+    upside_down: bool,
+    /// Upside down only; Commands each setting was last changed with, repeated at the start of
+    /// every line so it prints the same wherever it ends up
+    settings: BTreeMap<Setting, Vec<u8>>,
+    /// Upside down only; Line being built, then the finished lines to be sent in reverse
+    line: Option<Vec<u8>>,
+    lines: Vec<Vec<u8>>,
+    bytes: Vec<u8>,
+}
+
+impl CommandBuffer {
+    /// Encodes for the profile's protocol; Text is encoded in its code page, see
+    /// [`Self::select_code_page`]
+    pub const fn new(profile: &Profile) -> Self {
+        Self {
+            protocol: profile.protocol,
+            code_page: profile.code_page,
+            cjk: profile.cjk,
+            emoji: profile.emoji,
+            small: false,
+            next_glyph: FIRST_GLYPH,
+            upside_down: profile.upside_down,
+            settings: BTreeMap::new(),
+            line: None,
+            lines: Vec::new(),
+            bytes: Vec::new(),
+        }
+    }
+
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        self.flush_lines();
+        self.bytes
+    }
+
+    /// Raw, already encoded bytes; Kept together as one block when upside down, e.g. a whole
+    /// nested receipt or image
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.push(bytes);
+        self.end_line();
+        self
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> &mut Self {
+        if !self.upside_down {
+            self.bytes.extend_from_slice(bytes);
+            return self;
+        }
+        if self.line.is_none() {
+            // ESC { n / SI; Only takes effect at the start of a line
+            let mut line = match self.protocol {
+                Protocol::EscPos => vec![ESC, b'{', 0x01],
+                Protocol::StarLine => vec![SI],
+            };
+            line.extend(self.settings.values().flatten());
+            self.line = Some(line);
+        }
+        if let Some(line) = &mut self.line {
+            line.extend_from_slice(bytes);
+        }
+        self
+    }
+
+    /// Sends bytes ahead of any lines held back, for commands that aren't part of a line
+    fn direct(&mut self, bytes: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    fn set(&mut self, setting: Setting, bytes: &[u8]) -> &mut Self {
+        self.push(bytes);
+        if self.upside_down {
+            self.settings.insert(setting, bytes.to_vec());
+        }
+        self
+    }
+
+    fn end_line(&mut self) {
+        if let Some(line) = self.line.take() {
+            self.lines.push(line);
+        }
+    }
+
+    /// Sends the lines held back when upside down, last to first, so the receipt reads top to
+    /// bottom as it comes out
+    fn flush_lines(&mut self) {
+        self.end_line();
+        while let Some(line) = self.lines.pop() {
+            self.bytes.extend(line);
+        }
+    }
+
+    /// Text encoded in the code page, see [`CodePage::encode`]
+    ///
+    /// Emoji are printed according to the profile's [`EmojiStrategy`]. With a CJK font, runs of
+    /// characters outside of the code page are printed in kanji mode.
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        if self.upside_down {
+            if let Some((first, rest)) = text.split_once('\n') {
+                return self.text(first).line().text(rest);
+            }
+        }
+        if text.is_ascii() {
+            return self.encode(text);
+        }
+
+        let mut plain = String::new();
+        for grapheme in text.graphemes(true) {
+            let Some(emoji) = emoji::find(grapheme, self.code_page) else {
+                plain.push_str(grapheme);
+                continue;
+            };
+            match self.emoji.render(emoji, self.protocol) {
+                Glyph::Text(text) => plain.push_str(&text),
+                Glyph::Image(image) => {
+                    self.encode(&std::mem::take(&mut plain));
+                    self.glyph(&image);
+                }
+            }
+        }
+        self.encode(&plain)
+    }
+
+    fn encode(&mut self, text: &str) -> &mut Self {
+        let Some(cjk) = self.cjk else {
+            let encoded = self.code_page.encode(text);
+            return self.push(&encoded);
+        };
+
+        let mut single_byte = String::new();
+        let mut kanji = Vec::new();
+        for c in text.chars() {
+            // Chars the code page has are printed as is, CJK ones the font lacks transliterated
+            if let Some(bytes) = cjk.encode(c).filter(|_| !self.code_page.contains(c)) {
+                let encoded = self.code_page.encode(&std::mem::take(&mut single_byte));
+                self.push(&encoded);
+                kanji.extend(bytes);
+            } else {
+                self.kanji(&std::mem::take(&mut kanji));
+                single_byte.push(c);
+            }
+        }
+        self.kanji(&kanji);
+        let encoded = self.code_page.encode(&single_byte);
+        self.push(&encoded)
+    }
+
+    /// Double-byte characters, wrapped in kanji mode
+    fn kanji(&mut self, bytes: &[u8]) -> &mut Self {
+        if bytes.is_empty() {
+            return self;
+        }
+        match self.protocol {
+            // FS & ... FS .
+            Protocol::EscPos => self.push(&[FS, b'&']).push(bytes).push(&[FS, b'.']),
+            // ESC p ... ESC q
+            Protocol::StarLine => self.push(&[ESC, b'p']).push(bytes).push(&[ESC, b'q']),
+        }
+    }
+
+    /// Prints an image in place of a character, as a user-defined character sized for the
+    /// current font; ESC/POS only
+    fn glyph(&mut self, image: &Raster) -> &mut Self {
+        // Font A cells are 12 x 24 dots, font B 9 x 17; Glyphs are square, centered in the cell
+        let (cell_width, cell_height): (u8, usize) = if self.small { (9, 17) } else { (12, 24) };
+        let (width, height, rows) = image.dither(u32::from(cell_width));
+        let (width, height) = (width as usize, (height as usize).min(cell_height));
+        let row_bytes = width.div_ceil(8);
+        let (left, top) = (
+            (usize::from(cell_width) - width) / 2,
+            (cell_height - height) / 2,
+        );
+
+        // Column by column, 3 bytes from top to bottom, MSB first
+        let mut data = vec![0u8; usize::from(cell_width) * 3];
+        for y in 0..height {
+            for x in 0..width {
+                if rows[y * row_bytes + x / 8] & (0x80 >> (x % 8)) != 0 {
+                    let (column, row) = (left + x, top + y);
+                    data[column * 3 + row / 8] |= 0x80 >> (row % 8);
+                }
+            }
+        }
+
+        // A fresh code for every glyph, so glyphs still waiting to be printed aren't redefined
+        let code = self.next_glyph;
+        self.next_glyph = if code == LAST_GLYPH {
+            FIRST_GLYPH
+        } else {
+            code + 1
+        };
+
+        // ESC & y c1 c2 x d1...d(y * x)
+        self.push(&[ESC, b'&', 0x03, code, code, cell_width]);
+        self.push(&data);
+        // ESC % n; Only while selected are user-defined characters printed
+        self.push(&[ESC, b'%', 0x01, code, ESC, b'%', 0x00])
+    }
+
+    /// Prints the current line
+    pub fn line(&mut self) -> &mut Self {
+        self.push(&[LF]);
+        self.end_line();
+        self
+    }
+
+    /// Resets the printer
+    pub fn init(&mut self) -> &mut Self {
+        self.settings.clear();
+        self.direct(&[ESC, b'@'])
+    }
+
+    /// Switches the printer over to the code page text is encoded in
+    pub fn select_code_page(&mut self) -> &mut Self {
+        let n = self.code_page.number(self.protocol);
+        match self.protocol {
+            Protocol::EscPos => self.set(Setting::CodePage, &[ESC, b't', n]),
+            Protocol::StarLine => self.set(Setting::CodePage, &[ESC, GS, b't', n]),
+        }
+    }
+
+    pub fn font_smoothing(&mut self) -> &mut Self {
+        match self.protocol {
+            Protocol::EscPos => self.set(Setting::Smoothing, &[GS, b'b', 0x01]),
+            // ESC GS b n
+            Protocol::StarLine => self.set(Setting::Smoothing, &[ESC, GS, b'b', 0x01]),
+        }
+    }
+
+    /// Leaves kanji mode, so bytes above 0x7F are read as single byte characters again
+    pub fn cancel_kanji(&mut self) -> &mut Self {
+        match self.protocol {
+            // FS .
+            Protocol::EscPos => self.set(Setting::Kanji, &[FS, b'.']),
+            // ESC q
+            Protocol::StarLine => self.set(Setting::Kanji, &[ESC, b'q']),
+        }
+    }
+
+    pub fn justify(&mut self, justify: Justify) -> &mut Self {
+        match self.protocol {
+            Protocol::EscPos => self.set(
+                Setting::Justify,
+                match justify {
+                    Justify::Left => JUSTIFY_LEFT,
+                    Justify::Center => JUSTIFY_CENTER,
+                    Justify::Right => JUSTIFY_RIGHT,
+                },
+            ),
+            // ESC GS a n
+            Protocol::StarLine => self.set(Setting::Justify, &[ESC, GS, b'a', justify as u8]),
+        }
+    }
+
+    /// Sets the emphasis of the text that follows
+    pub fn style(&mut self, style: Style) -> &mut Self {
+        let Style {
+            bold,
+            underline,
+            invert,
+        } = style;
+        let bytes = match self.protocol {
+            // ESC E n, ESC - n, GS B n
+            Protocol::EscPos => [
+                ESC,
+                b'E',
+                u8::from(bold),
+                ESC,
+                b'-',
+                u8::from(underline),
+                GS,
+                b'B',
+                u8::from(invert),
+            ]
+            .to_vec(),
+            // ESC E / ESC F, ESC - n, ESC 4 / ESC 5
+            Protocol::StarLine => [
+                ESC,
+                if bold { b'E' } else { b'F' },
+                ESC,
+                b'-',
+                u8::from(underline),
+                ESC,
+                if invert { b'4' } else { b'5' },
+            ]
+            .to_vec(),
+        };
+        self.set(Setting::Style, &bytes)
+    }
+
+    /// Sets the character size as width & height multipliers, from 1 to 6
+    pub fn char_size(&mut self, width: u8, height: u8) -> &mut Self {
+        let (width, height) = (width.clamp(1, 6) - 1, height.clamp(1, 6) - 1);
+        match self.protocol {
+            Protocol::EscPos => self.set(Setting::Size, &[GS, b'!', width << 4 | height]),
+            // ESC i n1 n2; height first
+            Protocol::StarLine => self.set(Setting::Size, &[ESC, b'i', height, width]),
+        }
+    }
+
+    /// Switches between the default & the smaller character font
+    pub fn small_font(&mut self, small: bool) -> &mut Self {
+        self.small = small;
+        match self.protocol {
+            Protocol::EscPos => self.set(Setting::Font, &[ESC, b'M', u8::from(small)]),
+            // ESC RS F n; Font B is the smaller one
+            Protocol::StarLine => self.set(Setting::Font, &[ESC, RS, b'F', u8::from(small)]),
+        }
+    }
+
+    /// Prints the buffer and feeds `n` extra lines
+    pub fn feed(&mut self, n: u8) -> &mut Self {
+        if self.upside_down {
+            // Every line is sent on its own, as they're reordered
+            for _ in 0..=n {
+                self.line();
+            }
+            return self;
+        }
+        match self.protocol {
+            Protocol::EscPos => self.push(&[ESC, b'd', n]),
+            Protocol::StarLine => self.push(&[ESC, b'a', n]),
+        }
+    }
+
+    /// Raster image of `width` bytes by `height` dots, rows MSB first
+    ///
+    /// Rotated by half a turn when upside down, as the printer only turns characters around.
+    pub fn raster(&mut self, width: u16, height: u16, data: &[u8]) -> &mut Self {
+        let rotated: Vec<u8>;
+        let data = if self.upside_down {
+            rotated = data.iter().rev().map(|byte| byte.reverse_bits()).collect();
+            &rotated
+        } else {
+            data
+        };
+        let [x_low, x_high] = width.to_le_bytes();
+        let [y_low, y_high] = height.to_le_bytes();
+        match self.protocol {
+            // GS v 0 m xL xH yL yH d1...dk
+            Protocol::EscPos => self.push(&[GS, b'v', b'0', 0x00, x_low, x_high, y_low, y_high]),
+            // ESC GS S m xL xH yL yH n d1...dk; m = 1 for monochrome
+            Protocol::StarLine => {
+                self.push(&[ESC, GS, b'S', 0x01, x_low, x_high, y_low, y_high, 0x00])
+            }
+        };
+        self.push(data).line() // Print
+    }
+
+    /// Model 2 QR code with 6 dot modules & error correction M
+    pub fn qr_code(&mut self, data: &[u8]) -> &mut Self {
+        match self.protocol {
+            // GS ( k <pL> <pH> <cn = 49> <fn> ...; see ESC/POS `GS ( k` function 165 - 181
+            Protocol::EscPos => {
+                self.push(&[GS, b'(', b'k', 0x04, 0x00, 0x31, 0x41, 0x32, 0x00]); // Select model 2
+                self.push(&[GS, b'(', b'k', 0x03, 0x00, 0x31, 0x43, 0x06]); // Module size 6 dots
+                self.push(&[GS, b'(', b'k', 0x03, 0x00, 0x31, 0x45, 0x31]); // Error correction M
+
+                // Store data in symbol storage area; length includes the 3 bytes of cn, fn & m
+                let [len_low, len_high] = u16::try_from(data.len() + 3)
+                    .unwrap_or(u16::MAX)
+                    .to_le_bytes();
+                self.push(&[GS, b'(', b'k', len_low, len_high, 0x31, 0x50, 0x30]);
+                self.push(data);
+
+                self.push(&[GS, b'(', b'k', 0x03, 0x00, 0x31, 0x51, 0x30]); // Print symbol
+            }
+            // ESC GS y S / D / P
+            Protocol::StarLine => {
+                self.push(&[ESC, GS, b'y', b'S', b'0', 0x02]); // Select model 2
+                self.push(&[ESC, GS, b'y', b'S', b'1', 0x01]); // Error correction M
+                self.push(&[ESC, GS, b'y', b'S', b'2', 0x06]); // Module size 6 dots
+
+                let [len_low, len_high] =
+                    u16::try_from(data.len()).unwrap_or(u16::MAX).to_le_bytes();
+                self.push(&[ESC, GS, b'y', b'D', b'1', 0x00, len_low, len_high]);
+                self.push(data);
+
+                self.push(&[ESC, GS, b'y', b'P']); // Print symbol
+            }
+        }
+        self.line() // Print
+    }
+
+    /// CODE128 barcode, 80 dots tall with its text printed below
+    pub fn barcode(&mut self, data: &[u8]) -> &mut Self {
+        match self.protocol {
+            Protocol::EscPos => {
+                // GS h n, GS w n, GS H n; Height, module width & text position
+                self.push(&[GS, b'h', 80, GS, b'w', 0x02, GS, b'H', 0x02]);
+                // GS k m n d1...dn; Data starts by selecting code set B
+                let len = u8::try_from(data.len() + 2).unwrap_or(u8::MAX);
+                self.push(&[GS, b'k', 73, len, b'{', b'B'])
+                    .push(&data[..usize::from(len - 2)]);
+            }
+            // ESC b n1 n2 n3 n4 d1...dk RS; Type, text & line feed, module width & height
+            Protocol::StarLine => {
+                self.push(&[ESC, b'b', 0x06, 0x02, 0x02, 80])
+                    .push(data)
+                    .push(&[RS]);
+            }
+        }
+        self.end_line(); // Printed right away
+        self
+    }
+
+    /// Sounds the buzzer `times` times
+    pub fn beep(&mut self, times: u8) -> &mut Self {
+        match self.protocol {
+            // ESC B n t; Each beep lasts t x 50ms. Not Epson's, but most clones with a buzzer
+            // have it
+            Protocol::EscPos => self.direct(&[ESC, b'B', times, 0x02]),
+            // BEL drives external device 1, the buzzer if one is connected
+            Protocol::StarLine => self.direct(&[BEL].repeat(usize::from(times))),
+        }
+    }
+
+    /// Pulses drawer port pin 2 or 5 for `pulse_ms`, which is what kicks a cash drawer open
+    pub fn kick_drawer(&mut self, pin: u8, pulse_ms: u16) -> &mut Self {
+        match self.protocol {
+            // ESC p m t1 t2; On & off times in 2ms units
+            Protocol::EscPos => {
+                let time = u8::try_from(pulse_ms / 2).unwrap_or(u8::MAX);
+                self.direct(&[ESC, b'p', u8::from(pin == 5), time, time])
+            }
+            // BEL / SUB drive peripheral device 1 / 2; Pulse width is set by DIP switches
+            Protocol::StarLine => self.direct(&[if pin == 5 { SUB } else { BEL }]),
+        }
+    }
+
+    /// Feeds the receipt past the cutter and cuts it, finishing the job
+    ///
+    /// Feeds `feed` lines first; By default 6 lines on ESC/POS, and up to the cutter on Star.
+    pub fn cut(&mut self, cut: Cut, feed: Option<u8>) -> &mut Self {
+        self.flush_lines();
+        match self.protocol {
+            Protocol::EscPos => {
+                self.direct(&[ESC, b'd', feed.unwrap_or(6), LF]); // Feed 6 lines by default
+                match cut {
+                    Cut::Full => self.direct(&[ESC, b'i']), // Full cut; I think the auto cutter is 2 lines(?) behind, so the line above effectively feeds 4 line
+                    Cut::Partial => self.direct(&[ESC, b'm']), // Partial cut
+                    Cut::None => self,
+                };
+                self.direct(&[FF]) // Print and return to standard mode in page mode; Finishes the job
+            }
+            // ESC d n; n = 0 / 1 cuts right away, n = 2 / 3 feeds up to the cutter first
+            Protocol::StarLine => match (cut, feed) {
+                (Cut::Full, None) => self.direct(&[ESC, b'd', 0x02]),
+                (Cut::Partial, None) => self.direct(&[ESC, b'd', 0x03]),
+                (Cut::Full, Some(n)) => self.direct(&[ESC, b'a', n, ESC, b'd', 0x00]),
+                (Cut::Partial, Some(n)) => self.direct(&[ESC, b'a', n, ESC, b'd', 0x01]),
+                (Cut::None, n) => self.direct(&[ESC, b'a', n.unwrap_or(6)]), // Feed 6 lines by default
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(protocol: Protocol) -> CommandBuffer {
+        CommandBuffer::new(&Profile {
+            protocol,
+            ..Profile::default()
+        })
+    }
+
+    #[test]
+    fn char_size() {
+        let mut out = buffer(Protocol::EscPos);
+        out.char_size(2, 2).char_size(1, 3).char_size(0, 9);
+        assert_eq!(
+            out.into_bytes(),
+            [GS, b'!', 0x11, GS, b'!', 0x02, GS, b'!', 0x05]
+        );
+
+        // Height first
+        let mut out = buffer(Protocol::StarLine);
+        out.char_size(2, 2).char_size(1, 3);
+        assert_eq!(out.into_bytes(), [ESC, b'i', 1, 1, ESC, b'i', 2, 0]);
+    }
+
+    #[test]
+    fn justify() {
+        let mut out = buffer(Protocol::EscPos);
+        out.justify(Justify::Center).justify(Justify::Right);
+        assert_eq!(out.into_bytes(), [*JUSTIFY_CENTER, *JUSTIFY_RIGHT].concat());
+
+        let mut out = buffer(Protocol::StarLine);
+        out.justify(Justify::Center).justify(Justify::Right);
+        assert_eq!(out.into_bytes(), [ESC, GS, b'a', 1, ESC, GS, b'a', 2]);
+    }
+
+    #[test]
+    fn style() {
+        let style = Style {
+            bold: true,
+            underline: false,
+            invert: true,
+        };
+        let mut out = buffer(Protocol::EscPos);
+        out.style(style);
+        assert_eq!(out.into_bytes(), [ESC, b'E', 1, ESC, b'-', 0, GS, b'B', 1]);
+
+        let mut out = buffer(Protocol::StarLine);
+        out.style(style);
+        assert_eq!(out.into_bytes(), [ESC, b'E', ESC, b'-', 0, ESC, b'4']);
+    }
+
+    #[test]
+    fn text_in_code_page() {
+        let mut out = buffer(Protocol::EscPos);
+        out.select_code_page().text("Café\u{7}").line();
+        assert_eq!(out.into_bytes(), [ESC, b't', 0, b'C', b'a', b'f', 0x82, LF]);
+
+        let mut out = buffer(Protocol::StarLine);
+        out.select_code_page().text("Café");
+        assert_eq!(out.into_bytes(), [ESC, GS, b't', 1, b'C', b'a', b'f', 0x82]);
+    }
+
+    #[test]
+    fn kanji() {
+        let profile = Profile {
+            cjk: Some(CjkEncoding::ShiftJis),
+            ..Profile::default()
+        };
+        // é is in the code page, 한 isn't in the font
+        let mut out = CommandBuffer::new(&profile);
+        out.text("é日本한");
+        assert_eq!(
+            out.into_bytes(),
+            [0x82, FS, b'&', 0x93, 0xFA, 0x96, 0x7B, FS, b'.', b'h', b'a', b'n']
+        );
+
+        let mut out = CommandBuffer::new(&Profile {
+            protocol: Protocol::StarLine,
+            ..profile
+        });
+        out.text("日");
+        assert_eq!(out.into_bytes(), [ESC, b'p', 0x93, 0xFA, ESC, b'q']);
+    }
+
+    #[test]
+    fn cut() {
+        let mut out = buffer(Protocol::EscPos);
+        out.cut(Cut::Full, None).cut(Cut::Partial, Some(2));
+        assert_eq!(
+            out.into_bytes(),
+            [ESC, b'd', 6, LF, ESC, b'i', FF, ESC, b'd', 2, LF, ESC, b'm', FF]
+        );
+
+        let mut out = buffer(Protocol::StarLine);
+        out.cut(Cut::Full, None).cut(Cut::Partial, Some(2));
+        assert_eq!(
+            out.into_bytes(),
+            [ESC, b'd', 0x02, ESC, b'a', 2, ESC, b'd', 0x01]
+        );
+    }
+
+    #[test]
+    fn upside_down() {
+        let mut out = CommandBuffer::new(&Profile {
+            upside_down: true,
+            ..Profile::default()
+        });
+        out.init()
+            .justify(Justify::Center)
+            .text("a\nb")
+            .line()
+            .cut(Cut::None, Some(0));
+        // Last line first, each repeating the settings it's printed with
+        let mut expected = vec![ESC, b'@'];
+        expected.extend([ESC, b'{', 1, ESC, b'a', 1, b'b', LF]);
+        expected.extend([ESC, b'{', 1, ESC, b'a', 1, b'a', LF]);
+        expected.extend([ESC, b'd', 0, LF, FF]);
+        assert_eq!(out.into_bytes(), expected);
+    }
+}
@@ -0,0 +1,161 @@
+use std::{path::Path, sync::Mutex};
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, types::Type, Connection};
+use tracing::warn;
+
+use crate::printer::{Message, PrintData};
+
+/// Most entries [`History::search`] returns by default
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Store the print loop records into, once opened by [`open`]
+static HISTORY: Mutex<Option<History>> = Mutex::new(None);
+
+/// SQLite archive of every print that made it to the printer
+pub struct History {
+    conn: Connection,
+}
+
+/// A print as archived, see [`History::search`]
+pub struct Entry {
+    pub id: i64,
+    pub service: Option<String>,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub body: Option<String>,
+    pub printed_at: DateTime<Local>,
+}
+
+/// Filters for [`History::search`]; Unset ones match everything
+#[derive(Debug, Default)]
+pub struct Query {
+    pub service: Option<String>,
+    pub since: Option<DateTime<Local>>,
+    /// Text found in the title, subtitle or body
+    pub text: Option<String>,
+    pub limit: usize,
+}
+
+impl History {
+    /// Opens the database at `path`, creating it if needed
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS prints (
+                id INTEGER PRIMARY KEY,
+                service TEXT,
+                title TEXT NOT NULL,
+                subtitle TEXT,
+                body TEXT,
+                printed_at INTEGER NOT NULL,
+                -- The whole print as JSON, so it can be printed again
+                data TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS prints_printed_at ON prints (printed_at);",
+        )?;
+        Ok(Self { conn })
+    }
+
+    pub fn insert(&self, data: &PrintData, printed_at: DateTime<Local>) -> rusqlite::Result<()> {
+        let json = serde_json::to_string(data)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        self.conn.execute(
+            "INSERT INTO prints (service, title, subtitle, body, printed_at, data)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                data.logo,
+                data.title,
+                data.subtitle,
+                data.message.as_ref().map(Message::text),
+                printed_at.timestamp(),
+                json,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Prints matching the query, latest first
+    pub fn search(&self, query: &Query) -> rusqlite::Result<Vec<Entry>> {
+        let text = query.text.as_ref().map(|text| format!("%{text}%"));
+        let mut statement = self.conn.prepare(
+            "SELECT id, service, title, subtitle, body, printed_at FROM prints
+            WHERE (?1 IS NULL OR service = ?1)
+                AND (?2 IS NULL OR printed_at >= ?2)
+                AND (?3 IS NULL OR title LIKE ?3 OR subtitle LIKE ?3 OR body LIKE ?3)
+            ORDER BY printed_at DESC, id DESC
+            LIMIT ?4",
+        )?;
+        let rows = statement.query_map(
+            params![
+                query.service,
+                query.since.map(|since| since.timestamp()),
+                text,
+                i64::try_from(query.limit).unwrap_or(i64::MAX),
+            ],
+            |row| {
+                let printed_at: i64 = row.get(5)?;
+                Ok(Entry {
+                    id: row.get(0)?,
+                    service: row.get(1)?,
+                    title: row.get(2)?,
+                    subtitle: row.get(3)?,
+                    body: row.get(4)?,
+                    printed_at: DateTime::from_timestamp(printed_at, 0)
+                        .unwrap_or_default()
+                        .with_timezone(&Local),
+                })
+            },
+        )?;
+        rows.collect()
+    }
+
+    /// Prints to print again, oldest first; The `last` ones printed & the ones with the IDs given
+    pub fn reprints(&self, last: usize, ids: &[i64]) -> rusqlite::Result<Vec<PrintData>> {
+        let mut statement = self.conn.prepare(
+            "SELECT data FROM prints
+            WHERE id IN (SELECT id FROM prints ORDER BY printed_at DESC, id DESC LIMIT ?1)
+                OR id IN (SELECT value FROM json_each(?2))
+            ORDER BY printed_at, id",
+        )?;
+        let ids = serde_json::to_string(ids)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        let rows = statement.query_map(
+            params![i64::try_from(last).unwrap_or(i64::MAX), ids],
+            |row| {
+                let data: String = row.get(0)?;
+                let mut data: PrintData = serde_json::from_str(&data).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into())
+                })?;
+                // Printed already, so dedup would skip it
+                data.event_id = None;
+                Ok(data)
+            },
+        )?;
+        rows.collect()
+    }
+}
+
+/// Archives every print from now on in the database at `path`, see [`record`]
+pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<()> {
+    *HISTORY.lock().unwrap() = Some(History::open(path)?);
+    Ok(())
+}
+
+/// Archives a print that made it to the printer; No-op unless [`open`]ed
+pub fn record(data: &PrintData) {
+    if let Some(history) = HISTORY.lock().unwrap().as_ref() {
+        if let Err(e) = history.insert(data, Local::now()) {
+            warn!("Unable to archive `{}` in the history: {e}", data.title);
+        }
+    }
+}
+
+/// See [`History::reprints`]; None unless [`open`]ed
+pub fn reprints(last: usize, ids: &[i64]) -> Option<rusqlite::Result<Vec<PrintData>>> {
+    HISTORY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|history| history.reprints(last, ids))
+}
@@ -1,9 +1,18 @@
-use std::time::Duration;
+use std::{future::Future, time::Duration};
 
 use reqwest::{
-    header::{HeaderMap, HeaderValue, USER_AGENT},
-    Client,
+    header::{HeaderMap, HeaderValue, RETRY_AFTER, USER_AGENT},
+    Client, RequestBuilder, Response, StatusCode,
 };
+use tracing::warn;
+
+use crate::retry::Backoff;
+
+/// Retries given to a request before its failure is handed back
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Longest wait between retries; Requests asked to wait longer by `Retry-After` aren't retried
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
 
 pub fn client() -> Client {
     let mut default_header = HeaderMap::new();
@@ -26,3 +35,62 @@ pub fn client() -> Client {
         .build()
         .expect("Unable to build HTTP client")
 }
+
+/// [`RequestBuilder::send`], retried with [`Backoff`] on connection errors, timeouts, 5xx & 429
+pub trait SendRetrying {
+    /// Hands back the last response or error once out of retries
+    fn send_retrying(self) -> impl Future<Output = reqwest::Result<Response>> + Send;
+}
+
+impl SendRetrying for RequestBuilder {
+    async fn send_retrying(self) -> reqwest::Result<Response> {
+        let mut backoff =
+            Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY).with_max_attempts(MAX_ATTEMPTS);
+        let mut request = self;
+        loop {
+            // Streamed bodies can't be sent twice
+            let Some(retry) = request.try_clone() else {
+                return request.send().await;
+            };
+            let result = request.send().await;
+            let reason = match &result {
+                Ok(res)
+                    if res.status().is_server_error()
+                        || res.status() == StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    res.status().to_string()
+                }
+                // Not the error itself, as it names the URL, which may hold a token
+                Err(e) if e.is_timeout() => "Timed out".to_string(),
+                Err(e) if e.is_connect() => "Unable to connect".to_string(),
+                _ => return result,
+            };
+            if backoff.exhausted() {
+                return result;
+            }
+
+            let mut delay = backoff.next_delay();
+            if let Some(retry_after) = result.as_ref().ok().and_then(retry_after) {
+                if retry_after > MAX_RETRY_DELAY {
+                    return result;
+                }
+                delay = delay.max(retry_after);
+            }
+            warn!("Request failed, retrying in {delay:?}: {reason}");
+            tokio::time::sleep(delay).await;
+            request = retry;
+        }
+    }
+}
+
+/// Wait asked for by the `Retry-After` header, if given in seconds
+fn retry_after(res: &Response) -> Option<Duration> {
+    let seconds = res
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
@@ -0,0 +1,156 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::printer::PrintData;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "entry", rename_all = "lowercase")]
+enum Entry<D> {
+    Queued { id: u64, data: D },
+    Done { id: u64 },
+}
+
+/// Done entries the file may hold per pending print before it's compacted while running
+const COMPACT_RATIO: usize = 8;
+
+/// Done entries written before the file is compacted while running at the earliest, so a
+/// mostly empty queue doesn't get it rewritten after every print
+const COMPACT_MIN_DONE: usize = 256;
+
+/// Append-only log of queued prints, one JSON entry per line, so that prints waiting for the
+/// printer survive a restart
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    /// Lines of the prints not marked done yet, by ID
+    pending: BTreeMap<u64, String>,
+    last_id: Option<u64>,
+    /// Done entries written since the file was last compacted
+    done: usize,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, returning prints that were queued but never marked done &
+    /// the ID to carry on numbering prints from
+    ///
+    /// The file is compacted down to those pending prints on every open, keeping the last ID,
+    /// and again whenever done entries pile up, see [`COMPACT_RATIO`].
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<(Self, Vec<(u64, PrintData)>, u64)> {
+        let path = path.as_ref().to_path_buf();
+
+        let mut pending = BTreeMap::new();
+        let mut last_id = None;
+        if let Ok(file) = File::open(&path) {
+            for line in BufReader::new(file).lines() {
+                // The last line may be cut short by a crash mid-write
+                match serde_json::from_str::<Entry<PrintData>>(&line?) {
+                    Ok(Entry::Queued { id, data }) => {
+                        pending.insert(id, data);
+                        last_id = last_id.max(Some(id));
+                    }
+                    Ok(Entry::Done { id }) => {
+                        pending.remove(&id);
+                        last_id = last_id.max(Some(id));
+                    }
+                    Err(e) => warn!("Skipping unreadable print journal entry: {e}"),
+                }
+            }
+        }
+
+        let lines = pending
+            .iter()
+            .map(|(id, data)| {
+                let line = serde_json::to_string(&Entry::Queued { id: *id, data })?;
+                Ok((*id, line))
+            })
+            .collect::<std::io::Result<BTreeMap<_, _>>>()?;
+        let file = compact(&path, &lines, last_id)?;
+        let next_id = last_id.map_or(0, |id| id + 1);
+        let journal = Self {
+            path,
+            file,
+            pending: lines,
+            last_id,
+            done: 0,
+        };
+        Ok((journal, pending.into_iter().collect(), next_id))
+    }
+
+    /// Records a print as it comes in, before it's queued or held back
+    pub fn queued(&mut self, id: u64, data: &PrintData) {
+        if let Some(line) = self.append(&Entry::Queued { id, data }) {
+            self.pending.insert(id, line);
+        }
+        self.last_id = self.last_id.max(Some(id));
+    }
+
+    /// Records a print as printed (or dropped), so it isn't replayed
+    pub fn done(&mut self, id: u64) {
+        self.append(&Entry::Done { id });
+        self.pending.remove(&id);
+        self.done += 1;
+        if self.done >= COMPACT_MIN_DONE && self.done > self.pending.len() * COMPACT_RATIO {
+            // Tried again only once as many have piled up, should it fail
+            self.done = 0;
+            match compact(&self.path, &self.pending, self.last_id) {
+                Ok(file) => self.file = file,
+                Err(e) => warn!(
+                    "Unable to compact print journal {}: {e}",
+                    self.path.display()
+                ),
+            }
+        }
+    }
+
+    /// Writes an entry, returning its line
+    fn append(&mut self, entry: &Entry<&PrintData>) -> Option<String> {
+        let written = serde_json::to_string(entry)
+            .map_err(std::io::Error::from)
+            .and_then(|line| {
+                writeln!(self.file, "{line}")?;
+                // Not just handed to the OS, so a power cut doesn't lose it either
+                self.file.sync_data()?;
+                Ok(line)
+            });
+        written
+            .map_err(|e| {
+                warn!(
+                    "Unable to write to print journal {}: {e}",
+                    self.path.display()
+                );
+            })
+            .ok()
+    }
+}
+
+/// Rewrites the journal at `path` down to the `pending` lines, returning it opened for appending
+///
+/// Written to a temporary file first & renamed over the journal, so a crash midway leaves the
+/// old one in place.
+fn compact(
+    path: &Path,
+    pending: &BTreeMap<u64, String>,
+    last_id: Option<u64>,
+) -> std::io::Result<File> {
+    let compacted = path.with_extension("tmp");
+    let mut file = File::create(&compacted)?;
+    for line in pending.values() {
+        writeln!(file, "{line}")?;
+    }
+    // Print IDs double as sequence numbers, which shouldn't restart from 0
+    if let Some(id) = last_id.filter(|id| !pending.contains_key(id)) {
+        let entry = serde_json::to_string(&Entry::<PrintData>::Done { id })?;
+        writeln!(file, "{entry}")?;
+    }
+    file.sync_all()?;
+    std::fs::rename(&compacted, path)?;
+
+    OpenOptions::new().append(true).open(path)
+}
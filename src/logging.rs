@@ -0,0 +1,87 @@
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing::warn;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Name of the log files in `LOG_DIR`, suffixed with the date they're for
+const LOG_FILE: &str = "notifi-printer.log";
+
+/// Flushes file logs & exported spans when dropped
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+    tracer_provider: Option<TracerProvider>,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take() {
+            if let Err(e) = provider.shutdown() {
+                warn!("Unable to flush exported spans: {e}");
+            }
+        }
+    }
+}
+
+/// Logs to stdout, filtered by `LOG_FILTER` (or `RUST_LOG`, `info` by default)
+///
+/// `LOG_DIR` also logs as JSON lines to a file in there rotated daily, filtered by
+/// `LOG_FILE_FILTER` (`LOG_FILTER` by default). Filters take per-module directives, e.g.
+/// `info,notifi_printer::service::twitch=trace`.
+///
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` exports spans over OTLP/gRPC, e.g. to Grafana Tempo; Each
+/// print gets a `notification` span, from its service to the printer.
+///
+/// Keep the returned guard around until exiting.
+pub fn init() -> LogGuard {
+    let directives = std::env::var("LOG_FILTER")
+        .or_else(|_| std::env::var("RUST_LOG"))
+        .unwrap_or_else(|_| "info".to_string());
+    let filter = |directives: &str| {
+        EnvFilter::try_new(directives).unwrap_or_else(|e| panic!("Invalid log filter! {e}"))
+    };
+
+    let stdout = fmt::layer().with_filter(filter(&directives));
+
+    let (file, file_guard) = std::env::var("LOG_DIR").map_or((None, None), |dir| {
+        let (writer, guard) =
+            tracing_appender::non_blocking(tracing_appender::rolling::daily(dir, LOG_FILE));
+        let file_directives = std::env::var("LOG_FILE_FILTER").unwrap_or(directives.clone());
+        let layer = fmt::layer()
+            .json()
+            .with_writer(writer)
+            .with_filter(filter(&file_directives));
+        (Some(layer), Some(guard))
+    });
+
+    let tracer_provider = std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").map(|_| {
+        // Reads the endpoint & headers from the standard `OTEL_EXPORTER_OTLP_*` envs
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .build()
+            .unwrap_or_else(|e| panic!("Unable to set up OTLP span exporter! {e}"));
+        TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                "notifi-printer",
+            )]))
+            .build()
+    });
+    let otel = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("notifi-printer"))
+            .with_filter(filter(&directives))
+    });
+
+    tracing_subscriber::registry()
+        .with(stdout)
+        .with(file)
+        .with(otel)
+        .init();
+
+    LogGuard {
+        _file: file_guard,
+        tracer_provider,
+    }
+}
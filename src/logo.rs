@@ -0,0 +1,43 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{debug, warn};
+
+use crate::raster::Raster;
+
+/// Width in dots logos are scaled down to
+const LOGO_WIDTH: u32 = 192;
+
+/// Logos by name, decoded once on first use; `None` when there's no usable logo file
+static LOGOS: Mutex<BTreeMap<String, Option<Arc<Raster>>>> = Mutex::new(BTreeMap::new());
+
+/// Logo printed on top of a service's receipts, read from `<LOGO_DIR>/<name>.png`
+///
+/// `LOGO_DIR` defaults to `logos`. Missing logos are simply left out.
+pub fn get(name: &str) -> Option<Arc<Raster>> {
+    let mut logos = LOGOS.lock().unwrap();
+    logos
+        .entry(name.to_string())
+        .or_insert_with(|| load(name))
+        .clone()
+}
+
+fn load(name: &str) -> Option<Arc<Raster>> {
+    let dir = std::env::var("LOGO_DIR").unwrap_or_else(|_| "logos".to_string());
+    let path = PathBuf::from(dir).join(format!("{name}.png"));
+    let Ok(bytes) = std::fs::read(&path) else {
+        debug!("No logo for {name} at {}", path.display());
+        return None;
+    };
+
+    match Raster::from_bytes(&bytes, LOGO_WIDTH) {
+        Ok(logo) => Some(Arc::new(logo)),
+        Err(e) => {
+            warn!("Unable to decode logo {}: {e}", path.display());
+            None
+        }
+    }
+}
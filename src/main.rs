@@ -6,60 +6,458 @@
 #![warn(clippy::complexity)]
 #![warn(clippy::style)]
 
+use std::time::Duration;
+
+use backend::{
+    bluetooth::BluetoothBackend, png::PngBackend, serial::SerialBackend, tcp::TcpBackend,
+    terminal::TerminalBackend, usb::UsbBackend,
+};
+use clap::Parser;
+use config::Config;
+use dedup::Dedup;
+use digest::Digest;
 use printer::{process_prints, PrintData};
-use tokio::{net::TcpStream, sync::mpsc};
+use profile::Profile;
+use queue::{OverflowPolicy, PrintQueue};
+use redact::Redact;
+use service::Supervisor;
+use throttle::Throttle;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::mpsc,
+};
 use tokio_util::{sync::CancellationToken, task::TaskTracker};
-use tracing::{debug, info};
+use tracing::{error, info, warn};
 
+mod ack;
+mod admin;
+mod backend;
+mod cli;
+mod codepage;
+mod config;
+mod dav;
+mod decode;
+mod dedup;
+mod digest;
+mod emoji;
+mod error;
+mod escpos;
+mod history;
 mod http;
+mod journal;
+mod logging;
+mod logo;
 mod printer;
+mod profile;
+mod protocol;
+mod queue;
+mod raster;
+mod redact;
+mod retry;
+mod schedule;
+mod secrets;
+mod server;
 mod service;
+mod sink;
+mod state;
+mod stats;
+mod status;
+mod systemd;
+mod template;
+mod test_page;
+mod throttle;
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
-    tracing_subscriber::fmt::init();
+    // Before logging is set up, so the config can set it up
+    Config::load().apply();
+    let _log_guard = logging::init();
+
+    let cli = cli::Cli::parse();
+    if let Some(command) = cli.command {
+        cli::run(command).await;
+        return;
+    }
 
+    // Producers (services & the HTTP server) are stopped before the printer, so nothing is
+    // queued once it starts draining
     let task_tracker = TaskTracker::new();
     let cancel_token = CancellationToken::new();
+    let printer_tracker = TaskTracker::new();
+    let printer_cancel = CancellationToken::new();
 
     info!("Starting Notifi-printer...");
 
-    let addr = std::env::var("PRINTER_ADDR").expect("Env `PRINTER_ADDR` not set!");
-    let printer_stream = TcpStream::connect(&addr)
-        .await
-        .expect("Unable to connect to {addr}");
-    debug!("Opened a TCP Stream @ {addr}");
-    let (sender, receiver) = mpsc::channel::<PrintData>(16);
-
-    {
-        let cancel = cancel_token.clone();
-        task_tracker.spawn(process_prints(cancel, printer_stream, receiver));
-    }
+    // Prints are taken off as soon as they're sent, to be journaled & queued; The queue is the
+    // buffer, its capacity what makes services wait
+    let (sender, receiver) = mpsc::channel::<PrintData>(1);
+    let drain_timeout =
+        secrets::var("SHUTDOWN_DRAIN_TIMEOUT").map_or(Duration::from_secs(10), |t| {
+            Duration::from_secs(
+                t.parse()
+                    .expect("Invalid SHUTDOWN_DRAIN_TIMEOUT! Expected seconds"),
+            )
+        });
 
-    {
-        let cancel = cancel_token.clone();
-        let sender = sender.clone();
-        task_tracker.spawn(service::github::start_service(cancel, sender));
+    let queue = build_queue();
+    let (commands, command_receiver) = mpsc::channel::<admin::Command>(16);
+    if let Ok(path) = secrets::var("STATE_FILE") {
+        state::open(&path).unwrap_or_else(|e| panic!("Unable to open state file {path}: {e}"));
     }
-    {
-        let cancel = cancel_token.clone();
-        let sender = sender.clone();
-        task_tracker.spawn(service::twitch::start_service(cancel, sender));
+    if let Ok(path) = secrets::var("HISTORY_DB") {
+        history::open(&path).unwrap_or_else(|e| panic!("Unable to open history {path}: {e}"));
     }
+    sink::open().unwrap_or_else(|e| panic!("{e}"));
+
+    spawn_printer(
+        &printer_tracker,
+        &printer_cancel,
+        receiver,
+        queue,
+        command_receiver,
+        drain_timeout,
+        cli.dry_run,
+    )
+    .await;
+
     {
         let cancel = cancel_token.clone();
         let sender = sender.clone();
-        task_tracker.spawn(service::bsky::start_service(cancel, sender));
+        let commands = commands.clone();
+        task_tracker.spawn(server::start_server(cancel, sender, commands));
     }
 
-    tokio::signal::ctrl_c()
-        .await
-        .expect("Unable to listen to CTRL + C signal!");
-    info!("CTRL + C signal caught! Stopping all tasks...");
+    task_tracker.spawn(run_services(cancel_token.clone(), sender, commands));
+
+    systemd::ready();
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut keepalive = tokio::time::interval(systemd::keepalive_interval());
+    let caught = loop {
+        tokio::select! {
+            caught = &mut shutdown => break caught,
+            _ = keepalive.tick() => systemd::keepalive(),
+        }
+    };
+    info!("{caught} caught! Stopping services...");
+    systemd::stopping();
     cancel_token.cancel();
     task_tracker.close();
+    if tokio::time::timeout(drain_timeout, task_tracker.wait())
+        .await
+        .is_err()
+    {
+        warn!("Timed out waiting for services to stop");
+    }
 
-    task_tracker.wait().await;
+    info!("Flushing queued prints...");
+    printer_cancel.cancel();
+    printer_tracker.close();
+    printer_tracker.wait().await;
     info!("All tasks closed. Goodbye o/");
 }
+
+/// Print queue set up by the env, with dedup, digests, rate limits, quiet hours, redaction &
+/// its journal
+fn build_queue() -> PrintQueue {
+    let queue_capacity =
+        secrets::var("PRINT_QUEUE_CAPACITY").map_or(queue::DEFAULT_CAPACITY, |c| {
+            c.parse()
+                .expect("Invalid PRINT_QUEUE_CAPACITY! Expected a number")
+        });
+    let overflow_policy = secrets::var("PRINT_QUEUE_OVERFLOW")
+        .map_or_else(|_| Ok(OverflowPolicy::default()), |p| p.parse())
+        .expect("Invalid PRINT_QUEUE_OVERFLOW!");
+    let dedup_ttl = secrets::var("DEDUP_TTL").map_or(dedup::DEFAULT_TTL, |t| {
+        Duration::from_secs(t.parse().expect("Invalid DEDUP_TTL! Expected seconds"))
+    });
+    let mut dedup = Dedup::new(dedup_ttl);
+    if let Ok(path) = secrets::var("DEDUP_FILE") {
+        dedup = dedup
+            .with_file(&path)
+            .unwrap_or_else(|e| panic!("Unable to open dedup file {path}: {e}"));
+    }
+
+    let mut queue = PrintQueue::new(queue_capacity, overflow_policy).with_dedup(dedup);
+    if let Ok(interval) = secrets::var("DIGEST_INTERVAL") {
+        let interval = interval
+            .parse()
+            .expect("Invalid DIGEST_INTERVAL! Expected seconds");
+        let max_items = secrets::var("DIGEST_MAX_ITEMS").map_or(digest::DEFAULT_MAX_ITEMS, |n| {
+            n.parse()
+                .expect("Invalid DIGEST_MAX_ITEMS! Expected a number")
+        });
+        queue = queue.with_digest(Digest::new(Duration::from_secs(interval), max_items));
+    }
+    if let Ok(per_minute) = secrets::var("RATE_LIMIT_PER_MINUTE") {
+        let per_minute = per_minute
+            .parse()
+            .expect("Invalid RATE_LIMIT_PER_MINUTE! Expected a number");
+        let burst = secrets::var("RATE_LIMIT_BURST").map_or(per_minute, |n| {
+            n.parse()
+                .expect("Invalid RATE_LIMIT_BURST! Expected a number")
+        });
+        queue = queue.with_throttle(Throttle::new(per_minute, burst));
+    }
+    if let Ok(quiet_hours) = secrets::var("QUIET_HOURS") {
+        queue = queue.with_quiet_hours(quiet_hours.parse().expect("Invalid QUIET_HOURS!"));
+    }
+    if let Ok(priority) = secrets::var("QUIET_HOURS_BYPASS") {
+        queue = queue.with_quiet_bypass(priority.parse().expect("Invalid QUIET_HOURS_BYPASS!"));
+    }
+    let mut redact_rules: Vec<String> = secrets::var("REDACT")
+        .map(|rules| {
+            rules
+                .split(',')
+                .map(|rule| rule.trim().to_string())
+                .filter(|rule| !rule.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Ok(path) = secrets::var("REDACT_FILE") {
+        // One rule per line, for regexes with commas in them
+        let file = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Unable to read redaction rules {path}: {e}"));
+        redact_rules.extend(
+            file.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string),
+        );
+    }
+    if !redact_rules.is_empty() {
+        let redact = Redact::new(redact_rules.iter().map(String::as_str))
+            .unwrap_or_else(|e| panic!("Invalid REDACT! {e}"));
+        queue = queue.with_redact(redact);
+    }
+    if let Ok(path) = secrets::var("PRINT_JOURNAL") {
+        queue = queue
+            .with_journal(&path)
+            .unwrap_or_else(|e| panic!("Unable to open print journal {path}: {e}"));
+    }
+    queue
+}
+
+/// Resolves with the name of the first termination signal received; SIGINT (CTRL + C), SIGTERM
+/// (e.g. `systemctl stop`) or SIGQUIT
+async fn shutdown_signal() -> &'static str {
+    let listen = |kind: SignalKind, name: &str| {
+        signal(kind).unwrap_or_else(|e| panic!("Unable to listen to {name} signal! {e}"))
+    };
+    let mut interrupt = listen(SignalKind::interrupt(), "SIGINT");
+    let mut terminate = listen(SignalKind::terminate(), "SIGTERM");
+    let mut quit = listen(SignalKind::quit(), "SIGQUIT");
+
+    tokio::select! {
+        _ = interrupt.recv() => "SIGINT",
+        _ = terminate.recv() => "SIGTERM",
+        _ = quit.recv() => "SIGQUIT",
+    }
+}
+
+/// Connects to the printer through the transport picked by `PRINTER_TRANSPORT` (`tcp` by
+/// default, `terminal` on a dry run) and spawns the print loop on it, using the
+/// `PRINTER_PROFILE` printer profile
+async fn spawn_printer(
+    task_tracker: &TaskTracker,
+    cancel: &CancellationToken,
+    receiver: mpsc::Receiver<PrintData>,
+    queue: PrintQueue,
+    commands: mpsc::Receiver<admin::Command>,
+    drain_timeout: Duration,
+    dry_run: bool,
+) {
+    let transport = if dry_run {
+        "terminal".to_string()
+    } else {
+        std::env::var("PRINTER_TRANSPORT").unwrap_or_else(|_| "tcp".to_string())
+    };
+    let profile = Profile::from_env();
+    let cancel = cancel.clone();
+    match transport.as_str() {
+        "tcp" => task_tracker.spawn(process_prints(
+            cancel,
+            TcpBackend::from_env().await,
+            profile,
+            receiver,
+            queue,
+            commands,
+            drain_timeout,
+        )),
+        "usb" => task_tracker.spawn(process_prints(
+            cancel,
+            UsbBackend::from_env(),
+            profile,
+            receiver,
+            queue,
+            commands,
+            drain_timeout,
+        )),
+        "serial" => task_tracker.spawn(process_prints(
+            cancel,
+            SerialBackend::from_env(),
+            profile,
+            receiver,
+            queue,
+            commands,
+            drain_timeout,
+        )),
+        "bluetooth" => task_tracker.spawn(process_prints(
+            cancel,
+            BluetoothBackend::from_env().await,
+            profile,
+            receiver,
+            queue,
+            commands,
+            drain_timeout,
+        )),
+        "png" => {
+            let profile = profile.for_preview();
+            task_tracker.spawn(process_prints(
+                cancel,
+                PngBackend::from_env(&profile),
+                profile,
+                receiver,
+                queue,
+                commands,
+                drain_timeout,
+            ))
+        }
+        "terminal" => {
+            let profile = profile.for_preview();
+            task_tracker.spawn(process_prints(
+                cancel,
+                TerminalBackend::new(&profile),
+                profile,
+                receiver,
+                queue,
+                commands,
+                drain_timeout,
+            ))
+        }
+        other => panic!(
+            "Unknown PRINTER_TRANSPORT `{other}`! \
+             Expected tcp, usb, serial, bluetooth, png or terminal"
+        ),
+    };
+}
+
+/// Runs the services until cancelled; On SIGHUP, stops them, reloads the config & starts them
+/// again, passing new quiet hours & sinks on to the printer without losing queued prints
+async fn run_services(
+    cancel: CancellationToken,
+    sender: mpsc::Sender<PrintData>,
+    commands: mpsc::Sender<admin::Command>,
+) {
+    let mut hangup = signal(SignalKind::hangup()).expect("Unable to listen to SIGHUP signal!");
+    let mut crash_receipts = false;
+    loop {
+        match secrets::var("SERVICE_CRASH_RECEIPTS").map_or(Ok(false), |c| c.parse()) {
+            Ok(receipts) => crash_receipts = receipts,
+            Err(e) => error!(
+                "Keeping the previous crash receipts, invalid SERVICE_CRASH_RECEIPTS! Expected \
+                 true or false: {e}"
+            ),
+        }
+        let services = TaskTracker::new();
+        let services_cancel = cancel.child_token();
+        spawn_services(&services, &services_cancel, &sender, crash_receipts);
+        services.close();
+
+        tokio::select! {
+            () = cancel.cancelled() => {
+                services.wait().await;
+                return;
+            }
+            _ = hangup.recv() => {}
+        }
+
+        info!("SIGHUP caught! Stopping services to reload the config...");
+        services_cancel.cancel();
+        services.wait().await;
+
+        match Config::read() {
+            Ok(config) => config.reload(),
+            Err(e) => error!("Keeping the previous config: {e}"),
+        }
+        if let Err(e) = sink::open() {
+            error!("Keeping the previous sinks: {e}");
+        }
+        match secrets::var("QUIET_HOURS")
+            .ok()
+            .map(|q| q.parse())
+            .transpose()
+        {
+            Ok(quiet_hours) => {
+                let _ = commands
+                    .send(admin::Command::SetQuietHours(quiet_hours))
+                    .await;
+            }
+            Err(e) => error!("Keeping the previous quiet hours, invalid QUIET_HOURS! {e}"),
+        }
+        info!("Restarting services...");
+    }
+}
+
+/// Spawns every notification service under a [`Supervisor`], each with its own cancel token
+/// & sender handle
+///
+/// `SERVICES` (comma separated) only runs the ones named & `SERVICES_DISABLED` skips the ones
+/// named; `crash_receipts` (`SERVICE_CRASH_RECEIPTS`) prints a receipt whenever one crashes.
+fn spawn_services(
+    task_tracker: &TaskTracker,
+    cancel: &CancellationToken,
+    sender: &mpsc::Sender<PrintData>,
+    crash_receipts: bool,
+) {
+    let services = |var| {
+        secrets::var(var).ok().map(|services| {
+            services
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+    };
+    let supervisor = Supervisor::new(cancel.clone(), sender.clone())
+        .with_crash_receipts(crash_receipts)
+        .with_enabled(services("SERVICES"))
+        .with_disabled(services("SERVICES_DISABLED").unwrap_or_default());
+
+    supervisor.spawn(task_tracker, "github", service::github::start_service);
+    supervisor.spawn(task_tracker, "gitlab", service::gitlab::start_service);
+    supervisor.spawn(task_tracker, "gitea", service::gitea::start_service);
+    supervisor.spawn(task_tracker, "jira", service::jira::start_service);
+    supervisor.spawn(task_tracker, "linear", service::linear::start_service);
+    supervisor.spawn(task_tracker, "twitch", service::twitch::start_service);
+    supervisor.spawn(
+        task_tracker,
+        "twitch_chat",
+        service::twitch_chat::start_service,
+    );
+    supervisor.spawn(task_tracker, "bsky", service::bsky::start_service);
+    supervisor.spawn(task_tracker, "football", service::football::start_service);
+    supervisor.spawn(task_tracker, "chess", service::chess::start_service);
+    supervisor.spawn(task_tracker, "arxiv", service::arxiv::start_service);
+    supervisor.spawn(task_tracker, "caldav", service::caldav::start_service);
+    supervisor.spawn(
+        task_tracker,
+        "google_calendar",
+        service::google_calendar::start_service,
+    );
+    supervisor.spawn(task_tracker, "todoist", service::todoist::start_service);
+    supervisor.spawn(task_tracker, "carddav", service::carddav::start_service);
+    supervisor.spawn(task_tracker, "reminders", service::reminders::start_service);
+    supervisor.spawn(task_tracker, "bandcamp", service::bandcamp::start_service);
+    supervisor.spawn(task_tracker, "lastfm", service::lastfm::start_service);
+    supervisor.spawn(
+        task_tracker,
+        "now_playing",
+        service::now_playing::start_service,
+    );
+    supervisor.spawn(task_tracker, "strava", service::strava::start_service);
+    supervisor.spawn(task_tracker, "summary", service::summary::start_service);
+    supervisor.spawn(task_tracker, "heartbeat", service::heartbeat::start_service);
+}
@@ -1,104 +1,931 @@
+use std::{future::Future, pin::Pin, str::FromStr, time::Duration};
+
 use chrono::{DateTime, Local};
-use tokio::{io::AsyncWriteExt, net::TcpStream, sync::mpsc::Receiver};
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc::Receiver, time::Instant};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, instrument};
+use tracing::{debug, info, info_span, instrument, warn, Instrument, Span as TracingSpan};
+
+use crate::{
+    ack,
+    admin::Command,
+    backend::PrinterBackend,
+    digest, emoji,
+    escpos::{CommandBuffer, Justify, Style},
+    history, logo,
+    profile::{Cut, Profile},
+    queue::PrintQueue,
+    raster::Raster,
+    secrets, sink, stats, status, template, test_page,
+};
 
-pub const ESC: u8 = 0x1B;
-pub const GS: u8 = 0x1D;
-pub const LF: u8 = 0x0A;
+/// How timestamps are printed at the bottom of receipts
+pub const TIMESTAMP_FORMAT: &str = "%B %e, %r";
 
-pub const JUSTIFY_LEFT: &[u8; 3] = &[ESC, b'a', 0x0];
-pub const JUSTIFY_CENTER: &[u8; 3] = &[ESC, b'a', 0x1];
-pub const JUSTIFY_RIGHT: &[u8; 3] = &[ESC, b'a', 0x2];
+/// Longest wait between reconnection attempts while the printer is unreachable
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
 
 pub trait Printable {
-    fn into_print_data(self) -> Vec<u8>;
+    /// Renders into commands for the printer described by `profile`
+    fn into_print_data(self, profile: &Profile) -> Vec<u8>;
 }
 
 /// Default printdata
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct PrintData {
+    /// Name of the service the print is from, e.g. `github`; Events, rate limits, stats & the
+    /// per-service settings are keyed on it
+    pub service: Option<String>,
+    /// Name of the logo printed above the title, usually the service's; See [`logo::get`]
+    pub logo: Option<String>,
+    /// ID of the event within its service; Prints of an event already printed are skipped, see
+    /// [`Dedup`](crate::dedup::Dedup)
+    pub event_id: Option<String>,
+    /// Events of the prints merged into this one, e.g. by a digest, as (service, event ID);
+    /// Acked along with its own once printed, see [`ack`](crate::ack)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged_events: Vec<(String, String)>,
+    /// What the print is about within its service, e.g. a repo or streamer; Tallied in the
+    /// daily summary, see [`stats`](crate::stats)
+    pub source: Option<String>,
+    /// Prints jump ahead of queued ones of lower priority
+    #[serde(default)]
+    pub priority: Priority,
     pub title: String,
     pub subtitle: Option<String>,
+    /// Image printed centered below the title
+    pub image: Option<Raster>,
 
-    pub message: Option<String>,
+    pub message: Option<Message>,
+    /// QR codes printed below the message, in order
+    pub qr_codes: Vec<QrCode>,
     pub timestamp: DateTime<Local>,
+    #[serde(skip)]
+    pub trace: Trace,
+}
+
+/// `notification` span following a print from its service through the queue to the printer;
+/// Closes once the print is dropped, see [`crate::logging::init`]
+#[derive(Clone)]
+pub struct Trace(pub TracingSpan);
+
+impl Default for Trace {
+    /// Child of the span the print is made in, i.e. its service's
+    fn default() -> Self {
+        Self(info_span!("notification"))
+    }
+}
+
+/// How urgent a print is, from least to most
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// e.g. new followers
+    Low,
+    #[default]
+    Normal,
+    /// e.g. mentions & replies
+    High,
+    /// e.g. alerts
+    Urgent,
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "low" => Ok(Self::Low),
+            "normal" => Ok(Self::Normal),
+            "high" => Ok(Self::High),
+            "urgent" => Ok(Self::Urgent),
+            other => Err(format!(
+                "Unknown priority `{other}`; expected low, normal, high or urgent"
+            )),
+        }
+    }
 }
+
 impl Printable for PrintData {
-    fn into_print_data(self) -> Vec<u8> {
-        let mut out: Vec<u8> = vec![ESC, b'@']; // Initialize print
-        out.extend_from_slice(&[GS, b'b', 0x01]); // Enable font smoothing
-        out.extend_from_slice(&[ESC, b'M', 0x01]); // Uses smaller character font
-
-        // Extend_from_slice might just slowing things down too much
-        out.extend_from_slice(JUSTIFY_CENTER); // Set center
-        out.extend_from_slice(&[GS, b'!', 0x11]); // Set character size to 2x2
-        out.extend_from_slice(self.title.as_bytes()); // Send title
-        out.extend_from_slice(&[LF]); // Print
-
-        out.extend_from_slice(&[ESC, b'd', 0x00]); // Feed 1 line
-        out.extend_from_slice(&[ESC, b'M', 0x00]); // Uses default character font
-        out.extend_from_slice(&[GS, b'!', 0x00]); // Set character size to 1x1
-        out.extend_from_slice(JUSTIFY_LEFT); // Set justify left
+    fn into_print_data(self, profile: &Profile) -> Vec<u8> {
+        let mut out = CommandBuffer::new(profile);
+        out.init(); // Initialize print
+        if profile.font_smoothing {
+            out.font_smoothing();
+        }
+        if profile.kanji {
+            out.cancel_kanji(); // Single byte characters only
+        }
+        out.select_code_page();
+
+        let compact = profile.is_compact(&self);
+        let layout = if test_page::is_test_page(&self) {
+            Some(test_page::layout(profile))
+        } else {
+            template::render(&self, compact)
+        };
+        if let Some(layout) = layout {
+            self.layout(&layout, profile, &mut out);
+            return out.into_bytes();
+        }
+        // Extra lines fed before the message, QR codes & timestamp; Halved when compact
+        let gap = if compact { 0 } else { 1 };
+
+        out.justify(Justify::Center);
+        self.print_logo(profile, &mut out);
+        if compact {
+            out.style(Style {
+                bold: true,
+                ..Style::default()
+            })
+            .text(&wrap(&self.title, profile, profile.columns, 1)) // Send title
+            .style(Style::default())
+            .line();
+        } else {
+            out.small_font(true) // Uses smaller character font
+                .char_size(2, 2)
+                .text(&wrap(&self.title, profile, profile.small_columns, 2)) // Send title
+                .line();
+        }
+
+        if self.image.is_some() {
+            if !compact {
+                out.feed(0); // Feed 1 line
+            }
+            self.print_image(profile, &mut out); // Still centered
+        }
+
+        if !compact {
+            out.feed(0); // Feed 1 line
+        }
+        out.small_font(false).char_size(1, 1).justify(Justify::Left);
 
         if let Some(subtitle) = self.subtitle.as_ref() {
-            out.extend_from_slice(&[ESC, b'd', 0x00]); // Feed 1 lines
+            if !compact {
+                out.feed(0); // Feed 1 lines
+            }
+            out.text(&wrap(subtitle, profile, profile.columns, 1)) // Send subtitle
+                .line();
+            if !compact {
+                out.text(&"-".repeat(profile.columns)).line(); // Send line
+            }
+        }
+
+        if self.message.is_some() {
+            out.feed(gap); // Feed 2 lines, or 1 when compact
+            self.print_message(profile, &mut out);
+        }
+
+        if !self.qr_codes.is_empty() {
+            out.justify(Justify::Center);
+            self.print_qr_codes(profile, gap, &mut out);
+            out.justify(Justify::Left);
+        }
+
+        // Print timestamp
+        let human_time = self.timestamp.format(TIMESTAMP_FORMAT);
+        out.feed(gap) // Feed 2 lines, or 1 when compact
+            .text(&format!("Timestamp: {human_time}"))
+            .line();
+
+        out.into_bytes()
+    }
+}
+
+impl PrintData {
+    fn print_logo(&self, profile: &Profile, out: &mut CommandBuffer) {
+        if let Some(logo) = self.logo.as_deref().and_then(logo::get) {
+            out.bytes(&logo.as_ref().into_print_data(profile));
+        }
+    }
+
+    fn print_image(&self, profile: &Profile, out: &mut CommandBuffer) {
+        if let Some(image) = &self.image {
+            out.bytes(&image.into_print_data(profile));
+        }
+    }
+
+    fn print_message(&self, profile: &Profile, out: &mut CommandBuffer) {
+        let Some(message) = self.message.clone() else {
+            return;
+        };
+        let (message, omitted) = match profile.max_message_length {
+            Some(max) => message.truncate(max),
+            None => (message, 0),
+        };
+        for (style, text) in message.wrap(profile) {
+            out.style(style).text(&text);
+        }
+        out.style(Style::default()).line(); // Print final line if haven't
+        if omitted > 0 {
+            out.text(&format!("… (truncated, {omitted} chars omitted)"))
+                .line();
+        }
+    }
 
-            out.extend_from_slice(subtitle.as_bytes()); // Send subtitle
-            out.extend_from_slice(&[LF]); // Print
+    /// Feeds `feed` extra lines before each
+    fn print_qr_codes(&self, profile: &Profile, feed: u8, out: &mut CommandBuffer) {
+        for qr_code in &self.qr_codes {
+            out.feed(feed);
+            out.bytes(&qr_code.clone().into_print_data(profile));
+        }
+    }
+
+    /// Lays the receipt out from a rendered template, see [`template`]
+    ///
+    /// Lines are printed as is, wrapped to the current font & size, while lines starting with
+    /// `@` are directives:
+    ///
+    /// * `@logo`, `@image`, `@message` & `@qr_codes` print those parts of the receipt
+    /// * `@divider` prints a line of dashes across the paper
+    /// * `@barcode <data>` prints a CODE128 barcode
+    /// * `@left`, `@center` & `@right` justify what follows
+    /// * `@size <width> <height>` sets the character size, from 1 to 6
+    /// * `@font small` / `@font normal` switches fonts
+    /// * `@style [bold] [underline] [invert]` sets the text style; Reset by a bare `@style`
+    /// * `@feed <n>` feeds `n` extra lines
+    ///
+    /// Any other line starting with `@` is printed as text.
+    fn layout(&self, layout: &str, profile: &Profile, out: &mut CommandBuffer) {
+        let (mut small, mut scale) = (false, 1);
+        for line in layout.lines() {
+            let columns = if small {
+                profile.small_columns
+            } else {
+                profile.columns
+            } / usize::from(scale);
+
+            let Some(directive) = line.strip_prefix('@') else {
+                out.text(&wrap(line, profile, columns, 1)).line();
+                continue;
+            };
+            let mut args = directive.split_whitespace();
+            match (args.next(), args.next(), args.next()) {
+                (Some("logo"), ..) => self.print_logo(profile, out),
+                (Some("image"), ..) => self.print_image(profile, out),
+                (Some("message"), ..) => self.print_message(profile, out),
+                (Some("qr_codes"), ..) => self.print_qr_codes(profile, 1, out),
+                (Some("divider"), ..) => {
+                    out.text(&"-".repeat(columns)).line();
+                }
+                (Some("barcode"), Some(data), _) => {
+                    out.barcode(data.as_bytes());
+                }
+                (Some("left"), ..) => {
+                    out.justify(Justify::Left);
+                }
+                (Some("center"), ..) => {
+                    out.justify(Justify::Center);
+                }
+                (Some("right"), ..) => {
+                    out.justify(Justify::Right);
+                }
+                (Some("size"), Some(width), Some(height)) => {
+                    let (Ok(width), Ok(height)) = (width.parse::<u8>(), height.parse::<u8>())
+                    else {
+                        warn!("Invalid template directive `{line}`");
+                        continue;
+                    };
+                    scale = width.clamp(1, 6);
+                    out.char_size(width, height);
+                }
+                (Some("font"), Some(font @ ("small" | "normal")), _) => {
+                    small = font == "small";
+                    out.small_font(small);
+                }
+                (Some("style"), ..) => {
+                    let words = directive.split_whitespace().collect::<Vec<_>>();
+                    out.style(Style {
+                        bold: words.contains(&"bold"),
+                        underline: words.contains(&"underline"),
+                        invert: words.contains(&"invert"),
+                    });
+                }
+                (Some("feed"), Some(n), _) => {
+                    let Ok(n) = n.parse() else {
+                        warn!("Invalid template directive `{line}`");
+                        continue;
+                    };
+                    out.feed(n);
+                }
+                // e.g. a title starting with a handle
+                _ => {
+                    out.text(&wrap(line, profile, columns, 1)).line();
+                }
+            }
+        }
+    }
+}
+
+/// Message printed below the subtitle; Either plain text or a list of styled spans
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Message {
+    Plain(String),
+    Spans(Vec<Span>),
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Self::Plain(text)
+    }
+}
+
+impl From<Vec<Span>> for Message {
+    fn from(spans: Vec<Span>) -> Self {
+        Self::Spans(spans)
+    }
+}
+
+/// Run of message text printed in one style
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub text: String,
+    #[serde(flatten)]
+    pub style: Style,
+}
 
-            out.extend_from_slice([b'-'].repeat(48).as_slice()); // Send line
-            out.extend_from_slice(&[LF]); // Print
+impl Span {
+    pub fn plain(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            style: Style::default(),
         }
+    }
+
+    pub fn bold(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            style: Style {
+                bold: true,
+                ..Style::default()
+            },
+        }
+    }
 
-        if let Some(message) = self.message.as_ref() {
-            out.extend_from_slice(&[ESC, b'd', 0x01]); // Feed 2 lines
+    pub fn underline(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            style: Style {
+                underline: true,
+                ..Style::default()
+            },
+        }
+    }
 
-            let processed_message = message
-                .trim()
-                .chars()
-                .map(|c| {
-                    if c.is_whitespace() && c != ' ' {
-                        return LF;
+    pub fn invert(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            style: Style {
+                invert: true,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+impl Message {
+    pub fn into_spans(self) -> Vec<Span> {
+        match self {
+            Self::Plain(text) => vec![Span::plain(text)],
+            Self::Spans(spans) => spans,
+        }
+    }
+
+    /// The message's text, without styles
+    pub fn text(&self) -> String {
+        match self {
+            Self::Plain(text) => text.clone(),
+            Self::Spans(spans) => spans.iter().map(|span| span.text.as_str()).collect(),
+        }
+    }
+
+    /// Cuts the message down to at most `max` characters, at the last word boundary within
+    /// them; Returns how many characters were left out
+    fn truncate(self, max: usize) -> (Self, usize) {
+        let text = self.text();
+        let total = text.chars().count();
+        if total <= max {
+            return (self, 0);
+        }
+
+        let limit = text.char_indices().nth(max).map_or(text.len(), |(i, _)| i);
+        let end = if text[limit..].starts_with(char::is_whitespace) {
+            limit
+        } else {
+            // Words longer than the whole limit are split
+            text[..limit].rfind(char::is_whitespace).unwrap_or(limit)
+        };
+        let end = text[..end].trim_end().len();
+        let omitted = total - text[..end].chars().count();
+
+        // `end` counts bytes across all spans
+        let mut remaining = end;
+        let mut spans = Vec::new();
+        for mut span in self.into_spans() {
+            if remaining == 0 {
+                break;
+            }
+            let len = span.text.len().min(remaining);
+            span.text.truncate(len);
+            remaining -= len;
+            spans.push(span);
+        }
+        (spans.into(), omitted)
+    }
+
+    /// Wraps the message to the paper's width, like [`wrap`], keeping each character's style
+    ///
+    /// Returns runs of text in the same style; Whitespace other than spaces breaks the line.
+    fn wrap(self, profile: &Profile) -> Vec<(Style, String)> {
+        let spans = self.into_spans();
+
+        let mut text = String::new();
+        let mut styles = Vec::new();
+        for span in spans {
+            for c in emoji::replace(&span.text, profile).chars() {
+                let c = if c.is_whitespace() && c != ' ' {
+                    '\n'
+                } else {
+                    c
+                };
+                text.push(c);
+                styles.push(span.style);
+            }
+        }
+        let start = text.chars().take_while(|c| c.is_whitespace()).count();
+        let text = text.trim();
+        let wrapped = textwrap::fill(text, profile.columns.max(1));
+
+        // Line breaks replace spaces, or split up words too long for a line; Whitespace at the
+        // end of a line is dropped
+        let mut original = text.chars().zip(styles.into_iter().skip(start)).peekable();
+        let mut runs: Vec<(Style, String)> = Vec::new();
+        let mut style = Style::default();
+        for c in wrapped.chars() {
+            style = loop {
+                match original.peek() {
+                    Some(&(o, s)) if o == c || (c == '\n' && o == ' ') => {
+                        original.next();
+                        break s;
+                    }
+                    Some(&(o, _)) if o.is_whitespace() && c != '\n' => {
+                        original.next();
                     }
-                    c as u8
-                })
-                .collect::<Vec<u8>>();
-            out.extend_from_slice(processed_message.as_slice());
-            out.extend_from_slice(&[LF]); // Print final line if haven't
+                    _ => break style,
+                }
+            };
+            match runs.last_mut() {
+                Some((last, run)) if *last == style => run.push(c),
+                _ => runs.push((style, c.to_string())),
+            }
         }
+        runs
+    }
+}
 
-        // Print timestamp
-        let human_time = self.timestamp.format("%B %e, %r");
-        out.extend_from_slice(&[ESC, b'd', 0x01]); // Feed 2 lines
-        let timestamp_line = format!("Timestamp: {human_time}");
-        out.extend_from_slice(timestamp_line.as_bytes()); // Send timestamp_line
-        out.extend_from_slice(&[LF]); // Print timestamp
+/// QR code with an optional caption printed above it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QrCode {
+    pub caption: Option<String>,
+    pub data: String,
+}
+impl Printable for QrCode {
+    fn into_print_data(self, profile: &Profile) -> Vec<u8> {
+        let mut out = CommandBuffer::new(profile);
+        if let Some(caption) = self.caption {
+            out.text(&caption).line(); // Send caption
+        }
+        out.qr_code(self.data.as_bytes());
+
+        out.into_bytes()
+    }
+}
+
+/// Breaks `text` on word boundaries so that no line overflows `columns` characters, with
+/// characters printed `scale` times wider
+///
+/// Words longer than a whole line are still split, as the printer would've done. CJK
+/// characters take up two columns, just like their double-byte glyphs. Emoji are replaced
+/// beforehand, see [`emoji::replace`].
+fn wrap(text: &str, profile: &Profile, columns: usize, scale: u8) -> String {
+    textwrap::fill(
+        &emoji::replace(text, profile),
+        (columns / usize::from(scale.max(1))).max(1),
+    )
+}
+
+/// How links are printed by [`markdown`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarkdownLinks {
+    /// `text (url)`
+    #[default]
+    Inline,
+    /// `text [n]`, with the URL printed as the n-th QR code
+    QrCode,
+}
+
+impl FromStr for MarkdownLinks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "inline" => Ok(Self::Inline),
+            "qr" => Ok(Self::QrCode),
+            other => Err(format!(
+                "Unknown link style `{other}`; expected inline or qr"
+            )),
+        }
+    }
+}
 
-        out
+impl MarkdownLinks {
+    /// `PRINTER_MARKDOWN_LINKS`, inline if unset or invalid
+    pub fn from_env() -> Self {
+        let Ok(links) = secrets::var("PRINTER_MARKDOWN_LINKS") else {
+            return Self::default();
+        };
+        links.parse().unwrap_or_else(|e| {
+            warn!("Printing links inline, invalid PRINTER_MARKDOWN_LINKS! {e}");
+            Self::default()
+        })
     }
 }
 
-#[instrument(skip(cancel, printer, receiver))]
-pub async fn process_prints(
+/// Renders Markdown into styled spans, e.g. for GitHub comment bodies
+///
+/// Strong text & headings are bold, emphasis is underlined as there's no italic font, lists
+/// get bullets and code blocks are indented. Returns the QR codes of links alongside, if any.
+pub fn markdown(source: &str, links: MarkdownLinks) -> (Vec<Span>, Vec<QrCode>) {
+    let mut out = Markdown {
+        links,
+        ..Markdown::default()
+    };
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    for event in Parser::new_ext(source, options) {
+        out.event(event);
+    }
+
+    (out.spans, out.qr_codes)
+}
+
+#[derive(Default)]
+struct Markdown {
+    links: MarkdownLinks,
+    spans: Vec<Span>,
+    qr_codes: Vec<QrCode>,
+    /// Nesting depth of each style, as they can be nested in one another
+    bold: usize,
+    underline: usize,
+    /// Next number of each nested list; `None` for bullet lists
+    lists: Vec<Option<u64>>,
+    /// Bullet of the list item whose first line is yet to be printed
+    bullet: Option<String>,
+    quotes: usize,
+    code_block: bool,
+    /// URLs of the links being printed; `None` where the text is the URL itself
+    urls: Vec<Option<String>>,
+    /// Line breaks owed before the next text, collapsed between blocks
+    breaks: usize,
+}
+
+impl Markdown {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.code_block => {
+                for line in text.lines() {
+                    self.text(line);
+                    self.line_break(1);
+                }
+            }
+            Event::Text(text) | Event::Code(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                self.text(&text);
+            }
+            Event::SoftBreak => self.text(" "),
+            Event::HardBreak => self.line_break(1),
+            Event::Rule => {
+                self.line_break(2);
+                self.text("----------");
+                self.line_break(2);
+            }
+            Event::TaskListMarker(done) => self.text(if done { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.line_break(2),
+            Tag::Heading { .. } => {
+                self.line_break(2);
+                self.bold += 1;
+            }
+            Tag::BlockQuote(..) => {
+                self.line_break(2);
+                self.quotes += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.line_break(2);
+                self.code_block = true;
+            }
+            Tag::List(start) => {
+                self.line_break(if self.lists.is_empty() { 2 } else { 1 });
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.line_break(1);
+                self.bullet = Some(match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                });
+            }
+            Tag::Emphasis => self.underline += 1,
+            Tag::Strong => self.bold += 1,
+            Tag::Link {
+                link_type: LinkType::Autolink | LinkType::Email,
+                ..
+            } => self.urls.push(None),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.urls.push(Some(dest_url.into_string()));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => self.line_break(2),
+            TagEnd::List(_) => {
+                self.lists.pop();
+                self.line_break(if self.lists.is_empty() { 2 } else { 1 });
+            }
+            TagEnd::Heading(_) => {
+                self.bold -= 1;
+                self.line_break(2);
+            }
+            TagEnd::BlockQuote(..) => {
+                self.quotes -= 1;
+                self.line_break(2);
+            }
+            TagEnd::CodeBlock => {
+                self.code_block = false;
+                self.line_break(2);
+            }
+            TagEnd::Emphasis => self.underline -= 1,
+            TagEnd::Strong => self.bold -= 1,
+            TagEnd::Link | TagEnd::Image => {
+                let Some(Some(url)) = self.urls.pop() else {
+                    return;
+                };
+                match self.links {
+                    MarkdownLinks::Inline => self.text(&format!(" ({url})")),
+                    MarkdownLinks::QrCode => {
+                        let n = self.qr_codes.len() + 1;
+                        self.text(&format!(" [{n}]"));
+                        self.qr_codes.push(QrCode {
+                            caption: Some(format!("[{n}]")),
+                            data: url,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Owes at least `n` line breaks before the next text; 2 leaves a blank line
+    fn line_break(&mut self, n: usize) {
+        self.breaks = self.breaks.max(n);
+    }
+
+    fn text(&mut self, text: &str) {
+        if !self.spans.is_empty() {
+            for _ in 0..std::mem::take(&mut self.breaks) {
+                let indent = self.indent();
+                self.push("\n", Style::default());
+                self.push(&indent, Style::default());
+            }
+        }
+        self.breaks = 0;
+        if let Some(bullet) = self.bullet.take() {
+            self.push(&bullet, Style::default());
+        }
+
+        let style = Style {
+            bold: self.bold > 0,
+            underline: self.underline > 0,
+            invert: false,
+        };
+        self.push(text, style);
+    }
+
+    /// Quote markers & indentation of nested lists, code blocks & list item continuations
+    fn indent(&self) -> String {
+        let mut indent = "> ".repeat(self.quotes);
+        let levels = self
+            .lists
+            .len()
+            .saturating_sub(usize::from(self.bullet.is_some()));
+        indent.push_str(&"  ".repeat(levels));
+        if self.code_block {
+            indent.push_str("    ");
+        }
+        indent
+    }
+
+    fn push(&mut self, text: &str, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+        match self.spans.last_mut() {
+            Some(span) if span.style == style => span.text.push_str(text),
+            _ => self.spans.push(Span {
+                text: text.to_string(),
+                style,
+            }),
+        }
+    }
+}
+
+/// Print in progress; Resolves with the print's ID & the print, as an error if it has to be
+/// requeued
+type PrintJob<B> = Pin<Box<dyn Future<Output = (B, u64, Result<PrintData, PrintData>)> + Send>>;
+
+#[instrument(skip(cancel, printer, profile, receiver, queue, commands))]
+pub async fn process_prints<B: PrinterBackend>(
     cancel: CancellationToken,
-    mut printer: TcpStream,
+    printer: B,
+    profile: Profile,
     mut receiver: Receiver<PrintData>,
+    mut queue: PrintQueue,
+    mut commands: Receiver<Command>,
+    drain_timeout: Duration,
 ) {
+    // The printer is moved into the job being printed, so the channel keeps being emptied
+    // into the queue while the printer is busy (or stalled)
+    let mut printer = Some(printer);
+    let mut job: Option<PrintJob<B>> = None;
+
     loop {
+        status::set_queue_depth(queue.pending() + receiver.len());
+        if job.is_none() {
+            if let Some((id, data)) = queue.pop() {
+                let mut p = printer
+                    .take()
+                    .expect("Printer is neither idle nor printing");
+                let profile = profile.clone();
+                job = Some(Box::pin(async move {
+                    let span = info_span!(parent: &data.trace.0, "print_job", id);
+                    let printed = print_job(&mut p, &profile, id, data).instrument(span).await;
+                    (p, id, printed)
+                }));
+            }
+        }
+
+        let wake_at = queue.wake_at();
         tokio::select! {
             () = cancel.cancelled() => {
-                debug!("Cancel signal caught! Stopping service...");
+                debug!("Cancel signal caught! Draining queued prints...");
                 break;
             }
 
-            Some(data) = receiver.recv() => {
-                printer.write_all(&data.into_print_data()).await.unwrap();
+            Some(data) = receiver.recv(), if queue.accepts() => {
+                info!(parent: &data.trace.0, "Received by the print loop");
+                queue.push(data);
+            }
+
+            Some(command) = commands.recv() => command.apply(&mut queue),
+
+            () = tokio::time::sleep_until(wake_at.unwrap_or_else(Instant::now)),
+                if wake_at.is_some() => queue.tick(),
+
+            (p, id, printed) = async { job.as_mut().unwrap().await }, if job.is_some() => {
+                printer = Some(p);
+                job = None;
+                queue.settle(id, printed);
+            }
+        }
+    }
 
-                // Closing
-                printer.write_all(&[ESC, b'd', 0x06, LF]).await.unwrap(); // Feed 6 lines
-                printer.write_all(&[ESC, b'i']).await.unwrap(); // Full cut; I think the auto cutter is 2 lines(?) behind, so the line above effectively feeds 4 line
-                printer.write_all(&[0x0C]).await.unwrap(); // Print and return to standard mode in page mode; Finishes the job
+    // Flush whatever was already queued before shutting down, giving up after `drain_timeout`
+    let drain = async {
+        queue.flush_digest();
+        let mut printer = match job {
+            Some(job) => {
+                let (p, id, printed) = job.await;
+                queue.settle(id, printed);
+                p
+            }
+            None => printer.take().unwrap(),
+        };
+        let mut drained = 0;
+        loop {
+            while let Ok(data) = receiver.try_recv() {
+                queue.push(data);
+            }
+            let Some((id, data)) = queue.pop() else {
+                break;
+            };
+            let span = info_span!(parent: &data.trace.0, "print_job", id);
+            let printed = print_job(&mut printer, &profile, id, data)
+                .instrument(span)
+                .await;
+            if printed.is_ok() {
+                drained += 1;
             }
+            queue.settle(id, printed);
         }
+        drained
+    };
+    if let Ok(drained) = tokio::time::timeout(drain_timeout, drain).await {
+        info!("Drained {drained} queued prints");
+    } else {
+        warn!("Timed out draining queued prints, dropping the rest");
     }
 }
+
+/// Commands of one print job, framed by the profile's header & footer; Beeps & kicks the drawer
+/// first if the profile says so
+pub fn render_job(profile: &Profile, id: u64, data: &PrintData) -> Vec<u8> {
+    let frame = |lines: &str| {
+        wrap(
+            &lines.replace("{job}", &id.to_string()),
+            profile,
+            profile.columns,
+            1,
+        )
+    };
+
+    let mut job = CommandBuffer::new(profile);
+    if profile.beep.matches(data) {
+        job.beep(3);
+    }
+    if profile.drawer_kick.matches(data) {
+        job.kick_drawer(profile.drawer_pin, profile.drawer_pulse_ms);
+    }
+    if let Some(header) = &profile.header {
+        job.init()
+            .select_code_page()
+            .justify(Justify::Center)
+            .text(&frame(header))
+            .line();
+    }
+    job.bytes(&data.clone().into_print_data(profile));
+    if let Some(footer) = &profile.footer {
+        job.feed(0) // Feed 1 line
+            .justify(Justify::Center)
+            .text(&frame(footer))
+            .line();
+    }
+    let cut = if profile.cut_digests_only && !digest::is_digest(data) {
+        Cut::None
+    } else {
+        profile.cut
+    };
+    job.cut(cut, profile.cut_feed); // Closing
+    job.into_bytes()
+}
+
+/// Sends one print to the printer, see [`render_job`]
+///
+/// On failure, the printer is reconnected with exponential backoff and the print handed back
+/// to be requeued.
+async fn print_job(
+    printer: &mut impl PrinterBackend,
+    profile: &Profile,
+    id: u64,
+    data: PrintData,
+) -> Result<PrintData, PrintData> {
+    let job = render_job(profile, id, &data);
+
+    let Err(e) = printer.write_job(&job).await else {
+        status::printed(&data);
+        stats::printed(&data, profile);
+        history::record(&data);
+        ack::printed(&data);
+        sink::fan_out(&data, &job);
+        return Ok(data);
+    };
+    status::set_printer_connected(false);
+    warn!(
+        "Unable to print `{}`, reconnecting to the printer: {e}",
+        data.title
+    );
+
+    let mut delay = Duration::from_secs(1);
+    while let Err(e) = printer.reconnect().await {
+        warn!("Unable to reconnect to the printer, retrying in {delay:?}: {e}");
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+    status::set_printer_connected(true);
+    info!("Reconnected to the printer, requeueing `{}`", data.title);
+
+    Err(data)
+}
@@ -0,0 +1,358 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::{
+    codepage::{CjkEncoding, CodePage},
+    emoji::EmojiStrategy,
+    printer::{PrintData, Priority},
+    protocol::Protocol,
+};
+
+/// How the receipt is cut once printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cut {
+    #[default]
+    Full,
+    Partial,
+    /// No auto cutter; Only feeds the receipt out to be torn off
+    None,
+}
+
+impl FromStr for Cut {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "full" => Ok(Self::Full),
+            "partial" => Ok(Self::Partial),
+            "none" => Ok(Self::None),
+            other => Err(format!(
+                "Unknown cut `{other}`; expected full, partial or none"
+            )),
+        }
+    }
+}
+
+/// Which prints trigger an alert, by priority or service; None do by default
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Trigger {
+    /// Prints of at least this priority
+    pub priority: Option<Priority>,
+    /// Every print of these services, see [`PrintData::service`]
+    pub services: Vec<String>,
+}
+
+impl Trigger {
+    pub fn matches(&self, data: &PrintData) -> bool {
+        self.priority
+            .is_some_and(|priority| data.priority >= priority)
+            || data
+                .service
+                .as_ref()
+                .is_some_and(|service| self.services.contains(service))
+    }
+
+    /// Reads `<var>` as the lowest priority & `<var>_SERVICES` as a comma separated list
+    fn override_from_env(&mut self, var: &str) {
+        if let Ok(priority) = std::env::var(var) {
+            self.priority = Some(
+                priority
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Invalid {var}! {e}")),
+            );
+        }
+        if let Some(services) = services_from_env(&format!("{var}_SERVICES")) {
+            self.services = services;
+        }
+    }
+}
+
+/// Reads a comma separated list of services
+fn services_from_env(var: &str) -> Option<Vec<String>> {
+    let services = std::env::var(var).ok()?;
+    Some(
+        services
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
+    )
+}
+
+/// Commands & layout a printer model supports, akin to escpos-php's capability profiles
+///
+/// Picked with `PRINTER_PROFILE`, either a built-in profile name or a path to a TOML file:
+///
+/// ```toml
+/// protocol = "escpos"
+/// columns = 32
+/// small_columns = 42
+/// dots = 384
+/// font_smoothing = false
+/// kanji = true
+/// cjk = "gbk"
+/// code_page = "pc858"
+/// emoji = "image"
+/// upside_down = true
+/// compact_services = ["github", "bsky"]
+/// max_message_length = 1000
+/// cut = "partial"
+/// cut_feed = 3
+/// cut_digests_only = true
+/// header = "Kitchen #{job}"
+/// footer = "printed by notifi-printer"
+///
+/// [beep]
+/// priority = "high"
+/// services = ["twitch"]
+///
+/// [drawer_kick]
+/// priority = "urgent"
+/// ```
+///
+/// Fields left out of a TOML profile fall back to the `default` profile.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub protocol: Protocol,
+    /// Characters per line in the default font at 1x1
+    pub columns: usize,
+    /// Characters per line in the smaller font at 1x1
+    pub small_columns: usize,
+    /// Printable width in dots, images are scaled down to fit
+    pub dots: u32,
+    pub font_smoothing: bool,
+    /// Printer starts in kanji mode, which has to be turned off for single byte code pages
+    pub kanji: bool,
+    /// Encoding of the printer's CJK font, if it has one
+    pub cjk: Option<CjkEncoding>,
+    /// Code page text is printed in; Chars outside of it are transliterated
+    pub code_page: CodePage,
+    pub emoji: EmojiStrategy,
+    /// Printer is mounted upside down; Prints rotated & last line first, so receipts read top
+    /// to bottom as they come out
+    pub upside_down: bool,
+    /// Lays receipts out in about half the paper; Title at the normal size, fewer blank lines
+    /// & no dividers
+    pub compact: bool,
+    /// Services laid out compactly even if [`Self::compact`] isn't set
+    pub compact_services: Vec<String>,
+    /// Messages longer than this many characters are cut short at a word boundary, noting how
+    /// much was left out
+    pub max_message_length: Option<usize>,
+    pub cut: Cut,
+    /// Lines fed before cutting; The protocol's default if unset, see [`CommandBuffer::cut`]
+    ///
+    /// [`CommandBuffer::cut`]: crate::escpos::CommandBuffer::cut
+    pub cut_feed: Option<u8>,
+    /// Only cuts after digests, see [`Digest`](crate::digest::Digest); Other receipts are
+    /// only fed out, to be torn off together with the next digest
+    pub cut_digests_only: bool,
+    /// Lines printed above every receipt; `{job}` is replaced with the print's sequence number
+    /// & `{device}` with `DEVICE_NAME`
+    pub header: Option<String>,
+    /// Lines printed below every receipt, same as [`Self::header`]
+    pub footer: Option<String>,
+    /// Prints the buzzer sounds for before printing
+    pub beep: Trigger,
+    /// Prints the cash drawer is kicked for before printing, e.g. to drive a relay wired to
+    /// the drawer port
+    pub drawer_kick: Trigger,
+    /// Drawer port pin pulsed, 2 or 5
+    pub drawer_pin: u8,
+    /// How long the pin is pulsed for, up to 510ms
+    pub drawer_pulse_ms: u16,
+}
+
+impl Default for Profile {
+    /// 80mm ESC/POS printer, e.g. Epson TM-T88 & most of its clones
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::EscPos,
+            columns: 48,
+            small_columns: 64,
+            dots: 576,
+            font_smoothing: true,
+            kanji: false,
+            cjk: None,
+            code_page: CodePage::Pc437,
+            emoji: EmojiStrategy::Shortcode,
+            upside_down: false,
+            compact: false,
+            compact_services: Vec::new(),
+            max_message_length: None,
+            cut: Cut::Full,
+            cut_feed: None,
+            cut_digests_only: false,
+            header: None,
+            footer: None,
+            beep: Trigger::default(),
+            drawer_kick: Trigger::default(),
+            drawer_pin: 2,
+            drawer_pulse_ms: 200,
+        }
+    }
+}
+
+impl Profile {
+    /// Looks up a built-in profile by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            // Bare minimum ESC/POS; Cheap 58mm printers
+            "simple" => Some(Self {
+                columns: 32,
+                small_columns: 42,
+                dots: 384,
+                font_smoothing: false,
+                cut: Cut::None,
+                ..Self::default()
+            }),
+            // Chinese market printers which boot into kanji mode, e.g. Xprinter & Rongta
+            "kanji" => Some(Self {
+                kanji: true,
+                cjk: Some(CjkEncoding::Gbk),
+                font_smoothing: false,
+                cut: Cut::Partial,
+                ..Self::default()
+            }),
+            "star" => Some(Self {
+                protocol: Protocol::StarLine,
+                font_smoothing: false,
+                ..Self::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Sets the columns (for 12 & 9 dot wide fonts) & dots per line for 58mm or 80mm wide paper
+    pub fn set_paper_width(&mut self, mm: u32) -> Result<(), String> {
+        (self.columns, self.small_columns, self.dots) = match mm {
+            58 => (32, 42, 384),
+            80 => (48, 64, 576),
+            other => {
+                return Err(format!(
+                    "Unsupported paper width {other}mm; expected 58 or 80"
+                ))
+            }
+        };
+        Ok(())
+    }
+
+    /// Same printer but speaking plain ESC/POS the right way up, for previews which read the
+    /// commands back instead of printing them, see [`crate::decode`]
+    pub const fn for_preview(mut self) -> Self {
+        self.protocol = Protocol::EscPos;
+        self.upside_down = false;
+        self
+    }
+
+    /// Whether a print is laid out compactly, see [`Self::compact`]
+    pub fn is_compact(&self, data: &PrintData) -> bool {
+        self.compact
+            || data
+                .service
+                .as_ref()
+                .is_some_and(|service| self.compact_services.contains(service))
+    }
+
+    /// Loads `PRINTER_PROFILE` (`default` if unset), then applies `PRINTER_PROTOCOL`,
+    /// `PRINTER_PAPER_WIDTH` (in mm), `PRINTER_CODE_PAGE`, `PRINTER_CJK_ENCODING`,
+    /// `PRINTER_EMOJI`, `PRINTER_UPSIDE_DOWN`, `PRINTER_COMPACT(_SERVICES)`,
+    /// `PRINTER_MAX_MESSAGE_LENGTH`, `PRINTER_CUT`, `PRINTER_CUT_FEED`,
+    /// `PRINTER_CUT_DIGESTS_ONLY`, `PRINTER_HEADER`, `PRINTER_FOOTER`,
+    /// `PRINTER_BEEP(_SERVICES)`, `PRINTER_DRAWER_KICK(_SERVICES)`, `PRINTER_DRAWER_PIN` &
+    /// `PRINTER_DRAWER_PULSE` (in ms) on top
+    pub fn from_env() -> Self {
+        let name = std::env::var("PRINTER_PROFILE").unwrap_or_else(|_| "default".to_string());
+        let mut profile = Self::builtin(&name).unwrap_or_else(|| {
+            let file = std::fs::read_to_string(&name)
+                .unwrap_or_else(|e| panic!("Unknown PRINTER_PROFILE `{name}`! {e}"));
+            toml::from_str(&file)
+                .unwrap_or_else(|e| panic!("Printer profile {name} is malformed: {e}"))
+        });
+
+        if let Ok(protocol) = std::env::var("PRINTER_PROTOCOL") {
+            profile.protocol = protocol.parse().expect("Invalid PRINTER_PROTOCOL!");
+        }
+        if let Ok(width) = std::env::var("PRINTER_PAPER_WIDTH") {
+            width
+                .trim()
+                .trim_end_matches("mm")
+                .parse()
+                .map_err(|e| format!("{e}"))
+                .and_then(|mm| profile.set_paper_width(mm))
+                .expect("Invalid PRINTER_PAPER_WIDTH!");
+        }
+        if let Ok(code_page) = std::env::var("PRINTER_CODE_PAGE") {
+            profile.code_page = code_page.parse().expect("Invalid PRINTER_CODE_PAGE!");
+        }
+        if let Ok(cjk) = std::env::var("PRINTER_CJK_ENCODING") {
+            profile.cjk = Some(cjk.parse().expect("Invalid PRINTER_CJK_ENCODING!"));
+        }
+        if let Ok(emoji) = std::env::var("PRINTER_EMOJI") {
+            profile.emoji = emoji.parse().expect("Invalid PRINTER_EMOJI!");
+        }
+        if let Ok(upside_down) = std::env::var("PRINTER_UPSIDE_DOWN") {
+            profile.upside_down = upside_down
+                .parse()
+                .expect("Invalid PRINTER_UPSIDE_DOWN! Expected true or false");
+        }
+        if let Ok(compact) = std::env::var("PRINTER_COMPACT") {
+            profile.compact = compact
+                .parse()
+                .expect("Invalid PRINTER_COMPACT! Expected true or false");
+        }
+        if let Some(services) = services_from_env("PRINTER_COMPACT_SERVICES") {
+            profile.compact_services = services;
+        }
+        if let Ok(max) = std::env::var("PRINTER_MAX_MESSAGE_LENGTH") {
+            profile.max_message_length =
+                Some(max.parse().expect("Invalid PRINTER_MAX_MESSAGE_LENGTH!"));
+        }
+        if let Ok(cut) = std::env::var("PRINTER_CUT") {
+            profile.cut = cut.parse().expect("Invalid PRINTER_CUT!");
+        }
+        if let Ok(feed) = std::env::var("PRINTER_CUT_FEED") {
+            profile.cut_feed = Some(feed.parse().expect("Invalid PRINTER_CUT_FEED!"));
+        }
+        if let Ok(digests_only) = std::env::var("PRINTER_CUT_DIGESTS_ONLY") {
+            profile.cut_digests_only = digests_only
+                .parse()
+                .expect("Invalid PRINTER_CUT_DIGESTS_ONLY! Expected true or false");
+        }
+        if let Ok(header) = std::env::var("PRINTER_HEADER") {
+            profile.header = Some(header);
+        }
+        if let Ok(footer) = std::env::var("PRINTER_FOOTER") {
+            profile.footer = Some(footer);
+        }
+        profile.beep.override_from_env("PRINTER_BEEP");
+        profile.drawer_kick.override_from_env("PRINTER_DRAWER_KICK");
+        if let Ok(pin) = std::env::var("PRINTER_DRAWER_PIN") {
+            profile.drawer_pin = pin.parse().expect("Invalid PRINTER_DRAWER_PIN!");
+        }
+        if let Ok(pulse) = std::env::var("PRINTER_DRAWER_PULSE") {
+            profile.drawer_pulse_ms = pulse.parse().expect("Invalid PRINTER_DRAWER_PULSE!");
+        }
+        assert!(
+            matches!(profile.drawer_pin, 2 | 5),
+            "Invalid drawer pin {}; expected 2 or 5",
+            profile.drawer_pin
+        );
+
+        let device = std::env::var("DEVICE_NAME").unwrap_or_else(|_| "notifi-printer".to_string());
+        for lines in [&mut profile.header, &mut profile.footer]
+            .into_iter()
+            .flatten()
+        {
+            // Env vars can't easily hold line breaks
+            *lines = lines.replace("\\n", "\n").replace("{device}", &device);
+        }
+
+        profile
+    }
+}
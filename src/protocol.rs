@@ -0,0 +1,29 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// Command set spoken by the printer, set by its profile or `PRINTER_PROTOCOL`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Protocol {
+    /// Epson ESC/POS, spoken by most receipt printers
+    #[default]
+    #[serde(rename = "escpos")]
+    EscPos,
+    /// Star Micronics line mode
+    #[serde(rename = "star")]
+    StarLine,
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "escpos" => Ok(Self::EscPos),
+            "star" => Ok(Self::StarLine),
+            other => Err(format!(
+                "Unknown printer protocol `{other}`; expected escpos or star"
+            )),
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use std::{cmp::Reverse, collections::VecDeque, path::Path, str::FromStr};
+
+use chrono::Local;
+use tracing::{info, warn};
+
+use tokio::time::Instant;
+
+use crate::{
+    ack,
+    dedup::Dedup,
+    digest::Digest,
+    journal::Journal,
+    printer::{PrintData, Priority},
+    redact::Redact,
+    schedule::QuietHours,
+    test_page,
+    throttle::Throttle,
+};
+
+pub const DEFAULT_CAPACITY: usize = 16;
+
+/// What to do with new prints when the queue is full, e.g. while the printer is disconnected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Stop accepting prints once the queue is full; services wait on `send` until there's room
+    /// again
+    #[default]
+    Block,
+    /// Throw away the oldest queued print to make room for the new one
+    DropOldest,
+    /// Throw away the new print, and print a "N notifications dropped" receipt once there's room
+    DropAndCount,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "block" => Ok(Self::Block),
+            "drop-oldest" => Ok(Self::DropOldest),
+            "drop-and-count" => Ok(Self::DropAndCount),
+            other => Err(format!(
+                "Unknown overflow policy `{other}`; expected block, drop-oldest or drop-and-count"
+            )),
+        }
+    }
+}
+
+/// Prints waiting for the printer, pulled out of the channel as soon as they arrive so that
+/// producers never stall unless the policy says so
+///
+/// Prints are ordered by priority, then by arrival. Every print gets an ID as it comes in, used
+/// to mark it done in the journal once settled.
+pub struct PrintQueue {
+    jobs: VecDeque<(u64, PrintData)>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: usize,
+    next_id: u64,
+    journal: Option<Journal>,
+    dedup: Option<Dedup>,
+    digest: Option<Digest>,
+    /// IDs of the prints held in the digest
+    held: Vec<u64>,
+    quiet_hours: Option<QuietHours>,
+    /// Lowest priority printed during quiet hours anyway
+    quiet_bypass: Option<Priority>,
+    throttle: Option<Throttle>,
+    redact: Option<Redact>,
+    paused: bool,
+}
+
+impl PrintQueue {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            jobs: VecDeque::with_capacity(capacity),
+            capacity,
+            policy,
+            dropped: 0,
+            next_id: 0,
+            journal: None,
+            dedup: None,
+            digest: None,
+            held: Vec::new(),
+            quiet_hours: None,
+            quiet_bypass: None,
+            throttle: None,
+            redact: None,
+            paused: false,
+        }
+    }
+
+    /// Persists queued prints to the journal at `path`, replaying the ones left from last run
+    pub fn with_journal(mut self, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let (journal, pending, next_id) = Journal::open(path)?;
+        if !pending.is_empty() {
+            info!("Replaying {} prints from the print journal", pending.len());
+        }
+
+        self.next_id = next_id;
+        self.jobs.extend(pending);
+        self.jobs
+            .make_contiguous()
+            .sort_by_key(|(_, data)| Reverse(data.priority));
+        self.journal = Some(journal);
+        Ok(self)
+    }
+
+    /// Skips prints of events that were already printed, or are waiting for the printer
+    pub fn with_dedup(mut self, dedup: Dedup) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
+    /// Holds prints back to be printed together, see [`Digest`]
+    ///
+    /// Held prints stay in the journal until the digest of them is queued in their place.
+    pub fn with_digest(mut self, digest: Digest) -> Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    /// Queues the digest's held prints as one print
+    pub fn flush_digest(&mut self) {
+        if let Some(data) = self.digest.as_mut().and_then(Digest::take) {
+            self.enqueue_digest(data);
+        }
+    }
+
+    /// Holds prints in the queue during quiet hours, printing them all once they're over
+    ///
+    /// Once the queue is full, the overflow policy applies as usual; Pair with a journal & a
+    /// large enough capacity to hold on to a whole night's worth of prints.
+    pub fn with_quiet_hours(mut self, quiet_hours: QuietHours) -> Self {
+        self.quiet_hours = Some(quiet_hours);
+        self
+    }
+
+    /// Prints those of at least `priority` during quiet hours anyway, e.g. alerts
+    pub const fn with_quiet_bypass(mut self, priority: Priority) -> Self {
+        self.quiet_bypass = Some(priority);
+        self
+    }
+
+    /// Swaps the quiet hours, e.g. after the config is reloaded
+    pub fn set_quiet_hours(&mut self, quiet_hours: Option<QuietHours>) {
+        self.quiet_hours = quiet_hours;
+    }
+
+    /// When the ongoing quiet hours are over, if any
+    pub fn quiet_until(&self) -> Option<Instant> {
+        let remaining = self.quiet_hours.as_ref()?.remaining()?;
+        Some(Instant::now() + remaining)
+    }
+
+    /// Rate limits prints per service, see [`Throttle`]
+    pub fn with_throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Blacks out sensitive text in prints as they come in, see [`Redact`]
+    pub fn with_redact(mut self, redact: Redact) -> Self {
+        self.redact = Some(redact);
+        self
+    }
+
+    /// When the queue is next due a [`Self::tick`], e.g. to let go of the digest
+    pub fn wake_at(&self) -> Option<Instant> {
+        [
+            self.digest.as_ref().and_then(Digest::due),
+            self.throttle.as_ref().and_then(Throttle::due),
+            self.quiet_until(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Queues the digest & reports of suppressed prints once they're due
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if self
+            .digest
+            .as_ref()
+            .and_then(Digest::due)
+            .is_some_and(|due| due <= now)
+        {
+            self.flush_digest();
+        }
+        let reports = self
+            .throttle
+            .as_mut()
+            .map(Throttle::take_due)
+            .unwrap_or_default();
+        for report in reports {
+            let id = self.admit(&report);
+            self.enqueue(id, report);
+        }
+    }
+
+    /// Holds every print while paused, e.g. when the printer is out of paper
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Prints waiting for the printer with their IDs, next one first
+    pub fn jobs(&self) -> impl Iterator<Item = (u64, &PrintData)> {
+        self.jobs.iter().map(|(id, data)| (*id, data))
+    }
+
+    /// Drops a queued print; Returns false if it isn't queued (anymore)
+    pub fn cancel(&mut self, id: u64) -> bool {
+        let Some(index) = self.jobs.iter().position(|(queued, _)| *queued == id) else {
+            return false;
+        };
+        if let Some((id, data)) = self.jobs.remove(index) {
+            self.discard(id, &data);
+        }
+        true
+    }
+
+    /// Number of prints waiting for the printer
+    pub fn pending(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Whether another print should be taken off the channel
+    ///
+    /// When blocking, prints are left in the channel once the queue is full.
+    pub fn accepts(&self) -> bool {
+        match self.policy {
+            OverflowPolicy::Block => self.jobs.len() < self.capacity.max(1),
+            OverflowPolicy::DropOldest | OverflowPolicy::DropAndCount => true,
+        }
+    }
+
+    /// Takes a print in, journaling it right away
+    pub fn push(&mut self, mut data: PrintData) {
+        // Before journaling, so redacted text never hits the disk
+        if let Some(redact) = self.redact.as_ref() {
+            redact.apply(&mut data);
+        }
+        let id = self.admit(&data);
+        if test_page::is_test_page(&data) {
+            // Asked for on the spot; Never held back nor rate limited
+            self.enqueue(id, data);
+            return;
+        }
+        if self.dedup.as_mut().is_some_and(|d| d.is_duplicate(&data)) {
+            info!("Skipping duplicate print `{}`", data.title);
+            // Printed already, so its service can stop waiting on it
+            ack::printed(&data);
+            self.done(id);
+            return;
+        }
+        if self.throttle.as_mut().is_some_and(|t| !t.allow(&data)) {
+            // Reported on the suppressed prints receipt instead, so fetching it again would only
+            // get it suppressed again
+            ack::printed(&data);
+            self.done(id);
+            return;
+        }
+        if let Some(digest) = self.digest.as_mut() {
+            self.held.push(id);
+            if let Some(data) = digest.add(data) {
+                self.enqueue_digest(data);
+            }
+            return;
+        }
+        self.enqueue(id, data);
+    }
+
+    /// Numbers a print & records it in the journal
+    fn admit(&mut self, data: &PrintData) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some(journal) = self.journal.as_mut() {
+            journal.queued(id, data);
+        }
+        id
+    }
+
+    /// Queues a digest in place of the held prints it's made of
+    fn enqueue_digest(&mut self, data: PrintData) {
+        let id = self.admit(&data);
+        for held in std::mem::take(&mut self.held) {
+            self.done(held);
+        }
+        self.enqueue(id, data);
+    }
+
+    fn enqueue(&mut self, id: u64, data: PrintData) {
+        if self.jobs.len() >= self.capacity.max(1) {
+            match self.policy {
+                OverflowPolicy::Block => {}
+                OverflowPolicy::DropOldest => {
+                    // Oldest of the lowest priority prints
+                    let lowest = self
+                        .jobs
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(i, (_, data))| (data.priority, *i))
+                        .map(|(i, _)| i);
+                    if let Some((id, oldest)) = lowest.and_then(|i| self.jobs.remove(i)) {
+                        warn!("Print queue full, dropping oldest print `{}`", oldest.title);
+                        self.discard(id, &oldest);
+                    }
+                }
+                OverflowPolicy::DropAndCount => {
+                    warn!("Print queue full, dropping print `{}`", data.title);
+                    self.discard(id, &data);
+                    self.dropped += 1;
+                    return;
+                }
+            }
+        }
+
+        // Behind every print of the same or a higher priority
+        let index = self
+            .jobs
+            .iter()
+            .rposition(|(_, queued)| queued.priority >= data.priority)
+            .map_or(0, |i| i + 1);
+        self.jobs.insert(index, (id, data));
+    }
+
+    /// Marks a popped print as printed, or puts it back at the front if it failed to print
+    pub fn settle(&mut self, id: u64, printed: Result<PrintData, PrintData>) {
+        match printed {
+            Ok(data) => {
+                if let Some(dedup) = self.dedup.as_mut() {
+                    dedup.printed(&data);
+                }
+                self.done(id);
+            }
+            Err(data) => self.jobs.push_front((id, data)),
+        }
+    }
+
+    /// Settles a print thrown away without printing, e.g. cancelled
+    fn discard(&mut self, id: u64, data: &PrintData) {
+        if let Some(dedup) = self.dedup.as_mut() {
+            dedup.dropped(data);
+        }
+        ack::released(data);
+        self.done(id);
+    }
+
+    fn done(&mut self, id: u64) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.done(id);
+        }
+    }
+
+    /// Next print to send to the printer; Once the backlog is cleared, reports dropped prints
+    ///
+    /// Nothing is printed while paused, nor during quiet hours unless urgent enough to bypass
+    /// them.
+    pub fn pop(&mut self) -> Option<(u64, PrintData)> {
+        if self.paused {
+            return None;
+        }
+        if self.quiet_until().is_some() {
+            // Queued by priority, so if any print bypasses them the first one does
+            let bypass = self.jobs.front().is_some_and(|(_, data)| {
+                self.quiet_bypass
+                    .is_some_and(|priority| data.priority >= priority)
+            });
+            return if bypass { self.jobs.pop_front() } else { None };
+        }
+        if self.jobs.is_empty() && self.dropped > 0 {
+            let dropped = std::mem::take(&mut self.dropped);
+            self.push(PrintData {
+                title: "NOTIFI-PRINTER".to_string(),
+                subtitle: Some("Print queue overflowed".to_string()),
+                message: Some(
+                    format!(
+                        "{dropped} notification{} dropped",
+                        if dropped == 1 { " was" } else { "s were" }
+                    )
+                    .into(),
+                ),
+                timestamp: Local::now(),
+                ..Default::default()
+            });
+        }
+
+        self.jobs.pop_front()
+    }
+}
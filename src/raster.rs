@@ -0,0 +1,134 @@
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, GrayImage, Rgba,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{escpos::CommandBuffer, printer::Printable, profile::Profile};
+
+/// Widest images are kept at; 80mm paper fits 576 dots. Scaled further down to the paper's
+/// width when printed
+pub const MAX_IMAGE_WIDTH: u32 = 576;
+
+/// Grayscale image, dithered to black & white dots once the paper width is known
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Raster {
+    width: u32,
+    height: u32,
+    /// Luma of each pixel, row by row
+    pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// Scales an image down to `max_width` dots; Transparent pixels are printed as paper
+    pub fn from_image(image: &DynamicImage, max_width: u32) -> Self {
+        let mut image = image.to_rgba8();
+        // Transparent pixels would otherwise come out black
+        for Rgba([r, g, b, a]) in image.pixels_mut() {
+            let blend = |c: &mut u8| {
+                *c = u8::try_from(
+                    (u16::from(*c) * u16::from(*a) + 255 * (255 - u16::from(*a))) / 255,
+                )
+                .unwrap_or(u8::MAX);
+            };
+            blend(r);
+            blend(g);
+            blend(b);
+            *a = u8::MAX;
+        }
+        let gray = fit(DynamicImage::ImageRgba8(image).to_luma8(), max_width);
+        let (width, height) = gray.dimensions();
+
+        Self {
+            width,
+            height,
+            pixels: gray.into_raw(),
+        }
+    }
+
+    /// Decodes PNG / JPEG bytes into a printable raster
+    pub fn from_bytes(bytes: &[u8], max_width: u32) -> Result<Self, image::ImageError> {
+        Ok(Self::from_image(
+            &image::load_from_memory(bytes)?,
+            max_width,
+        ))
+    }
+
+    /// Height in dots once scaled down to `max_width` dots, as printed
+    pub fn printed_height(&self, max_width: u32) -> u32 {
+        if self.width <= max_width {
+            return self.height;
+        }
+        u32::try_from(u64::from(self.height) * u64::from(max_width) / u64::from(self.width))
+            .unwrap_or(u32::MAX)
+            .max(1)
+    }
+
+    /// Scales down to `max_width` dots and dithers to black & white
+    ///
+    /// Returns the width, height & rows of `width.div_ceil(8)` bytes, MSB first; 1 = black dot
+    pub fn dither(&self, max_width: u32) -> (u32, u32, Vec<u8>) {
+        let gray = GrayImage::from_raw(self.width, self.height, self.pixels.clone())
+            .expect("Raster pixels don't match its dimensions");
+        let gray = fit(gray, max_width);
+        let (width, height) = gray.dimensions();
+
+        // Floyd-Steinberg dithering, carrying the error over in an i16 buffer
+        let mut pixels: Vec<i16> = gray.pixels().map(|p| i16::from(p.0[0])).collect();
+        let row_bytes = width.div_ceil(8) as usize;
+        let mut data = vec![0u8; row_bytes * height as usize];
+        let (w, h) = (width as usize, height as usize);
+        for y in 0..h {
+            for x in 0..w {
+                let old = pixels[y * w + x];
+                let new = if old < 128 { 0 } else { 255 };
+                let error = old - new;
+                if new == 0 {
+                    data[y * row_bytes + x / 8] |= 0x80 >> (x % 8);
+                }
+
+                if x + 1 < w {
+                    pixels[y * w + x + 1] += error * 7 / 16;
+                }
+                if y + 1 < h {
+                    if x > 0 {
+                        pixels[(y + 1) * w + x - 1] += error * 3 / 16;
+                    }
+                    pixels[(y + 1) * w + x] += error * 5 / 16;
+                    if x + 1 < w {
+                        pixels[(y + 1) * w + x + 1] += error / 16;
+                    }
+                }
+            }
+        }
+
+        (width, height, data)
+    }
+}
+
+/// Scales down to `max_width` dots, keeping the aspect ratio
+fn fit(gray: GrayImage, max_width: u32) -> GrayImage {
+    if gray.width() <= max_width {
+        return gray;
+    }
+    let height =
+        u32::try_from(u64::from(gray.height()) * u64::from(max_width) / u64::from(gray.width()))
+            .unwrap_or(u32::MAX)
+            .max(1);
+    imageops::resize(&gray, max_width, height, FilterType::Triangle)
+}
+
+impl Printable for &Raster {
+    fn into_print_data(self, profile: &Profile) -> Vec<u8> {
+        let (width, height, data) = self.dither(profile.dots);
+
+        // Width is in bytes, height is in dots
+        let width = u16::try_from(width.div_ceil(8)).unwrap_or(u16::MAX);
+        let height = u16::try_from(height).unwrap_or(u16::MAX);
+
+        let mut out = CommandBuffer::new(profile);
+        out.raster(width, height, &data);
+
+        out.into_bytes()
+    }
+}
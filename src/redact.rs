@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+
+use regex::Regex;
+
+use crate::printer::{Message, PrintData};
+
+/// What sensitive text is replaced with
+const MASK: &str = "█████";
+
+/// Built-in rules by name
+const RULES: &[(&str, &str)] = &[
+    ("email", r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+"),
+    (
+        "phone",
+        r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{2,4}\)|\b\d{2,4})[\s.-]?\d{3,4}[\s.-]?\d{3,4}\b",
+    ),
+    // GitHub, Slack & AWS keys, JWTs & `sk-` style API keys
+    (
+        "token",
+        concat!(
+            r"\b(?:gh[pousr]_[A-Za-z0-9]{36,}|github_pat_\w{22,}|xox[abprs]-[\w-]{10,}",
+            r"|AKIA[0-9A-Z]{16}|eyJ[\w-]+\.[\w-]+\.[\w-]+|sk-[\w-]{20,})",
+        ),
+    ),
+];
+
+/// Blacks out sensitive text in prints, e.g. email addresses & API tokens, before they're
+/// queued; So it never reaches the paper, nor the journal
+pub struct Redact {
+    patterns: Vec<Regex>,
+}
+
+impl Redact {
+    /// Rules are either the name of a built-in rule, `email`, `phone` or `token`, or a regex
+    pub fn new<'a>(rules: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let patterns = rules
+            .into_iter()
+            .map(|rule| {
+                let pattern = RULES
+                    .iter()
+                    .find(|(name, _)| *name == rule)
+                    .map_or(rule, |(_, pattern)| pattern);
+                Regex::new(pattern).map_err(|e| format!("Invalid redaction rule `{rule}`: {e}"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Redacts the print's title, subtitle, message & QR code captions
+    ///
+    /// Matches spanning differently styled parts of a message aren't caught.
+    pub fn apply(&self, data: &mut PrintData) {
+        self.redact(&mut data.title);
+        if let Some(subtitle) = data.subtitle.as_mut() {
+            self.redact(subtitle);
+        }
+        match data.message.as_mut() {
+            Some(Message::Plain(text)) => self.redact(text),
+            Some(Message::Spans(spans)) => {
+                for span in spans {
+                    self.redact(&mut span.text);
+                }
+            }
+            None => {}
+        }
+        for qr_code in &mut data.qr_codes {
+            if let Some(caption) = qr_code.caption.as_mut() {
+                self.redact(caption);
+            }
+        }
+    }
+
+    fn redact(&self, text: &mut String) {
+        for pattern in &self.patterns {
+            if let Cow::Owned(redacted) = pattern.replace_all(text, MASK) {
+                *text = redacted;
+            }
+        }
+    }
+}
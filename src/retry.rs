@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+/// Exponential backoff with jitter, so retries of several services failing at once (e.g. when the
+/// network drops) don't all land at the same time
+///
+/// The `n`th retry waits between half & all of `initial * 2^n`, capped at `max`.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    /// Retries given before [`Self::exhausted`]; Unlimited if None
+    max_attempts: Option<u32>,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub const fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            max_attempts: None,
+            attempts: 0,
+        }
+    }
+
+    /// Gives up after `attempts` retries
+    pub const fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Wait before the next retry, counting it as an attempt
+    pub fn next_delay(&mut self) -> Duration {
+        let ceiling = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(self.attempts))
+            .min(self.max);
+        self.attempts = self.attempts.saturating_add(1);
+        ceiling / 2 + (ceiling / 2).mul_f64(fastrand::f64())
+    }
+
+    /// Whether every retry has been used up
+    pub fn exhausted(&self) -> bool {
+        self.max_attempts.is_some_and(|max| self.attempts >= max)
+    }
+
+    /// Starts over from `initial`, e.g. once the service is back up
+    pub const fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
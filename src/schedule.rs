@@ -0,0 +1,76 @@
+use std::{str::FromStr, time::Duration};
+
+use chrono::{Local, NaiveTime, TimeDelta};
+
+use crate::{
+    error::{Error, Result},
+    secrets,
+};
+
+/// Time left until the next time the local clock reads `time`
+///
+/// If `time` has already passed today, returns the duration until `time` tomorrow
+pub fn duration_until(time: NaiveTime) -> Duration {
+    let now = Local::now();
+    let today = now.date_naive().and_time(time);
+    let next = if today > now.naive_local() {
+        today
+    } else {
+        today + TimeDelta::days(1)
+    };
+
+    (next - now.naive_local()).to_std().unwrap_or_default()
+}
+
+/// Parses a `HH:MM` time-of-day, as used by the scheduled services' env vars
+pub fn parse_time_of_day(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+/// `HH:MM` time-of-day of an env var, or its secret; `default` if it isn't set, which it has to
+/// be without one
+pub fn env_time(var: &str, default: Option<&str>) -> Result<NaiveTime> {
+    let time = match (secrets::var(var), default) {
+        (Ok(time), _) => time,
+        (Err(_), Some(default)) => default.to_string(),
+        (Err(_), None) => return Err(Error::MissingEnv(var.to_string())),
+    };
+    parse_time_of_day(&time)
+        .ok_or_else(|| Error::InvalidEnv(var.to_string(), format!("Expected HH:MM, got `{time}`")))
+}
+
+/// Time of day prints are held back in, so the printer doesn't cut receipts at 3am; Written as
+/// `HH:MM-HH:MM`, and may span midnight
+#[derive(Debug, Clone, Copy)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Expected quiet hours as HH:MM-HH:MM, got `{s}`"))?;
+        let parse = |time| parse_time_of_day(time).ok_or_else(|| format!("Invalid time `{time}`"));
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl QuietHours {
+    /// Time left until the quiet hours are over, if they're ongoing
+    pub fn remaining(&self) -> Option<Duration> {
+        let now = Local::now().time();
+        let quiet = if self.start <= self.end {
+            self.start <= now && now < self.end
+        } else {
+            self.start <= now || now < self.end
+        };
+        quiet.then(|| duration_until(self.end))
+    }
+}
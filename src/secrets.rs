@@ -0,0 +1,51 @@
+use std::env::VarError;
+
+use tracing::warn;
+
+use crate::config;
+
+/// Service name secrets are stored under in the OS keyring
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "notifi-printer";
+
+/// Reads a setting or secret, like [`std::env::var`], from the first of
+///
+/// - The env var itself, e.g. `GITHUB_PAT`, or the config file's value once it's reloaded, see
+///   [`Config::reload`](crate::config::Config::reload)
+/// - The file named by the var suffixed with `_FILE`, e.g. `GITHUB_PAT_FILE=/run/secrets/pat`
+///   as Docker secrets are mounted; Trailing newlines are trimmed
+/// - The OS keyring, when built with the `keyring` feature; Stored there with `notifi-printer
+///   secret <VAR>`
+pub fn var(var: &str) -> Result<String, VarError> {
+    match config::reloaded(var) {
+        Some(Ok(value)) => return Ok(value),
+        // Unset by the reload, though still in the env
+        Some(Err(_)) => {}
+        None => match std::env::var(var) {
+            Err(VarError::NotPresent) => {}
+            value => return value,
+        },
+    }
+
+    if let Some(path) = std::env::var_os(format!("{var}_FILE")) {
+        match std::fs::read_to_string(&path) {
+            Ok(value) => return Ok(value.trim_end_matches(['\r', '\n']).to_string()),
+            Err(e) => warn!("Unable to read {var}_FILE {}: {e}", path.display()),
+        }
+    }
+
+    #[cfg(feature = "keyring")]
+    match keyring::Entry::new(KEYRING_SERVICE, var).and_then(|entry| entry.get_password()) {
+        Ok(value) => return Ok(value),
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => warn!("Unable to read {var} from the keyring: {e}"),
+    }
+
+    Err(VarError::NotPresent)
+}
+
+/// Stores a secret in the OS keyring, where [`var`] finds it when its env var isn't set
+#[cfg(feature = "keyring")]
+pub fn store(var: &str, value: &str) -> keyring::Result<()> {
+    keyring::Entry::new(KEYRING_SERVICE, var)?.set_password(value)
+}
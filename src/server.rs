@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::Local;
+use serde::Deserialize;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc::Sender, oneshot},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument};
+
+use crate::{
+    admin::{self, Command, Job},
+    backend::png,
+    history,
+    printer::{self, MarkdownLinks, PrintData, Priority},
+    profile::Profile,
+    secrets,
+    service::{github, github_sponsors, now_playing, strava, twitch},
+    status::{self, Status},
+    test_page,
+};
+
+#[derive(Clone)]
+struct AppState {
+    sender: Sender<PrintData>,
+    commands: Sender<Command>,
+}
+
+#[instrument(skip(cancel_token, sender, commands))]
+pub async fn start_server(
+    cancel_token: CancellationToken,
+    sender: Sender<PrintData>,
+    commands: Sender<Command>,
+) {
+    let Ok(addr) = std::env::var("HTTP_ADDR") else {
+        info!("Env `HTTP_ADDR` not set, HTTP server disabled");
+        return;
+    };
+
+    let admin = Router::new()
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/{id}", delete(cancel_job))
+        .route("/print", post(print))
+        .route("/preview", post(preview))
+        .route("/reprint", post(reprint))
+        .route_layer(middleware::from_fn(require_admin_token));
+
+    // Endpoints that print on demand, guarded like the admin API so nobody else can use up the
+    // paper
+    let printing = Router::new()
+        .route("/note", post(print_note))
+        .route("/now-playing", post(print_now_playing))
+        .route("/test-page", post(print_test_page))
+        .route_layer(middleware::from_fn(require_admin_token));
+
+    let app = Router::new()
+        .nest("/admin", admin)
+        .merge(printing)
+        .route("/dashboard", get(dashboard))
+        .route("/healthz", get(health))
+        .route("/github/webhook", post(receive_github_event))
+        .route("/twitch/webhook", post(receive_twitch_event))
+        .route(
+            "/strava/webhook",
+            get(verify_strava_subscription).post(receive_strava_event),
+        )
+        .with_state(AppState { sender, commands });
+
+    let listener = TcpListener::bind(&addr)
+        .await
+        .unwrap_or_else(|e| panic!("Unable to bind HTTP server to {addr}: {e}"));
+    info!("HTTP server listening on {addr}");
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(cancel_token.cancelled_owned())
+        .await
+        .expect("HTTP server crashed");
+}
+
+/// `GET /healthz` - Reports printer connectivity, queue depth & each service's last success;
+/// 503 while the printer is unreachable
+async fn health() -> (StatusCode, Json<Status>) {
+    let status = status::get();
+    let code = if status.printer_connected {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(status))
+}
+
+/// `GET /dashboard` - Page showing the status & queue, driving the admin API from the browser
+async fn dashboard() -> Html<&'static str> {
+    Html(include_str!("dashboard.html"))
+}
+
+fn note_print_data(text: &str) -> PrintData {
+    let (message, qr_codes) = printer::markdown(text, MarkdownLinks::from_env());
+    PrintData {
+        service: Some("note".to_string()),
+        logo: Some("note".to_string()),
+        title: "NOTE".to_string(),
+        subtitle: None,
+        message: Some(message.into()),
+        qr_codes,
+        timestamp: Local::now(),
+        ..Default::default()
+    }
+}
+
+/// `POST /note` - Prints the request body as a quick note
+async fn print_note(State(state): State<AppState>, body: String) -> StatusCode {
+    if body.trim().is_empty() {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    match state.sender.send(note_print_data(&body)).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(e) => {
+            error!("Unable to queue note: {e}");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+/// `POST /now-playing` - Prints the currently playing track
+async fn print_now_playing(State(state): State<AppState>) -> StatusCode {
+    match now_playing::print_now_playing(&state.sender).await {
+        Ok(true) => StatusCode::ACCEPTED,
+        Ok(false) => StatusCode::NO_CONTENT,
+        Err(e) => {
+            error!("Unable to print now playing: {e}");
+            StatusCode::BAD_GATEWAY
+        }
+    }
+}
+
+/// `POST /test-page` - Prints a page exercising every command, to check a printer's support
+async fn print_test_page(State(state): State<AppState>) -> StatusCode {
+    match state.sender.send(test_page::print_data()).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(e) => {
+            error!("Unable to queue test page: {e}");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+/// `GET /strava/webhook` - Echoes the challenge back when Strava validates the subscription
+async fn verify_strava_subscription(
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let Some(verify_token) = strava::webhook_verify_token() else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    if params.get("hub.verify_token") != Some(&verify_token) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let challenge = params.get("hub.challenge").ok_or(StatusCode::BAD_REQUEST)?;
+
+    Ok(Json(serde_json::json!({ "hub.challenge": challenge })))
+}
+
+/// `POST /strava/webhook` - Prints newly uploaded activities
+///
+/// Strava expects an answer within 2 seconds, so the activity is fetched in the background.
+async fn receive_strava_event(
+    State(state): State<AppState>,
+    Json(event): Json<strava::WebhookEvent>,
+) -> StatusCode {
+    if strava::webhook_verify_token().is_none() {
+        return StatusCode::NOT_FOUND;
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = strava::handle_webhook_event(&state.sender, event).await {
+            error!("Unable to print Strava activity: {e}");
+        }
+    });
+    StatusCode::OK
+}
+
+/// `POST /github/webhook` - Prints org, repo & sponsorship events GitHub delivers, e.g. pushes &
+/// new sponsors; Deliveries not signed with `GITHUB_WEBHOOK_SECRET` are refused
+async fn receive_github_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let Some(secret) = github::webhook_secret() else {
+        return StatusCode::NOT_FOUND;
+    };
+    let header = |name: &str| headers.get(name).and_then(|h| h.to_str().ok());
+    let signature = header("X-Hub-Signature-256").unwrap_or_default();
+    if !github::verify_signature(&secret, &body, signature) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    let (Some(event), Some(delivery)) = (header("X-GitHub-Event"), header("X-GitHub-Delivery"))
+    else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let Ok(payload) = serde_json::from_slice(&body) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    // Sponsorships are of an account rather than a repo
+    let printed = if event == "sponsorship" {
+        github_sponsors::webhook_print_data(delivery, &payload)
+    } else {
+        github::webhook_print_data(event, delivery, &payload)
+    };
+    let data = match printed {
+        Ok(Some(data)) => data,
+        Ok(None) => return StatusCode::NO_CONTENT,
+        Err(e) => {
+            error!("Unable to print GitHub {event} event: {e}");
+            return StatusCode::UNPROCESSABLE_ENTITY;
+        }
+    };
+    match state.sender.send(data).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(e) => {
+            error!("Unable to queue GitHub event: {e}");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+/// `POST /twitch/webhook` - Hands EventSub deliveries over to the Twitch service, while it uses
+/// the webhook transport; Deliveries not signed with `TWITCH_WEBHOOK_SECRET` are refused
+async fn receive_twitch_event(headers: HeaderMap, body: Bytes) -> Response {
+    let Some(secret) = twitch::webhook_secret() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let header = |name: &str| headers.get(name).and_then(|h| h.to_str().ok());
+    let (Some(message_id), Some(timestamp), Some(message_type)) = (
+        header("Twitch-Eventsub-Message-Id"),
+        header("Twitch-Eventsub-Message-Timestamp"),
+        header("Twitch-Eventsub-Message-Type"),
+    ) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let signature = header("Twitch-Eventsub-Message-Signature").unwrap_or_default();
+    if !twitch::verify_signature(&secret, message_id, timestamp, &body, signature) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let Ok(payload) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    // Twitch checks the callback is ours by having the challenge echoed back
+    if message_type == "webhook_callback_verification" {
+        return payload["challenge"].as_str().map_or_else(
+            || StatusCode::BAD_REQUEST.into_response(),
+            |challenge| ([(CONTENT_TYPE, "text/plain")], challenge.to_string()).into_response(),
+        );
+    }
+    if twitch::deliver(message_id, message_type, timestamp, payload) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        error!("Unable to hand Twitch {message_type} over to the service");
+        StatusCode::SERVICE_UNAVAILABLE.into_response()
+    }
+}
+
+/// Guards the admin API & printing endpoints behind `Authorization: Bearer <ADMIN_TOKEN>`;
+/// Disabled if the env isn't set
+async fn require_admin_token(headers: HeaderMap, request: Request, next: Next) -> Response {
+    let Ok(token) = secrets::var("ADMIN_TOKEN") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let given = headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    if !given.is_some_and(|given| admin::token_matches(given, &token)) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    next.run(request).await
+}
+
+async fn send_command(state: &AppState, command: Command) -> StatusCode {
+    match state.commands.send(command).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// `POST /admin/pause` - Holds queued prints, e.g. while the printer is out of paper
+async fn pause(State(state): State<AppState>) -> StatusCode {
+    send_command(&state, Command::Pause).await
+}
+
+/// `POST /admin/resume` - Prints held prints again
+async fn resume(State(state): State<AppState>) -> StatusCode {
+    send_command(&state, Command::Resume).await
+}
+
+/// `GET /admin/jobs` - Lists the prints waiting for the printer, next one first
+async fn list_jobs(State(state): State<AppState>) -> Result<Json<Vec<Job>>, StatusCode> {
+    let (reply, jobs) = oneshot::channel();
+    if state.commands.send(Command::Pending(reply)).await.is_err() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    jobs.await
+        .map(Json)
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)
+}
+
+/// `DELETE /admin/jobs/{id}` - Drops a queued print; 404 if it's not queued (anymore)
+async fn cancel_job(State(state): State<AppState>, Path(id): Path<u64>) -> StatusCode {
+    let (reply, cancelled) = oneshot::channel();
+    if state
+        .commands
+        .send(Command::Cancel(id, reply))
+        .await
+        .is_err()
+    {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    match cancelled.await {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+#[derive(Deserialize)]
+struct PrintRequest {
+    title: String,
+    subtitle: Option<String>,
+    message: Option<String>,
+    service: Option<String>,
+    logo: Option<String>,
+    #[serde(default)]
+    priority: Priority,
+}
+
+impl PrintRequest {
+    fn into_print_data(self) -> PrintData {
+        PrintData {
+            service: self.service,
+            logo: self.logo,
+            priority: self.priority,
+            title: self.title,
+            subtitle: self.subtitle,
+            message: self.message.map(Into::into),
+            timestamp: Local::now(),
+            ..Default::default()
+        }
+    }
+}
+
+/// `POST /admin/print` - Prints a receipt from JSON, e.g. `{"title": "Hi", "message": "..."}`
+async fn print(State(state): State<AppState>, Json(request): Json<PrintRequest>) -> StatusCode {
+    match state.sender.send(request.into_print_data()).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(e) => {
+            error!("Unable to queue print: {e}");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+/// `POST /admin/preview` - Renders a receipt from JSON to a PNG without printing it, as with
+/// `/admin/print`
+async fn preview(Json(request): Json<PrintRequest>) -> Response {
+    let profile = Profile::from_env().for_preview();
+    let job = printer::render_job(&profile, 0, &request.into_print_data());
+    match png::render_png(&job, &profile) {
+        Ok(png) => ([(CONTENT_TYPE, "image/png")], png).into_response(),
+        Err(e) => {
+            error!("Unable to render preview: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReprintRequest {
+    /// How many of the latest prints to print again
+    #[serde(default)]
+    last: usize,
+    /// IDs of prints in the history to print again
+    #[serde(default)]
+    ids: Vec<i64>,
+}
+
+/// `POST /admin/reprint` - Prints archived prints again, e.g. `{"last": 5}` or `{"ids": [12]}`,
+/// when the paper jammed or faded; 404 without `HISTORY_DB` or if none match
+async fn reprint(State(state): State<AppState>, Json(request): Json<ReprintRequest>) -> StatusCode {
+    let reprints = match history::reprints(request.last, &request.ids) {
+        Some(Ok(reprints)) if !reprints.is_empty() => reprints,
+        Some(Ok(_)) | None => return StatusCode::NOT_FOUND,
+        Some(Err(e)) => {
+            error!("Unable to read prints to reprint from the history: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    info!("Reprinting {} prints", reprints.len());
+    for data in reprints {
+        if let Err(e) = state.sender.send(data).await {
+            error!("Unable to queue reprint: {e}");
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+    }
+    StatusCode::ACCEPTED
+}
@@ -0,0 +1,226 @@
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use reqwest::Client;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    error::{self, Result},
+    http::{self, SendRetrying},
+    printer::PrintData,
+    schedule, secrets, status,
+};
+
+const API_URL: &str = "https://export.arxiv.org/api/query";
+const ATOM_NS: &str = "http://www.w3.org/2005/Atom";
+
+const ABSTRACT_EXCERPT_LENGTH: usize = 300;
+
+struct Paper {
+    id: String,
+    title: String,
+    authors: Vec<String>,
+    summary: String,
+    published: DateTime<Utc>,
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let Ok(categories) = secrets::var("ARXIV_CATEGORIES") else {
+        info!("Env `ARXIV_CATEGORIES` not set, arXiv service disabled");
+        return;
+    };
+    let keywords = secrets::var("ARXIV_KEYWORDS").unwrap_or_default();
+    let settings = || -> Result<_> {
+        Ok((
+            schedule::env_time("ARXIV_PRINT_TIME", Some("08:00"))?,
+            error::env_or("ARXIV_MAX_RESULTS", 10)?,
+        ))
+    };
+    let (print_time, max_results) = match settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            info!("{e}, arXiv service disabled");
+            return;
+        }
+    };
+
+    let query = build_search_query(&categories, &keywords);
+    debug!("Using search query: {query}");
+
+    let http_client = http::client();
+    let mut last_printed = Utc::now() - TimeDelta::days(1);
+
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(schedule::duration_until(print_time)) => {}
+        }
+
+        let papers = match search_papers(&http_client, &query, max_results).await {
+            Ok(p) => {
+                status::service_ok("arxiv");
+                p
+            }
+            Err(e) => {
+                error!("Unable to fetch arXiv papers: {e}");
+                continue;
+            }
+        };
+        let new_papers = papers
+            .into_iter()
+            .filter(|p| p.published > last_printed)
+            .collect::<Vec<Paper>>();
+        last_printed = Utc::now();
+
+        if new_papers.is_empty() {
+            info!("No new papers today");
+            continue;
+        }
+
+        info!("Printing {} new papers", new_papers.len());
+        let message = new_papers
+            .iter()
+            .map(Paper::format)
+            .collect::<Vec<String>>()
+            .join(&format!("\n{}\n", "-".repeat(48)));
+
+        if sender
+            .send(PrintData {
+                service: Some("arxiv".to_string()),
+                logo: Some("arxiv".to_string()),
+                title: "arXiv: New Papers".to_string(),
+                subtitle: Some(format!("{} new in {categories}", new_papers.len())),
+                message: Some(message.into()),
+                timestamp: Local::now(),
+                ..Default::default()
+            })
+            .await
+            .is_err()
+        {
+            debug!("Print queue closed! Stopping service...");
+            return;
+        }
+    }
+}
+
+/// Builds an arXiv search query, e.g. `(cat:cs.AI OR cat:cs.LG) AND (all:"diffusion")`
+fn build_search_query(categories: &str, keywords: &str) -> String {
+    let join = |prefix: &str, values: &str| {
+        values
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                if v.contains(' ') {
+                    format!("{prefix}:\"{v}\"")
+                } else {
+                    format!("{prefix}:{v}")
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" OR ")
+    };
+
+    let categories = join("cat", categories);
+    let keywords = join("all", keywords);
+    if keywords.is_empty() {
+        format!("({categories})")
+    } else {
+        format!("({categories}) AND ({keywords})")
+    }
+}
+
+impl Paper {
+    fn format(&self) -> String {
+        let authors = if self.authors.len() > 3 {
+            format!("{} et al.", self.authors[..3].join(", "))
+        } else {
+            self.authors.join(", ")
+        };
+
+        format!(
+            "{}\n{authors}\n\n{}\n{}",
+            self.title,
+            excerpt(&self.summary, ABSTRACT_EXCERPT_LENGTH),
+            self.id
+        )
+    }
+}
+
+/// Cuts `text` down to roughly `max_len` chars, ending on a word boundary
+fn excerpt(text: &str, max_len: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.chars().count() <= max_len {
+        return text;
+    }
+
+    let mut out = String::new();
+    for word in text.split(' ') {
+        if out.chars().count() + word.chars().count() + 1 > max_len {
+            break;
+        }
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    out.push_str("...");
+
+    out
+}
+
+#[instrument(skip(client))]
+async fn search_papers(client: &Client, query: &str, max_results: usize) -> Result<Vec<Paper>> {
+    let body = client
+        .get(API_URL)
+        .query(&[
+            ("search_query", query),
+            ("sortBy", "submittedDate"),
+            ("sortOrder", "descending"),
+            ("max_results", &max_results.to_string()),
+        ])
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let document = roxmltree::Document::parse(&body)?;
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|c| c.has_tag_name((ATOM_NS, name)))
+            .and_then(|c| c.text())
+            .map(|t| t.split_whitespace().collect::<Vec<&str>>().join(" "))
+            .unwrap_or_default()
+    };
+
+    let papers = document
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name((ATOM_NS, "entry")))
+        .filter_map(|entry| {
+            let published = DateTime::parse_from_rfc3339(&child_text(entry, "published")).ok()?;
+            let authors = entry
+                .children()
+                .filter(|c| c.has_tag_name((ATOM_NS, "author")))
+                .map(|author| child_text(author, "name"))
+                .collect();
+
+            Some(Paper {
+                id: child_text(entry, "id"),
+                title: child_text(entry, "title"),
+                authors,
+                summary: child_text(entry, "summary"),
+                published: published.with_timezone(&Utc),
+            })
+        })
+        .collect();
+
+    Ok(papers)
+}
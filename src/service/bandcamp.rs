@@ -0,0 +1,185 @@
+use std::{collections::HashSet, time::Duration};
+
+use chrono::{DateTime, Local};
+use reqwest::Client;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    error::{Error, Result},
+    http::{self, SendRetrying},
+    printer::{PrintData, QrCode},
+    secrets, status,
+};
+
+const ATOM_NS: &str = "http://www.w3.org/2005/Atom";
+
+const POLL_INTERVAL: Duration = Duration::from_hours(1);
+
+struct Release {
+    id: String,
+    artist: String,
+    title: String,
+    link: String,
+    published: Option<DateTime<Local>>,
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let Ok(feeds) = secrets::var("BANDCAMP_FEEDS") else {
+        info!("Env `BANDCAMP_FEEDS` not set, Bandcamp service disabled");
+        return;
+    };
+    let feeds = feeds
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(ToString::to_string)
+        .collect::<Vec<String>>();
+
+    let http_client = http::client();
+
+    // None = First poll; releases that already exist shouldn't be printed
+    let mut seen: Option<HashSet<String>> = None;
+
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        let mut releases = Vec::new();
+        for feed in &feeds {
+            match get_releases(&http_client, feed).await {
+                Ok(r) => {
+                    status::service_ok("bandcamp");
+                    releases.extend(r);
+                }
+                Err(e) => error!("Unable to fetch Bandcamp feed {feed}: {e}"),
+            }
+        }
+
+        if let Some(seen) = seen.as_mut() {
+            for release in releases {
+                if !seen.insert(release.id.clone()) {
+                    continue;
+                }
+
+                info!("New release: {} - {}", release.artist, release.title);
+                if sender.send(release.into_print_data()).await.is_err() {
+                    debug!("Print queue closed! Stopping service...");
+                    return;
+                }
+            }
+        } else {
+            debug!("Seeding {} existing releases", releases.len());
+            seen = Some(releases.into_iter().map(|r| r.id).collect());
+        }
+
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+}
+
+impl Release {
+    fn into_print_data(self) -> PrintData {
+        PrintData {
+            service: Some("bandcamp".to_string()),
+            logo: Some("bandcamp".to_string()),
+            title: "Bandcamp: New Release".to_string(),
+            subtitle: Some(self.artist),
+            message: Some(self.title.into()),
+            qr_codes: vec![QrCode {
+                caption: Some("Listen".to_string()),
+                data: self.link,
+            }],
+            timestamp: self.published.unwrap_or_else(Local::now),
+            ..Default::default()
+        }
+    }
+}
+
+/// Fetches an RSS 2.0 or Atom feed of an artist / label
+#[instrument(skip(client))]
+async fn get_releases(client: &Client, url: &str) -> Result<Vec<Release>> {
+    let body = client
+        .get(url)
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let document = roxmltree::Document::parse(&body)?;
+
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|c| c.tag_name().name() == name)
+            .and_then(|c| c.text())
+            .map(|t| t.trim().to_string())
+    };
+
+    let root = document.root_element();
+    let releases = if root.has_tag_name((ATOM_NS, "feed")) {
+        let feed_title = child_text(root, "title").unwrap_or_default();
+        root.children()
+            .filter(|n| n.has_tag_name((ATOM_NS, "entry")))
+            .map(|entry| {
+                let link = entry
+                    .children()
+                    .find(|c| c.has_tag_name((ATOM_NS, "link")))
+                    .and_then(|l| l.attribute("href"))
+                    .unwrap_or_default()
+                    .to_string();
+                let artist = entry
+                    .children()
+                    .find(|c| c.has_tag_name((ATOM_NS, "author")))
+                    .and_then(|a| child_text(a, "name"))
+                    .unwrap_or_else(|| feed_title.clone());
+
+                Release {
+                    id: child_text(entry, "id").unwrap_or_else(|| link.clone()),
+                    artist,
+                    title: child_text(entry, "title").unwrap_or_default(),
+                    published: child_text(entry, "updated")
+                        .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                        .map(|t| t.with_timezone(&Local)),
+                    link,
+                }
+            })
+            .collect()
+    } else {
+        let channel = root
+            .children()
+            .find(|n| n.tag_name().name() == "channel")
+            .ok_or_else(|| Error::Response("Feed is neither RSS nor Atom".to_string()))?;
+        let feed_title = child_text(channel, "title").unwrap_or_default();
+        channel
+            .children()
+            .filter(|n| n.tag_name().name() == "item")
+            .map(|item| {
+                let link = child_text(item, "link").unwrap_or_default();
+                Release {
+                    id: child_text(item, "guid").unwrap_or_else(|| link.clone()),
+                    artist: child_text(item, "creator")
+                        .or_else(|| child_text(item, "author"))
+                        .unwrap_or_else(|| feed_title.clone()),
+                    title: child_text(item, "title").unwrap_or_default(),
+                    published: child_text(item, "pubDate")
+                        .and_then(|t| DateTime::parse_from_rfc2822(&t).ok())
+                        .map(|t| t.with_timezone(&Local)),
+                    link,
+                }
+            })
+            .collect()
+    };
+
+    Ok(releases)
+}
@@ -1,194 +1,384 @@
 use std::{str::FromStr, time::Duration};
 
-use chrono::Utc;
-use reqwest::{StatusCode, Url};
+use futures_util::future::join_all;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc::Sender},
+    time::Instant,
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info, instrument};
-
-use crate::{http, printer::PrintData};
-
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::{
+    ack::{self, Acked, InFlight},
+    error::{str_at, Error, Result},
+    http::{self, SendRetrying},
+    printer::{PrintData, Priority, Span},
+    retry::Backoff,
+    secrets,
+    service::Account,
+    state, status,
+};
+
+/// How often notifications are fetched when `BSKY_POLL_INTERVAL` isn't set
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Wait before polling again after a failed poll, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(10);
+
+/// Polls the notifications of every account in `BSKY_ACCOUNTS` at once, see [`Account`]
 #[instrument(skip(cancel_token, sender))]
-pub async fn start_service(
-    cancel_token: CancellationToken,
-    sender: tokio::sync::mpsc::Sender<PrintData>,
-) {
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
     let reqwest = http::client();
+    let poll_interval = secrets::var("BSKY_POLL_INTERVAL").map_or(DEFAULT_POLL_INTERVAL, |s| {
+        Duration::from_secs(
+            s.parse()
+                .expect("Invalid BSKY_POLL_INTERVAL! Expected seconds"),
+        )
+    });
+
+    join_all(
+        Account::all("BSKY_ACCOUNTS")
+            .into_iter()
+            .map(|account| run(&reqwest, &cancel_token, &sender, account, poll_interval)),
+    )
+    .await;
+}
 
+#[instrument(skip_all, fields(account = account.label.as_deref()))]
+async fn run(
+    reqwest: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    account: Account,
+    poll_interval: Duration,
+) {
+    let name = account.name("Bsky");
     // None = Expired
     let mut access_token: Option<Box<str>> = None;
-    // None = New
-    let mut refresh_jwt: Option<Box<str>> = None;
+    // None = New; Kept across restarts, as logging in again is rate limited
+    let mut refresh_jwt: Option<Box<str>> =
+        state::get(&account.state_key("bsky"), "refresh_jwt").map(Into::into);
+    let mut seen = Seen::new(&account);
+    let mut acks = ack::subscribe();
 
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
     loop {
         if cancel_token.is_cancelled() {
             debug!("Cancel signal caught! Stopping service...");
             return;
         }
 
-        // Refresh JWT is None if initial run
-        if refresh_jwt.is_none() {
-            let session = create_session(reqwest.clone()).await;
-            access_token = Some(session.0);
-            refresh_jwt = Some(session.1);
-        }
-
-        // Refresh Access Token if expired
-        if access_token.is_none() {
-            let Ok(session) = refresh_session(reqwest.clone(), &refresh_jwt.unwrap()).await else {
-                error!("Unable to refresh session! Going to remake session from scratch...");
-                refresh_jwt = None;
+        let polled = poll(
+            reqwest,
+            sender,
+            &account,
+            &mut access_token,
+            &mut refresh_jwt,
+            &mut seen,
+        )
+        .await;
+        let delay = match polled {
+            Ok(()) => {
+                status::service_ok("bsky");
+                backoff.reset();
+                poll_interval
+            }
+            // Token expired - Set access token to none & retry right away
+            Err(Error::Unauthorized) => {
+                access_token = None;
                 continue;
-            };
-            access_token = Some(session.0);
-            refresh_jwt = Some(session.1);
+            }
+            Err(Error::MissingEnv(var)) => {
+                info!("Env `{var}` not set, {name} disabled");
+                return;
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("Unable to fetch {name} notifications, retrying in {delay:?}: {e}");
+                delay
+            }
+            Err(e) => {
+                error!("Stopping {name} service: {e}");
+                return;
+            }
+        };
+
+        let next_poll = Instant::now() + delay;
+        loop {
+            tokio::select! {
+                () = cancel_token.cancelled() => return,
+                () = tokio::time::sleep_until(next_poll) => break,
+                acked = acks.recv() => match acked {
+                    Ok(acked) => {
+                        if !seen.acked(&acked) {
+                            continue;
+                        }
+                        if let Some(access_token) = &access_token {
+                            mark_seen(reqwest, access_token, &mut seen).await;
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Missed {missed} acks, their notifications are printed again later");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
         }
+    }
+}
 
-        let unread_notifications =
-            get_unread_notifications(reqwest.clone(), access_token.as_ref().unwrap()).await;
+/// How far notifications can be marked seen; Up to the latest one handled, once none of those
+/// printed are waiting for the printer anymore
+struct Seen {
+    state_key: String,
+    /// Printed notifications waiting for the printer, by URI, with when they were indexed
+    in_flight: InFlight,
+    /// Latest `indexedAt` handled since notifications were last marked seen
+    handled: Option<String>,
+}
 
-        // Token expired - Set access token to none & continue loop
-        if matches!(unread_notifications, Err(BskyError::ExpiredToken)) {
-            access_token = None;
-            continue;
+impl Seen {
+    fn new(account: &Account) -> Self {
+        let state_key = account.state_key("bsky");
+        Self {
+            in_flight: InFlight::load("bsky", state_key.clone()),
+            state_key,
+            handled: None,
         }
-        let unread_notifications = unread_notifications.unwrap();
-
-        if !unread_notifications.is_empty() {
-            // Loop over all unreads & print
-            for n in unread_notifications {
-                info!("Notif: {n}");
-                let notif_type = n["reason"]
-                    .as_str()
-                    .expect("Malformed data: notification does not have field `reason`");
-                let timestamp = n["record"]["createdAt"].as_str().unwrap();
-                let print_data: PrintData = match notif_type {
-                    "follow" => {
-                        let did = n["author"]["did"].as_str().unwrap();
-                        let profile_info =
-                            get_profile_info(reqwest.clone(), access_token.as_ref().unwrap(), did)
-                                .await
-                                .unwrap();
-
-                        PrintData {
-                            title: "Bsky: New follower".to_string(),
-                            subtitle: None,
-                            message: Some(format!(
-                                "{} ({}) followed you\n{}\n{} Following | {} Followers",
-                                profile_info.display_name,
-                                profile_info.handle,
-                                profile_info.description,
-                                profile_info.follows_count,
-                                profile_info.followers_count
-                            )),
-                            timestamp: chrono::DateTime::from_str(timestamp).unwrap(),
-                        }
-                    }
+    }
 
-                    "reply" => {
-                        let display_name = n["author"]["displayName"].as_str().unwrap();
-                        let handle = n["author"]["handle"].as_str().unwrap();
-                        let text = n["record"]["text"].as_str().unwrap();
+    /// Counts a notification as handled, i.e. printed, skipped or failed
+    fn handled(&mut self, indexed_at: &str) {
+        if self
+            .handled
+            .as_deref()
+            .is_none_or(|handled| indexed_at > handled)
+        {
+            self.handled = Some(indexed_at.to_string());
+        }
+    }
 
-                        let Some(parent_uri) = n["record"]["reply"]["parent"]["uri"].as_str()
-                        else {
-                            error!("Reply does not have any parent. Skipping this message!");
-                            continue;
-                        };
-                        let parent_post = get_post_details(
-                            reqwest.clone(),
-                            access_token.as_ref().unwrap(),
-                            parent_uri,
-                        )
-                        .await
-                        .unwrap();
-                        let parent_display_name = parent_post["thread"]["post"]["author"]
-                            ["displayName"]
-                            .as_str()
-                            .unwrap();
-                        let parent_handle = parent_post["thread"]["post"]["author"]["handle"]
-                            .as_str()
-                            .unwrap();
-                        let parent_text = parent_post["thread"]["post"]["record"]["text"]
-                            .as_str()
-                            .unwrap();
-                        let parent_text_wrapped = textwrap::wrap(
-                            parent_text,
-                            textwrap::Options::new(48).initial_indent("> "),
-                        )
-                        .join("\n");
-
-                        PrintData {
-                            title: "Bsky: New reply".to_string(),
-                            subtitle: None,
-                            message: Some(textwrap::dedent(&format!(
-                                "
-                                > {parent_display_name} ({parent_handle}) said
-                                {parent_text_wrapped}
-
-                                {display_name} ({handle}) replied:
-                                {text}"
-                            ))),
-                            timestamp: chrono::DateTime::from_str(timestamp).unwrap(),
-                        }
-                    }
+    /// Handles a printed notification; Returns whether it was one of ours
+    fn acked(&mut self, acked: &Acked) -> bool {
+        let Some(indexed_at) = self.in_flight.acked(acked) else {
+            return false;
+        };
+        self.handled(&indexed_at);
+        true
+    }
+}
 
-                    // Noop, too spammy
-                    "like" | "repost" => {
-                        // let display_name = n["author"]["displayName"].as_str().unwrap();
-                        // let handle = n["author"]["handle"].as_str().unwrap();
-
-                        // PrintData {
-                        //     title: "Bsky: New like".to_string(),
-                        //     subtitle: None,
-                        //     message: Some(format!("{display_name} ({handle}) liked your post")),
-                        //     timestamp: chrono::DateTime::from_str(timestamp).unwrap(),
-                        // }
-                        continue;
-                    }
+/// Marks notifications seen, both here & on Bsky, up to the latest handled once none are in
+/// flight
+async fn mark_seen(reqwest: &Client, access_token: &str, seen: &mut Seen) {
+    if !seen.in_flight.is_empty() {
+        return;
+    }
+    let Some(seen_at) = seen.handled.take() else {
+        return;
+    };
+    state::set(&seen.state_key, "seen_at", Some(&seen_at));
+    if let Err(e) = update_last_read_notification(reqwest, access_token, &seen_at).await {
+        error!("Unable to update last read notifications: {e}");
+    }
+}
 
-                    _ => {
-                        error!("Unknown notification reason caught: {notif_type}");
-                        continue;
+/// Prints unread notifications, then marks them as seen; Logs in again when needed
+async fn poll(
+    reqwest: &Client,
+    sender: &Sender<PrintData>,
+    account: &Account,
+    access_token: &mut Option<Box<str>>,
+    refresh_jwt: &mut Option<Box<str>>,
+    seen: &mut Seen,
+) -> Result<()> {
+    let state_key = account.state_key("bsky");
+    let access_token: &str = match access_token {
+        Some(access_token) => access_token,
+        None => {
+            let session = match refresh_jwt.as_deref() {
+                // Refresh Access Token if expired
+                Some(refresh) => match refresh_session(reqwest, refresh).await {
+                    Ok(session) => session,
+                    Err(e) => {
+                        error!("Unable to refresh session! Remaking session from scratch: {e}");
+                        create_session(reqwest, account).await?
                     }
-                };
+                },
+                // Refresh JWT is None if initial run
+                None => create_session(reqwest, account).await?,
+            };
+            state::set(&state_key, "refresh_jwt", Some(&session.1));
+            *refresh_jwt = Some(session.1);
+            access_token.insert(session.0)
+        }
+    };
+
+    // Notifications are left unread if marking them seen failed, so they're only printed if
+    // indexed after the last ones printed; Timestamps are all UTC, so they sort as strings
+    let seen_at = state::get(&seen.state_key, "seen_at");
+    let unread_notifications = get_unread_notifications(reqwest, access_token)
+        .await?
+        .into_iter()
+        .filter(|n| {
+            let indexed_at = n["indexedAt"].as_str().unwrap_or_default();
+            seen_at
+                .as_deref()
+                .is_none_or(|seen_at| indexed_at > seen_at)
+        })
+        .filter(|n| {
+            n["uri"]
+                .as_str()
+                .is_none_or(|uri| !seen.in_flight.contains(uri))
+        })
+        .collect::<Vec<Value>>();
+
+    // Loop over all unreads & print
+    for n in &unread_notifications {
+        info!("Notif: {n}");
+        let indexed_at = n["indexedAt"].as_str().unwrap_or_default();
+        match notification_print_data(reqwest, account, access_token, n).await {
+            Ok(Some(print_data)) => {
+                match &print_data.event_id {
+                    // Persisted before it's queued, so a restart can't lose track of it
+                    Some(uri) => seen.in_flight.insert(uri.clone(), indexed_at.to_string()),
+                    None => seen.handled(indexed_at),
+                }
+                sender.send(print_data).await?;
+            }
+            Ok(None) => seen.handled(indexed_at),
+            Err(Error::Unauthorized) => return Err(Error::Unauthorized),
+            Err(e) => {
+                error!("Unable to print Bsky notification: {e}\n{n}");
+                seen.handled(indexed_at);
+            }
+        }
+    }
+
+    mark_seen(reqwest, access_token, seen).await;
+    Ok(())
+}
 
-                sender.send(print_data).await.unwrap();
+/// What to print for a notification; None for ones that aren't printed
+async fn notification_print_data(
+    reqwest: &Client,
+    account: &Account,
+    access_token: &str,
+    n: &Value,
+) -> Result<Option<PrintData>> {
+    let name = account.name("Bsky");
+    let notif_type = str_at(n, "/reason")?;
+    let timestamp = chrono::DateTime::from_str(str_at(n, "/record/createdAt")?)?;
+    let print_data = match notif_type {
+        "follow" => {
+            let did = str_at(n, "/author/did")?;
+            let profile_info = get_profile_info(reqwest, access_token, did).await?;
+
+            PrintData {
+                service: Some("bsky".to_string()),
+                logo: Some("bsky".to_string()),
+                event_id: n["uri"].as_str().map(str::to_string),
+                priority: Priority::Low,
+                title: format!("{name}: New follower"),
+                subtitle: None,
+                message: Some(
+                    vec![
+                        Span::bold(&profile_info.display_name),
+                        Span::plain(format!(
+                            " ({}) followed you\n{}\n{} Following | {} Followers",
+                            profile_info.handle,
+                            profile_info.description,
+                            profile_info.follows_count,
+                            profile_info.followers_count
+                        )),
+                    ]
+                    .into(),
+                ),
+                timestamp,
+                ..Default::default()
             }
+        }
 
-            // Update last read notification time
-            // If error updating, log the error
-            // Potential error: Token expired in-between requests
-            if let Err(e) =
-                update_last_read_notification(reqwest.clone(), access_token.as_ref().unwrap()).await
-            {
-                error!("Unable to update last read notifications: {e:?}");
+        "reply" => {
+            let display_name = str_at(n, "/author/displayName")?;
+            let handle = str_at(n, "/author/handle")?;
+            let text = str_at(n, "/record/text")?;
+
+            let Some(parent_uri) = n["record"]["reply"]["parent"]["uri"].as_str() else {
+                error!("Reply does not have any parent. Skipping this message!");
+                return Ok(None);
+            };
+            let parent_post = get_post_details(reqwest, access_token, parent_uri).await?;
+            let parent_display_name = str_at(&parent_post, "/thread/post/author/displayName")?;
+            let parent_handle = str_at(&parent_post, "/thread/post/author/handle")?;
+            let parent_text = str_at(&parent_post, "/thread/post/record/text")?;
+            let parent_text_wrapped =
+                textwrap::wrap(parent_text, textwrap::Options::new(48).initial_indent("> "))
+                    .join("\n");
+
+            PrintData {
+                service: Some("bsky".to_string()),
+                logo: Some("bsky".to_string()),
+                event_id: n["uri"].as_str().map(str::to_string),
+                priority: Priority::High,
+                title: format!("{name}: New reply"),
+                subtitle: None,
+                message: Some(
+                    textwrap::dedent(&format!(
+                        "
+                        > {parent_display_name} ({parent_handle}) said
+                        {parent_text_wrapped}
+
+                        {display_name} ({handle}) replied:
+                        {text}"
+                    ))
+                    .into(),
+                ),
+                timestamp,
+                ..Default::default()
             }
         }
 
-        tokio::select! {
-            () = cancel_token.cancelled() => {}
-            () = tokio::time::sleep(Duration::from_secs(10)) => {}
+        // Noop, too spammy
+        "like" | "repost" => {
+            // let display_name = n["author"]["displayName"].as_str().unwrap();
+            // let handle = n["author"]["handle"].as_str().unwrap();
+
+            // PrintData {
+            //     title: "Bsky: New like".to_string(),
+            //     subtitle: None,
+            //     message: Some(format!("{display_name} ({handle}) liked your post")),
+            //     timestamp: chrono::DateTime::from_str(timestamp).unwrap(),
+            // }
+            return Ok(None);
         }
-    }
+
+        _ => {
+            error!("Unknown notification reason caught: {notif_type}");
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(print_data))
 }
 
-#[derive(Debug)]
-enum BskyError {
-    ExpiredToken,
-    BadRequest,
+/// Access & refresh JWTs out of a session response
+fn session_tokens(res: &Value) -> Result<(Box<str>, Box<str>)> {
+    Ok((
+        str_at(res, "/accessJwt")?.into(),
+        str_at(res, "/refreshJwt")?.into(),
+    ))
 }
 
 const CREATE_SESSION_URL: &str = "https://bsky.social/xrpc/com.atproto.server.createSession";
-/// # Panic
-///
-/// * Panics on HTTP request fails
-/// * Panics on malformed data returned from backend
-#[instrument(skip(client))]
-async fn create_session(client: reqwest::Client) -> (Box<str>, Box<str>) {
-    let id = std::env::var("BSKY_IDENTIFIER").expect("Envvar BSKY_IDENTIFIER not supplied!");
-    let pass = std::env::var("BSKY_PASSWORD").expect("Envvar BSKY_PASSWORD not supplied!");
+#[instrument(skip_all)]
+async fn create_session(client: &Client, account: &Account) -> Result<(Box<str>, Box<str>)> {
+    let id = account.env("BSKY_IDENTIFIER")?;
+    let pass = account.env("BSKY_PASSWORD")?;
 
     let req = client
         .post(CREATE_SESSION_URL)
@@ -196,88 +386,63 @@ async fn create_session(client: reqwest::Client) -> (Box<str>, Box<str>) {
             "identifier": id,
             "password": pass
         }))
-        .send()
-        .await
-        .unwrap();
-
-    assert!(req.status() == StatusCode::OK);
-    let res: Value = req.json().await.unwrap();
-    let access_jwt = res["accessJwt"]
-        .as_str()
-        .expect("Session's `accessJwt` is missing!")
-        .to_string()
-        .into_boxed_str();
-    let refresh_jwt = res["refreshJwt"]
-        .as_str()
-        .expect("Session's `refreshJwt` is missing!")
-        .to_string()
-        .into_boxed_str();
-
-    (access_jwt, refresh_jwt)
+        .send_retrying()
+        .await?;
+
+    // Wrong identifier or password
+    if req.status() == StatusCode::UNAUTHORIZED {
+        return Err(Error::Status(req.status()));
+    }
+    let res: Value = req.error_for_status()?.json().await?;
+
+    session_tokens(&res)
 }
 
 const REFRESH_SESSION_URL: &str = "https://bsky.social/xrpc/com.atproto.server.refreshSession";
 #[instrument(skip(client, refresh_token))]
-async fn refresh_session(
-    client: reqwest::Client,
-    refresh_token: &str,
-) -> Result<(Box<str>, Box<str>), BskyError> {
+async fn refresh_session(client: &Client, refresh_token: &str) -> Result<(Box<str>, Box<str>)> {
     debug!("Refreshing session token");
 
     let req = client
         .post(REFRESH_SESSION_URL)
         .bearer_auth(refresh_token)
-        .send()
-        .await
-        .unwrap();
+        .send_retrying()
+        .await?;
 
     if req.status() != StatusCode::OK {
-        error!("request status: {}", req.status());
-        let res = req.text().await.unwrap();
+        let status = req.status();
+        error!("request status: {status}");
+        let res = req.text().await?;
         error!("request data: {res}");
 
-        return Err(BskyError::BadRequest);
+        return Err(Error::Status(status));
     }
 
-    let res: Value = req.json().await.unwrap();
-    let access_jwt = res["accessJwt"]
-        .as_str()
-        .expect("Session's `accessJwt` is missing!")
-        .to_string()
-        .into_boxed_str();
-    let refresh_jwt = res["refreshJwt"]
-        .as_str()
-        .expect("Session's `refreshJwt` is missing!")
-        .to_string()
-        .into_boxed_str();
+    let res: Value = req.json().await?;
+    let tokens = session_tokens(&res)?;
     info!("Session token refreshed!");
 
-    Ok((access_jwt, refresh_jwt))
+    Ok(tokens)
 }
 
 const LIST_NOTIFICATION_URL: &str =
     "https://bsky.social/xrpc/app.bsky.notification.listNotifications";
 #[instrument(skip(client, access_token))]
-async fn get_unread_notifications(
-    client: reqwest::Client,
-    access_token: &str,
-) -> Result<Vec<Value>, BskyError> {
+async fn get_unread_notifications(client: &Client, access_token: &str) -> Result<Vec<Value>> {
     let req = client
         .get(LIST_NOTIFICATION_URL)
         .bearer_auth(access_token)
-        .send()
-        .await
-        .unwrap();
+        .send_retrying()
+        .await?;
 
     // If token is expired / invalid, status code is BadRequest
     match req.status() {
         StatusCode::OK => {
-            let text = req.text().await.unwrap();
-            // println!("{text}");
-
-            let res: Value = serde_json::from_str(&text).unwrap();
+            let res: Value = req.json().await?;
 
-            let notifications = res["notifications"].as_array().unwrap();
+            let notifications = res["notifications"]
+                .as_array()
+                .ok_or_else(|| Error::MissingField("/notifications".to_string()))?;
             Ok(notifications
                 .iter()
                 .filter(|n| !n["isRead"].as_bool().unwrap_or(true))
@@ -285,8 +450,8 @@ async fn get_unread_notifications(
                 .collect())
         }
 
-        StatusCode::BAD_REQUEST => Err(BskyError::ExpiredToken),
-        _ => Err(BskyError::BadRequest),
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => Err(Error::Unauthorized),
+        status => Err(Error::Status(status)),
     }
 }
 
@@ -294,18 +459,17 @@ const UPDATE_LAST_READ_NOTIFICATION_URL: &str =
     "https://bsky.social/xrpc/app.bsky.notification.updateSeen";
 #[instrument(skip(client, access_token))]
 async fn update_last_read_notification(
-    client: reqwest::Client,
+    client: &Client,
     access_token: &str,
-) -> Result<(), BskyError> {
-    let request = client
+    seen_at: &str,
+) -> Result<()> {
+    client
         .post(UPDATE_LAST_READ_NOTIFICATION_URL)
         .bearer_auth(access_token)
-        .json(&json!({ "seenAt": Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true) }))
-        .send()
-        .await
-        .unwrap();
-
-    assert!(request.status() == StatusCode::OK);
+        .json(&json!({ "seenAt": seen_at }))
+        .send_retrying()
+        .await?
+        .error_for_status()?;
 
     Ok(())
 }
@@ -331,43 +495,33 @@ struct BskyProfile {
 }
 
 const GET_PROFILE_URL: &str = "https://public.api.bsky.app/xrpc/app.bsky.actor.getProfile";
-async fn get_profile_info(
-    client: reqwest::Client,
-    access_token: &str,
-    actor: &str,
-) -> Result<BskyProfile, BskyError> {
-    let url = Url::parse_with_params(GET_PROFILE_URL, &[("actor", actor)]).unwrap();
+async fn get_profile_info(client: &Client, access_token: &str, actor: &str) -> Result<BskyProfile> {
     let req = client
-        .get(url)
+        .get(GET_PROFILE_URL)
+        .query(&[("actor", actor)])
         .bearer_auth(access_token)
-        .send()
-        .await
-        .unwrap();
+        .send_retrying()
+        .await?;
 
     if req.status() == StatusCode::UNAUTHORIZED {
-        return Err(BskyError::ExpiredToken);
+        return Err(Error::Unauthorized);
     }
 
-    Ok(req.json::<BskyProfile>().await.unwrap())
+    Ok(req.error_for_status()?.json::<BskyProfile>().await?)
 }
 
 const GET_POST_THREAD_URL: &str = "https://public.api.bsky.app/xrpc/app.bsky.feed.getPostThread";
-async fn get_post_details(
-    client: reqwest::Client,
-    access_token: &str,
-    post_uri: &str,
-) -> Result<Value, BskyError> {
-    let url = Url::parse_with_params(GET_POST_THREAD_URL, &[("uri", post_uri)]).unwrap();
+async fn get_post_details(client: &Client, access_token: &str, post_uri: &str) -> Result<Value> {
     let req = client
-        .get(url)
+        .get(GET_POST_THREAD_URL)
+        .query(&[("uri", post_uri)])
         .bearer_auth(access_token)
-        .send()
-        .await
-        .unwrap();
+        .send_retrying()
+        .await?;
 
     if req.status() == StatusCode::UNAUTHORIZED {
-        return Err(BskyError::ExpiredToken);
+        return Err(Error::Unauthorized);
     }
 
-    Ok(json!({}))
+    Ok(req.error_for_status()?.json().await?)
 }
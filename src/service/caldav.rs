@@ -0,0 +1,245 @@
+use std::{collections::HashSet, time::Duration};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace};
+
+use crate::{
+    dav::{self, ContentLine},
+    error::{self, Result},
+    http,
+    printer::PrintData,
+    secrets, status,
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const FETCH_INTERVAL: Duration = Duration::from_mins(5);
+
+struct Event {
+    uid: String,
+    summary: String,
+    location: Option<String>,
+    description: Option<String>,
+    start: DateTime<Local>,
+    end: Option<DateTime<Local>>,
+    all_day: bool,
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let Ok(url) = secrets::var("CALDAV_URL") else {
+        info!("Env `CALDAV_URL` not set, CalDAV service disabled");
+        return;
+    };
+    let settings = || -> Result<_> {
+        Ok((
+            error::env("CALDAV_USER")?,
+            error::env("CALDAV_PASSWORD")?,
+            TimeDelta::minutes(error::env_or("CALDAV_LEAD_MINUTES", 15)?),
+        ))
+    };
+    let (username, password, lead_time) = match settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            info!("{e}, CalDAV service disabled");
+            return;
+        }
+    };
+
+    let http_client = http::client();
+
+    let mut events: Vec<Event> = Vec::new();
+    let mut last_fetch: Option<Instant> = None;
+    // (UID, start) of every instance we've reminded about; recurring events share their UID
+    let mut reminded: HashSet<(String, DateTime<Local>)> = HashSet::new();
+
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        if last_fetch.is_none_or(|t| t.elapsed() >= FETCH_INTERVAL) {
+            // Fetch slightly further than the lead time so events don't fall between fetches
+            let now = Utc::now();
+            let window_end = now + lead_time + TimeDelta::from_std(FETCH_INTERVAL).unwrap() * 3;
+            match get_events(&http_client, &url, &username, &password, now, window_end).await {
+                Ok(e) => {
+                    debug!("Fetched {} upcoming events", e.len());
+                    events = e;
+                    last_fetch = Some(Instant::now());
+                    status::service_ok("caldav");
+                }
+                Err(e) => error!("Unable to fetch CalDAV events: {e}"),
+            }
+        }
+
+        let now = Local::now();
+        for event in &events {
+            if event.all_day || event.start < now || event.start - lead_time > now {
+                continue;
+            }
+            if !reminded.insert((event.uid.clone(), event.start)) {
+                continue;
+            }
+
+            info!("Reminding about event {}", event.uid);
+            if sender.send(reminder_print_data(event)).await.is_err() {
+                debug!("Print queue closed! Stopping service...");
+                return;
+            }
+        }
+        reminded.retain(|(_, start)| *start > now - TimeDelta::days(1));
+
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(CHECK_INTERVAL) => {}
+        }
+    }
+}
+
+fn reminder_print_data(event: &Event) -> PrintData {
+    let minutes_left = (event.start - Local::now()).num_minutes().max(0);
+    let mut when = event.start.format("%H:%M").to_string();
+    if let Some(end) = event.end {
+        when = format!("{when} - {}", end.format("%H:%M"));
+    }
+
+    let mut message = format!("Starts in {minutes_left} min ({when})");
+    if let Some(location) = &event.location {
+        message = format!("{message}\nLocation: {location}");
+    }
+    if let Some(description) = &event.description {
+        message = format!("{message}\n\n{description}");
+    }
+
+    PrintData {
+        service: Some("caldav".to_string()),
+        logo: Some("caldav".to_string()),
+        title: "Upcoming Event".to_string(),
+        subtitle: Some(event.summary.clone()),
+        message: Some(message.into()),
+        timestamp: Local::now(),
+        ..Default::default()
+    }
+}
+
+/// Fetches every event instance between `start` and `end`
+///
+/// Recurring events are expanded server-side through `CALDAV:expand`, so each occurrence in the
+/// range is returned as its own event with a UTC start time.
+#[instrument(skip(client, password))]
+async fn get_events(
+    client: &reqwest::Client,
+    url: &str,
+    username: &str,
+    password: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<Event>> {
+    let start = start.format("%Y%m%dT%H%M%SZ");
+    let end = end.format("%Y%m%dT%H%M%SZ");
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <C:calendar-data>
+      <C:expand start="{start}" end="{end}"/>
+    </C:calendar-data>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">
+        <C:time-range start="{start}" end="{end}"/>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#
+    );
+
+    let calendars = dav::report(
+        client,
+        url,
+        username,
+        password,
+        body,
+        (dav::CALDAV_NS, "calendar-data"),
+    )
+    .await?;
+
+    let mut events: Vec<Event> = calendars.iter().flat_map(|c| parse_events(c)).collect();
+    events.sort_by_key(|e| e.start);
+    trace!("Parsed {} events", events.len());
+
+    Ok(events)
+}
+
+/// Parses every `VEVENT` in an iCalendar object
+fn parse_events(calendar: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut current: Option<Vec<ContentLine>> = None;
+
+    for line in dav::parse_content_lines(calendar) {
+        match (line.name.as_str(), line.value.as_str()) {
+            ("BEGIN", "VEVENT") => current = Some(Vec::new()),
+            ("END", "VEVENT") => {
+                if let Some(event) = current.take().and_then(|lines| build_event(&lines)) {
+                    events.push(event);
+                }
+            }
+            _ => {
+                if let Some(lines) = current.as_mut() {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+
+    events
+}
+
+fn build_event(lines: &[ContentLine]) -> Option<Event> {
+    let get = |name: &str| lines.iter().find(|l| l.name == name);
+    let text = |name: &str| {
+        get(name)
+            .map(|l| l.value.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    let dtstart = get("DTSTART")?;
+    let all_day = dtstart.param("VALUE") == Some("DATE") || dtstart.value.len() == 8;
+
+    Some(Event {
+        uid: text("UID").unwrap_or_default(),
+        summary: text("SUMMARY").unwrap_or_else(|| "(No title)".to_string()),
+        location: text("LOCATION"),
+        description: text("DESCRIPTION"),
+        start: parse_date_time(&dtstart.value)?,
+        end: get("DTEND").and_then(|l| parse_date_time(&l.value)),
+        all_day,
+    })
+}
+
+/// Parses an iCalendar `DATE` or `DATE-TIME` value
+///
+/// Times without a `Z` suffix (floating or `TZID`-qualified) are treated as local time.
+fn parse_date_time(value: &str) -> Option<DateTime<Local>> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Utc.from_utc_datetime(&time).with_timezone(&Local));
+    }
+
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y%m%d").map(|d| d.and_time(NaiveTime::default()))
+        })
+        .ok()?;
+    Local.from_local_datetime(&time).earliest()
+}
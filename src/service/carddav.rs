@@ -0,0 +1,222 @@
+use chrono::{Datelike, Local, NaiveDate, NaiveTime, TimeDelta};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    dav,
+    error::{self, Result},
+    http,
+    printer::PrintData,
+    schedule, secrets, status,
+};
+
+const ADDRESSBOOK_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:addressbook-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:carddav">
+  <D:prop>
+    <D:getetag/>
+    <C:address-data/>
+  </D:prop>
+</C:addressbook-query>"#;
+
+#[derive(Clone, Copy)]
+enum OccasionKind {
+    Birthday,
+    Anniversary,
+}
+
+struct Occasion {
+    kind: OccasionKind,
+    name: String,
+    /// Year is `None` when the contact only stores month & day
+    year: Option<i32>,
+    month: u32,
+    day: u32,
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let Ok(url) = secrets::var("CARDDAV_URL") else {
+        info!("Env `CARDDAV_URL` not set, CardDAV service disabled");
+        return;
+    };
+    let settings = || -> Result<_> {
+        Ok((
+            error::env("CARDDAV_USER")?,
+            error::env("CARDDAV_PASSWORD")?,
+            schedule::env_time("BIRTHDAY_EVENING_TIME", Some("20:00"))?,
+            schedule::env_time("BIRTHDAY_MORNING_TIME", Some("08:00"))?,
+        ))
+    };
+    let (username, password, evening_time, morning_time) = match settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            info!("{e}, CardDAV service disabled");
+            return;
+        }
+    };
+
+    let http_client = http::client();
+
+    loop {
+        // Evening reminders are about tomorrow, morning reminders are about today
+        let (wait, is_evening) = next_reminder(evening_time, morning_time);
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(wait) => {}
+        }
+
+        let contacts = match dav::report(
+            &http_client,
+            &url,
+            &username,
+            &password,
+            ADDRESSBOOK_QUERY.to_string(),
+            (dav::CARDDAV_NS, "address-data"),
+        )
+        .await
+        {
+            Ok(c) => {
+                status::service_ok("carddav");
+                c
+            }
+            Err(e) => {
+                error!("Unable to fetch CardDAV contacts: {e}");
+                continue;
+            }
+        };
+
+        let date = if is_evening {
+            Local::now().date_naive() + TimeDelta::days(1)
+        } else {
+            Local::now().date_naive()
+        };
+        let occasions = contacts
+            .iter()
+            .flat_map(|c| parse_occasions(c))
+            .filter(|o| o.month == date.month() && o.day == date.day())
+            .collect::<Vec<Occasion>>();
+        debug!("{} occasions on {date}", occasions.len());
+
+        if occasions.is_empty() {
+            continue;
+        }
+
+        info!(
+            "Printing {} birthday / anniversary reminders",
+            occasions.len()
+        );
+        if sender
+            .send(reminder_print_data(&occasions, date, is_evening))
+            .await
+            .is_err()
+        {
+            debug!("Print queue closed! Stopping service...");
+            return;
+        }
+    }
+}
+
+/// Returns the time until the next reminder and whether that's the evening reminder
+fn next_reminder(evening: NaiveTime, morning: NaiveTime) -> (std::time::Duration, bool) {
+    let until_evening = schedule::duration_until(evening);
+    let until_morning = schedule::duration_until(morning);
+    if until_evening < until_morning {
+        (until_evening, true)
+    } else {
+        (until_morning, false)
+    }
+}
+
+fn reminder_print_data(occasions: &[Occasion], date: NaiveDate, is_evening: bool) -> PrintData {
+    let lines = occasions
+        .iter()
+        .map(|o| {
+            let nth = o.year.map(|year| date.year() - year).filter(|n| *n > 0);
+            match (o.kind, nth) {
+                (OccasionKind::Birthday, Some(age)) => format!("{} turns {age}", o.name),
+                (OccasionKind::Birthday, None) => format!("{}'s birthday", o.name),
+                (OccasionKind::Anniversary, Some(years)) => {
+                    format!("{}'s {} anniversary", o.name, ordinal(years))
+                }
+                (OccasionKind::Anniversary, None) => format!("{}'s anniversary", o.name),
+            }
+        })
+        .collect::<Vec<String>>();
+
+    let day = if is_evening { "Tomorrow" } else { "Today" };
+    PrintData {
+        service: Some("carddav".to_string()),
+        logo: Some("carddav".to_string()),
+        title: "Birthdays & Anniversaries".to_string(),
+        subtitle: Some(format!("{day}, {}", date.format("%A, %B %e"))),
+        message: Some(lines.join("\n").into()),
+        timestamp: Local::now(),
+        ..Default::default()
+    }
+}
+
+fn ordinal(n: i32) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+
+    format!("{n}{suffix}")
+}
+
+/// Extracts birthdays & anniversaries from a vCard
+fn parse_occasions(vcard: &str) -> Vec<Occasion> {
+    let lines = dav::parse_content_lines(vcard);
+    let Some(name) = lines
+        .iter()
+        .find(|l| l.name == "FN")
+        .map(|l| l.value.clone())
+    else {
+        return Vec::new();
+    };
+
+    lines
+        .iter()
+        .filter_map(|l| {
+            let kind = match l.name.as_str() {
+                "BDAY" => OccasionKind::Birthday,
+                "ANNIVERSARY" | "X-ANNIVERSARY" => OccasionKind::Anniversary,
+                _ => return None,
+            };
+            let (year, month, day) = parse_vcard_date(&l.value)?;
+
+            Some(Occasion {
+                kind,
+                name: name.clone(),
+                year,
+                month,
+                day,
+            })
+        })
+        .collect()
+}
+
+/// Parses vCard dates: `19900115`, `1990-01-15`, `--0115`, `--01-15`, with optional time suffix
+fn parse_vcard_date(value: &str) -> Option<(Option<i32>, u32, u32)> {
+    let date = value.split('T').next()?;
+    if let Some(month_day) = date.strip_prefix("--") {
+        let month_day = month_day.replace('-', "");
+        let month = month_day.get(0..2)?.parse().ok()?;
+        let day = month_day.get(2..4)?.parse().ok()?;
+        return Some((None, month, day));
+    }
+
+    let date = NaiveDate::parse_from_str(&date.replace('-', ""), "%Y%m%d").ok()?;
+    // Apple Contacts stores birthdays without a year as 1604
+    let year = Some(date.year()).filter(|y| *y != 1604);
+    Some((year, date.month(), date.day()))
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{Local, Utc};
+use reqwest::{header::ACCEPT, Client};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace};
+
+use crate::{
+    error::Result,
+    http::{self, SendRetrying},
+    printer::PrintData,
+    secrets, status,
+};
+
+const LICHESS_PLAYING_URL: &str = "https://lichess.org/api/account/playing";
+const LICHESS_EXPORT_URL: &str = "https://lichess.org/game/export/";
+const CHESSCOM_PLAYER_URL: &str = "https://api.chess.com/pub/player/";
+
+// Correspondence games are measured in days, no need to hammer the APIs
+const POLL_INTERVAL: Duration = Duration::from_mins(5);
+
+#[derive(Deserialize)]
+struct LichessPlaying {
+    #[serde(rename = "nowPlaying")]
+    now_playing: Vec<LichessGame>,
+}
+
+#[derive(Deserialize)]
+struct LichessGame {
+    #[serde(rename = "gameId")]
+    game_id: String,
+    color: String,
+    #[serde(rename = "isMyTurn")]
+    is_my_turn: bool,
+    #[serde(rename = "secondsLeft")]
+    seconds_left: Option<i64>,
+    speed: String,
+    opponent: LichessOpponent,
+}
+
+#[derive(Deserialize)]
+struct LichessOpponent {
+    username: String,
+    rating: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct LichessExport {
+    #[serde(default)]
+    moves: String,
+}
+
+#[derive(Deserialize)]
+struct ChesscomGames {
+    games: Vec<ChesscomGame>,
+}
+
+#[derive(Deserialize)]
+struct ChesscomGame {
+    url: String,
+    #[serde(default)]
+    pgn: String,
+    turn: String,
+    white: String,
+    black: String,
+    move_by: i64,
+}
+
+/// A game where the opponent has moved and it's now our turn
+struct PendingMove {
+    site: &'static str,
+    game_url: String,
+    opponent: String,
+    /// SAN of the opponent's last move, prefixed with its move number (e.g. `12... Nf6`)
+    last_move: Option<String>,
+    seconds_left: Option<i64>,
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let mut lichess_token = secrets::var("LICHESS_TOKEN").ok();
+    let mut chesscom_username = secrets::var("CHESSCOM_USERNAME")
+        .ok()
+        .map(|u| u.to_lowercase());
+    if lichess_token.is_none() && chesscom_username.is_none() {
+        info!("Neither `LICHESS_TOKEN` nor `CHESSCOM_USERNAME` set, chess service disabled");
+        return;
+    }
+
+    let http_client = http::client();
+
+    // Game URL -> Last move we've already printed
+    let mut printed_moves: HashMap<String, Option<String>> = HashMap::new();
+
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        let mut pending = Vec::new();
+        if let Some(token) = &lichess_token {
+            match get_lichess_pending(&http_client, token).await {
+                Ok(p) => {
+                    status::service_ok("chess");
+                    pending.extend(p);
+                }
+                Err(e) if e.is_transient() => error!("Unable to fetch Lichess games: {e}"),
+                Err(e) => {
+                    error!("Stopping Lichess polling: {e}");
+                    lichess_token = None;
+                }
+            }
+        }
+        if let Some(username) = &chesscom_username {
+            match get_chesscom_pending(&http_client, username).await {
+                Ok(p) => {
+                    status::service_ok("chess");
+                    pending.extend(p);
+                }
+                Err(e) if e.is_transient() => error!("Unable to fetch Chess.com games: {e}"),
+                Err(e) => {
+                    error!("Stopping Chess.com polling: {e}");
+                    chesscom_username = None;
+                }
+            }
+        }
+        if lichess_token.is_none() && chesscom_username.is_none() {
+            error!("Stopping chess service, neither site can be polled");
+            break;
+        }
+
+        for game in pending {
+            if printed_moves.get(&game.game_url) == Some(&game.last_move) {
+                trace!("Already notified for {}", game.game_url);
+                continue;
+            }
+
+            info!("Our move in {}", game.game_url);
+            printed_moves.insert(game.game_url.clone(), game.last_move.clone());
+            if sender.send(game.into_print_data()).await.is_err() {
+                debug!("Print queue closed! Stopping service...");
+                return;
+            }
+        }
+
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+}
+
+impl PendingMove {
+    fn into_print_data(self) -> PrintData {
+        let mut message = format!(
+            "{} played {}",
+            self.opponent,
+            self.last_move.as_deref().unwrap_or("the first move")
+        );
+        if let Some(seconds) = self.seconds_left {
+            let remaining = format_remaining(seconds);
+            message = format!("{message}\n\nTime remaining: {remaining}");
+        }
+        message = format!("{message}\n{}", self.game_url);
+
+        PrintData {
+            service: Some("chess".to_string()),
+            logo: Some("chess".to_string()),
+            title: format!("{}: Your Move", self.site),
+            subtitle: Some(format!("vs {}", self.opponent)),
+            message: Some(message.into()),
+            timestamp: Local::now(),
+            ..Default::default()
+        }
+    }
+}
+
+fn format_remaining(seconds: i64) -> String {
+    let days = seconds / 86_400;
+    let hours = (seconds % 86_400) / 3_600;
+    let minutes = (seconds % 3_600) / 60;
+
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else {
+        format!("{hours}h {minutes}m")
+    }
+}
+
+/// Formats the final move of a SAN move list as `N. move` or `N... move`
+fn last_move_notation(moves: &[&str]) -> Option<String> {
+    let last = moves.last()?;
+    let move_number = moves.len().div_ceil(2);
+    if moves.len() % 2 == 1 {
+        Some(format!("{move_number}. {last}"))
+    } else {
+        Some(format!("{move_number}... {last}"))
+    }
+}
+
+#[instrument(skip(client, token))]
+async fn get_lichess_pending(client: &Client, token: &str) -> Result<Vec<PendingMove>> {
+    let playing = client
+        .get(LICHESS_PLAYING_URL)
+        .bearer_auth(token)
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<LichessPlaying>()
+        .await?;
+
+    let mut pending = Vec::new();
+    for game in playing.now_playing {
+        if !game.is_my_turn || game.speed != "correspondence" {
+            continue;
+        }
+
+        // `lastMove` in the playing list is UCI, the game export gives us SAN
+        let export = client
+            .get(format!("{LICHESS_EXPORT_URL}{}", game.game_id))
+            .query(&[("moves", "true"), ("clocks", "false"), ("evals", "false")])
+            .header(ACCEPT, "application/json")
+            .send_retrying()
+            .await?
+            .error_for_status()?
+            .json::<LichessExport>()
+            .await?;
+        let moves = export.moves.split_whitespace().collect::<Vec<&str>>();
+        trace!("Game {} is being played as {}", game.game_id, game.color);
+
+        pending.push(PendingMove {
+            site: "Lichess",
+            game_url: format!("https://lichess.org/{}", game.game_id),
+            opponent: game.opponent.rating.map_or_else(
+                || game.opponent.username.clone(),
+                |rating| format!("{} ({rating})", game.opponent.username),
+            ),
+            last_move: last_move_notation(&moves),
+            seconds_left: game.seconds_left,
+        });
+    }
+
+    Ok(pending)
+}
+
+#[instrument(skip(client))]
+async fn get_chesscom_pending(client: &Client, username: &str) -> Result<Vec<PendingMove>> {
+    let games = client
+        .get(format!("{CHESSCOM_PLAYER_URL}{username}/games"))
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<ChesscomGames>()
+        .await?;
+
+    let now = Utc::now().timestamp();
+    let pending = games
+        .games
+        .into_iter()
+        .filter_map(|game| {
+            let (our_side, their_side) = if player(&game.white).eq_ignore_ascii_case(username) {
+                ("white", &game.black)
+            } else {
+                ("black", &game.white)
+            };
+            if game.turn != our_side {
+                return None;
+            }
+
+            let moves = pgn_moves(&game.pgn);
+            let opponent = player(their_side);
+            // `move_by` is a unix timestamp deadline, 0 when there's no deadline
+            let seconds_left = (game.move_by > 0).then(|| game.move_by - now);
+
+            Some(PendingMove {
+                site: "Chess.com",
+                opponent: opponent.to_string(),
+                last_move: last_move_notation(&moves),
+                game_url: game.url,
+                seconds_left,
+            })
+        })
+        .collect();
+
+    Ok(pending)
+}
+
+/// Username of a Chess.com player field, which is a profile URL, e.g.
+/// `https://api.chess.com/pub/player/hikaru`
+fn player(url: &str) -> &str {
+    url.rsplit('/').next().unwrap_or(url)
+}
+
+/// Extracts SAN moves from a PGN, dropping tags, comments, move numbers and the result
+fn pgn_moves(pgn: &str) -> Vec<&str> {
+    let mut in_comment = false;
+    let mut moves = Vec::new();
+    for token in pgn
+        .lines()
+        .filter(|line| !line.starts_with('['))
+        .flat_map(str::split_whitespace)
+    {
+        if token.starts_with('{') {
+            in_comment = true;
+        }
+        if in_comment {
+            in_comment = !token.ends_with('}');
+            continue;
+        }
+        if token.ends_with('.') || matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            continue;
+        }
+
+        moves.push(token);
+    }
+
+    moves
+}
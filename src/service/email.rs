@@ -2,61 +2,88 @@ use std::time::Duration;
 
 use imap::extensions::idle::WaitOutcome;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info, instrument};
+use tracing::{debug, error, info, instrument};
 
-use crate::printer::PrintData;
+use crate::{
+    error::{self, Error, Result},
+    printer::PrintData,
+    retry::Backoff,
+};
 
-#[instrument(skip(cancel_token, sender))]
+/// Wait before reconnecting after the connection failed, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(10);
+
+#[instrument(skip(cancel_token, _sender))]
 pub async fn start_service(
     cancel_token: CancellationToken,
-    sender: tokio::sync::mpsc::Sender<PrintData>,
+    _sender: tokio::sync::mpsc::Sender<PrintData>,
 ) {
-    let domain = std::env::var("IMAP_DOMAIN").unwrap();
-    let port = std::env::var("IMAP_PORT")
-        .unwrap()
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
+    loop {
+        let delay = match session(&cancel_token) {
+            Ok(()) => break,
+            Err(Error::MissingEnv(var)) => {
+                info!("Env `{var}` not set, email service disabled");
+                break;
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("IMAP connection failed, reconnecting in {delay:?}: {e}");
+                delay
+            }
+            Err(e) => {
+                error!("Stopping email service: {e}");
+                break;
+            }
+        };
+
+        tokio::select! {
+            () = cancel_token.cancelled() => break,
+            () = tokio::time::sleep(delay) => {}
+        }
+    }
+}
+
+/// Connects to the IMAP server & idles on the inbox until cancelled
+fn session(cancel_token: &CancellationToken) -> Result<()> {
+    let domain = error::env("IMAP_DOMAIN")?;
+    let port = error::env("IMAP_PORT")?
         .parse::<u16>()
         .expect("Invalid IMAP_PORT! Port is not an u16!");
-    let username = std::env::var("IMAP_USER").unwrap();
-    let password = std::env::var("IMAP_PASSWORD").unwrap();
+    let username = error::env("IMAP_USER")?;
+    let password = error::env("IMAP_PASSWORD")?;
 
-    let client = imap::connect(
-        (domain.clone(), port),
-        &domain,
-        &native_tls::TlsConnector::new().unwrap(),
-    )
-    .unwrap();
+    tokio::task::block_in_place(|| {
+        let client = imap::connect(
+            (domain.clone(), port),
+            &domain,
+            &native_tls::TlsConnector::new()?,
+        )?;
 
-    let mut session = client
-        .login(username, password)
-        .expect("Unable to login to IMAP server!");
+        let mut session = client.login(username, password).map_err(|(e, _)| e)?;
 
-    // session.list(None, None).unwrap().iter().for_each(|m| {
-    //     info!("Mailbox {m:?} exists");
-    // });
+        // session.list(None, None).unwrap().iter().for_each(|m| {
+        //     info!("Mailbox {m:?} exists");
+        // });
 
-    session
-        .select("INBOX")
-        .expect("Unable to select main mailbox!");
+        session.select("INBOX")?;
 
-    loop {
-        if cancel_token.is_cancelled() {
-            break;
-        }
+        loop {
+            if cancel_token.is_cancelled() {
+                let _ = session.logout();
+                return Ok(());
+            }
 
-        let result = tokio::task::block_in_place(|| {
-            session
-                .idle()
-                .expect("IMAP server does not support the IDLE command!")
-                .wait_with_timeout(Duration::from_secs(10))
-                .unwrap()
-        });
-
-        if result == WaitOutcome::TimedOut {
-            debug!("No new email...");
-            continue;
-        }
+            let result = session.idle()?.wait_with_timeout(Duration::from_secs(10))?;
 
-        // Handle new email
-        todo!();
-    }
+            if result == WaitOutcome::TimedOut {
+                debug!("No new email...");
+                continue;
+            }
+
+            // TODO: Handle new email
+            info!("New email arrived");
+        }
+    })
 }
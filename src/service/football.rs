@@ -0,0 +1,342 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace};
+
+use crate::{
+    error::{self, Error, Result},
+    http::{self, SendRetrying},
+    printer::PrintData,
+    secrets, status,
+};
+
+const API_BASE_URL: &str = "https://api.football-data.org/v4";
+
+// Free tier allows 10 requests / minute; one request per team + one per live match
+const POLL_INTERVAL: Duration = Duration::from_mins(1);
+
+#[derive(Deserialize)]
+struct MatchList {
+    matches: Vec<Match>,
+}
+
+#[derive(Deserialize)]
+struct Match {
+    id: u64,
+    #[serde(rename = "utcDate")]
+    utc_date: DateTime<chrono::Utc>,
+    status: String,
+    competition: Competition,
+    #[serde(rename = "homeTeam")]
+    home_team: Team,
+    #[serde(rename = "awayTeam")]
+    away_team: Team,
+    score: Score,
+    #[serde(default)]
+    goals: Vec<Goal>,
+}
+
+#[derive(Deserialize)]
+struct Competition {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Team {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Score {
+    #[serde(rename = "fullTime")]
+    full_time: ScoreLine,
+    #[serde(rename = "halfTime")]
+    half_time: ScoreLine,
+}
+
+#[derive(Deserialize)]
+struct ScoreLine {
+    home: Option<u32>,
+    away: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct Goal {
+    minute: Option<u32>,
+    #[serde(rename = "injuryTime")]
+    injury_time: Option<u32>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    team: Team,
+    scorer: Option<Player>,
+    assist: Option<Player>,
+    score: ScoreLine,
+}
+
+#[derive(Deserialize)]
+struct Player {
+    name: String,
+}
+
+impl Team {
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("TBD")
+    }
+}
+
+impl ScoreLine {
+    fn format(&self) -> String {
+        format!("{} - {}", self.home.unwrap_or(0), self.away.unwrap_or(0))
+    }
+}
+
+impl Goal {
+    /// e.g. `45+2'`
+    fn minute(&self) -> String {
+        match (self.minute, self.injury_time) {
+            (Some(minute), Some(extra)) => format!("{minute}+{extra}'"),
+            (Some(minute), None) => format!("{minute}'"),
+            _ => "?'".to_string(),
+        }
+    }
+
+    fn scorer(&self) -> &str {
+        self.scorer.as_ref().map_or("Unknown", |p| p.name.as_str())
+    }
+
+    /// Stays the same across polls, unlike the goal's position in the list, which VAR may change
+    fn event_id(&self, match_id: u64) -> String {
+        format!("{match_id}:goal:{}:{}", self.minute(), self.scorer())
+    }
+}
+
+impl Match {
+    fn fixture(&self) -> String {
+        format!("{} vs {}", self.home_team.name(), self.away_team.name())
+    }
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let Ok(token) = secrets::var("FOOTBALL_DATA_TOKEN") else {
+        info!("Env `FOOTBALL_DATA_TOKEN` not set, live score service disabled");
+        return;
+    };
+    let team_ids = match team_ids() {
+        Ok(team_ids) => team_ids,
+        Err(e) => {
+            info!("{e}, live score service disabled");
+            return;
+        }
+    };
+
+    let http_client = http::client();
+
+    // Match ID -> Event IDs of goals already printed
+    let mut printed_goals: HashMap<u64, HashSet<String>> = HashMap::new();
+    let mut printed_full_time: HashSet<u64> = HashSet::new();
+    // Don't print matches that were already over before the service started
+    let mut first_run = true;
+
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        let today = Local::now().date_naive();
+        for team_id in &team_ids {
+            let matches = match get_team_matches(&http_client, &token, *team_id, today).await {
+                Ok(m) => {
+                    status::service_ok("football");
+                    m
+                }
+                Err(e) if e.is_transient() => {
+                    error!("Unable to fetch matches for team {team_id}: {e}");
+                    continue;
+                }
+                Err(e) => {
+                    error!("Stopping live score service: {e}");
+                    return;
+                }
+            };
+
+            for m in matches {
+                if !matches!(m.status.as_str(), "IN_PLAY" | "PAUSED" | "FINISHED") {
+                    trace!("Match {} ({}) has not started yet", m.id, m.fixture());
+                    continue;
+                }
+                if printed_full_time.contains(&m.id) {
+                    continue;
+                }
+                if first_run && m.status == "FINISHED" {
+                    printed_full_time.insert(m.id);
+                    continue;
+                }
+
+                // Match list endpoint doesn't include goal details, fetch the match itself
+                let details = match get_match(&http_client, &token, m.id).await {
+                    Ok(d) => d,
+                    Err(e) => {
+                        error!("Unable to fetch match {}: {e}", m.id);
+                        continue;
+                    }
+                };
+
+                let already_printed = printed_goals.entry(details.id).or_default();
+                for goal in &details.goals {
+                    let event_id = goal.event_id(details.id);
+                    if already_printed.contains(&event_id) {
+                        continue;
+                    }
+                    info!("New goal in match {}", details.id);
+                    if sender.send(goal_print_data(&details, goal)).await.is_err() {
+                        debug!("Print queue closed! Stopping service...");
+                        return;
+                    }
+                    already_printed.insert(event_id);
+                }
+
+                if details.status == "FINISHED" {
+                    info!("Match {} finished", details.id);
+                    if sender.send(full_time_print_data(&details)).await.is_err() {
+                        debug!("Print queue closed! Stopping service...");
+                        return;
+                    }
+                    printed_full_time.insert(details.id);
+                    printed_goals.remove(&details.id);
+                }
+            }
+        }
+        first_run = false;
+
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+}
+
+/// Teams in `FOOTBALL_TEAM_IDS`, as comma separated football-data.org team IDs
+fn team_ids() -> Result<Vec<u64>> {
+    error::env("FOOTBALL_TEAM_IDS")?
+        .split(',')
+        .map(|id| {
+            id.trim().parse().map_err(|_| {
+                Error::InvalidEnv(
+                    "FOOTBALL_TEAM_IDS".to_string(),
+                    format!("Bad team ID `{id}`"),
+                )
+            })
+        })
+        .collect()
+}
+
+fn goal_print_data(m: &Match, goal: &Goal) -> PrintData {
+    let mut message = format!("{} {} ({})", goal.minute(), goal.scorer(), goal.team.name());
+    match goal.kind.as_deref() {
+        Some("PENALTY") => message.push_str("\nPenalty"),
+        Some("OWN") => message.push_str("\nOwn goal"),
+        _ => {}
+    }
+    if let Some(assist) = &goal.assist {
+        message = format!("{message}\nAssist: {}", assist.name);
+    }
+
+    PrintData {
+        service: Some("football".to_string()),
+        logo: Some("football".to_string()),
+        title: "GOAL!".to_string(),
+        subtitle: Some(format!(
+            "{}\n{}\nScore: {}",
+            m.competition.name,
+            m.fixture(),
+            goal.score.format()
+        )),
+        message: Some(message.into()),
+        timestamp: Local::now(),
+        event_id: Some(goal.event_id(m.id)),
+        ..Default::default()
+    }
+}
+
+fn full_time_print_data(m: &Match) -> PrintData {
+    let goals = m
+        .goals
+        .iter()
+        .map(|g| {
+            format!(
+                "{}' {} ({})",
+                g.minute.unwrap_or(0),
+                g.scorer(),
+                g.team.name()
+            )
+        })
+        .collect::<Vec<String>>();
+    let goals = if goals.is_empty() {
+        "No goals".to_string()
+    } else {
+        goals.join("\n")
+    };
+
+    PrintData {
+        service: Some("football".to_string()),
+        logo: Some("football".to_string()),
+        title: "Full Time".to_string(),
+        subtitle: Some(format!("{}\n{}", m.competition.name, m.fixture())),
+        message: Some(
+            format!(
+                "{} {} {}\nHalf time: {}\n\n{goals}",
+                m.home_team.name(),
+                m.score.full_time.format(),
+                m.away_team.name(),
+                m.score.half_time.format(),
+            )
+            .into(),
+        ),
+        timestamp: m.utc_date.with_timezone(&Local),
+        ..Default::default()
+    }
+}
+
+#[instrument(skip(client, token))]
+async fn get_team_matches(
+    client: &Client,
+    token: &str,
+    team_id: u64,
+    date: chrono::NaiveDate,
+) -> Result<Vec<Match>> {
+    let res = client
+        .get(format!("{API_BASE_URL}/teams/{team_id}/matches"))
+        .header("X-Auth-Token", token)
+        .query(&[("dateFrom", date.to_string()), ("dateTo", date.to_string())])
+        .send_retrying()
+        .await?
+        .error_for_status()?;
+
+    Ok(res.json::<MatchList>().await?.matches)
+}
+
+#[instrument(skip(client, token))]
+async fn get_match(client: &Client, token: &str, match_id: u64) -> Result<Match> {
+    Ok(client
+        .get(format!("{API_BASE_URL}/matches/{match_id}"))
+        .header("X-Auth-Token", token)
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<Match>()
+        .await?)
+}
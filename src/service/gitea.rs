@@ -0,0 +1,283 @@
+use std::{str::FromStr, time::Duration};
+
+use chrono::DateTime;
+use futures_util::future::join_all;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::Value;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc::Sender},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace, warn};
+
+use crate::{
+    ack::{self, InFlight},
+    error::{str_at, Error, Result},
+    http::{self, SendRetrying},
+    printer::{markdown, MarkdownLinks, PrintData, Priority, QrCode, Span},
+    retry::Backoff,
+    service::Account,
+    status,
+};
+
+const DEFAULT_URL: &str = "https://codeberg.org";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Wait before polling again after a failed poll, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
+/// Polls the notifications of every account in `GITEA_ACCOUNTS` at once, see [`Account`]; Works
+/// with Forgejo instances too, e.g. Codeberg
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
+    let http_client = http::client();
+    join_all(
+        Account::all("GITEA_ACCOUNTS")
+            .into_iter()
+            .map(|account| run(&http_client, &cancel_token, &sender, account)),
+    )
+    .await;
+}
+
+#[instrument(skip_all, fields(account = account.label.as_deref()))]
+async fn run(
+    http_client: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    account: Account,
+) {
+    let name = account.name("Gitea");
+    let token = match account.env("GITEA_TOKEN") {
+        Ok(token) => token,
+        Err(e) => {
+            info!("{e}, {name} disabled");
+            return;
+        }
+    };
+    let url = account
+        .env("GITEA_URL")
+        .unwrap_or_else(|_| DEFAULT_URL.to_string());
+    let api = Api {
+        http_client: http_client.clone(),
+        base_url: format!("{}/api/v1", url.trim_end_matches('/')),
+        token,
+    };
+    // Notifications are only marked read once printed, by thread ID
+    let mut in_flight = InFlight::load("gitea", account.state_key("gitea"));
+    let mut acks = ack::subscribe();
+    // Fetched on the first poll, to tell mentions apart
+    let mut username = None;
+
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        let poll_interval = match poll(&api, sender, &name, &mut username, &mut in_flight).await {
+            Ok(()) => {
+                status::service_ok("gitea");
+                backoff.reset();
+                POLL_INTERVAL
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("Unable to fetch {name} notifications, retrying in {delay:?}: {e}");
+                delay
+            }
+            Err(e) => {
+                error!("Stopping {name} service: {e}");
+                break;
+            }
+        };
+
+        let next_poll = Instant::now() + poll_interval;
+        loop {
+            tokio::select! {
+                () = cancel_token.cancelled() => {
+                    debug!("Cancel signal caught! Stopping service...");
+                    return;
+                }
+                () = tokio::time::sleep_until(next_poll) => break,
+                acked = acks.recv() => match acked {
+                    Ok(acked) => {
+                        let Some(thread_id) = in_flight.acked(&acked) else {
+                            continue;
+                        };
+                        // Left unread, it's printed again on the next poll
+                        if let Err(e) = api.mark_read(&thread_id).await {
+                            warn!("Unable to mark {name} notification {thread_id} read: {e}");
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Missed {missed} acks, their notifications are printed again later");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+}
+
+/// Gitea or Forgejo instance's API, as an account
+struct Api {
+    http_client: Client,
+    /// e.g. `https://codeberg.org/api/v1`
+    base_url: String,
+    token: String,
+}
+
+impl Api {
+    fn authenticate(&self, req: RequestBuilder) -> RequestBuilder {
+        req.header("Authorization", format!("token {}", self.token))
+    }
+
+    /// JSON at an API URL, or a path of the API, e.g. `/notifications`
+    async fn get(&self, url: &str) -> Result<Value> {
+        trace!("Fetching {url}");
+        let url = if url.starts_with('/') {
+            format!("{}{url}", self.base_url)
+        } else {
+            url.to_string()
+        };
+        let res = self
+            .authenticate(self.http_client.get(url))
+            .send_retrying()
+            .await?;
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::Unauthorized);
+        }
+        Ok(res.error_for_status()?.json().await?)
+    }
+
+    async fn mark_read(&self, thread_id: &str) -> Result<()> {
+        let url = format!("{}/notifications/threads/{thread_id}", self.base_url);
+        self.authenticate(self.http_client.patch(url))
+            .send_retrying()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Prints unread notifications that aren't in flight already
+async fn poll(
+    api: &Api,
+    sender: &Sender<PrintData>,
+    name: &str,
+    username: &mut Option<String>,
+    in_flight: &mut InFlight,
+) -> Result<()> {
+    if username.is_none() {
+        let user = api.get("/user").await?;
+        *username = Some(str_at(&user, "/login")?.to_string());
+    }
+    let username = username.as_deref().unwrap_or_default();
+    let notifs = api.get("/notifications?status-types=unread").await?;
+    let notifs = notifs
+        .as_array()
+        .ok_or_else(|| Error::MissingField("/".to_string()))?;
+    // Oldest first
+    for notif in notifs.iter().rev() {
+        let thread_id = notif["id"].to_string();
+        // Threads are updated in place, their events are told apart by when
+        let event_id = format!("{thread_id}:{}", str_at(notif, "/updated_at")?);
+        if in_flight.contains(&event_id) {
+            continue;
+        }
+
+        match notif_print_data(api, name, username, notif).await {
+            Ok(Some(data)) => {
+                info!("New notification with ID: {thread_id}");
+                // Persisted before it's queued, so a restart can't lose track of it
+                in_flight.insert(event_id, thread_id);
+                sender.send(data).await?;
+            }
+            // Marked read right away, as there's nothing to print
+            Ok(None) => {
+                if let Err(e) = api.mark_read(&thread_id).await {
+                    warn!("Unable to mark Gitea notification {thread_id} read: {e}");
+                }
+            }
+            // Left unread, so it's tried again
+            Err(e) => error!("Unable to print Gitea notification: {e}\n{notif}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Receipt of a notification with its latest comment, or the issue or PR itself if it's new;
+/// None for commits & repos, e.g. new releases
+async fn notif_print_data(
+    api: &Api,
+    name: &str,
+    username: &str,
+    notif: &Value,
+) -> Result<Option<PrintData>> {
+    let repo = str_at(notif, "/repository/full_name")?;
+    let subject = str_at(notif, "/subject/title")?;
+    let kind = match str_at(notif, "/subject/type")? {
+        "Issue" => "Issue",
+        "Pull" => "PR",
+        other => {
+            debug!("Ignoring Gitea {other} notification");
+            return Ok(None);
+        }
+    };
+
+    // Issues & PRs without comments yet have none to link to
+    let comment_url = str_at(notif, "/subject/latest_comment_url").ok();
+    let (post, title) = match comment_url.filter(|url| !url.is_empty()) {
+        Some(url) => (api.get(url).await?, format!("New {kind} Comment")),
+        None => (
+            api.get(str_at(notif, "/subject/url")?).await?,
+            format!("New {kind}"),
+        ),
+    };
+    let author = str_at(&post, "/user/login")?;
+    // Descriptions may be left empty
+    let body = str_at(&post, "/body").unwrap_or_default();
+    let mentioned = body.contains(&format!("@{username}"));
+
+    let (body, qr_codes) = markdown(body, MarkdownLinks::from_env());
+    let mut message = vec![Span::bold(author), Span::plain(":\n")];
+    message.extend(body);
+    let qr_codes = if qr_codes.is_empty() {
+        str_at(&post, "/html_url")
+            .map(|url| QrCode {
+                caption: Some("Open".to_string()),
+                data: url.to_string(),
+            })
+            .into_iter()
+            .collect()
+    } else {
+        qr_codes
+    };
+
+    Ok(Some(PrintData {
+        service: Some("gitea".to_string()),
+        logo: Some("gitea".to_string()),
+        event_id: Some(format!("{}:{}", notif["id"], str_at(notif, "/updated_at")?)),
+        source: Some(repo.to_string()),
+        priority: if mentioned {
+            Priority::High
+        } else {
+            Priority::Normal
+        },
+        title: if mentioned {
+            format!("{name}: Mentioned")
+        } else {
+            format!("{name}: {title}")
+        },
+        subtitle: Some(format!("Repo: {repo}\n{subject}")),
+        message: Some(message.into()),
+        qr_codes,
+        timestamp: DateTime::from_str(str_at(notif, "/updated_at")?)?,
+        ..Default::default()
+    }))
+}
@@ -1,175 +1,1376 @@
-use std::{str::FromStr, time::Duration};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    str::FromStr,
+    time::Duration,
+};
 
-use chrono::DateTime;
+use chrono::{DateTime, Local};
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
 use reqwest::{
-    header::{ACCEPT, IF_MODIFIED_SINCE, LAST_MODIFIED},
-    StatusCode,
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Client, Method, StatusCode,
+};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc::Sender},
+    time::Instant,
 };
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info, instrument, trace};
+use tracing::{debug, error, info, instrument, trace, warn};
 
-use crate::{http, printer::PrintData};
+use crate::{
+    ack::{self, InFlight},
+    error::{str_at, Error, Result},
+    http,
+    printer::{markdown, MarkdownLinks, PrintData, Priority, QrCode, Span},
+    retry::Backoff,
+    secrets,
+    service::{github_app::Credentials, github_client::GitHubClient, Account},
+    state, status,
+};
 
 const HTTP_ENDPOINT: &str = "https://api.github.com/notifications";
 
+/// Wait before polling again after a failed poll, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
+/// Wait between polls of the repos' events as a GitHub App, see [`poll_events`]
+const EVENTS_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Characters of release notes printed, see [`truncate_notes`]
+const MAX_RELEASE_NOTES: usize = 1000;
+
+/// Polls the notifications of every account in `GITHUB_ACCOUNTS` at once, see [`Account`]
 #[instrument(skip(cancel_token, sender))]
-pub async fn start_service(
-    cancel_token: CancellationToken,
-    sender: tokio::sync::mpsc::Sender<PrintData>,
-) {
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
     let http_client = http::client();
-    let mut last_modified_time: Option<Box<str>> = None;
+    join_all(
+        Account::all("GITHUB_ACCOUNTS")
+            .into_iter()
+            .map(|account| run(&http_client, &cancel_token, &sender, account)),
+    )
+    .await;
+}
 
+#[instrument(skip_all, fields(account = account.label.as_deref()))]
+async fn run(
+    http_client: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    account: Account,
+) {
+    let name = account.name("GitHub");
+    let credentials = match Credentials::from_account(&account) {
+        Ok(credentials) => credentials,
+        Err(Error::MissingEnv(var)) => {
+            info!("Env `{var}` not set, {name} disabled");
+            return;
+        }
+        Err(e) => {
+            error!("Stopping {name} service: {e}");
+            return;
+        }
+    };
+    let settings = match Settings::from_account(&account) {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    let mut client = GitHubClient::new(http_client.clone(), credentials);
+    let state_key = account.state_key("github");
+    let mut last_modified_time: Option<Box<str>> =
+        state::get(&state_key, "last_modified").map(Into::into);
+    let mut handled = Handled::load(state_key.clone());
+    let mut unread = Unread::load(state_key.clone());
+    // Notifications are only marked read once printed, by thread ID
+    let mut in_flight = InFlight::load("github", state_key);
+    let mut acks = ack::subscribe();
+
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
     loop {
         if cancel_token.is_cancelled() {
             info!("Stopping service due to cancel token...");
             break;
         }
 
-        trace!("Building new request");
-        let mut req = http_client
-            .get(HTTP_ENDPOINT)
-            .bearer_auth(std::env::var("GITHUB_PAT").expect("GITHUB_PAT env var is not set!"))
-            .header(ACCEPT, "application/vnd.github.v3+json")
-            .header("X-GitHub-Api-Version", "2022-11-28");
+        let polled = if client.is_app() {
+            poll_events(&mut client, sender, &account, &settings.repos).await
+        } else {
+            poll(
+                &mut client,
+                sender,
+                &account,
+                &mut last_modified_time,
+                &mut in_flight,
+                &settings,
+                &mut handled,
+            )
+            .await
+        };
+        let polled = match polled {
+            Ok(poll_interval) if !settings.watch.is_empty() => {
+                poll_issues(&mut client, sender, &account, &settings.watch)
+                    .await
+                    .map(|()| poll_interval)
+            }
+            polled => polled,
+        };
+        let poll_interval = match polled {
+            Ok(poll_interval) => {
+                status::service_ok("github");
+                backoff.reset();
+                client.poll_interval(poll_interval)
+            }
+            Err(Error::RateLimited(wait)) => {
+                warn!("{name} rate limit used up, waiting {wait:?} for it to reset");
+                wait
+            }
+            Err(Error::MissingEnv(var)) => {
+                info!("Env `{var}` not set, {name} disabled");
+                break;
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("Unable to fetch {name} notifications, retrying in {delay:?}: {e}");
+                delay
+            }
+            Err(e) => {
+                error!("Stopping {name} service: {e}");
+                break;
+            }
+        };
 
-        // Add Last modified time for long polling; Recommended by GitHub's API docs
-        // https://docs.github.com/en/rest/activity/notifications?apiVersion=2022-11-28#about-github-notifications
-        if let Some(last_modified_time) = &last_modified_time {
-            trace!("Using last modified time: {last_modified_time}");
-            req = req.header(IF_MODIFIED_SINCE, last_modified_time.to_string());
+        let next_poll = Instant::now() + poll_interval;
+        loop {
+            tokio::select! {
+                () = cancel_token.cancelled() => {
+                    debug!("Cancel signal caught! Stopping service...");
+                    return;
+                }
+                () = tokio::time::sleep_until(next_poll) => break,
+                () = unread.due(), if !unread.is_empty() => {
+                    let Some(thread_id) = unread.pop() else {
+                        continue;
+                    };
+                    // Left unread, it's still not printed again as it's been handled
+                    if let Err(e) = mark_read(&mut client, &thread_id).await {
+                        warn!("Unable to mark {name} notification {thread_id} read: {e}");
+                    }
+                }
+                acked = acks.recv() => match acked {
+                    Ok(acked) => {
+                        let Some(thread_id) = in_flight.acked(&acked) else {
+                            continue;
+                        };
+                        handled.insert(acked.event_id);
+                        match settings.mark_read {
+                            MarkRead::Never => {}
+                            MarkRead::Printed => unread.push(thread_id, Duration::ZERO),
+                            MarkRead::After(delay) => unread.push(thread_id, delay),
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Missed {missed} acks, their notifications are printed again later");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+}
+
+/// Prints new notifications; Returns how long GitHub asks to wait before polling again
+async fn poll(
+    client: &mut GitHubClient,
+    sender: &Sender<PrintData>,
+    account: &Account,
+    last_modified_time: &mut Option<Box<str>>,
+    in_flight: &mut InFlight,
+    settings: &Settings,
+    handled: &mut Handled,
+) -> Result<Duration> {
+    trace!("Building new request");
+    let state_key = account.state_key("github");
+    let mut req = client.request(Method::GET, HTTP_ENDPOINT);
+
+    // Add Last modified time for long polling; Recommended by GitHub's API docs
+    // https://docs.github.com/en/rest/activity/notifications?apiVersion=2022-11-28#about-github-notifications
+    if let Some(last_modified_time) = &last_modified_time {
+        trace!("Using last modified time: {last_modified_time}");
+        req = req.header(IF_MODIFIED_SINCE, last_modified_time.to_string());
+    }
+    // And the ETag, which tells apart changes made within the same second as Last-Modified
+    if let Some(etag) = state::get(&state_key, "etag") {
+        trace!("Using ETag: {etag}");
+        req = req.header(IF_NONE_MATCH, etag);
+    }
+
+    trace!("Sending HTTP request");
+    let res = client.send(req).await?;
+    let poll_interval = res
+        .headers()
+        .get("X-Poll-Interval")
+        .and_then(|h| h.to_str().ok()?.parse().ok())
+        .map_or(Duration::from_secs(60), Duration::from_secs);
+
+    // Has no body to read; Doesn't count against the rate limit either
+    if res.status() == StatusCode::NOT_MODIFIED {
+        trace!("No new notifications since last fetch. Waiting for next interval...");
+        return Ok(poll_interval);
+    }
+
+    let res = res.error_for_status()?;
+    let header = |name| {
+        res.headers()
+            .get(name)
+            .and_then(|h| h.to_str().ok())
+            .map(ToString::to_string)
+    };
+    let (last_modified, etag) = (header(LAST_MODIFIED), header(ETAG));
+    let res = res.json::<Value>().await?;
+    let notifs = res
+        .as_array()
+        .ok_or_else(|| Error::MissingField("/".to_string()))?;
+    // Only once read, so notifications that failed to are fetched again
+    if let Some(time) = last_modified {
+        debug!("Next request using Last-Modified header: {time:?}");
+        state::set(&state_key, "last_modified", Some(&time));
+        *last_modified_time = Some(time.into());
+    }
+    if let Some(etag) = etag {
+        state::set(&state_key, "etag", Some(&etag));
+    }
+    handled.retain_unread(notifs);
+    for notif in notifs {
+        // Left unread on failure, so it's tried again
+        let printed =
+            print_notification(client, sender, account, in_flight, settings, handled, notif).await;
+        match printed {
+            Ok(()) => {}
+            Err(Error::QueueClosed) => return Err(Error::QueueClosed),
+            Err(e) => error!("Unable to print GitHub notification: {e}\n{notif}"),
         }
+    }
+
+    Ok(poll_interval)
+}
 
-        trace!("Sending HTTP request");
-        let res = req.send().await;
-        if let Err(e) = res {
-            error!("Error on sending HTTP request\n{e}");
-            tokio::time::sleep(Duration::from_secs(5)).await;
+/// Prints new events of the repos the app is installed on, as installation tokens can't read
+/// notifications; Returns how long to wait before polling again
+///
+/// Only events after the last one seen are printed, none on the first poll of a repo.
+async fn poll_events(
+    client: &mut GitHubClient,
+    sender: &Sender<PrintData>,
+    account: &Account,
+    filter: &RepoFilter,
+) -> Result<Duration> {
+    let name = account.name("GitHub");
+    let state_key = account.state_key("github");
+    let repos = client
+        .get("https://api.github.com/installation/repositories?per_page=100")
+        .await?;
+    let repos = repos
+        .pointer("/repositories")
+        .and_then(Value::as_array)
+        .ok_or_else(|| Error::MissingField("/repositories".to_string()))?;
+
+    for repo in repos {
+        let repo = str_at(repo, "/full_name")?;
+        if !filter.allows(repo) {
+            continue;
+        }
+        let url = format!("https://api.github.com/repos/{repo}/events?per_page=30");
+        let Some(events) = client.get_if_changed(&url).await? else {
             continue;
+        };
+        let events = events
+            .as_array()
+            .ok_or_else(|| Error::MissingField("/".to_string()))?;
+        let id = |event: &Value| str_at(event, "/id").ok()?.parse::<u64>().ok();
+
+        let key = format!("last_event:{repo}");
+        let last_seen = state::get(&state_key, &key).and_then(|id| id.parse().ok());
+        if let Some(last_seen) = last_seen {
+            // Latest first
+            for event in events
+                .iter()
+                .rev()
+                .filter(|&event| id(event) > Some(last_seen))
+            {
+                let data = match event_print_data(event) {
+                    Ok(Some(data)) => data,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        error!("Unable to print GitHub event: {e}\n{event}");
+                        continue;
+                    }
+                };
+                sender
+                    .send(PrintData {
+                        title: data.title.replacen("GitHub", &name, 1),
+                        ..data
+                    })
+                    .await?;
+            }
+        }
+        if let Some(latest) = events.first().and_then(id) {
+            state::set(&state_key, &key, Some(&latest.to_string()));
         }
+    }
+
+    Ok(EVENTS_POLL_INTERVAL)
+}
 
-        let res = res.unwrap();
-        let poll_interval = res.headers().get("X-Poll-Interval").map_or(60, |h| {
-            h.to_str().unwrap().to_string().parse::<u64>().unwrap()
-        });
-        if let Some(header) = res.headers().get(LAST_MODIFIED) {
-            let time = header.to_str().unwrap().to_string();
-            debug!("Next request using Last-Modified header: {time:?}");
-            last_modified_time = Some(time.into_boxed_str());
+/// Prints issues newly opened in the watched repos, see `GITHUB_WATCH_REPOS`
+///
+/// Only issues after the last one seen are printed, none on the first poll of a repo.
+async fn poll_issues(
+    client: &mut GitHubClient,
+    sender: &Sender<PrintData>,
+    account: &Account,
+    repos: &[String],
+) -> Result<()> {
+    let name = account.name("GitHub");
+    let state_key = account.state_key("github");
+    for repo in repos {
+        // Closed ones too, in case they're closed before the next poll
+        let url = format!(
+            "https://api.github.com/repos/{repo}/issues\
+             ?state=all&sort=created&direction=desc&per_page=30"
+        );
+        let issues = match client.get_if_changed(&url).await {
+            Ok(Some(issues)) => issues,
+            Ok(None) => continue,
+            Err(e @ Error::RateLimited(_)) => return Err(e),
+            // e.g. a typo in the repo, which shouldn't stop the others from being watched
+            Err(e) => {
+                error!("Unable to fetch issues of {repo}: {e}");
+                continue;
+            }
         };
+        let issues = issues
+            .as_array()
+            .ok_or_else(|| Error::MissingField("/".to_string()))?;
+        let number = |issue: &Value| issue.pointer("/number").and_then(Value::as_u64);
 
-        if res.status() == StatusCode::NOT_MODIFIED {
-            trace!("No new notifications since last fetch. Waiting for next interval...");
+        let key = format!("last_issue:{repo}");
+        let last_seen = state::get(&state_key, &key).and_then(|number| number.parse().ok());
+        if let Some(last_seen) = last_seen {
+            // Latest first; PRs are listed as issues too
+            for issue in issues
+                .iter()
+                .rev()
+                .filter(|&issue| number(issue) > Some(last_seen))
+                .filter(|issue| issue.get("pull_request").is_none())
+            {
+                let payload = json!({
+                    "action": "opened",
+                    "issue": issue,
+                    "repository": { "full_name": repo },
+                    "sender": issue.pointer("/user").cloned().unwrap_or_default(),
+                });
+                let id = format!("issue:{repo}#{}", number(issue).unwrap_or_default());
+                let data = match webhook_print_data("issues", &id, &payload) {
+                    Ok(Some(data)) => data,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        error!("Unable to print GitHub issue: {e}\n{issue}");
+                        continue;
+                    }
+                };
+                sender
+                    .send(PrintData {
+                        title: format!("{name}: New Issue"),
+                        ..data
+                    })
+                    .await?;
+            }
+        }
+        if let Some(latest) = issues.first().and_then(number) {
+            state::set(&state_key, &key, Some(&latest.to_string()));
+        }
+    }
 
-            tokio::select! {
-                () = cancel_token.cancelled() => {
-                    debug!("Cancel signal caught! Stopping service...");
-                    break;
-                }
-                () = tokio::time::sleep(Duration::from_secs(poll_interval)) => {}
+    Ok(())
+}
+
+/// Receipt of a repo event, see [`webhook_print_data`]; Events carry much the same payload as
+/// webhooks, short of the repo & who it's from
+fn event_print_data(event: &Value) -> Result<Option<PrintData>> {
+    let kind = match str_at(event, "/type")? {
+        "PushEvent" => "push",
+        "IssuesEvent" => "issues",
+        "WatchEvent" => "star",
+        "ForkEvent" => "fork",
+        "ReleaseEvent" => "release",
+        _ => return Ok(None),
+    };
+    let mut payload = event.pointer("/payload").cloned().unwrap_or_default();
+    payload["repository"] = json!({ "full_name": str_at(event, "/repo/name")? });
+    payload["sender"] = json!({ "login": str_at(event, "/actor/login")? });
+    match kind {
+        // Starring is "watching", see https://docs.github.com/en/rest/activity/starring
+        "star" => payload["action"] = json!("created"),
+        // Commits are identified by their SHA, `id` in webhooks
+        "push" => {
+            for commit in payload["commits"].as_array_mut().into_iter().flatten() {
+                commit["id"] = commit["sha"].clone();
             }
+        }
+        _ => {}
+    }
 
-            continue;
+    webhook_print_data(kind, &format!("event:{}", str_at(event, "/id")?), &payload)
+}
+
+/// Prints a notification with what it's about, marked as read once printed; Ones that aren't
+/// printed are marked read right away
+async fn print_notification(
+    client: &mut GitHubClient,
+    sender: &Sender<PrintData>,
+    account: &Account,
+    in_flight: &mut InFlight,
+    settings: &Settings,
+    handled: &mut Handled,
+    notif: &Value,
+) -> Result<()> {
+    let thread_id = str_at(notif, "/id")?;
+    let updated_time = str_at(notif, "/updated_at")?;
+    let event_id = event_id(notif)?;
+    if in_flight.contains(&event_id) || handled.contains(&event_id) {
+        return Ok(());
+    }
+    let repo = str_at(notif, "/repository/full_name")?;
+    // Before fetching what it's about, to save on requests
+    if !settings.repos.allows(repo) {
+        trace!("Skipping notification {thread_id} of {repo}");
+        skip(client, handled, settings.mark_read, thread_id, event_id).await;
+        return Ok(());
+    }
+    info!("New notification with ID: {thread_id}");
+
+    let subject = str_at(notif, "/subject/title")?;
+    let name = account.name("GitHub");
+    let base = PrintData {
+        service: Some("github".to_string()),
+        logo: Some("github".to_string()),
+        event_id: Some(event_id.clone()),
+        source: Some(repo.to_string()),
+        subtitle: Some(format!("Repo: {repo}\n{subject}")),
+        timestamp: DateTime::from_str(updated_time)?,
+        ..Default::default()
+    };
+
+    match notification_print_data(client, notif, &name, base).await? {
+        Some(data) => {
+            // Persisted before it's queued, so a restart can't lose track of it
+            in_flight.insert(event_id, thread_id.to_string());
+            sender.send(data).await?;
         }
+        None => skip(client, handled, settings.mark_read, thread_id, event_id).await,
+    }
 
-        let res = res.json::<serde_json::Value>().await.unwrap();
-        // info!("{}", res);
-
-        for notif in res.as_array().expect("GitHub returned malformed JSON data") {
-            info!(
-                "New notification with ID: {}",
-                notif["id"].as_str().unwrap()
-            );
-
-            let latest_comment_url = notif["subject"]["latest_comment_url"].as_str().unwrap();
-            let latest_comment_req = http_client
-                .get(latest_comment_url)
-                .bearer_auth(std::env::var("GITHUB_PAT").expect("GITHUB_PAT env var is not set!"))
-                .header(ACCEPT, "application/vnd.github.v3+json")
-                .header("X-GitHub-Api-Version", "2022-11-28")
-                .send()
-                .await
-                .expect("Unable to fetch latest comment data");
-            let latest_comment_data: serde_json::Value = latest_comment_req.json().await.unwrap();
-            let updated_time = notif["updated_at"].as_str().unwrap();
-            let thread_id = notif["id"].as_str();
-            //
-
-            match notif["reason"].as_str().unwrap() {
-                "manual" | "comment" | "author" | "mention" => {
-                    sender
-                        .send(PrintData {
-                            title: "GitHub: New Issue Comment".to_string(),
-                            subtitle: Some(format!(
-                                "Repo: {}\n{}",
-                                notif["repository"]["full_name"].as_str().unwrap(),
-                                notif["subject"]["title"].as_str().unwrap(),
-                            )),
-                            message: Some(format!(
-                                "{}:\n{}",
-                                latest_comment_data["user"]["login"].as_str().unwrap(),
-                                latest_comment_data["body"].as_str().unwrap(),
-                            )),
-                            timestamp: DateTime::from_str(updated_time).unwrap(),
-                        })
-                        .await
-                        .unwrap();
-                }
+    Ok(())
+}
 
-                "subscribed" => {
-                    sender
-                        .send(PrintData {
-                            title: "GitHub: New Issue on Subbed Repo".to_string(),
-                            subtitle: Some(format!(
-                                "Repo: {}\n{}",
-                                notif["repository"]["full_name"].as_str().unwrap(),
-                                notif["subject"]["title"].as_str().unwrap(),
-                            )),
-                            message: Some(format!(
-                                "{}:\n{}",
-                                latest_comment_data["user"]["login"].as_str().unwrap(),
-                                latest_comment_data["body"].as_str().unwrap(),
-                            )),
-                            timestamp: DateTime::from_str(updated_time).unwrap(),
-                        })
-                        .await
-                        .unwrap();
-                }
+/// Receipt of a notification by why it was sent, with what it's about; None for ones that
+/// aren't printed
+async fn notification_print_data(
+    client: &mut GitHubClient,
+    notif: &Value,
+    name: &str,
+    base: PrintData,
+) -> Result<Option<PrintData>> {
+    let repo = str_at(notif, "/repository/full_name")?;
+    let subject = str_at(notif, "/subject/title")?;
+    let kind = str_at(notif, "/subject/type")?;
 
-                "state_change" => {
-                    info!("Got a state_change notif");
-                }
+    let data = match str_at(notif, "/reason")? {
+        "subscribed" if kind == "Release" => {
+            let release = client.get(str_at(notif, "/subject/url")?).await?;
+            Some(release_print_data(&release, name, repo, base)?)
+        }
+
+        // Discussions are left out of the REST API, the notification doesn't link to them
+        reason if kind == "Discussion" => {
+            let discussion = discussion(client, repo, subject).await?;
+            let data = discussion_print_data(&discussion, repo, base)?;
+            Some(PrintData {
+                title: format!("{name}: {}", data.title),
+                priority: if reason.ends_with("mention") {
+                    Priority::High
+                } else {
+                    data.priority
+                },
+                ..data
+            })
+        }
+
+        reason @ ("manual" | "comment" | "author" | "mention" | "team_mention" | "subscribed") => {
+            Some(comment_print_data(client, notif, name, reason, base).await?)
+        }
+
+        "review_requested" => {
+            let pull = client.get(str_at(notif, "/subject/url")?).await?;
+            Some(PrintData {
+                priority: Priority::High,
+                title: format!("{name}: Review Requested"),
+                message: Some(pull_summary(&pull)?.into()),
+                qr_codes: link(&pull, "Review"),
+                ..base
+            })
+        }
+
+        // Alerts aren't linked to, the repo's latest Dependabot alert is what's new
+        "security_alert" => match latest_alert(client, repo).await? {
+            Some(alert) => {
+                let data = alert_print_data(&alert, repo, base)?;
+                Some(PrintData {
+                    title: format!("{name}: {}", data.title),
+                    ..data
+                })
+            }
+            None => Some(PrintData {
+                priority: Priority::High,
+                title: format!("{name}: Security Alert"),
+                ..base
+            }),
+        },
+
+        "assign" => {
+            let issue = client.get(str_at(notif, "/subject/url")?).await?;
+            let (mut message, qr_codes) = post(&issue)?;
+            message.insert(0, Span::plain("Assigned to you\n\n"));
+            Some(PrintData {
+                title: format!("{name}: Assigned"),
+                message: Some(message.into()),
+                qr_codes: if qr_codes.is_empty() {
+                    link(&issue, "Open")
+                } else {
+                    qr_codes
+                },
+                ..base
+            })
+        }
+
+        // Workflow runs have no API URL to fetch, the subject says how they went, e.g. "CI
+        // workflow run failed for main branch"
+        "ci_activity" => Some(PrintData {
+            priority: if subject.contains("failed") {
+                Priority::High
+            } else {
+                Priority::Low
+            },
+            title: format!("{name}: CI Activity"),
+            subtitle: Some(format!("Repo: {repo}")),
+            message: Some(subject.to_string().into()),
+            ..base
+        }),
+
+        "state_change" => {
+            info!("Got a state_change notif");
+            None
+        }
+
+        other => {
+            error!("Unhandled notification reason {other}:\n{notif}");
+            None
+        }
+    };
+    Ok(data)
+}
+
+/// Receipt of the latest comment on what a notification is about, or of the review it's part of
+async fn comment_print_data(
+    client: &mut GitHubClient,
+    notif: &Value,
+    name: &str,
+    reason: &str,
+    base: PrintData,
+) -> Result<PrintData> {
+    let (title, priority) = match reason {
+        "mention" => ("Mentioned", Priority::High),
+        "team_mention" => ("Team Mentioned", Priority::High),
+        "subscribed" => ("New Issue on Subbed Repo", Priority::Normal),
+        _ => ("New Issue Comment", Priority::Normal),
+    };
+    let comment = latest_comment(client, notif).await?;
+    if let Some(review) = review(client, notif, &comment).await? {
+        let data = review_print_data(&review, &comment, base)?;
+        Ok(PrintData {
+            title: format!("{name}: {}", data.title),
+            priority: data.priority.max(priority),
+            ..data
+        })
+    } else {
+        let (message, qr_codes) = post(&comment)?;
+        Ok(PrintData {
+            priority,
+            title: format!("{name}: {title}"),
+            message: Some(message.into()),
+            qr_codes,
+            ..base
+        })
+    }
+}
+
+/// Leaves a notification that isn't printed be; Marked read right away, unless notifications
+/// are never marked read
+async fn skip(
+    client: &mut GitHubClient,
+    handled: &mut Handled,
+    mark_read_after: MarkRead,
+    thread_id: &str,
+    event_id: String,
+) {
+    if matches!(mark_read_after, MarkRead::Never) {
+        handled.insert(event_id);
+    } else if let Err(e) = mark_read(client, thread_id).await {
+        warn!("Unable to mark GitHub notification {thread_id} read: {e}");
+        handled.insert(event_id);
+    }
+}
+
+/// Latest comment on the notification's issue or PR, or its description if there are none yet
+async fn latest_comment(client: &mut GitHubClient, notif: &Value) -> Result<Value> {
+    // Null on issues without comments, e.g. when mentioned in the description
+    let url =
+        str_at(notif, "/subject/latest_comment_url").or_else(|_| str_at(notif, "/subject/url"))?;
+    client.get(url).await
+}
+
+/// PR review the notification's latest comment is, or belongs to if it's a review comment
+async fn review(
+    client: &mut GitHubClient,
+    notif: &Value,
+    comment: &Value,
+) -> Result<Option<Value>> {
+    if str_at(notif, "/subject/type")? != "PullRequest" {
+        return Ok(None);
+    }
+    // Only reviews have a verdict
+    if comment.pointer("/state").is_some() && comment.pointer("/submitted_at").is_some() {
+        return Ok(Some(comment.clone()));
+    }
+    let Some(review_id) = comment
+        .pointer("/pull_request_review_id")
+        .and_then(Value::as_u64)
+    else {
+        return Ok(None);
+    };
+    let pull_url = str_at(notif, "/subject/url")?;
+    let url = format!("{pull_url}/reviews/{review_id}");
+    client.get(&url).await.map(Some)
+}
+
+/// Receipt of a PR review showing the reviewer, their verdict & its summary; The review
+/// comment stands in for the summary of reviews left without one
+fn review_print_data(review: &Value, comment: &Value, base: PrintData) -> Result<PrintData> {
+    let (title, verdict, priority) = match str_at(review, "/state")? {
+        "APPROVED" => ("PR Approved", "APPROVED", Priority::Normal),
+        "CHANGES_REQUESTED" => ("Changes Requested", "CHANGES REQUESTED", Priority::High),
+        "DISMISSED" => ("PR Review Dismissed", "DISMISSED", Priority::Low),
+        _ => ("PR Reviewed", "COMMENTED", Priority::Normal),
+    };
+    let summary = match review.pointer("/body").and_then(Value::as_str) {
+        Some(body) if !body.trim().is_empty() => body,
+        _ => comment
+            .pointer("/body")
+            .and_then(Value::as_str)
+            .unwrap_or_default(),
+    };
+
+    let (summary, mut qr_codes) = markdown(summary, MarkdownLinks::from_env());
+    let mut message = vec![
+        Span::bold(str_at(review, "/user/login")?),
+        Span::plain("\n"),
+        Span::invert(format!(" {verdict} ")),
+        Span::plain("\n\n"),
+    ];
+    message.extend(summary);
+    if qr_codes.is_empty() {
+        qr_codes = link(review, "Review");
+    }
+
+    Ok(PrintData {
+        priority,
+        title: title.to_string(),
+        message: Some(message.into()),
+        qr_codes,
+        ..base
+    })
+}
+
+/// Comment, issue or PR as its author's name followed by its Markdown body
+fn post(post: &Value) -> Result<(Vec<Span>, Vec<QrCode>)> {
+    // Descriptions may be left empty
+    let body = post
+        .pointer("/body")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let (mut message, qr_codes) = markdown(body, MarkdownLinks::from_env());
+    message.splice(
+        0..0,
+        [Span::bold(str_at(post, "/user/login")?), Span::plain(":\n")],
+    );
+    Ok((message, qr_codes))
+}
+
+/// Who opened a PR, between which branches & how big it is
+fn pull_summary(pull: &Value) -> Result<Vec<Span>> {
+    let count = |pointer: &str| pull.pointer(pointer).and_then(Value::as_u64).unwrap_or(0);
+    Ok(vec![
+        Span::bold(str_at(pull, "/user/login")?),
+        Span::plain(" wants your review\n"),
+        Span::underline(str_at(pull, "/base/ref")?),
+        Span::plain(" <- "),
+        Span::underline(str_at(pull, "/head/label")?),
+        Span::plain("\n"),
+        Span::plain(format!(
+            "+{} -{} in {} files",
+            count("/additions"),
+            count("/deletions"),
+            count("/changed_files"),
+        )),
+    ])
+}
 
-                other => {
-                    error!("Unhandled notification reason {other}:\n{notif}");
+/// Discussion of a repo with its latest comment, found by its title as notifications don't say
+/// which it is; Recently updated ones are looked through, as it was just updated
+async fn discussion(client: &mut GitHubClient, repo: &str, title: &str) -> Result<Value> {
+    const QUERY: &str = "
+        query($owner: String!, $name: String!) {
+            repository(owner: $owner, name: $name) {
+                discussions(first: 25, orderBy: { field: UPDATED_AT, direction: DESC }) {
+                    nodes {
+                        title
+                        url
+                        body
+                        author { login }
+                        category { name }
+                        comments(last: 1) {
+                            nodes { url body author { login } }
+                        }
+                    }
                 }
             }
+        }
+    ";
+    let (owner, name) = repo
+        .split_once('/')
+        .ok_or_else(|| Error::MissingField("/repository/full_name".to_string()))?;
+    let data = client
+        .graphql(QUERY, json!({ "owner": owner, "name": name }))
+        .await?;
+    data.pointer("/repository/discussions/nodes")
+        .and_then(Value::as_array)
+        .and_then(|discussions| {
+            discussions
+                .iter()
+                .find(|discussion| discussion["title"] == title)
+        })
+        .cloned()
+        .ok_or_else(|| Error::MissingField(format!("discussion `{title}`")))
+}
 
-            // Mark notif as read
-            if let Some(thread_id) = thread_id {
-                let req = http_client
-                    .patch(format!(
-                        "https://api.github.com/notifications/threads/{thread_id}"
-                    ))
-                    .bearer_auth(
-                        std::env::var("GITHUB_PAT").expect("GITHUB_PAT env var is not set!"),
-                    )
-                    .header(ACCEPT, "application/vnd.github.v3+json")
-                    .header("X-GitHub-Api-Version", "2022-11-28")
-                    .send()
-                    .await
-                    .unwrap();
+/// Receipt of a discussion showing its category & title, then its latest comment, or what it
+/// was started with if there are none yet
+fn discussion_print_data(discussion: &Value, repo: &str, base: PrintData) -> Result<PrintData> {
+    let category = str_at(discussion, "/category/name")?;
+    let title = str_at(discussion, "/title")?;
+    let comment = discussion
+        .pointer("/comments/nodes/0")
+        .filter(|comment| !comment.is_null());
+    let post = comment.unwrap_or(discussion);
+    // Authors of deleted accounts are null
+    let author = str_at(post, "/author/login").unwrap_or("ghost");
+
+    let (body, qr_codes) = markdown(str_at(post, "/body")?, MarkdownLinks::from_env());
+    let mut message = vec![Span::bold(author), Span::plain(":\n")];
+    message.extend(body);
+    let qr_codes = if qr_codes.is_empty() {
+        str_at(post, "/url")
+            .map(|url| QrCode {
+                caption: Some("Discussion".to_string()),
+                data: url.to_string(),
+            })
+            .into_iter()
+            .collect()
+    } else {
+        qr_codes
+    };
+
+    Ok(PrintData {
+        title: if comment.is_some() {
+            "New Discussion Comment".to_string()
+        } else {
+            "New Discussion".to_string()
+        },
+        subtitle: Some(format!("Repo: {repo}\n[{category}] {title}")),
+        message: Some(message.into()),
+        qr_codes,
+        ..base
+    })
+}
+
+/// Latest open Dependabot alert of a repo; None if they can't be read, e.g. as Dependabot alerts
+/// are turned off or the token lacks access to them
+async fn latest_alert(client: &mut GitHubClient, repo: &str) -> Result<Option<Value>> {
+    let url = format!(
+        "https://api.github.com/repos/{repo}/dependabot/alerts\
+         ?state=open&sort=created&direction=desc&per_page=1"
+    );
+    let req = client.request(Method::GET, &url);
+    let res = client.send(req).await?;
+    if matches!(res.status(), StatusCode::FORBIDDEN | StatusCode::NOT_FOUND) {
+        debug!(
+            "Unable to read Dependabot alerts of {repo}: {}",
+            res.status()
+        );
+        return Ok(None);
+    }
+    let mut alerts: Value = res.error_for_status()?.json().await?;
+    Ok(alerts.get_mut(0).map(Value::take))
+}
+
+/// Receipt of a Dependabot alert with the vulnerable package, how severe it is & what fixes it;
+/// High priority for high & critical ones
+fn alert_print_data(alert: &Value, repo: &str, base: PrintData) -> Result<PrintData> {
+    let package = str_at(alert, "/dependency/package/name")?;
+    let ecosystem = str_at(alert, "/dependency/package/ecosystem")?;
+    let severity = str_at(alert, "/security_advisory/severity")?;
+    let summary = str_at(alert, "/security_advisory/summary")?;
+    let ghsa_id = str_at(alert, "/security_advisory/ghsa_id")?;
+
+    let mut message = vec![
+        Span::invert(format!(" {} ", severity.to_uppercase())),
+        Span::plain(" "),
+        Span::bold(format!("{package} ({ecosystem})")),
+        Span::plain(format!("\n{summary}\n\n")),
+    ];
+    if let Ok(range) = str_at(alert, "/security_vulnerability/vulnerable_version_range") {
+        message.push(Span::plain(format!("Vulnerable: {range}\n")));
+    }
+    // Unpatched vulnerabilities have no fix yet
+    let patched = str_at(
+        alert,
+        "/security_vulnerability/first_patched_version/identifier",
+    )
+    .unwrap_or("none yet");
+    message.push(Span::plain(format!("Patched: {patched}")));
+
+    let advisory = str_at(alert, "/security_advisory/cve_id").unwrap_or(ghsa_id);
+    let mut qr_codes = vec![QrCode {
+        caption: Some("Advisory".to_string()),
+        data: format!("https://github.com/advisories/{ghsa_id}"),
+    }];
+    qr_codes.extend(link(alert, "Alert"));
+
+    Ok(PrintData {
+        priority: match severity {
+            "critical" | "high" => Priority::High,
+            "low" => Priority::Low,
+            _ => Priority::Normal,
+        },
+        title: "Security Alert".to_string(),
+        subtitle: Some(format!("Repo: {repo}\n{advisory}")),
+        message: Some(message.into()),
+        qr_codes,
+        ..base
+    })
+}
+
+/// Receipt of a release of a watched repo, with its notes cut short
+fn release_print_data(
+    release: &Value,
+    name: &str,
+    repo: &str,
+    base: PrintData,
+) -> Result<PrintData> {
+    let tag = str_at(release, "/tag_name")?;
+    let mut subtitle = format!("Repo: {repo}\n{tag}");
+    // Named after their tag unless given a name
+    if let Some(release_name) = release
+        .pointer("/name")
+        .and_then(Value::as_str)
+        .filter(|release_name| !release_name.is_empty() && *release_name != tag)
+    {
+        subtitle = format!("{subtitle}: {release_name}");
+    }
+    let prerelease = release
+        .pointer("/prerelease")
+        .and_then(Value::as_bool)
+        .unwrap_or_default();
+
+    let notes = release
+        .pointer("/body")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let (message, _) = markdown(&truncate_notes(notes), MarkdownLinks::Inline);
+
+    Ok(PrintData {
+        priority: if prerelease {
+            Priority::Low
+        } else {
+            Priority::Normal
+        },
+        title: if prerelease {
+            format!("{name}: New Pre-release")
+        } else {
+            format!("{name}: New Release")
+        },
+        subtitle: Some(subtitle),
+        message: (!message.is_empty()).then(|| message.into()),
+        qr_codes: link(release, "Release notes"),
+        ..base
+    })
+}
+
+/// Release notes up to the last whole line within [`MAX_RELEASE_NOTES`] characters, as they
+/// tend to be long lists of every change
+fn truncate_notes(notes: &str) -> String {
+    let mut truncated = String::new();
+    for line in notes.lines() {
+        if truncated.chars().count() + line.chars().count() > MAX_RELEASE_NOTES {
+            truncated.push_str("\n...");
+            break;
+        }
+        truncated.push_str(line);
+        truncated.push('\n');
+    }
+    truncated
+}
+
+/// QR code linking to an issue or PR on the web
+fn link(issue: &Value, caption: &str) -> Vec<QrCode> {
+    issue
+        .pointer("/html_url")
+        .and_then(Value::as_str)
+        .map(|url| QrCode {
+            caption: Some(caption.to_string()),
+            data: url.to_string(),
+        })
+        .into_iter()
+        .collect()
+}
+
+/// Threads are updated in place, so unread ones are fetched again until marked read; Their
+/// events are told apart by when they were updated
+fn event_id(notif: &Value) -> Result<String> {
+    let thread_id = str_at(notif, "/id")?;
+    let updated_time = str_at(notif, "/updated_at")?;
+    Ok(format!("{thread_id}@{updated_time}"))
+}
+
+/// Marks a notification's thread as read; Fails without marking it if the thread is gone, e.g.
+/// as the repo was deleted
+async fn mark_read(client: &mut GitHubClient, thread_id: &str) -> Result<()> {
+    let url = format!("https://api.github.com/notifications/threads/{thread_id}");
+    let req = client.request(Method::PATCH, &url);
+    // 205 Reset Content usually, 304 Not Modified if it was already read
+    let res = client.send(req).await?;
+    if !res.status().is_success() && res.status() != StatusCode::NOT_MODIFIED {
+        return Err(Error::Status(res.status()));
+    }
+
+    Ok(())
+}
+
+/// How an account's notifications are handled
+struct Settings {
+    mark_read: MarkRead,
+    repos: RepoFilter,
+    /// Repos newly opened issues are printed from, see [`poll_issues`]
+    watch: Vec<String>,
+}
+
+impl Settings {
+    fn from_account(account: &Account) -> Result<Self, String> {
+        let mark_read_var = account.var("GITHUB_MARK_READ");
+        let mark_read = secrets::var(&mark_read_var)
+            .map_or(Ok(MarkRead::Printed), |m| m.parse())
+            .map_err(|e| format!("Invalid {mark_read_var}! {e}"))?;
+        let list = |var: &str| {
+            secrets::var(&account.var(var))
+                .ok()
+                .map(|list| repo_list(&list))
+        };
+        Ok(Self {
+            mark_read,
+            repos: RepoFilter {
+                only: list("GITHUB_REPOS"),
+                ignore: list("GITHUB_IGNORE_REPOS").unwrap_or_default(),
+            },
+            watch: list("GITHUB_WATCH_REPOS").unwrap_or_default(),
+        })
+    }
+}
+
+/// Repos printed from, through `GITHUB_REPOS` & `GITHUB_IGNORE_REPOS`; Comma separated repos,
+/// e.g. `rust-lang/rust`, or owners for all their repos, e.g. `rust-lang`
+#[derive(Debug)]
+struct RepoFilter {
+    /// None to print from every repo not ignored
+    only: Option<Vec<String>>,
+    ignore: Vec<String>,
+}
+
+impl RepoFilter {
+    /// Whether to print from a repo, e.g. `owner/name`; Ignored ones never are, even if listed
+    fn allows(&self, repo: &str) -> bool {
+        let owner = repo.split_once('/').map_or(repo, |(owner, _)| owner);
+        let matches =
+            |entry: &String| entry.eq_ignore_ascii_case(repo) || entry.eq_ignore_ascii_case(owner);
+        self.only
+            .as_ref()
+            .is_none_or(|only| only.iter().any(matches))
+            && !self.ignore.iter().any(matches)
+    }
+}
+
+fn repo_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|repo| !repo.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// When printed notifications are marked read on GitHub, through `GITHUB_MARK_READ`: `never`,
+/// once `printed` (the default), or some hours after, e.g. `12h`, leaving them in the inbox
+/// meanwhile
+#[derive(Debug, Clone, Copy)]
+enum MarkRead {
+    Never,
+    Printed,
+    After(Duration),
+}
+
+impl FromStr for MarkRead {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "never" => Ok(Self::Never),
+            "printed" => Ok(Self::Printed),
+            other => other
+                .strip_suffix('h')
+                .and_then(|hours| hours.parse::<u64>().ok())
+                .map(|hours| Self::After(Duration::from_secs(hours * 60 * 60)))
+                .ok_or_else(|| {
+                    format!("Unknown `{other}`; expected never, printed or hours, e.g. 12h")
+                }),
+        }
+    }
+}
+
+/// Events of notifications printed (or skipped) but left unread, so they're not handled again
+/// while they stay in the inbox; Kept in the state
+struct Handled {
+    state_key: String,
+    events: BTreeSet<String>,
+}
+
+impl Handled {
+    fn load(state_key: String) -> Self {
+        let events = state::get(&state_key, "handled")
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { state_key, events }
+    }
+
+    fn contains(&self, event_id: &str) -> bool {
+        self.events.contains(event_id)
+    }
+
+    fn insert(&mut self, event_id: String) {
+        self.events.insert(event_id);
+        self.save();
+    }
+
+    /// Forgets events no longer among the unread notifications, e.g. as they were read
+    fn retain_unread(&mut self, notifs: &[Value]) {
+        let unread = notifs
+            .iter()
+            .filter_map(|notif| event_id(notif).ok())
+            .collect::<BTreeSet<_>>();
+        let len = self.events.len();
+        self.events.retain(|event_id| unread.contains(event_id));
+        if self.events.len() != len {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(&self.events) {
+            Ok(json) => state::set(&self.state_key, "handled", Some(&json)),
+            Err(e) => warn!("Unable to save handled GitHub notifications: {e}"),
+        }
+    }
+}
+
+/// Threads to mark read later, see [`MarkRead::After`]; Kept in the state, oldest first
+struct Unread {
+    state_key: String,
+    threads: VecDeque<(DateTime<Local>, String)>,
+}
+
+impl Unread {
+    fn load(state_key: String) -> Self {
+        let threads = state::get(&state_key, "mark_read")
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { state_key, threads }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.threads.is_empty()
+    }
+
+    fn push(&mut self, thread_id: String, delay: Duration) {
+        let at = Local::now() + delay;
+        self.threads.push_back((at, thread_id));
+        self.save();
+    }
+
+    /// Waits until the oldest thread is due to be marked read
+    async fn due(&self) {
+        let Some((at, _)) = self.threads.front() else {
+            return std::future::pending().await;
+        };
+        let wait = (*at - Local::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+    }
 
-                assert!(req.status() == StatusCode::RESET_CONTENT);
+    /// Oldest thread, once [`Self::due`]
+    fn pop(&mut self) -> Option<String> {
+        let (_, thread_id) = self.threads.pop_front()?;
+        self.save();
+        Some(thread_id)
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(&self.threads) {
+            Ok(json) => state::set(&self.state_key, "mark_read", Some(&json)),
+            Err(e) => warn!("Unable to save GitHub notifications to mark read: {e}"),
+        }
+    }
+}
+
+/// Secret GitHub signs webhook deliveries with; Webhooks are only received when it's set
+pub fn webhook_secret() -> Option<String> {
+    secrets::var("GITHUB_WEBHOOK_SECRET").ok()
+}
+
+/// Whether a delivery was signed with the secret, going by its `X-Hub-Signature-256` header
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(signature) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex| hex::decode(hex).ok())
+    else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    // In constant time, so the signature can't be guessed byte by byte
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Receipt of a webhook delivery of an org or repo, by its `X-GitHub-Event` & `X-GitHub-Delivery`
+/// headers; None for events & actions that aren't printed, e.g. `ping` or unstarring
+///
+/// Covers the events polling misses or only learns of late: `push`, `issues`, `star`, `fork`,
+/// `release` & `dependabot_alert`.
+pub fn webhook_print_data(
+    event: &str,
+    delivery: &str,
+    payload: &Value,
+) -> Result<Option<PrintData>> {
+    let repo = str_at(payload, "/repository/full_name")?;
+    let sender = str_at(payload, "/sender/login")?;
+    let action = payload.pointer("/action").and_then(Value::as_str);
+    let base = PrintData {
+        service: Some("github".to_string()),
+        logo: Some("github".to_string()),
+        event_id: Some(format!("webhook:{delivery}")),
+        source: Some(repo.to_string()),
+        subtitle: Some(format!("Repo: {repo}")),
+        timestamp: Local::now(),
+        ..Default::default()
+    };
+
+    let data = match (event, action) {
+        ("push", _) => return push_print_data(payload, sender, repo, base),
+
+        ("issues", Some(action @ ("opened" | "closed" | "reopened"))) => {
+            issue_print_data(payload, action, sender, repo, base)?
+        }
+
+        ("star", Some("created")) => {
+            let stars = payload
+                .pointer("/repository/stargazers_count")
+                .and_then(Value::as_u64)
+                .unwrap_or_default();
+            PrintData {
+                priority: Priority::Low,
+                title: "GitHub: New Star".to_string(),
+                message: Some(
+                    vec![
+                        Span::bold(sender),
+                        Span::plain(format!(" starred it, now at {stars} stars")),
+                    ]
+                    .into(),
+                ),
+                ..base
             }
         }
 
-        tokio::select! {
-            () = cancel_token.cancelled() => {
-                debug!("Cancel signal caught! Stopping service...");
-                break;
+        ("fork", _) => PrintData {
+            priority: Priority::Low,
+            title: "GitHub: New Fork".to_string(),
+            message: Some(
+                vec![
+                    Span::bold(sender),
+                    Span::plain(format!(
+                        " forked it to {}",
+                        str_at(payload, "/forkee/full_name")?
+                    )),
+                ]
+                .into(),
+            ),
+            ..base
+        },
+
+        ("release", Some("published")) => {
+            let release = payload
+                .pointer("/release")
+                .ok_or_else(|| Error::MissingField("/release".to_string()))?;
+            release_print_data(release, "GitHub", repo, base)?
+        }
+
+        ("dependabot_alert", Some("created" | "reintroduced")) => {
+            let alert = payload
+                .pointer("/alert")
+                .ok_or_else(|| Error::MissingField("/alert".to_string()))?;
+            let data = alert_print_data(alert, repo, base)?;
+            PrintData {
+                title: format!("GitHub: {}", data.title),
+                ..data
             }
-            () = tokio::time::sleep(Duration::from_secs(poll_interval)) => {}
         }
+
+        (event, action) => {
+            debug!("Ignoring {event} {} webhook", action.unwrap_or_default());
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(data))
+}
+
+/// Receipt of a `push` webhook, listing the commits pushed; None for deleted branches & tags
+fn push_print_data(
+    payload: &Value,
+    sender: &str,
+    repo: &str,
+    base: PrintData,
+) -> Result<Option<PrintData>> {
+    let commits = payload
+        .pointer("/commits")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    // Deleted branches & pushed tags have no commits
+    if commits.is_empty() {
+        return Ok(None);
     }
+    let branch = str_at(payload, "/ref")?.trim_start_matches("refs/heads/");
+    let mut message = vec![Span::bold(sender), Span::plain(" pushed:\n")];
+    for commit in commits {
+        let id = str_at(commit, "/id")?;
+        let summary = str_at(commit, "/message")?
+            .lines()
+            .next()
+            .unwrap_or_default();
+        message.push(Span::plain(format!(
+            "- {} {summary}\n",
+            id.get(..7).unwrap_or(id)
+        )));
+    }
+    let qr_codes = payload
+        .pointer("/compare")
+        .and_then(Value::as_str)
+        .map(|url| QrCode {
+            caption: Some("Compare".to_string()),
+            data: url.to_string(),
+        })
+        .into_iter()
+        .collect();
+    Ok(Some(PrintData {
+        priority: Priority::Low,
+        title: "GitHub: Push".to_string(),
+        subtitle: Some(format!(
+            "Repo: {repo}\n{} commits to {branch}",
+            commits.len()
+        )),
+        message: Some(message.into()),
+        qr_codes,
+        ..base
+    }))
+}
+
+/// Receipt of an `issues` webhook of an issue being opened, closed or reopened
+fn issue_print_data(
+    payload: &Value,
+    action: &str,
+    sender: &str,
+    repo: &str,
+    base: PrintData,
+) -> Result<PrintData> {
+    let issue = payload
+        .pointer("/issue")
+        .ok_or_else(|| Error::MissingField("/issue".to_string()))?;
+    let number = issue
+        .pointer("/number")
+        .and_then(Value::as_u64)
+        .unwrap_or_default();
+    let (message, qr_codes) = if action == "opened" {
+        post(issue)?
+    } else {
+        (
+            vec![Span::bold(sender), Span::plain(format!(" {action} it"))],
+            Vec::new(),
+        )
+    };
+    Ok(PrintData {
+        title: format!("GitHub: Issue {}", capitalize(action)),
+        subtitle: Some(format!(
+            "Repo: {repo}\n#{number} {}",
+            str_at(issue, "/title")?
+        )),
+        message: Some(message.into()),
+        qr_codes: if qr_codes.is_empty() {
+            link(issue, "Open")
+        } else {
+            qr_codes
+        },
+        ..base
+    })
+}
+
+/// `opened` -> `Opened`
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
 }
@@ -0,0 +1,114 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    error::{Error, Result},
+    http::SendRetrying,
+    service::{github_client, Account},
+};
+
+/// Installation tokens are renewed this long before they expire, so none expires mid-request
+const RENEW_BEFORE: TimeDelta = TimeDelta::minutes(5);
+
+/// How the GitHub service authenticates; A personal access token (`GITHUB_PAT`), or a GitHub App
+/// installation (`GITHUB_APP_ID`, `GITHUB_APP_PRIVATE_KEY` in PEM & `GITHUB_APP_INSTALLATION_ID`)
+/// through short-lived installation tokens, renewed as they expire
+pub enum Credentials {
+    Pat(String),
+    App(App),
+}
+
+pub struct App {
+    app_id: String,
+    installation_id: String,
+    key: EncodingKey,
+    /// Installation token & when it expires; None until first needed
+    token: Option<(String, DateTime<Utc>)>,
+}
+
+/// Claims of the JWT the app authenticates with to get installation tokens
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+#[derive(Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl Credentials {
+    /// The account's PAT, or its app installation if it has none
+    pub fn from_account(account: &Account) -> Result<Self> {
+        if let Ok(pat) = account.env("GITHUB_PAT") {
+            return Ok(Self::Pat(pat));
+        }
+        let Ok(app_id) = account.env("GITHUB_APP_ID") else {
+            return Err(Error::MissingEnv(account.var("GITHUB_PAT")));
+        };
+        let key = account.env("GITHUB_APP_PRIVATE_KEY")?;
+        Ok(Self::App(App {
+            app_id,
+            installation_id: account.env("GITHUB_APP_INSTALLATION_ID")?,
+            key: EncodingKey::from_rsa_pem(key.as_bytes())?,
+            token: None,
+        }))
+    }
+
+    pub const fn is_app(&self) -> bool {
+        matches!(self, Self::App(_))
+    }
+
+    /// Returns a token to authenticate requests with, renewing the installation token once it's
+    /// about to expire
+    pub async fn token(&mut self, http_client: &Client) -> Result<String> {
+        let app = match self {
+            Self::Pat(pat) => return Ok(pat.clone()),
+            Self::App(app) => app,
+        };
+        if let Some((token, expires_at)) = &app.token {
+            if *expires_at - RENEW_BEFORE > Utc::now() {
+                return Ok(token.clone());
+            }
+        }
+
+        debug!("Renewing GitHub App installation token");
+        let url = format!(
+            "https://api.github.com/app/installations/{}/access_tokens",
+            app.installation_id
+        );
+        let res = github_client::authenticate(http_client.post(url), &app.jwt()?)
+            .send_retrying()
+            .await?;
+        if res.status().is_client_error() {
+            return Err(Error::Unauthorized);
+        }
+        let installation: InstallationToken = res.error_for_status()?.json().await?;
+        app.token = Some((installation.token.clone(), installation.expires_at));
+        Ok(installation.token)
+    }
+}
+
+impl App {
+    /// JWT signed with the app's private key, valid for the 10 minutes GitHub allows at most
+    fn jwt(&self) -> Result<String> {
+        let now = Utc::now();
+        let claims = Claims {
+            // Backdated, in case GitHub's clock is behind
+            iat: (now - TimeDelta::minutes(1)).timestamp(),
+            exp: (now + TimeDelta::minutes(9)).timestamp(),
+            iss: self.app_id.clone(),
+        };
+        Ok(jsonwebtoken::encode(
+            &Header::new(Algorithm::RS256),
+            &claims,
+            &self.key,
+        )?)
+    }
+}
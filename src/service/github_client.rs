@@ -0,0 +1,167 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    header::{HeaderMap, ACCEPT, ETAG, IF_NONE_MATCH, RETRY_AFTER},
+    Client, Method, RequestBuilder, Response, StatusCode,
+};
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+
+use crate::{
+    error::{Error, Result},
+    http::SendRetrying,
+    service::github_app::Credentials,
+};
+
+/// Requests left before polling slows down, spreading them out until the limit resets
+const LOW_REMAINING: u64 = 100;
+
+/// Talks to the GitHub API as an account, keeping track of its rate limit
+///
+/// Requests are refused with [`Error::RateLimited`] once the limit is used up, until it resets.
+pub struct GitHubClient {
+    http_client: Client,
+    credentials: Credentials,
+    /// Requests left until `reset`, going by the last response
+    remaining: Option<u64>,
+    reset: Option<DateTime<Utc>>,
+    /// ETags of what [`Self::get_if_changed`] last fetched, by URL
+    etags: HashMap<String, String>,
+}
+
+impl GitHubClient {
+    pub fn new(http_client: Client, credentials: Credentials) -> Self {
+        Self {
+            http_client,
+            credentials,
+            remaining: None,
+            reset: None,
+            etags: HashMap::new(),
+        }
+    }
+
+    pub const fn is_app(&self) -> bool {
+        self.credentials.is_app()
+    }
+
+    /// Request to build on before [`Self::send`]ing it
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.http_client.request(method, url)
+    }
+
+    /// Sends a request as the account, see [`authenticate`]
+    pub async fn send(&mut self, req: RequestBuilder) -> Result<Response> {
+        if let Some(wait) = self.exhausted() {
+            return Err(Error::RateLimited(wait));
+        }
+        let token = self.credentials.token(&self.http_client).await?;
+        let res = authenticate(req, &token).send_retrying().await?;
+        self.track(res.headers());
+
+        if matches!(
+            res.status(),
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+        ) {
+            // Secondary rate limits, e.g. for too many requests at once, say how long to wait
+            let retry_after = res
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|h| h.to_str().ok()?.parse().ok())
+                .map(Duration::from_secs);
+            if let Some(wait) = retry_after.or_else(|| self.exhausted()) {
+                return Err(Error::RateLimited(wait));
+            }
+        }
+        Ok(res)
+    }
+
+    /// JSON at an API URL
+    pub async fn get(&mut self, url: &str) -> Result<Value> {
+        let req = self.request(Method::GET, url);
+        Ok(self.send(req).await?.error_for_status()?.json().await?)
+    }
+
+    /// JSON at an API URL polled over & over, or None if it's unchanged since last fetched;
+    /// Unchanged responses don't count against the rate limit
+    pub async fn get_if_changed(&mut self, url: &str) -> Result<Option<Value>> {
+        let mut req = self.request(Method::GET, url);
+        if let Some(etag) = self.etags.get(url) {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        let res = self.send(req).await?;
+        // Has no body to read
+        if res.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let res = res.error_for_status()?;
+        let etag = res
+            .headers()
+            .get(ETAG)
+            .and_then(|h| h.to_str().ok())
+            .map(ToString::to_string);
+        let json = res.json().await?;
+        // Only once read, so a response that failed to be is fetched in full again
+        if let Some(etag) = etag {
+            self.etags.insert(url.to_string(), etag);
+        }
+        Ok(Some(json))
+    }
+
+    /// Data a GraphQL query returns, for what the REST API leaves out, e.g. discussions
+    pub async fn graphql(&mut self, query: &str, variables: Value) -> Result<Value> {
+        let req = self
+            .request(Method::POST, "https://api.github.com/graphql")
+            .json(&json!({ "query": query, "variables": variables }));
+        let mut res: Value = self.send(req).await?.error_for_status()?.json().await?;
+        // Queries fail with 200 OK, naming what went wrong
+        if let Some(message) = res.pointer("/errors/0/message").and_then(Value::as_str) {
+            warn!("GitHub GraphQL query failed: {message}");
+        }
+        res.get_mut("data")
+            .filter(|data| !data.is_null())
+            .map(Value::take)
+            .ok_or_else(|| Error::MissingField("/data".to_string()))
+    }
+
+    /// How long to wait before polling again, at least `wanted`; Longer once requests run low,
+    /// so the rest last until the limit resets
+    pub fn poll_interval(&self, wanted: Duration) -> Duration {
+        match (self.remaining, self.until_reset()) {
+            (Some(remaining), Some(until_reset)) if remaining < LOW_REMAINING => {
+                let spread = until_reset / u32::try_from(remaining + 1).unwrap_or(u32::MAX);
+                debug!("{remaining} GitHub requests left, polling every {spread:?}");
+                wanted.max(spread)
+            }
+            _ => wanted,
+        }
+    }
+
+    fn track(&mut self, headers: &HeaderMap) {
+        let header = |name: &str| headers.get(name)?.to_str().ok()?.parse::<i64>().ok();
+        if let Some(remaining) = header("X-RateLimit-Remaining") {
+            self.remaining = u64::try_from(remaining).ok();
+        }
+        if let Some(reset) = header("X-RateLimit-Reset") {
+            self.reset = DateTime::from_timestamp(reset, 0);
+        }
+    }
+
+    /// Time until the rate limit resets if it's used up
+    fn exhausted(&self) -> Option<Duration> {
+        (self.remaining == Some(0))
+            .then(|| self.until_reset())
+            .flatten()
+    }
+
+    fn until_reset(&self) -> Option<Duration> {
+        (self.reset? - Utc::now()).to_std().ok()
+    }
+}
+
+/// Authenticates & pins the API version
+pub fn authenticate(req: RequestBuilder, token: &str) -> RequestBuilder {
+    req.bearer_auth(token)
+        .header(ACCEPT, "application/vnd.github.v3+json")
+        .header("X-GitHub-Api-Version", "2022-11-28")
+}
@@ -0,0 +1,107 @@
+use chrono::Local;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{
+    error::{str_at, Error, Result},
+    printer::{PrintData, Priority, Span},
+};
+
+/// Receipt of a `sponsorship` webhook delivery, set up in the Sponsors dashboard of the account
+/// being sponsored; None for actions that aren't printed, e.g. pending changes or edits
+pub fn webhook_print_data(delivery: &str, payload: &Value) -> Result<Option<PrintData>> {
+    let sponsorship = payload
+        .pointer("/sponsorship")
+        .ok_or_else(|| Error::MissingField("/sponsorship".to_string()))?;
+    let sponsor = str_at(sponsorship, "/sponsor/login")?;
+    let sponsored = str_at(sponsorship, "/sponsorable/login")?;
+    let tier = tier(
+        sponsorship
+            .pointer("/tier")
+            .ok_or_else(|| Error::MissingField("/sponsorship/tier".to_string()))?,
+    )?;
+    let base = PrintData {
+        service: Some("github_sponsors".to_string()),
+        logo: Some("github".to_string()),
+        event_id: Some(format!("webhook:{delivery}")),
+        source: Some(sponsored.to_string()),
+        subtitle: Some(format!("Sponsoring {sponsored}")),
+        timestamp: Local::now(),
+        ..Default::default()
+    };
+
+    let data = match str_at(payload, "/action")? {
+        "created" => PrintData {
+            priority: Priority::High,
+            title: "GitHub: New Sponsor".to_string(),
+            message: Some(
+                vec![
+                    Span::bold(sponsor),
+                    Span::plain(format!(" is sponsoring you\n{tier}")),
+                ]
+                .into(),
+            ),
+            ..base
+        },
+
+        "tier_changed" => {
+            // Tiers changed from are missing from older deliveries
+            let from = payload
+                .pointer("/changes/tier/from")
+                .map(self::tier)
+                .transpose()?
+                .unwrap_or_else(|| "?".to_string());
+            PrintData {
+                title: "GitHub: Sponsor Tier Changed".to_string(),
+                message: Some(
+                    vec![
+                        Span::bold(sponsor),
+                        Span::plain(format!(" changed tiers\n{from} -> {tier}")),
+                    ]
+                    .into(),
+                ),
+                ..base
+            }
+        }
+
+        "cancelled" => PrintData {
+            title: "GitHub: Sponsorship Cancelled".to_string(),
+            message: Some(
+                vec![
+                    Span::bold(sponsor),
+                    Span::plain(format!(" stopped sponsoring you\n{tier}")),
+                ]
+                .into(),
+            ),
+            ..base
+        },
+
+        action => {
+            debug!("Ignoring sponsorship {action} webhook");
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(data))
+}
+
+/// Amount of a tier & its name, e.g. `$5 a month (Supporter)`
+fn tier(tier: &Value) -> Result<String> {
+    let dollars = tier
+        .pointer("/monthly_price_in_dollars")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| Error::MissingField("/tier/monthly_price_in_dollars".to_string()))?;
+    let one_time = tier
+        .pointer("/is_one_time")
+        .and_then(Value::as_bool)
+        .unwrap_or_default();
+    let amount = if one_time {
+        format!("${dollars} once")
+    } else {
+        format!("${dollars} a month")
+    };
+    Ok(match str_at(tier, "/name") {
+        Ok(name) if !name.is_empty() => format!("{amount} ({name})"),
+        _ => amount,
+    })
+}
@@ -0,0 +1,384 @@
+use std::{str::FromStr, time::Duration};
+
+use chrono::DateTime;
+use futures_util::future::join_all;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc::Sender},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace, warn};
+
+use crate::{
+    ack::{self, InFlight},
+    error::{str_at, Error, Result},
+    http::{self, SendRetrying},
+    printer::{markdown, MarkdownLinks, PrintData, Priority, QrCode, Span},
+    retry::Backoff,
+    service::Account,
+    status,
+};
+
+const DEFAULT_URL: &str = "https://gitlab.com";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Wait before polling again after a failed poll, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
+/// Failed jobs listed on pipeline failure receipts
+const MAX_FAILED_JOBS: usize = 5;
+
+/// Polls the to-do list of every account in `GITLAB_ACCOUNTS` at once, see [`Account`]
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
+    let http_client = http::client();
+    join_all(
+        Account::all("GITLAB_ACCOUNTS")
+            .into_iter()
+            .map(|account| run(&http_client, &cancel_token, &sender, account)),
+    )
+    .await;
+}
+
+#[instrument(skip_all, fields(account = account.label.as_deref()))]
+async fn run(
+    http_client: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    account: Account,
+) {
+    let name = account.name("GitLab");
+    let token = match account.env("GITLAB_TOKEN") {
+        Ok(token) => token,
+        Err(e) => {
+            info!("{e}, {name} disabled");
+            return;
+        }
+    };
+    // Self-hosted instances are at their own URL
+    let url = account
+        .env("GITLAB_URL")
+        .unwrap_or_else(|_| DEFAULT_URL.to_string());
+    let api = Api {
+        http_client: http_client.clone(),
+        base_url: format!("{}/api/v4", url.trim_end_matches('/')),
+        token,
+    };
+    // To-dos are only marked done once printed, by ID
+    let mut in_flight = InFlight::load("gitlab", account.state_key("gitlab"));
+    let mut acks = ack::subscribe();
+
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        let poll_interval = match poll(&api, sender, &name, &mut in_flight).await {
+            Ok(()) => {
+                status::service_ok("gitlab");
+                backoff.reset();
+                POLL_INTERVAL
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("Unable to fetch {name} to-dos, retrying in {delay:?}: {e}");
+                delay
+            }
+            Err(e) => {
+                error!("Stopping {name} service: {e}");
+                break;
+            }
+        };
+
+        let next_poll = Instant::now() + poll_interval;
+        loop {
+            tokio::select! {
+                () = cancel_token.cancelled() => {
+                    debug!("Cancel signal caught! Stopping service...");
+                    return;
+                }
+                () = tokio::time::sleep_until(next_poll) => break,
+                acked = acks.recv() => match acked {
+                    Ok(acked) => {
+                        let Some(todo_id) = in_flight.acked(&acked) else {
+                            continue;
+                        };
+                        // Left pending, it's printed again on the next poll
+                        if let Err(e) = api.mark_done(&todo_id).await {
+                            warn!("Unable to mark {name} to-do {todo_id} done: {e}");
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Missed {missed} acks, their to-dos are printed again later");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+}
+
+/// GitLab instance's REST API, as an account
+struct Api {
+    http_client: Client,
+    /// e.g. `https://gitlab.com/api/v4`
+    base_url: String,
+    token: String,
+}
+
+impl Api {
+    /// JSON at a path of the API, e.g. `/todos`
+    async fn get(&self, path: &str) -> Result<Value> {
+        trace!("Fetching {path}");
+        let res = self
+            .http_client
+            .get(format!("{}{path}", self.base_url))
+            .header("PRIVATE-TOKEN", &self.token)
+            .send_retrying()
+            .await?;
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::Unauthorized);
+        }
+        Ok(res.error_for_status()?.json().await?)
+    }
+
+    async fn mark_done(&self, todo_id: &str) -> Result<()> {
+        self.http_client
+            .post(format!("{}/todos/{todo_id}/mark_as_done", self.base_url))
+            .header("PRIVATE-TOKEN", &self.token)
+            .send_retrying()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Prints pending to-dos that aren't in flight already
+async fn poll(
+    api: &Api,
+    sender: &Sender<PrintData>,
+    name: &str,
+    in_flight: &mut InFlight,
+) -> Result<()> {
+    let todos = api.get("/todos?state=pending&per_page=50").await?;
+    let todos = todos
+        .as_array()
+        .ok_or_else(|| Error::MissingField("/".to_string()))?;
+    // Oldest first
+    for todo in todos.iter().rev() {
+        let todo_id = todo
+            .pointer("/id")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| Error::MissingField("/id".to_string()))?;
+        let event_id = format!("todo:{todo_id}");
+        if in_flight.contains(&event_id) {
+            continue;
+        }
+
+        // Left pending on failure, so it's tried again
+        match todo_print_data(api, name, todo).await {
+            Ok(Some(data)) => {
+                info!("New to-do with ID: {todo_id}");
+                // Persisted before it's queued, so a restart can't lose track of it
+                in_flight.insert(event_id, todo_id.to_string());
+                sender.send(data).await?;
+            }
+            // Left on the to-do list, for the user to deal with
+            Ok(None) => {}
+            Err(e) => error!("Unable to print GitLab to-do: {e}\n{todo}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Receipt of a to-do with what it's about; None for actions that aren't printed, e.g. to-dos
+/// added by hand
+async fn todo_print_data(api: &Api, name: &str, todo: &Value) -> Result<Option<PrintData>> {
+    let project = str_at(todo, "/project/path_with_namespace")?;
+    let project_id = todo
+        .pointer("/project/id")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| Error::MissingField("/project/id".to_string()))?;
+    let target_type = str_at(todo, "/target_type")?;
+    let target = todo
+        .pointer("/target")
+        .ok_or_else(|| Error::MissingField("/target".to_string()))?;
+    // MRs are referred to as !1, issues as #1
+    let reference = match (target_type, target.pointer("/iid").and_then(Value::as_u64)) {
+        ("MergeRequest", Some(iid)) => format!("!{iid} "),
+        (_, Some(iid)) => format!("#{iid} "),
+        (_, None) => String::new(),
+    };
+    let title = str_at(target, "/title").unwrap_or_default();
+    let author = str_at(todo, "/author/username")?;
+    let base = PrintData {
+        service: Some("gitlab".to_string()),
+        logo: Some("gitlab".to_string()),
+        event_id: Some(format!("todo:{}", todo["id"])),
+        source: Some(project.to_string()),
+        subtitle: Some(format!("Project: {project}\n{reference}{title}")),
+        qr_codes: link(todo, "Open"),
+        timestamp: DateTime::from_str(str_at(todo, "/created_at")?)?,
+        ..Default::default()
+    };
+
+    let data = match str_at(todo, "/action_name")? {
+        // The to-do's body is the comment it was made for
+        action @ ("mentioned" | "directly_addressed") => {
+            let (body, qr_codes) = markdown(str_at(todo, "/body")?, MarkdownLinks::from_env());
+            let mut message = vec![Span::bold(author), Span::plain(":\n")];
+            message.extend(body);
+            PrintData {
+                priority: Priority::High,
+                title: if action == "mentioned" {
+                    format!("{name}: Mentioned")
+                } else {
+                    format!("{name}: New Comment")
+                },
+                message: Some(message.into()),
+                qr_codes: if qr_codes.is_empty() {
+                    base.qr_codes.clone()
+                } else {
+                    qr_codes
+                },
+                ..base
+            }
+        }
+
+        "review_requested" => {
+            let iid = target["iid"].as_u64().unwrap_or_default();
+            let merge_request = api
+                .get(&format!("/projects/{project_id}/merge_requests/{iid}"))
+                .await?;
+            PrintData {
+                priority: Priority::High,
+                title: format!("{name}: Review Requested"),
+                message: Some(merge_request_summary(&merge_request)?.into()),
+                ..base
+            }
+        }
+
+        "build_failed" => {
+            let pipeline = failed_pipeline(api, project_id, target_type, target).await?;
+            let message = match &pipeline {
+                Some(pipeline) => failed_jobs(api, project_id, pipeline).await?,
+                None => vec![Span::plain("Pipeline failed")],
+            };
+            PrintData {
+                priority: Priority::High,
+                title: format!("{name}: Pipeline Failed"),
+                message: Some(message.into()),
+                qr_codes: pipeline.as_ref().map_or_else(
+                    || base.qr_codes.clone(),
+                    |pipeline| link(pipeline, "Pipeline"),
+                ),
+                ..base
+            }
+        }
+
+        "assigned" => {
+            let description = str_at(target, "/description").unwrap_or_default();
+            let (body, _) = markdown(description, MarkdownLinks::Inline);
+            let mut message = vec![Span::plain("Assigned to you by "), Span::bold(author)];
+            if !body.is_empty() {
+                message.push(Span::plain("\n\n"));
+                message.extend(body);
+            }
+            PrintData {
+                title: format!("{name}: Assigned"),
+                message: Some(message.into()),
+                ..base
+            }
+        }
+
+        other => {
+            debug!("Ignoring GitLab to-do {other}");
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(data))
+}
+
+/// Which pipeline failed on what ref, with its first failed jobs
+async fn failed_jobs(api: &Api, project_id: u64, pipeline: &Value) -> Result<Vec<Span>> {
+    let jobs = api
+        .get(&format!(
+            "/projects/{project_id}/pipelines/{}/jobs?scope[]=failed",
+            pipeline["id"]
+        ))
+        .await?;
+    let mut message = vec![Span::plain(format!(
+        "Pipeline #{} failed on {}\n",
+        pipeline["id"],
+        str_at(pipeline, "/ref")?
+    ))];
+    for job in jobs.as_array().into_iter().flatten().take(MAX_FAILED_JOBS) {
+        message.push(Span::plain(format!(
+            "- {}: {}\n",
+            str_at(job, "/stage")?,
+            str_at(job, "/name")?
+        )));
+    }
+    Ok(message)
+}
+
+/// Latest pipeline of the MR or commit a `build_failed` to-do is for
+async fn failed_pipeline(
+    api: &Api,
+    project_id: u64,
+    target_type: &str,
+    target: &Value,
+) -> Result<Option<Value>> {
+    let path = match target_type {
+        "MergeRequest" => format!(
+            "/projects/{project_id}/merge_requests/{}/pipelines",
+            target["iid"]
+        ),
+        "Commit" => format!(
+            "/projects/{project_id}/pipelines?sha={}&per_page=1",
+            str_at(target, "/id")?
+        ),
+        _ => return Ok(None),
+    };
+    let mut pipelines = api.get(&path).await?;
+    // Latest first
+    Ok(pipelines.get_mut(0).map(Value::take))
+}
+
+/// Who opened an MR, between which branches & how big it is
+fn merge_request_summary(merge_request: &Value) -> Result<Vec<Span>> {
+    Ok(vec![
+        Span::bold(str_at(merge_request, "/author/username")?),
+        Span::plain(" wants your review\n"),
+        Span::underline(str_at(merge_request, "/target_branch")?),
+        Span::plain(" <- "),
+        Span::underline(str_at(merge_request, "/source_branch")?),
+        Span::plain("\n"),
+        // A string, as it's "1000+" for huge MRs
+        Span::plain(format!(
+            "{} files changed",
+            str_at(merge_request, "/changes_count").unwrap_or("?")
+        )),
+    ])
+}
+
+/// QR code linking to a to-do's target or a pipeline on the web
+fn link(value: &Value, caption: &str) -> Vec<QrCode> {
+    ["/target_url", "/web_url"]
+        .into_iter()
+        .find_map(|pointer| value.pointer(pointer).and_then(Value::as_str))
+        .map(|url| QrCode {
+            caption: Some(caption.to_string()),
+            data: url.to_string(),
+        })
+        .into_iter()
+        .collect()
+}
@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local, TimeDelta};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    error::{self, Result},
+    http::{self, SendRetrying},
+    printer::{PrintData, QrCode},
+    schedule, secrets, status,
+};
+
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const EVENTS_URL: &str = "https://www.googleapis.com/calendar/v3/calendars";
+
+/// Width of the `HH:MM-HH:MM ` time column
+const TIME_COLUMN_WIDTH: usize = 12;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct EventList {
+    #[serde(default)]
+    items: Vec<Event>,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    summary: Option<String>,
+    location: Option<String>,
+    start: EventTime,
+    end: EventTime,
+    #[serde(rename = "hangoutLink")]
+    hangout_link: Option<String>,
+    #[serde(rename = "conferenceData")]
+    conference_data: Option<ConferenceData>,
+}
+
+#[derive(Deserialize)]
+struct EventTime {
+    /// `None` for all-day events, which only have a `date`
+    #[serde(rename = "dateTime")]
+    date_time: Option<DateTime<chrono::FixedOffset>>,
+}
+
+#[derive(Deserialize)]
+struct ConferenceData {
+    #[serde(rename = "entryPoints", default)]
+    entry_points: Vec<EntryPoint>,
+}
+
+#[derive(Deserialize)]
+struct EntryPoint {
+    #[serde(rename = "entryPointType")]
+    entry_point_type: String,
+    uri: String,
+}
+
+/// OAuth access token obtained through a long-lived refresh token
+struct Credentials {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+
+    access_token: Option<(String, Instant)>,
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let Ok(refresh_token) = secrets::var("GOOGLE_REFRESH_TOKEN") else {
+        info!("Env `GOOGLE_REFRESH_TOKEN` not set, Google Calendar service disabled");
+        return;
+    };
+    let settings = || -> Result<_> {
+        let credentials = Credentials {
+            client_id: error::env("GOOGLE_CLIENT_ID")?,
+            client_secret: error::env("GOOGLE_CLIENT_SECRET")?,
+            refresh_token,
+            access_token: None,
+        };
+        Ok((
+            credentials,
+            schedule::env_time("AGENDA_PRINT_TIME", Some("07:00"))?,
+        ))
+    };
+    let (mut credentials, print_time) = match settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            info!("{e}, Google Calendar service disabled");
+            return;
+        }
+    };
+    let calendar_id = secrets::var("GOOGLE_CALENDAR_ID").unwrap_or_else(|_| "primary".to_string());
+
+    let http_client = http::client();
+
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(schedule::duration_until(print_time)) => {}
+        }
+
+        let access_token = match credentials.access_token(&http_client).await {
+            Ok(t) => t,
+            Err(e) => {
+                error!("Unable to refresh Google access token: {e}");
+                continue;
+            }
+        };
+
+        let events = match get_todays_events(&http_client, &access_token, &calendar_id).await {
+            Ok(e) => {
+                status::service_ok("google_calendar");
+                e
+            }
+            Err(e) => {
+                error!("Unable to fetch today's events: {e}");
+                continue;
+            }
+        };
+
+        info!("Printing agenda with {} events", events.len());
+        if sender.send(agenda_print_data(&events)).await.is_err() {
+            debug!("Print queue closed! Stopping service...");
+            return;
+        }
+    }
+}
+
+impl Credentials {
+    /// Returns the cached access token, refreshing it if it's (about to be) expired
+    async fn access_token(&mut self, client: &Client) -> Result<String> {
+        if let Some((token, expires_at)) = &self.access_token {
+            if *expires_at > Instant::now() {
+                return Ok(token.clone());
+            }
+        }
+
+        debug!("Refreshing Google access token");
+        let res = client
+            .post(TOKEN_URL)
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("refresh_token", self.refresh_token.as_str()),
+                ("grant_type", "refresh_token"),
+            ])
+            .send_retrying()
+            .await?
+            .error_for_status()?
+            .json::<TokenResponse>()
+            .await?;
+
+        // Refresh a minute early so a token never expires mid-request
+        let expires_at = Instant::now() + Duration::from_secs(res.expires_in.saturating_sub(60));
+        self.access_token = Some((res.access_token.clone(), expires_at));
+
+        Ok(res.access_token)
+    }
+}
+
+impl Event {
+    /// Video call link; either Meet's `hangoutLink` or a conference `video` entry point
+    fn video_link(&self) -> Option<&str> {
+        self.hangout_link.as_deref().or_else(|| {
+            self.conference_data.as_ref().and_then(|c| {
+                c.entry_points
+                    .iter()
+                    .find(|e| e.entry_point_type == "video")
+                    .map(|e| e.uri.as_str())
+            })
+        })
+    }
+
+    fn time_range(&self) -> String {
+        match (self.start.date_time, self.end.date_time) {
+            (Some(start), Some(end)) => format!(
+                "{}-{}",
+                start.with_timezone(&Local).format("%H:%M"),
+                end.with_timezone(&Local).format("%H:%M")
+            ),
+            _ => "All day".to_string(),
+        }
+    }
+}
+
+fn agenda_print_data(events: &[Event]) -> PrintData {
+    let today = Local::now();
+    if events.is_empty() {
+        return PrintData {
+            service: Some("google_calendar".to_string()),
+            logo: Some("google_calendar".to_string()),
+            title: "Today's Agenda".to_string(),
+            subtitle: Some(today.format("%A, %B %e").to_string()),
+            message: Some("Nothing scheduled today!".to_string().into()),
+            timestamp: today,
+            ..Default::default()
+        };
+    }
+
+    let padding = " ".repeat(TIME_COLUMN_WIDTH);
+    let mut lines = Vec::new();
+    let mut qr_codes = Vec::new();
+    for event in events {
+        let summary = event.summary.as_deref().unwrap_or("(No title)");
+        lines.push(format!(
+            "{:<width$}{summary}",
+            event.time_range(),
+            width = TIME_COLUMN_WIDTH
+        ));
+        if let Some(location) = &event.location {
+            lines.push(format!("{padding}@ {location}"));
+        }
+        if let Some(link) = event.video_link() {
+            lines.push(format!("{padding}Video call: QR #{}", qr_codes.len() + 1));
+            qr_codes.push(QrCode {
+                caption: Some(format!("#{} {summary}", qr_codes.len() + 1)),
+                data: link.to_string(),
+            });
+        }
+    }
+
+    PrintData {
+        service: Some("google_calendar".to_string()),
+        logo: Some("google_calendar".to_string()),
+        title: "Today's Agenda".to_string(),
+        subtitle: Some(format!(
+            "{}\n{} events",
+            today.format("%A, %B %e"),
+            events.len()
+        )),
+        message: Some(lines.join("\n").into()),
+        qr_codes,
+        timestamp: today,
+        ..Default::default()
+    }
+}
+
+#[instrument(skip(client, access_token))]
+async fn get_todays_events(
+    client: &Client,
+    access_token: &str,
+    calendar_id: &str,
+) -> Result<Vec<Event>> {
+    let now = Local::now();
+    // Midnight may be skipped by a DST change, the agenda then starts now
+    let start_of_day = now
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|t| t.and_local_timezone(Local).earliest())
+        .unwrap_or(now);
+    let end_of_day = start_of_day + TimeDelta::days(1);
+
+    let mut url = Url::parse(EVENTS_URL).expect("EVENTS_URL is a valid URL");
+    url.path_segments_mut()
+        .expect("EVENTS_URL has a path")
+        .extend([calendar_id, "events"]);
+
+    let events = client
+        .get(url)
+        .bearer_auth(access_token)
+        .query(&[
+            ("timeMin", start_of_day.to_rfc3339()),
+            ("timeMax", end_of_day.to_rfc3339()),
+            ("singleEvents", "true".to_string()),
+            ("orderBy", "startTime".to_string()),
+        ])
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<EventList>()
+        .await?;
+
+    Ok(events.items)
+}
@@ -0,0 +1,76 @@
+use std::{fmt::Write, time::Duration};
+
+use chrono::{DateTime, Local};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    error,
+    printer::{PrintData, Priority},
+    status::{self, ServiceState},
+};
+
+/// Prints a tiny receipt every `HEARTBEAT_INTERVAL` seconds, so a broken pipeline shows as
+/// missing heartbeats rather than going unnoticed until something important doesn't print
+///
+/// Printers are write-only on every transport, so the receipt goes through the whole pipeline
+/// instead of querying the printer's status.
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let interval = match error::env_parse("HEARTBEAT_INTERVAL") {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(e) => {
+            info!("{e}, heartbeat service disabled");
+            return;
+        }
+    };
+
+    let mut last_sent: Option<DateTime<Local>> = None;
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(interval) => {}
+        }
+
+        let status = status::get();
+        // Nothing has printed since the last heartbeat was queued, so it's still stuck
+        if last_sent.is_some_and(|sent| status.last_print.is_none_or(|printed| printed < sent)) {
+            warn!("Previous heartbeat hasn't printed yet, skipping this one");
+            continue;
+        }
+
+        let mut subtitle = format!("Queue: {}", status.queue_depth);
+        let crashed = status
+            .services
+            .iter()
+            .filter(|(_, service)| service.state == ServiceState::Crashed)
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>();
+        if !crashed.is_empty() {
+            let _ = write!(subtitle, "\nCrashed: {}", crashed.join(", "));
+        }
+
+        let now = Local::now();
+        let data = PrintData {
+            service: Some("heartbeat".to_string()),
+            logo: Some("heartbeat".to_string()),
+            priority: Priority::Low,
+            title: "Heartbeat".to_string(),
+            subtitle: Some(subtitle),
+            timestamp: now,
+            ..Default::default()
+        };
+        if sender.send(data).await.is_err() {
+            debug!("Print queue closed! Stopping service...");
+            return;
+        }
+        last_sent = Some(now);
+        status::service_ok("heartbeat");
+    }
+}
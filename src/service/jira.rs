@@ -0,0 +1,285 @@
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Local};
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace};
+
+use crate::{
+    error::{self, str_at, Error, Result},
+    http::{self, SendRetrying},
+    printer::{PrintData, Priority, QrCode, Span},
+    retry::Backoff,
+    state, status,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_mins(2);
+
+/// Wait before polling again after a failed poll, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
+/// Issues assigned to you or mentioning you in a comment
+const JQL: &str = "(assignee = currentUser() OR comment ~ currentUser())";
+
+/// Comments of an updated issue looked through for new ones
+const MAX_COMMENTS: usize = 10;
+
+/// Prints updates of Jira Cloud issues assigned to you & comments mentioning you
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
+    let api = match Api::from_env() {
+        Ok(api) => api,
+        Err(e) => {
+            info!("{e}, Jira service disabled");
+            return;
+        }
+    };
+
+    // Fetched on the first poll, to skip your own changes & tell mentions apart
+    let mut account_id = None;
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        let poll_interval = match poll(&api, &sender, &mut account_id).await {
+            Ok(()) => {
+                status::service_ok("jira");
+                backoff.reset();
+                POLL_INTERVAL
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("Unable to fetch Jira issues, retrying in {delay:?}: {e}");
+                delay
+            }
+            Err(e) => {
+                error!("Stopping Jira service: {e}");
+                break;
+            }
+        };
+
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(poll_interval) => {}
+        }
+    }
+}
+
+/// Jira Cloud site's REST API, authenticated with an API token
+struct Api {
+    http_client: Client,
+    /// e.g. `https://example.atlassian.net`
+    url: String,
+    email: String,
+    token: String,
+}
+
+impl Api {
+    /// Reads `JIRA_URL`, `JIRA_EMAIL` & `JIRA_API_TOKEN`
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            http_client: http::client(),
+            url: error::env("JIRA_URL")?.trim_end_matches('/').to_string(),
+            email: error::env("JIRA_EMAIL")?,
+            token: error::env("JIRA_API_TOKEN")?,
+        })
+    }
+
+    /// JSON at a path of the REST API, e.g. `/myself`
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Value> {
+        trace!("Fetching {path}");
+        let res = self
+            .http_client
+            .get(format!("{}/rest/api/2{path}", self.url))
+            .basic_auth(&self.email, Some(&self.token))
+            .query(query)
+            .send_retrying()
+            .await?;
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::Unauthorized);
+        }
+        Ok(res.error_for_status()?.json().await?)
+    }
+}
+
+/// Prints what changed on issues since the last poll; Nothing on the first one
+async fn poll(
+    api: &Api,
+    sender: &Sender<PrintData>,
+    account_id: &mut Option<String>,
+) -> Result<()> {
+    if account_id.is_none() {
+        let myself = api.get("/myself", &[]).await?;
+        *account_id = Some(str_at(&myself, "/accountId")?.to_string());
+    }
+    let account_id = account_id.as_deref().unwrap_or_default();
+
+    let polled_at = Local::now();
+    let Some(since) =
+        state::get("jira", "last_poll").and_then(|since| DateTime::parse_from_rfc3339(&since).ok())
+    else {
+        state::set("jira", "last_poll", Some(&polled_at.to_rfc3339()));
+        return Ok(());
+    };
+    // JQL only takes minutes relative to now, changes are filtered precisely afterwards
+    let minutes = (polled_at.fixed_offset() - since).num_minutes() + 1;
+    let jql = format!("{JQL} AND updated >= -{minutes}m ORDER BY updated ASC");
+    let issues = api
+        .get(
+            "/search/jql",
+            &[
+                ("jql", &jql),
+                ("fields", "summary,status,updated"),
+                ("expand", "changelog"),
+            ],
+        )
+        .await?;
+
+    for issue in issues
+        .pointer("/issues")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let key = str_at(issue, "/key")?;
+        let comments = api
+            .get(
+                &format!("/issue/{key}/comment"),
+                &[
+                    ("orderBy", "-created"),
+                    ("maxResults", &MAX_COMMENTS.to_string()),
+                ],
+            )
+            .await?;
+        let data = match issue_print_data(&api.url, account_id, since, issue, &comments) {
+            Ok(Some(data)) => data,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Unable to print Jira issue {key}: {e}");
+                continue;
+            }
+        };
+        info!("Jira issue {key} was updated");
+        sender.send(data).await?;
+    }
+
+    state::set("jira", "last_poll", Some(&polled_at.to_rfc3339()));
+    Ok(())
+}
+
+/// Receipt of what others changed on an issue since `since`: status transitions, assigning it to
+/// you & new comments; None if there's nothing new, e.g. only your own changes
+fn issue_print_data(
+    url: &str,
+    account_id: &str,
+    since: DateTime<FixedOffset>,
+    issue: &Value,
+    comments: &Value,
+) -> Result<Option<PrintData>> {
+    let key = str_at(issue, "/key")?;
+    let summary = str_at(issue, "/fields/summary")?;
+    let is_new = |change: &Value| -> Result<bool> {
+        let created = timestamp(str_at(change, "/created")?)?;
+        let by_others = str_at(change, "/author/accountId").ok() != Some(account_id);
+        Ok(created > since && by_others)
+    };
+
+    let mut message = Vec::new();
+    let mut assigned = false;
+    let histories = issue
+        .pointer("/changelog/histories")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for history in histories {
+        if !is_new(history)? {
+            continue;
+        }
+        for item in history
+            .pointer("/items")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let to = str_at(item, "/toString").unwrap_or_default();
+            match str_at(item, "/field")? {
+                "status" => message.extend([
+                    Span::plain(format!("{} -> ", str_at(item, "/fromString")?)),
+                    Span::invert(format!(" {to} ")),
+                    Span::plain("\n"),
+                ]),
+                "assignee" if str_at(item, "/to").ok() == Some(account_id) => assigned = true,
+                _ => {}
+            }
+        }
+    }
+
+    let mut mentioned = false;
+    let mut new_comments = comments
+        .pointer("/comments")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|comment| is_new(comment).unwrap_or_default())
+        .collect::<Vec<_>>();
+    // Oldest first
+    new_comments.reverse();
+    let mention = format!("[~accountid:{account_id}]");
+    for comment in new_comments {
+        let body = str_at(comment, "/body")?;
+        mentioned |= body.contains(&mention);
+        message.extend([
+            Span::plain("\n"),
+            Span::bold(str_at(comment, "/author/displayName")?),
+            Span::plain(format!(":\n{}\n", body.replace(&mention, "@you"))),
+        ]);
+    }
+
+    if message.is_empty() && !assigned {
+        return Ok(None);
+    }
+    let (priority, title) = if mentioned {
+        (Priority::High, "Jira: Mentioned")
+    } else if assigned {
+        (Priority::High, "Jira: Assigned")
+    } else {
+        (Priority::Normal, "Jira: Issue Updated")
+    };
+    let updated = str_at(issue, "/fields/updated")?;
+    Ok(Some(PrintData {
+        service: Some("jira".to_string()),
+        logo: Some("jira".to_string()),
+        event_id: Some(format!("{key}:{updated}")),
+        source: key.split_once('-').map(|(project, _)| project.to_string()),
+        priority,
+        title: title.to_string(),
+        subtitle: Some(format!(
+            "{key}: {summary}\nStatus: {}",
+            str_at(issue, "/fields/status/name")?
+        )),
+        message: (!message.is_empty()).then(|| message.into()),
+        qr_codes: vec![QrCode {
+            caption: Some("Open".to_string()),
+            data: format!("{url}/browse/{key}"),
+        }],
+        timestamp: timestamp(updated)?.with_timezone(&Local),
+        ..Default::default()
+    }))
+}
+
+/// Jira's timestamps have no colon in their offset, e.g. `2024-01-01T12:00:00.000+0000`
+fn timestamp(timestamp: &str) -> Result<DateTime<FixedOffset>> {
+    Ok(DateTime::parse_from_str(
+        timestamp,
+        "%Y-%m-%dT%H:%M:%S%.f%z",
+    )?)
+}
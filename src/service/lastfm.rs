@@ -0,0 +1,208 @@
+use chrono::{Datelike, Local, NaiveTime, TimeDelta, Weekday};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    error::{self, Result},
+    http::{self, SendRetrying},
+    printer::PrintData,
+    schedule, secrets, status,
+};
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+const TOP_ENTRIES: usize = 5;
+/// Width of the block character bar charts
+const BAR_WIDTH: usize = 20;
+
+#[derive(Deserialize)]
+struct WeeklyArtistChart {
+    #[serde(rename = "weeklyartistchart")]
+    chart: ArtistChart,
+}
+
+#[derive(Deserialize)]
+struct ArtistChart {
+    artist: Vec<ChartEntry>,
+}
+
+#[derive(Deserialize)]
+struct WeeklyTrackChart {
+    #[serde(rename = "weeklytrackchart")]
+    chart: TrackChart,
+}
+
+#[derive(Deserialize)]
+struct TrackChart {
+    track: Vec<ChartEntry>,
+}
+
+#[derive(Deserialize)]
+struct ChartEntry {
+    name: String,
+    artist: Option<ChartArtist>,
+    /// Last.fm returns counts as strings
+    playcount: String,
+}
+
+#[derive(Deserialize)]
+struct ChartArtist {
+    #[serde(rename = "#text")]
+    name: String,
+}
+
+impl ChartEntry {
+    fn plays(&self) -> u32 {
+        self.playcount.parse().unwrap_or(0)
+    }
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let Ok(api_key) = secrets::var("LASTFM_API_KEY") else {
+        info!("Env `LASTFM_API_KEY` not set, Last.fm service disabled");
+        return;
+    };
+    let settings = || -> Result<_> {
+        Ok((
+            error::env("LASTFM_USER")?,
+            schedule::env_time("LASTFM_PRINT_TIME", Some("21:00"))?,
+        ))
+    };
+    let (username, print_time) = match settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            info!("{e}, Last.fm service disabled");
+            return;
+        }
+    };
+
+    let http_client = http::client();
+
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(duration_until_sunday(print_time)) => {}
+        }
+
+        let summary = match get_weekly_summary(&http_client, &api_key, &username).await {
+            Ok(s) => {
+                status::service_ok("lastfm");
+                s
+            }
+            Err(e) => {
+                error!("Unable to fetch Last.fm weekly charts: {e}");
+                continue;
+            }
+        };
+
+        info!("Printing weekly listening summary");
+        if sender.send(summary).await.is_err() {
+            debug!("Print queue closed! Stopping service...");
+            return;
+        }
+    }
+}
+
+/// Time left until `time` on the upcoming Sunday (or today, if it's Sunday and not past `time`)
+fn duration_until_sunday(time: NaiveTime) -> std::time::Duration {
+    let until_time = schedule::duration_until(time);
+    let next_run = Local::now() + TimeDelta::from_std(until_time).unwrap_or_default();
+    let days_left =
+        (7 + Weekday::Sun.num_days_from_monday() - next_run.weekday().num_days_from_monday()) % 7;
+
+    until_time + std::time::Duration::from_hours(24 * u64::from(days_left))
+}
+
+/// Renders `value` as a bar of block characters relative to `max`
+///
+/// Only uses full & half blocks, which are part of the printer's default code page
+fn bar(value: u32, max: u32) -> String {
+    let halves = (u64::from(value) * BAR_WIDTH as u64 * 2)
+        .checked_div(u64::from(max))
+        .unwrap_or(0);
+    let full = usize::try_from(halves / 2).unwrap_or(BAR_WIDTH);
+    let half = if halves % 2 == 1 { "▌" } else { "" };
+
+    format!("{}{half}", "█".repeat(full))
+}
+
+fn chart_section(heading: &str, entries: &[ChartEntry]) -> String {
+    let max = entries.first().map_or(0, ChartEntry::plays);
+    let lines = entries
+        .iter()
+        .take(TOP_ENTRIES)
+        .enumerate()
+        .map(|(i, e)| {
+            let name = e
+                .artist
+                .as_ref()
+                .map_or_else(|| e.name.clone(), |a| format!("{} - {}", a.name, e.name));
+            format!(
+                "{}. {name}\n   {} {}",
+                i + 1,
+                bar(e.plays(), max),
+                e.plays()
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!("{heading}\n{}", lines.join("\n"))
+}
+
+#[instrument(skip(client, api_key))]
+async fn get_weekly_summary(client: &Client, api_key: &str, username: &str) -> Result<PrintData> {
+    let request = |method: &'static str| {
+        client.get(API_URL).query(&[
+            ("method", method),
+            ("user", username),
+            ("api_key", api_key),
+            ("format", "json"),
+        ])
+    };
+
+    let artists = request("user.getweeklyartistchart")
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<WeeklyArtistChart>()
+        .await?
+        .chart
+        .artist;
+    let tracks = request("user.getweeklytrackchart")
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<WeeklyTrackChart>()
+        .await?
+        .chart
+        .track;
+
+    // Every scrobble belongs to exactly one track, so the track chart sums up to the total
+    let total_scrobbles: u32 = tracks.iter().map(ChartEntry::plays).sum();
+
+    Ok(PrintData {
+        service: Some("lastfm".to_string()),
+        logo: Some("lastfm".to_string()),
+        title: "Last.fm: Your Week".to_string(),
+        subtitle: Some(format!("{username}\n{total_scrobbles} scrobbles this week")),
+        message: Some(
+            format!(
+                "{}\n\n{}",
+                chart_section("Top Artists", &artists),
+                chart_section("Top Tracks", &tracks)
+            )
+            .into(),
+        ),
+        timestamp: Local::now(),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,209 @@
+use std::{str::FromStr, time::Duration};
+
+use chrono::{DateTime, Local};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::{
+    error::{self, str_at, Error, Result},
+    http::{self, SendRetrying},
+    printer::{markdown, MarkdownLinks, PrintData, Priority, QrCode, Span},
+    retry::Backoff,
+    secrets, state, status,
+};
+
+const API_URL: &str = "https://api.linear.app/graphql";
+
+const POLL_INTERVAL: Duration = Duration::from_mins(1);
+
+/// Wait before polling again after a failed poll, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
+/// Latest notifications of the inbox, with the issue & comment they're about
+const QUERY: &str = "
+    query {
+        notifications(first: 50, orderBy: createdAt) {
+            nodes {
+                id
+                type
+                createdAt
+                actor { name }
+                ... on IssueNotification {
+                    issue { identifier title url team { key } }
+                    comment { body url user { name } }
+                }
+            }
+        }
+    }
+";
+
+/// Prints new issue assignments, comments & mentions from your Linear inbox, of the team in
+/// `LINEAR_TEAM` only if set
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
+    let api_key = match error::env("LINEAR_API_KEY") {
+        Ok(api_key) => api_key,
+        Err(e) => {
+            info!("{e}, Linear service disabled");
+            return;
+        }
+    };
+    // By key, e.g. `ENG`
+    let team = secrets::var("LINEAR_TEAM").ok();
+    let http_client = http::client();
+
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        let poll_interval = match poll(&http_client, &api_key, team.as_deref(), &sender).await {
+            Ok(()) => {
+                status::service_ok("linear");
+                backoff.reset();
+                POLL_INTERVAL
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("Unable to fetch Linear notifications, retrying in {delay:?}: {e}");
+                delay
+            }
+            Err(e) => {
+                error!("Stopping Linear service: {e}");
+                break;
+            }
+        };
+
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(poll_interval) => {}
+        }
+    }
+}
+
+/// Prints notifications newer than the last one seen; Nothing on the first poll
+async fn poll(
+    http_client: &Client,
+    api_key: &str,
+    team: Option<&str>,
+    sender: &Sender<PrintData>,
+) -> Result<()> {
+    let res = http_client
+        .post(API_URL)
+        // Personal API keys go as is, without `Bearer`
+        .header("Authorization", api_key)
+        .json(&json!({ "query": QUERY }))
+        .send_retrying()
+        .await?;
+    if res.status() == StatusCode::UNAUTHORIZED {
+        return Err(Error::Unauthorized);
+    }
+    let res: Value = res.error_for_status()?.json().await?;
+    // Queries fail with 200 OK, naming what went wrong
+    if let Some(message) = res.pointer("/errors/0/message").and_then(Value::as_str) {
+        warn!("Linear GraphQL query failed: {message}");
+    }
+    let notifs = res
+        .pointer("/data/notifications/nodes")
+        .and_then(Value::as_array)
+        .ok_or_else(|| Error::MissingField("/data/notifications/nodes".to_string()))?;
+    let created_at = |notif: &Value| {
+        str_at(notif, "/createdAt")
+            .ok()
+            .and_then(|at| DateTime::<Local>::from_str(at).ok())
+    };
+
+    let last_seen = state::get("linear", "last_notification")
+        .and_then(|at| DateTime::<Local>::from_str(&at).ok());
+    let latest = notifs.iter().filter_map(created_at).max();
+    if let Some(last_seen) = last_seen {
+        let mut new = notifs
+            .iter()
+            .filter(|&notif| created_at(notif) > Some(last_seen))
+            .filter(|notif| team.is_none_or(|team| notif["issue"]["team"]["key"] == team))
+            .collect::<Vec<_>>();
+        // Oldest first
+        new.sort_by_key(|notif| created_at(notif));
+        for notif in new {
+            match notif_print_data(notif) {
+                Ok(Some(data)) => sender.send(data).await?,
+                Ok(None) => {}
+                Err(e) => error!("Unable to print Linear notification: {e}\n{notif}"),
+            }
+        }
+    }
+    if let Some(latest) = latest.filter(|&latest| Some(latest) > last_seen) {
+        state::set("linear", "last_notification", Some(&latest.to_rfc3339()));
+    }
+
+    Ok(())
+}
+
+/// Receipt of an issue notification; None for the kinds that aren't printed, e.g. status changes
+fn notif_print_data(notif: &Value) -> Result<Option<PrintData>> {
+    let (title, priority) = match str_at(notif, "/type")? {
+        "issueAssignedToYou" => ("Linear: Assigned", Priority::Normal),
+        "issueNewComment" => ("Linear: New Comment", Priority::Normal),
+        "issueMention" | "issueCommentMention" => ("Linear: Mentioned", Priority::High),
+        other => {
+            debug!("Ignoring Linear {other} notification");
+            return Ok(None);
+        }
+    };
+    let issue = notif
+        .pointer("/issue")
+        .filter(|issue| !issue.is_null())
+        .ok_or_else(|| Error::MissingField("/issue".to_string()))?;
+    let identifier = str_at(issue, "/identifier")?;
+    // Made by automations if there's no actor
+    let actor = str_at(notif, "/actor/name").unwrap_or("Linear");
+
+    let comment = notif
+        .pointer("/comment")
+        .filter(|comment| !comment.is_null());
+    let (message, qr_codes) = match comment {
+        Some(comment) => {
+            let (body, qr_codes) = markdown(str_at(comment, "/body")?, MarkdownLinks::from_env());
+            let author = str_at(comment, "/user/name").unwrap_or(actor);
+            let mut message = vec![Span::bold(author), Span::plain(":\n")];
+            message.extend(body);
+            (message, qr_codes)
+        }
+        None => (vec![Span::plain("By "), Span::bold(actor)], Vec::new()),
+    };
+    let qr_codes = if qr_codes.is_empty() {
+        let url = comment.map_or_else(|| str_at(issue, "/url"), |c| str_at(c, "/url"))?;
+        vec![QrCode {
+            caption: Some("Open".to_string()),
+            data: url.to_string(),
+        }]
+    } else {
+        qr_codes
+    };
+
+    Ok(Some(PrintData {
+        service: Some("linear".to_string()),
+        logo: Some("linear".to_string()),
+        event_id: Some(str_at(notif, "/id")?.to_string()),
+        source: issue
+            .pointer("/team/key")
+            .and_then(Value::as_str)
+            .map(ToString::to_string),
+        priority,
+        title: title.to_string(),
+        subtitle: Some(format!("{identifier}: {}", str_at(issue, "/title")?)),
+        message: Some(message.into()),
+        qr_codes,
+        timestamp: DateTime::from_str(str_at(notif, "/createdAt")?)?,
+        ..Default::default()
+    }))
+}
@@ -1,6 +1,250 @@
+use std::{future::Future, time::Duration};
+
+use chrono::Local;
+use tokio::{sync::mpsc::Sender, task::JoinError, time::Instant};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{error, info, warn};
+
+use crate::{
+    error::{self, Result},
+    printer::{PrintData, Priority},
+    secrets,
+    status::{self, ServiceState},
+};
+
+pub mod arxiv;
+pub mod bandcamp;
 pub mod bsky;
+pub mod caldav;
+pub mod carddav;
+pub mod chess;
 pub mod email;
+pub mod football;
+pub mod gitea;
 pub mod github;
+pub mod github_app;
+pub mod github_client;
+pub mod github_sponsors;
+pub mod gitlab;
+pub mod google_calendar;
+pub mod heartbeat;
+pub mod jira;
+pub mod lastfm;
+pub mod linear;
+pub mod now_playing;
+pub mod reminders;
+pub mod strava;
+pub mod summary;
+pub mod todoist;
 pub mod twitch;
+pub mod twitch_chat;
 
 pub trait NotificationService {}
+
+/// One of the accounts a service runs for, e.g. a work & a personal GitHub account
+///
+/// The unlabelled account reads the service's env vars as is, e.g. `GITHUB_PAT`. Labelled ones,
+/// listed like `GITHUB_ACCOUNTS=work,personal`, read them suffixed with their label, e.g.
+/// `GITHUB_PAT_WORK`.
+#[derive(Debug, Clone, Default)]
+pub struct Account {
+    pub label: Option<String>,
+}
+
+impl Account {
+    /// The unlabelled account, followed by the labelled ones listed in `accounts_var`
+    pub fn all(accounts_var: &str) -> Vec<Self> {
+        let labels = secrets::var(accounts_var).unwrap_or_default();
+        std::iter::once(Self::default())
+            .chain(
+                labels
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|label| !label.is_empty())
+                    .map(|label| Self {
+                        label: Some(label.to_string()),
+                    }),
+            )
+            .collect()
+    }
+
+    /// This account's version of an env var
+    pub fn var(&self, var: &str) -> String {
+        self.label
+            .as_ref()
+            .map_or_else(|| var.to_string(), |label| account_var(var, label))
+    }
+
+    /// Reads an env var this account can't do without, see [`error::env`]
+    pub fn env(&self, var: &str) -> Result<String> {
+        error::env(&self.var(var))
+    }
+
+    /// Service name as printed on receipts, e.g. `GitHub (work)`
+    pub fn name(&self, service: &str) -> String {
+        self.label.as_ref().map_or_else(
+            || service.to_string(),
+            |label| format!("{service} ({label})"),
+        )
+    }
+
+    /// Name the account's cursors & tokens are kept under in the state, e.g. `github.work`
+    pub fn state_key(&self, service: &str) -> String {
+        self.label
+            .as_ref()
+            .map_or_else(|| service.to_string(), |label| format!("{service}.{label}"))
+    }
+}
+
+/// Env var of a labelled account, e.g. `GITHUB_PAT_WORK` for `GITHUB_PAT` & `work`
+pub fn account_var(var: &str, label: &str) -> String {
+    let suffix = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{var}_{suffix}")
+}
+
+/// Longest wait before restarting a service that keeps crashing
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Services that crash after running this long are restarted without waiting longer than at
+/// first, as they weren't crash looping
+const STABLE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Runs services, restarting them with exponential backoff whenever they panic
+///
+/// Services returning on their own, e.g. when left unconfigured, stay stopped.
+#[derive(Clone)]
+pub struct Supervisor {
+    cancel: CancellationToken,
+    sender: Sender<PrintData>,
+    crash_receipts: bool,
+    /// Names of the services to run; All of them if None
+    enabled: Option<Vec<String>>,
+    /// Names of the services not to run, even if enabled
+    disabled: Vec<String>,
+}
+
+impl Supervisor {
+    pub const fn new(cancel: CancellationToken, sender: Sender<PrintData>) -> Self {
+        Self {
+            cancel,
+            sender,
+            crash_receipts: false,
+            enabled: None,
+            disabled: Vec::new(),
+        }
+    }
+
+    /// Prints a receipt every time a service crashes
+    pub const fn with_crash_receipts(mut self, crash_receipts: bool) -> Self {
+        self.crash_receipts = crash_receipts;
+        self
+    }
+
+    /// Only runs the services named, skipping the rest
+    pub fn with_enabled(mut self, enabled: Option<Vec<String>>) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Skips the services named
+    pub fn with_disabled(mut self, disabled: Vec<String>) -> Self {
+        self.disabled = disabled;
+        self
+    }
+
+    /// Whether the service is to be run, per [`Supervisor::with_enabled`] &
+    /// [`Supervisor::with_disabled`]
+    fn is_enabled(&self, name: &str) -> bool {
+        self.enabled
+            .as_ref()
+            .is_none_or(|enabled| enabled.iter().any(|service| service == name))
+            && !self.disabled.iter().any(|service| service == name)
+    }
+
+    /// Spawns a service on the tracker, started by `start` with its own cancel token & sender
+    /// handle
+    pub fn spawn<F, Fut>(&self, task_tracker: &TaskTracker, name: &'static str, start: F)
+    where
+        F: Fn(CancellationToken, Sender<PrintData>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if !self.is_enabled(name) {
+            info!("Service {name} disabled");
+            return;
+        }
+        task_tracker.spawn(self.clone().supervise(name, start));
+    }
+
+    async fn supervise<F, Fut>(self, name: &'static str, start: F)
+    where
+        F: Fn(CancellationToken, Sender<PrintData>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut delay = Duration::from_secs(1);
+        loop {
+            let started = Instant::now();
+            status::set_service_state(name, ServiceState::Running);
+            let Err(e) = tokio::spawn(start(self.cancel.clone(), self.sender.clone())).await else {
+                status::set_service_state(name, ServiceState::Stopped);
+                return;
+            };
+            if self.cancel.is_cancelled() {
+                status::set_service_state(name, ServiceState::Stopped);
+                return;
+            }
+            status::set_service_state(name, ServiceState::Crashed);
+
+            if started.elapsed() >= STABLE_AFTER {
+                delay = Duration::from_secs(1);
+            }
+            let reason = panic_message(e);
+            error!("Service {name} crashed, restarting in {delay:?}: {reason}");
+            if self.crash_receipts {
+                self.print_crash(name, &reason, delay).await;
+            }
+
+            tokio::select! {
+                () = self.cancel.cancelled() => return,
+                () = tokio::time::sleep(delay) => {}
+            }
+            info!("Restarting service {name}");
+            delay = (delay * 2).min(MAX_RESTART_DELAY);
+        }
+    }
+
+    async fn print_crash(&self, name: &str, reason: &str, delay: Duration) {
+        let data = PrintData {
+            service: Some(name.to_string()),
+            logo: Some(name.to_string()),
+            priority: Priority::High,
+            title: format!("Service {name} crashed"),
+            subtitle: Some(format!("Restarting in {}s", delay.as_secs())),
+            message: Some(reason.to_string().into()),
+            timestamp: Local::now(),
+            ..Default::default()
+        };
+        if let Err(e) = self.sender.send(data).await {
+            warn!("Unable to queue crash receipt of {name}: {e}");
+        }
+    }
+}
+
+fn panic_message(e: JoinError) -> String {
+    match e.try_into_panic() {
+        Ok(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Unknown panic".to_string()),
+        Err(e) => e.to_string(),
+    }
+}
@@ -0,0 +1,396 @@
+use std::{io::ErrorKind, time::Duration};
+
+use chrono::Local;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::mpsc::Sender,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace};
+
+use crate::{
+    error::{self, Error, Result},
+    http::{self, SendRetrying},
+    printer::PrintData,
+    raster::{Raster, MAX_IMAGE_WIDTH},
+    secrets, status,
+};
+
+const SPOTIFY_TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const SPOTIFY_CURRENTLY_PLAYING_URL: &str =
+    "https://api.spotify.com/v1/me/player/currently-playing";
+
+const GPIO_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const GPIO_DEBOUNCE: Duration = Duration::from_secs(1);
+/// Width of the progress bar, in characters
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+struct NowPlaying {
+    source: &'static str,
+    title: String,
+    artist: String,
+    album: Option<String>,
+    elapsed: Duration,
+    duration: Option<Duration>,
+    album_art: Option<Vec<u8>>,
+}
+
+#[derive(Deserialize)]
+struct SpotifyToken {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct SpotifyCurrentlyPlaying {
+    progress_ms: Option<u64>,
+    item: Option<SpotifyTrack>,
+}
+
+#[derive(Deserialize)]
+struct SpotifyTrack {
+    name: String,
+    duration_ms: u64,
+    artists: Vec<SpotifyArtist>,
+    album: SpotifyAlbum,
+}
+
+#[derive(Deserialize)]
+struct SpotifyArtist {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct SpotifyAlbum {
+    name: String,
+    images: Vec<SpotifyImage>,
+}
+
+#[derive(Deserialize)]
+struct SpotifyImage {
+    url: String,
+}
+
+/// Watches the GPIO button configured through `NOW_PLAYING_GPIO` and prints the current track
+/// whenever it's pressed
+///
+/// The same receipt can be triggered through `POST /now-playing` on the HTTP server, with the
+/// `ADMIN_TOKEN`.
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
+    let Ok(gpio_path) = secrets::var("NOW_PLAYING_GPIO") else {
+        info!("Env `NOW_PLAYING_GPIO` not set, now playing button disabled");
+        return;
+    };
+    // Buttons are usually wired active-low, with a pull-up resistor
+    let pressed_value = if secrets::var("NOW_PLAYING_GPIO_ACTIVE_HIGH").is_ok_and(|v| v == "true") {
+        "1"
+    } else {
+        "0"
+    };
+
+    let mut was_pressed = false;
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(GPIO_POLL_INTERVAL) => {}
+        }
+
+        // sysfs GPIO value files contain `0\n` or `1\n`
+        let is_pressed = match tokio::fs::read_to_string(&gpio_path).await {
+            Ok(value) => {
+                status::service_ok("now_playing");
+                value.trim() == pressed_value
+            }
+            Err(e) => {
+                error!("Unable to read GPIO value at {gpio_path}: {e}");
+                tokio::time::sleep(GPIO_DEBOUNCE).await;
+                continue;
+            }
+        };
+
+        if is_pressed && !was_pressed {
+            info!("Now playing button pressed");
+            if let Err(e) = print_now_playing(&sender).await {
+                error!("Unable to print now playing: {e}");
+            }
+            tokio::time::sleep(GPIO_DEBOUNCE).await;
+        }
+        was_pressed = is_pressed;
+    }
+}
+
+/// Prints the track currently playing on Spotify (if configured) or MPD
+///
+/// Returns `false` if nothing is playing.
+#[instrument(skip(sender))]
+pub async fn print_now_playing(sender: &Sender<PrintData>) -> Result<bool> {
+    let http_client = http::client();
+
+    let now_playing = if let Ok(refresh_token) = secrets::var("SPOTIFY_REFRESH_TOKEN") {
+        get_spotify_now_playing(&http_client, &refresh_token).await?
+    } else if let Ok(addr) = secrets::var("MPD_ADDR") {
+        get_mpd_now_playing(&addr).await?
+    } else {
+        // MPD is the fallback for when Spotify isn't set up
+        return Err(Error::MissingEnv("MPD_ADDR".to_string()));
+    };
+
+    let Some(now_playing) = now_playing else {
+        debug!("Nothing is playing");
+        return Ok(false);
+    };
+
+    sender.send(now_playing.into_print_data()).await?;
+    Ok(true)
+}
+
+impl NowPlaying {
+    fn into_print_data(self) -> PrintData {
+        let image = self.album_art.and_then(|bytes| {
+            Raster::from_bytes(&bytes, MAX_IMAGE_WIDTH)
+                .inspect_err(|e| error!("Unable to decode album art: {e}"))
+                .ok()
+        });
+
+        let mut message = format!("{}\n{}", self.title, self.artist);
+        if let Some(album) = &self.album {
+            message = format!("{message}\n{album}");
+        }
+        message = format!("{message}\n\n{}", progress(self.elapsed, self.duration));
+
+        PrintData {
+            service: Some("now_playing".to_string()),
+            logo: Some("now_playing".to_string()),
+            title: format!("{}: Now Playing", self.source),
+            image,
+            message: Some(message.into()),
+            timestamp: Local::now(),
+            ..Default::default()
+        }
+    }
+}
+
+/// `1:23 ████████░░░░░░░░ 3:45`
+fn progress(elapsed: Duration, duration: Option<Duration>) -> String {
+    let format_time = |d: Duration| format!("{}:{:02}", d.as_secs() / 60, d.as_secs() % 60);
+    let Some(duration) = duration.filter(|d| !d.is_zero()) else {
+        return format_time(elapsed);
+    };
+
+    let filled = usize::try_from(
+        (elapsed.as_millis() * PROGRESS_BAR_WIDTH as u128 / duration.as_millis())
+            .min(PROGRESS_BAR_WIDTH as u128),
+    )
+    .unwrap_or(PROGRESS_BAR_WIDTH);
+
+    format!(
+        "{} {}{} {}",
+        format_time(elapsed),
+        "█".repeat(filled),
+        "░".repeat(PROGRESS_BAR_WIDTH - filled),
+        format_time(duration)
+    )
+}
+
+#[instrument(skip(client, refresh_token))]
+async fn get_spotify_now_playing(
+    client: &Client,
+    refresh_token: &str,
+) -> Result<Option<NowPlaying>> {
+    let client_id = error::env("SPOTIFY_CLIENT_ID")?;
+    let client_secret = error::env("SPOTIFY_CLIENT_SECRET")?;
+
+    let token = client
+        .post(SPOTIFY_TOKEN_URL)
+        .basic_auth(client_id, Some(client_secret))
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<SpotifyToken>()
+        .await?;
+
+    let res = client
+        .get(SPOTIFY_CURRENTLY_PLAYING_URL)
+        .bearer_auth(&token.access_token)
+        .send_retrying()
+        .await?
+        .error_for_status()?;
+    // 204 = Nothing is playing
+    if res.status() == StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+
+    let playing = res.json::<SpotifyCurrentlyPlaying>().await?;
+    let Some(track) = playing.item else {
+        return Ok(None);
+    };
+
+    // Images are ordered widest first
+    let album_art = match track.album.images.first() {
+        Some(image) => Some(
+            client
+                .get(&image.url)
+                .send_retrying()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?
+                .to_vec(),
+        ),
+        None => None,
+    };
+
+    Ok(Some(NowPlaying {
+        source: "Spotify",
+        title: track.name,
+        artist: track
+            .artists
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", "),
+        album: Some(track.album.name),
+        elapsed: Duration::from_millis(playing.progress_ms.unwrap_or(0)),
+        duration: Some(Duration::from_millis(track.duration_ms)),
+        album_art,
+    }))
+}
+
+/// Talks to MPD through its text protocol; <https://mpd.readthedocs.io/en/latest/protocol.html>
+#[instrument]
+async fn get_mpd_now_playing(addr: &str) -> Result<Option<NowPlaying>> {
+    let stream = TcpStream::connect(addr).await?;
+    let mut stream = BufReader::new(stream);
+
+    let mut greeting = String::new();
+    stream.read_line(&mut greeting).await?;
+    trace!("MPD greeting: {}", greeting.trim());
+
+    let status = mpd_command(&mut stream, "status").await?;
+    let state = mpd_field(&status, "state");
+    if state != Some("play") && state != Some("pause") {
+        return Ok(None);
+    }
+
+    let song = mpd_command(&mut stream, "currentsong").await?;
+    let Some(file) = mpd_field(&song, "file").map(ToString::to_string) else {
+        return Ok(None);
+    };
+
+    let seconds = |key: &str| {
+        mpd_field(&status, key)
+            .and_then(|v| v.parse::<f64>().ok())
+            .map(Duration::from_secs_f64)
+    };
+    let album_art = mpd_album_art(&mut stream, &file)
+        .await
+        .inspect_err(|e| debug!("No album art for {file}: {e}"))
+        .ok();
+
+    Ok(Some(NowPlaying {
+        source: "MPD",
+        title: mpd_field(&song, "Title").map_or_else(|| file.clone(), ToString::to_string),
+        artist: mpd_field(&song, "Artist")
+            .unwrap_or("Unknown artist")
+            .to_string(),
+        album: mpd_field(&song, "Album").map(ToString::to_string),
+        elapsed: seconds("elapsed").unwrap_or_default(),
+        duration: seconds("duration"),
+        album_art,
+    }))
+}
+
+/// Sends a command and collects its `key: value` response lines until `OK`
+async fn mpd_command(
+    stream: &mut BufReader<TcpStream>,
+    command: &str,
+) -> Result<Vec<(String, String)>> {
+    stream
+        .get_mut()
+        .write_all(format!("{command}\n").as_bytes())
+        .await?;
+
+    let mut fields = Vec::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(Error::Io(ErrorKind::UnexpectedEof.into()));
+        }
+        let line = line.trim_end();
+        if line == "OK" {
+            return Ok(fields);
+        }
+        if line.starts_with("ACK") {
+            return Err(Error::Response(format!("MPD error: {line}")));
+        }
+        if let Some((key, value)) = line.split_once(": ") {
+            fields.push((key.to_string(), value.to_string()));
+        }
+    }
+}
+
+fn mpd_field<'a>(fields: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Reads the embedded / folder album art of a song through `albumart`, chunk by chunk
+async fn mpd_album_art(stream: &mut BufReader<TcpStream>, file: &str) -> Result<Vec<u8>> {
+    let escaped = file.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut art = Vec::new();
+    loop {
+        stream
+            .get_mut()
+            .write_all(format!("albumart \"{escaped}\" {}\n", art.len()).as_bytes())
+            .await?;
+
+        // size: <total>\nbinary: <chunk length>\n<chunk>\nOK\n
+        let mut total_size = None;
+        let mut chunk_size = None;
+        while chunk_size.is_none() {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(Error::Io(ErrorKind::UnexpectedEof.into()));
+            }
+            let line = line.trim_end();
+            if line.starts_with("ACK") {
+                return Err(Error::Response(format!("MPD error: {line}")));
+            }
+            let size = |size: &str| {
+                size.parse::<usize>()
+                    .map_err(|_| Error::Response(format!("Malformed MPD size `{line}`")))
+            };
+            if let Some(size) = line.strip_prefix("size: ").map(size) {
+                total_size = Some(size?);
+            }
+            if let Some(size) = line.strip_prefix("binary: ").map(size) {
+                chunk_size = Some(size?);
+            }
+        }
+
+        let mut chunk = vec![0; chunk_size.unwrap_or(0)];
+        stream.read_exact(&mut chunk).await?;
+        art.extend_from_slice(&chunk);
+
+        // Trailing newline after the binary data, then OK
+        let mut trailer = String::new();
+        stream.read_line(&mut trailer).await?;
+        stream.read_line(&mut trailer).await?;
+
+        if chunk.is_empty() || total_size.is_none_or(|total| art.len() >= total) {
+            return Ok(art);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Local};
+use cron::Schedule;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument};
+
+use crate::{printer::PrintData, secrets, status};
+
+#[derive(Deserialize)]
+struct RemindersFile {
+    #[serde(default, rename = "reminder")]
+    reminders: Vec<ReminderConfig>,
+}
+
+/// A `[[reminder]]` entry of the reminders file
+///
+/// ```toml
+/// [[reminder]]
+/// schedule = "0 20 * * Tue"
+/// title = "Bins"
+/// template = "Take out the bins! It's {weekday} {time}"
+/// ```
+#[derive(Deserialize)]
+struct ReminderConfig {
+    /// Cron expression; either 5 fields (`min hour day month weekday`) or with seconds & year
+    schedule: String,
+    title: String,
+    template: String,
+}
+
+struct Reminder {
+    schedule: Schedule,
+    title: String,
+    template: String,
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let path = secrets::var("REMINDERS_FILE").unwrap_or_else(|_| "reminders.toml".to_string());
+    let Ok(file) = std::fs::read_to_string(&path) else {
+        info!("Reminders file `{path}` not found, reminder service disabled");
+        return;
+    };
+    let reminders = match parse_reminders(&file) {
+        Ok(reminders) => reminders,
+        Err(e) => {
+            info!("{e}, reminder service disabled");
+            return;
+        }
+    };
+    info!("Loaded {} reminders from {path}", reminders.len());
+
+    loop {
+        let now = Local::now();
+        let Some(next_fire) = reminders
+            .iter()
+            .filter_map(|r| r.schedule.after(&now).next())
+            .min()
+        else {
+            info!("No upcoming reminders, stopping service...");
+            break;
+        };
+        debug!("Next reminder fires at {next_fire}");
+
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep((next_fire - now).to_std().unwrap_or_default()) => {}
+        }
+
+        for reminder in reminders
+            .iter()
+            .filter(|r| r.schedule.after(&now).next() == Some(next_fire))
+        {
+            info!("Firing reminder {}", reminder.title);
+            status::service_ok("reminders");
+            if sender
+                .send(PrintData {
+                    service: Some("reminders".to_string()),
+                    logo: Some("reminders".to_string()),
+                    title: reminder.title.clone(),
+                    subtitle: None,
+                    message: Some(render_template(&reminder.template, next_fire).into()),
+                    timestamp: next_fire,
+                    ..Default::default()
+                })
+                .await
+                .is_err()
+            {
+                debug!("Print queue closed! Stopping service...");
+                return;
+            }
+        }
+    }
+}
+
+/// Every `[[reminder]]` of the reminders file
+fn parse_reminders(file: &str) -> Result<Vec<Reminder>, String> {
+    let file: RemindersFile =
+        toml::from_str(file).map_err(|e| format!("Reminders file is malformed: {e}"))?;
+    file.reminders
+        .into_iter()
+        .map(|r| {
+            Ok(Reminder {
+                schedule: parse_schedule(&r.schedule)
+                    .map_err(|e| format!("Invalid cron expression `{}`: {e}", r.schedule))?,
+                title: r.title,
+                template: r.template,
+            })
+        })
+        .collect()
+}
+
+/// Parses a cron expression, accepting the classic 5 field format without seconds
+fn parse_schedule(expression: &str) -> Result<Schedule, cron::error::Error> {
+    if expression.split_whitespace().count() == 5 {
+        Schedule::from_str(&format!("0 {expression}"))
+    } else {
+        Schedule::from_str(expression)
+    }
+}
+
+/// Fills in `{date}`, `{time}` and `{weekday}` placeholders
+#[allow(clippy::literal_string_with_formatting_args)]
+fn render_template(template: &str, time: DateTime<Local>) -> String {
+    template
+        .replace("{date}", &time.format("%B %e").to_string())
+        .replace("{time}", &time.format("%H:%M").to_string())
+        .replace("{weekday}", &time.format("%A").to_string())
+}
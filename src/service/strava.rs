@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    error::{self, Result},
+    http::{self, SendRetrying},
+    printer::PrintData,
+    secrets, status,
+};
+
+const TOKEN_URL: &str = "https://www.strava.com/oauth/token";
+const API_BASE_URL: &str = "https://www.strava.com/api/v3";
+
+const POLL_INTERVAL: Duration = Duration::from_mins(15);
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct Activity {
+    id: u64,
+    name: String,
+    sport_type: String,
+    /// Meters
+    distance: f64,
+    /// Seconds
+    moving_time: u32,
+    /// Meters
+    total_elevation_gain: f64,
+    kudos_count: u32,
+    start_date: DateTime<Utc>,
+}
+
+/// Push subscription event; <https://developers.strava.com/docs/webhooks/>
+#[derive(Deserialize)]
+pub struct WebhookEvent {
+    object_type: String,
+    object_id: u64,
+    aspect_type: String,
+}
+
+/// Polls the athlete's activities, unless `STRAVA_VERIFY_TOKEN` is set; In that case new
+/// activities are pushed to the HTTP server's `/strava/webhook` route instead.
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
+    if secrets::var("STRAVA_REFRESH_TOKEN").is_err() {
+        info!("Env `STRAVA_REFRESH_TOKEN` not set, Strava service disabled");
+        return;
+    }
+    if webhook_verify_token().is_some() {
+        info!("Strava webhooks enabled, activities will be received by the HTTP server");
+        return;
+    }
+
+    let http_client = http::client();
+    let mut last_poll = Utc::now();
+
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+
+        let poll_start = Utc::now();
+        let activities = match get_activities_after(&http_client, last_poll).await {
+            Ok(a) => {
+                status::service_ok("strava");
+                a
+            }
+            Err(e) => {
+                error!("Unable to fetch Strava activities: {e}");
+                continue;
+            }
+        };
+        last_poll = poll_start;
+
+        for activity in activities {
+            info!("New activity {}", activity.id);
+            if sender.send(activity.into_print_data()).await.is_err() {
+                debug!("Print queue closed! Stopping service...");
+                return;
+            }
+        }
+    }
+}
+
+/// Token Strava echoes back when validating the webhook subscription
+pub fn webhook_verify_token() -> Option<String> {
+    secrets::var("STRAVA_VERIFY_TOKEN").ok()
+}
+
+/// Prints newly created activities pushed through the webhook
+#[instrument(skip(sender, event), fields(object_id = event.object_id))]
+pub async fn handle_webhook_event(sender: &Sender<PrintData>, event: WebhookEvent) -> Result<()> {
+    if event.object_type != "activity" || event.aspect_type != "create" {
+        debug!("Ignoring {} {} event", event.object_type, event.aspect_type);
+        return Ok(());
+    }
+
+    let activity = get_activity(&http::client(), event.object_id).await?;
+    info!("New activity {}", activity.id);
+    sender.send(activity.into_print_data()).await?;
+
+    Ok(())
+}
+
+impl Activity {
+    fn into_print_data(self) -> PrintData {
+        let distance_km = self.distance / 1000.0;
+        let is_ride = self.sport_type.contains("Ride");
+
+        // Rides are measured by speed, everything on foot by pace
+        let pace = if distance_km <= 0.0 {
+            "-".to_string()
+        } else if is_ride {
+            format!(
+                "{:.1} km/h",
+                distance_km / (f64::from(self.moving_time) / 3600.0)
+            )
+        } else {
+            format!(
+                "{} /km",
+                format_duration(
+                    Duration::from_secs_f64(f64::from(self.moving_time) / distance_km).as_secs()
+                )
+            )
+        };
+
+        PrintData {
+            service: Some("strava".to_string()),
+            logo: Some("strava".to_string()),
+            title: format!("Strava: {}", self.sport_type),
+            subtitle: Some(self.name),
+            message: Some(format!(
+                "Distance:  {distance_km:.2} km\nTime:      {}\n{}{pace}\nElevation: {:.0} m\nKudos:     {}",
+                format_duration(u64::from(self.moving_time)),
+                if is_ride { "Speed:     " } else { "Pace:      " },
+                self.total_elevation_gain,
+                self.kudos_count
+            ).into()),
+            timestamp: self.start_date.with_timezone(&Local),
+            ..Default::default()
+        }
+    }
+}
+
+/// `1:02:03` or `2:03`
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+async fn access_token(client: &Client) -> Result<String> {
+    let res = client
+        .post(TOKEN_URL)
+        .form(&[
+            ("client_id", error::env("STRAVA_CLIENT_ID")?),
+            ("client_secret", error::env("STRAVA_CLIENT_SECRET")?),
+            ("refresh_token", error::env("STRAVA_REFRESH_TOKEN")?),
+            ("grant_type", "refresh_token".to_string()),
+        ])
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<TokenResponse>()
+        .await?;
+
+    Ok(res.access_token)
+}
+
+#[instrument(skip(client))]
+async fn get_activity(client: &Client, id: u64) -> Result<Activity> {
+    Ok(client
+        .get(format!("{API_BASE_URL}/activities/{id}"))
+        .bearer_auth(access_token(client).await?)
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<Activity>()
+        .await?)
+}
+
+#[instrument(skip(client))]
+async fn get_activities_after(client: &Client, after: DateTime<Utc>) -> Result<Vec<Activity>> {
+    Ok(client
+        .get(format!("{API_BASE_URL}/athlete/activities"))
+        .bearer_auth(access_token(client).await?)
+        .query(&[("after", after.timestamp())])
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<Vec<Activity>>()
+        .await?)
+}
@@ -0,0 +1,95 @@
+use chrono::Local;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument};
+
+use crate::{
+    error::{self, Result},
+    printer::{PrintData, Span},
+    schedule,
+    stats::{self, Stats},
+    status,
+};
+
+/// Sources listed per service by default
+const DEFAULT_TOP: usize = 3;
+
+/// Prints a summary of the day's receipts every day at `SUMMARY_PRINT_TIME`; Receipts per
+/// service, the top `SUMMARY_TOP` repos, streamers, etc. of each & the paper used
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let settings = || -> Result<_> {
+        Ok((
+            schedule::env_time("SUMMARY_PRINT_TIME", None)?,
+            error::env_or("SUMMARY_TOP", DEFAULT_TOP)?,
+        ))
+    };
+    let (print_time, top) = match settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            info!("{e}, summary service disabled");
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(schedule::duration_until(print_time)) => {}
+        }
+
+        let stats = stats::take();
+        status::service_ok("summary");
+        if stats.receipts == 0 {
+            info!("Nothing printed since the last summary");
+            continue;
+        }
+
+        info!("Printing summary of {} receipts", stats.receipts);
+        if sender.send(print_data(&stats, top)).await.is_err() {
+            debug!("Print queue closed! Stopping service...");
+            return;
+        }
+    }
+}
+
+fn print_data(stats: &Stats, top: usize) -> PrintData {
+    let mut message = vec![Span::bold("Receipts\n")];
+    for (service, count) in &stats.services {
+        message.push(Span::plain(format!("{service}: {count}\n")));
+    }
+    message.push(Span::plain(format!(
+        "\nTotal: {}\nPaper: ~{} cm\n",
+        stats.receipts,
+        stats.paper_mm().div_ceil(10)
+    )));
+
+    for service in stats.services.keys() {
+        let sources = stats.top_sources(service, top);
+        if sources.is_empty() {
+            continue;
+        }
+        message.push(Span::bold(format!("\nTop {service}\n")));
+        for (source, count) in sources {
+            message.push(Span::plain(format!("{source}: {count}\n")));
+        }
+    }
+
+    PrintData {
+        service: Some("summary".to_string()),
+        logo: Some("summary".to_string()),
+        title: "Daily Summary".to_string(),
+        subtitle: Some(stats.since.map_or_else(
+            || "Since startup".to_string(),
+            |since| format!("Since {}", since.format("%B %e, %H:%M")),
+        )),
+        message: Some(message.into()),
+        timestamp: Local::now(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use chrono::Local;
+use reqwest::Client;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    error::Result,
+    http::{self, SendRetrying},
+    printer::PrintData,
+    schedule, secrets, status,
+};
+
+const API_BASE_URL: &str = "https://api.todoist.com/rest/v2";
+
+const ASSIGNED_POLL_INTERVAL: Duration = Duration::from_mins(2);
+
+#[derive(Deserialize)]
+struct Task {
+    id: String,
+    content: String,
+    #[serde(default)]
+    description: String,
+    project_id: String,
+    /// 4 is the most urgent (shown as `p1` in the app), 1 is the default
+    priority: u8,
+    due: Option<Due>,
+}
+
+#[derive(Deserialize)]
+struct Due {
+    string: String,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    id: String,
+    name: String,
+}
+
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(
+    cancel_token: CancellationToken,
+    sender: tokio::sync::mpsc::Sender<PrintData>,
+) {
+    let Ok(token) = secrets::var("TODOIST_TOKEN") else {
+        info!("Env `TODOIST_TOKEN` not set, Todoist service disabled");
+        return;
+    };
+    let print_time = match schedule::env_time("TODOIST_PRINT_TIME", Some("07:00")) {
+        Ok(print_time) => print_time,
+        Err(e) => {
+            info!("{e}, Todoist service disabled");
+            return;
+        }
+    };
+
+    let http_client = http::client();
+
+    // None = Not fetched yet; tasks assigned before startup shouldn't be printed
+    let mut seen_assigned: Option<HashSet<String>> = None;
+
+    loop {
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+
+        match get_tasks(&http_client, &token, "assigned to: me").await {
+            Ok(tasks) => {
+                status::service_ok("todoist");
+                if let Some(seen) = seen_assigned.as_mut() {
+                    let new_tasks = tasks
+                        .into_iter()
+                        .filter(|t| !seen.contains(&t.id))
+                        .collect::<Vec<Task>>();
+                    if !new_tasks.is_empty() {
+                        let projects = get_projects(&http_client, &token).await.unwrap_or_default();
+                        for task in new_tasks {
+                            info!("Task {} was assigned to us", task.id);
+                            seen.insert(task.id.clone());
+                            if sender
+                                .send(assigned_print_data(&task, &projects))
+                                .await
+                                .is_err()
+                            {
+                                debug!("Print queue closed! Stopping service...");
+                                return;
+                            }
+                        }
+                    }
+                } else {
+                    seen_assigned = Some(tasks.into_iter().map(|t| t.id).collect());
+                }
+            }
+            Err(e) => error!("Unable to fetch assigned tasks: {e}"),
+        }
+
+        let until_daily_list = schedule::duration_until(print_time);
+        if until_daily_list > ASSIGNED_POLL_INTERVAL {
+            tokio::select! {
+                () = cancel_token.cancelled() => {
+                    debug!("Cancel signal caught! Stopping service...");
+                    break;
+                }
+                () = tokio::time::sleep(ASSIGNED_POLL_INTERVAL) => {}
+            }
+            continue;
+        }
+
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                break;
+            }
+            () = tokio::time::sleep(until_daily_list) => {}
+        }
+
+        let tasks = match get_tasks(&http_client, &token, "today | overdue").await {
+            Ok(t) => {
+                status::service_ok("todoist");
+                t
+            }
+            Err(e) => {
+                error!("Unable to fetch today's tasks: {e}");
+                continue;
+            }
+        };
+        let projects = get_projects(&http_client, &token).await.unwrap_or_default();
+
+        info!("Printing {} tasks due today", tasks.len());
+        if sender
+            .send(daily_print_data(tasks, &projects))
+            .await
+            .is_err()
+        {
+            debug!("Print queue closed! Stopping service...");
+            return;
+        }
+    }
+}
+
+/// Priority marker, `!!!` for p1 down to nothing for p4
+const fn priority_marker(priority: u8) -> &'static str {
+    match priority {
+        4 => " !!!",
+        3 => " !!",
+        2 => " !",
+        _ => "",
+    }
+}
+
+fn daily_print_data(mut tasks: Vec<Task>, projects: &HashMap<String, String>) -> PrintData {
+    let now = Local::now();
+    if tasks.is_empty() {
+        return PrintData {
+            service: Some("todoist".to_string()),
+            logo: Some("todoist".to_string()),
+            title: "Todoist: Today".to_string(),
+            subtitle: Some(now.format("%A, %B %e").to_string()),
+            message: Some("Nothing due today!".to_string().into()),
+            timestamp: now,
+            ..Default::default()
+        };
+    }
+
+    tasks.sort_by_key(|t| std::cmp::Reverse(t.priority));
+    let lines = tasks
+        .iter()
+        .map(|t| {
+            let project = projects.get(&t.project_id).map_or("", String::as_str);
+            format!(
+                "[ ] {}{}\n    #{project}",
+                t.content,
+                priority_marker(t.priority)
+            )
+        })
+        .collect::<Vec<String>>();
+
+    PrintData {
+        service: Some("todoist".to_string()),
+        logo: Some("todoist".to_string()),
+        title: "Todoist: Today".to_string(),
+        subtitle: Some(format!(
+            "{}\n{} tasks due",
+            now.format("%A, %B %e"),
+            tasks.len()
+        )),
+        message: Some(lines.join("\n").into()),
+        timestamp: now,
+        ..Default::default()
+    }
+}
+
+fn assigned_print_data(task: &Task, projects: &HashMap<String, String>) -> PrintData {
+    let mut message = format!("[ ] {}{}", task.content, priority_marker(task.priority));
+    if let Some(due) = &task.due {
+        message = format!("{message}\nDue: {}", due.string);
+    }
+    if !task.description.is_empty() {
+        message = format!("{message}\n\n{}", task.description);
+    }
+
+    PrintData {
+        service: Some("todoist".to_string()),
+        logo: Some("todoist".to_string()),
+        title: "Todoist: New Task".to_string(),
+        subtitle: projects
+            .get(&task.project_id)
+            .map(|p| format!("Project: {p}")),
+        message: Some(message.into()),
+        timestamp: Local::now(),
+        ..Default::default()
+    }
+}
+
+#[instrument(skip(client, token))]
+async fn get_tasks(client: &Client, token: &str, filter: &str) -> Result<Vec<Task>> {
+    Ok(client
+        .get(format!("{API_BASE_URL}/tasks"))
+        .bearer_auth(token)
+        .query(&[("filter", filter)])
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<Vec<Task>>()
+        .await?)
+}
+
+/// Project ID -> Project name
+#[instrument(skip(client, token))]
+async fn get_projects(client: &Client, token: &str) -> Result<HashMap<String, String>> {
+    let projects = client
+        .get(format!("{API_BASE_URL}/projects"))
+        .bearer_auth(token)
+        .send_retrying()
+        .await?
+        .error_for_status()?
+        .json::<Vec<Project>>()
+        .await?;
+
+    Ok(projects.into_iter().map(|p| (p.id, p.name)).collect())
+}
@@ -1,19 +1,39 @@
-use std::{str::FromStr, time::Duration};
+use std::{collections::HashMap, fmt::Write, str::FromStr, sync::Mutex, time::Duration};
 use tracing::instrument;
 
-use chrono::DateTime;
+use chrono::{DateTime, Local, TimeDelta};
 use futures_util::StreamExt;
-use serde_json::{json, Value::String};
-use tokio_tungstenite::tungstenite::{protocol::WebSocketConfig, ClientRequestBuilder, Message};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    net::TcpStream,
+    sync::mpsc::{self, Receiver, Sender},
+    time::Instant,
+};
+use tokio_tungstenite::{
+    tungstenite::{http::Uri, protocol::WebSocketConfig, ClientRequestBuilder, Message},
+    MaybeTlsStream, WebSocketStream,
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::printer::PrintData;
+use crate::{
+    error::{self, str_at, Error, Result},
+    http::SendRetrying,
+    printer::{PrintData, Priority, QrCode, Span},
+    retry::Backoff,
+    secrets,
+    service::github,
+    state, status,
+};
 
 const EVENT_SUBSCRIPTION_URL: &str = "https://api.twitch.tv/helix/eventsub/subscriptions";
 const CHANNEL_INFO_URL: &str = "https://api.twitch.tv/helix/channels?broadcaster_id=";
+const STREAMS_URL: &str = "https://api.twitch.tv/helix/streams?user_id=";
 
-const BROADCASTER_IDS: [&str; 4] = [
+/// Channels followed when `TWITCH_BROADCASTER_IDS` isn't set
+const DEFAULT_BROADCASTER_IDS: [&str; 4] = [
     "88547576",  // RTGame
     "57220741",  // CakeJumper
     "132141901", // narpy
@@ -22,215 +42,1525 @@ const BROADCASTER_IDS: [&str; 4] = [
 
 const DEFAULT_WS_URL: &str = "wss://eventsub.wss.twitch.tv/ws?keepalive_timeout_seconds=30";
 
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Characters of the hype train & goal progress bars
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+// https://twitchapps.com/tmi/
+const CLIENT_ID: &str = "q6batx0epp608isickayubi39itsckt";
+
+const DEVICE_URL: &str = "https://id.twitch.tv/oauth2/device";
+const TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+const VALIDATE_URL: &str = "https://id.twitch.tv/oauth2/validate";
+/// Twitch asks apps to validate their tokens hourly
+const VALIDATE_INTERVAL: Duration = Duration::from_hours(1);
+
+/// Waited for on top of the keepalive timeout Twitch sends, for latency, when
+/// `TWITCH_KEEPALIVE_MARGIN` isn't set
+const DEFAULT_KEEPALIVE_MARGIN: Duration = Duration::from_secs(10);
+
+/// Webhook subscriptions are made again this often, in case one was revoked while the HTTP server
+/// was down
+const WEBHOOK_RESUBSCRIBE_INTERVAL: Duration = Duration::from_hours(1);
+/// Deliveries the service can fall behind on before more are refused, for Twitch to retry
+const WEBHOOK_CAPACITY: usize = 64;
+/// Older deliveries are refused, as they may be replayed
+const WEBHOOK_MAX_AGE: TimeDelta = TimeDelta::minutes(10);
+
+/// Where deliveries to `/twitch/webhook` go while the webhook transport is used, see [`deliver`]
+static WEBHOOK_INBOX: Mutex<Option<Inbox>> = Mutex::new(None);
+
+/// Wait before reconnecting after the connection failed, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
 #[instrument(skip(cancel_token, sender))]
-pub async fn start_service(
-    cancel_token: CancellationToken,
-    sender: tokio::sync::mpsc::Sender<PrintData>,
-) {
-    // Connect URL may change dynamically via a Reconnect Message
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
+    let reqwest = crate::http::client();
+    let broadcaster_ids: Vec<String> = secrets::var("TWITCH_BROADCASTER_IDS").map_or_else(
+        |_| DEFAULT_BROADCASTER_IDS.map(str::to_string).into(),
+        |ids| {
+            ids.split(',')
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect()
+        },
+    );
+
+    // Your own channel's events, see `ChannelEvent`
+    let channel_events = secrets::var("TWITCH_CHANNEL_EVENTS")
+        .map(|events| {
+            events
+                .split(',')
+                .map(str::trim)
+                .filter(|event| !event.is_empty())
+                .map(|event| {
+                    event
+                        .parse()
+                        .unwrap_or_else(|e| panic!("Invalid TWITCH_CHANNEL_EVENTS! {e}"))
+                })
+                .collect::<Vec<ChannelEvent>>()
+        })
+        .unwrap_or_default();
+    let channel_id = secrets::var("TWITCH_CHANNEL_ID").ok();
+    if channel_id.is_none() && !channel_events.is_empty() {
+        panic!("Env `TWITCH_CHANNEL_ID` not set! Needed for TWITCH_CHANNEL_EVENTS");
+    }
+    let subscriptions = subscriptions(&broadcaster_ids, channel_id.as_deref(), &channel_events);
+    let scopes = channel_events
+        .iter()
+        .filter_map(|event| event.scope())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let keepalive_margin =
+        secrets::var("TWITCH_KEEPALIVE_MARGIN").map_or(DEFAULT_KEEPALIVE_MARGIN, |s| {
+            Duration::from_secs(
+                s.parse()
+                    .expect("Invalid TWITCH_KEEPALIVE_MARGIN! Expected seconds"),
+            )
+        });
+
+    let transport = secrets::var("TWITCH_TRANSPORT").map_or(Transport::WebSocket, |transport| {
+        transport
+            .parse()
+            .unwrap_or_else(|e| panic!("Invalid TWITCH_TRANSPORT! {e}"))
+    });
+    let credentials = match transport {
+        Transport::WebSocket => Credentials::from_env(scopes),
+        Transport::Webhook => Credentials::app_from_env(),
+    };
+    let mut credentials = match credentials {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            info!("{e}, Twitch service disabled");
+            return;
+        }
+    };
+    let mut webhook = match transport {
+        Transport::WebSocket => None,
+        Transport::Webhook => match Webhook::from_env() {
+            Ok(webhook) => Some(webhook),
+            Err(e) => {
+                info!("{e}, Twitch service disabled");
+                return;
+            }
+        },
+    };
+
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
+    loop {
+        let result = match &mut webhook {
+            Some(webhook) => {
+                webhook_session(
+                    &reqwest,
+                    &cancel_token,
+                    &sender,
+                    &mut credentials,
+                    &subscriptions,
+                    webhook,
+                )
+                .await
+            }
+            None => {
+                session(
+                    &reqwest,
+                    &cancel_token,
+                    &sender,
+                    &mut credentials,
+                    &subscriptions,
+                    keepalive_margin,
+                )
+                .await
+            }
+        };
+        let delay = match result {
+            Ok(()) => {
+                backoff.reset();
+                None
+            }
+            Err(Error::Cancelled) => break,
+            Err(Error::Unauthorized) if credentials.refreshable => {
+                let delay = backoff.next_delay();
+                error!("Twitch rejected the access token, refreshing it in {delay:?}");
+                credentials.expire();
+                Some(delay)
+            }
+            Err(Error::Unauthorized) => {
+                error!("Twitch rejected TWITCH_OAUTH_TOKEN, it may have expired; Stopping service");
+                info!("Set TWITCH_CLIENT_ID to log in with an app of your own & refresh tokens");
+                break;
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("Twitch connection failed, reconnecting in {delay:?}: {e}");
+                Some(delay)
+            }
+            Err(e) => {
+                error!("Stopping Twitch service: {e}");
+                break;
+            }
+        };
+        if let Some(delay) = delay {
+            tokio::select! {
+                () = cancel_token.cancelled() => {}
+                () = tokio::time::sleep(delay) => {}
+            }
+        }
+
+        // Check if we break out of loop because of cancel token
+        if cancel_token.is_cancelled() {
+            break;
+        }
+    }
+}
+
+/// Connects to EventSub & prints events until cancelled or the connection is lost
+///
+/// Subscriptions are made on connecting, then carried over when Twitch asks to move to another
+/// connection, see [`connect`]; Ones Twitch revokes are made again.
+async fn session(
+    reqwest: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    credentials: &mut Credentials,
+    subscriptions: &[Value],
+    keepalive_margin: Duration,
+) -> Result<()> {
+    let token = credentials
+        .access_token(reqwest, cancel_token, sender)
+        .await?;
+
+    let (mut stream, welcome_message) = connect(DEFAULT_WS_URL.parse()?).await?;
+    // Extract session id and subscribe to event
+    let (mut session_id, mut idle_timeout) = welcomed(&welcome_message, keepalive_margin)?;
+    info!("Session ID: {session_id}");
+    status::service_ok("twitch");
+    let transport = json!({ "method": "websocket", "session_id": session_id });
+    let subscribed = subscribe_all(
+        reqwest,
+        &credentials.client_id,
+        &token,
+        &transport,
+        subscriptions,
+    );
+    let mut active = subscribed.await?;
+    info!(
+        "Subscribed to {} of {} Twitch events",
+        active.len(),
+        subscriptions.len()
+    );
+
+    // Connection Twitch asked to move to, while the old one is still read until it's welcomed
     // https://dev.twitch.tv/docs/eventsub/handling-websocket-events#reconnect-message
-    let mut custom_connect_url: Option<Box<str>> = None;
+    let mut reconnect = None;
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                let _ = stream.close(None).await;
+                return Ok(());
+            }
 
-    let reqwest = crate::http::client();
+            // When client doesn't receive an event or keepalive message for longer
+            // than keepalive_timeout_seconds, Assume that the connection is lost
+            // Plus a margin, to be safe from latency
+            () = tokio::time::sleep(idle_timeout) => {
+                error!(
+                    "Didn't get any message for {idle_timeout:?}, closing connection & \
+                    reconnecting..."
+                );
+                let _ = stream.close(None).await;
+                return Ok(());
+            }
+
+            connected = async { reconnect.as_mut().expect("reconnect is set").await },
+                if reconnect.is_some() =>
+            {
+                reconnect = None;
+                let (new_stream, welcome_message) = connected?;
+                (session_id, idle_timeout) = welcomed(&welcome_message, keepalive_margin)?;
+                info!("Reconnected, session ID: {session_id}");
+                let _ = stream.close(None).await;
+                stream = new_stream;
+            }
+
+            message = stream.next() => {
+                let message = match message {
+                    Some(message) => message?,
+                    None => {
+                        error!("Twitch websocket stream ended");
+                        Message::Close(None)
+                    }
+                };
+                match message {
+                    Message::Text(data) => {
+                        let handled = handle_message(
+                            reqwest,
+                            cancel_token,
+                            sender,
+                            credentials,
+                            &session_id,
+                            &mut active,
+                            &data,
+                        );
+                        if let Some(url) = handled.await? {
+                            reconnect = Some(Box::pin(connect(url.parse()?)));
+                        }
+                    },
 
+                    Message::Ping(_) |  Message::Pong(_) | Message::Frame(_) => {},
+                    Message::Binary(vec) => {
+                        info!("Twitch set binary message: {:?}", vec);
+                    },
+                    Message::Close(frame) => {
+                        error!("Twitch ended websocket connection");
+                        if let Some(frame) = frame {
+                            error!("Close frame: {frame:?}");
+                        }
+                        // The old connection may be closed before the new one is welcomed
+                        let Some(reconnect) = reconnect.take() else {
+                            return Ok(());
+                        };
+                        let (new_stream, welcome_message) = reconnect.await?;
+                        (session_id, idle_timeout) =
+                            welcomed(&welcome_message, keepalive_margin)?;
+                        info!(
+                            "Reconnected after the old connection was closed, session ID: \
+                            {session_id}"
+                        );
+                        stream = new_stream;
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// How EventSub events get to the service, in `TWITCH_TRANSPORT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    /// A connection of the service's own; The default
+    WebSocket,
+    /// Deliveries to the HTTP server, see [`Webhook`]; Sturdier for servers running unattended,
+    /// as nothing is missed while reconnecting
+    Webhook,
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "websocket" => Ok(Self::WebSocket),
+            "webhook" => Ok(Self::Webhook),
+            other => Err(format!(
+                "Unknown transport `{other}`; expected websocket or webhook"
+            )),
+        }
+    }
+}
+
+/// Deliveries to `/twitch/webhook` at the public `TWITCH_WEBHOOK_CALLBACK` URL, signed with
+/// `TWITCH_WEBHOOK_SECRET`; Subscribed to as an app, so channel events need you to have logged in
+/// with it once over the websocket
+struct Webhook {
+    callback: String,
+    secret: String,
+    events: Receiver<Value>,
+}
+
+/// Hands deliveries over to a running [`Webhook`]
+struct Inbox {
+    secret: String,
+    events: Sender<Value>,
+}
+
+impl Webhook {
+    /// Also has the HTTP server take deliveries, until it's dropped
+    fn from_env() -> Result<Self> {
+        let callback = error::env("TWITCH_WEBHOOK_CALLBACK")?;
+        let secret = error::env("TWITCH_WEBHOOK_SECRET")?;
+        let (sender, events) = mpsc::channel(WEBHOOK_CAPACITY);
+        *WEBHOOK_INBOX.lock().unwrap() = Some(Inbox {
+            secret: secret.clone(),
+            events: sender,
+        });
+        Ok(Self {
+            callback,
+            secret,
+            events,
+        })
+    }
+}
+
+impl Drop for Webhook {
+    fn drop(&mut self) {
+        *WEBHOOK_INBOX.lock().unwrap() = None;
+    }
+}
+
+/// Secret deliveries to `/twitch/webhook` are signed with; None unless the webhook transport is
+/// used, see [`Transport`]
+pub fn webhook_secret() -> Option<String> {
+    WEBHOOK_INBOX
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|inbox| inbox.secret.clone())
+}
+
+/// Whether a delivery's `Twitch-Eventsub-Message-Signature` is of its message ID, timestamp & body
+/// signed with the secret, and it's recent enough not to be a replay
+pub fn verify_signature(
+    secret: &str,
+    message_id: &str,
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+) -> bool {
+    let Ok(sent_at) = DateTime::parse_from_rfc3339(timestamp) else {
+        return false;
+    };
+    if Local::now().fixed_offset() - sent_at > WEBHOOK_MAX_AGE {
+        return false;
+    }
+    // Signed the same way as GitHub's, over more than the body
+    let message = [message_id.as_bytes(), timestamp.as_bytes(), body].concat();
+    github::verify_signature(secret, &message, signature)
+}
+
+/// Hands a verified delivery over to the service, as the message it'd be over the websocket;
+/// False if it can't take it, e.g. while it's stopped or behind
+pub fn deliver(message_id: &str, message_type: &str, timestamp: &str, payload: Value) -> bool {
+    let message = json!({
+        "metadata": {
+            "message_id": message_id,
+            "message_type": message_type,
+            "message_timestamp": timestamp,
+            "subscription_type": payload["subscription"]["type"],
+        },
+        "payload": payload,
+    });
+    WEBHOOK_INBOX
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|inbox| inbox.events.try_send(message).is_ok())
+}
+
+/// Subscribes to webhook deliveries & prints them until it's time to subscribe again, e.g. as
+/// one was revoked
+///
+/// Subscriptions made before are kept, so nothing is missed in between.
+async fn webhook_session(
+    reqwest: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    credentials: &mut Credentials,
+    subscriptions: &[Value],
+    webhook: &mut Webhook,
+) -> Result<()> {
+    let token = credentials
+        .access_token(reqwest, cancel_token, sender)
+        .await?;
+    let transport = json!({
+        "method": "webhook",
+        "callback": webhook.callback,
+        "secret": webhook.secret,
+    });
+    let subscribed = subscribe_all(
+        reqwest,
+        &credentials.client_id,
+        &token,
+        &transport,
+        subscriptions,
+    );
+    let subscribed = subscribed.await?.len();
+    info!("Subscribed to {subscribed} more Twitch events over webhooks");
+    status::service_ok("twitch");
+
+    let resubscribe_at = Instant::now() + WEBHOOK_RESUBSCRIBE_INTERVAL;
     loop {
-        let client_request = ClientRequestBuilder::new(
-            custom_connect_url
-                .as_ref()
-                .unwrap_or(&DEFAULT_WS_URL.to_string().into_boxed_str())
-                .parse()
-                .unwrap(),
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                return Ok(());
+            }
+            () = tokio::time::sleep_until(resubscribe_at) => return Ok(()),
+            message = webhook.events.recv() => {
+                let Some(data) = message else {
+                    return Ok(());
+                };
+                status::service_ok("twitch");
+                if data["metadata"]["message_type"] == "revocation" {
+                    let revoked = &data["payload"]["subscription"];
+                    warn!("Twitch revoked {} subscription: {}", revoked["type"], revoked["status"]);
+                    return Ok(());
+                }
+
+                notify(reqwest, cancel_token, sender, credentials, &data).await?;
+            }
+        }
+    }
+}
+
+/// Handles a text message of a WebSocket session; The URL Twitch asked to reconnect to, if any
+async fn handle_message(
+    reqwest: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    credentials: &mut Credentials,
+    session_id: &str,
+    active: &mut HashMap<String, &Value>,
+    data: &str,
+) -> Result<Option<String>> {
+    // info!("{data}");
+    let data = serde_json::from_str::<Value>(data)?;
+    let Value::String(message_type) = &data["metadata"]["message_type"] else {
+        error!("Twitch message is missing message_type\n{data}\nSkipping...");
+        return Ok(None);
+    };
+    match message_type.as_str() {
+        "session_keepalive" => {
+            status::service_ok("twitch");
+            // debug!("Keepalive message got");
+        }
+
+        "session_reconnect" => {
+            info!("Twitch sent reconnecting message!");
+            let url = str_at(&data, "/payload/session/reconnect_url")?;
+            return Ok(Some(url.to_string()));
+        }
+
+        "revocation" => {
+            let revoked = &data["payload"]["subscription"];
+            let resubscribed = resubscribe(
+                reqwest,
+                cancel_token,
+                sender,
+                credentials,
+                session_id,
+                active,
+                revoked,
+            );
+            resubscribed.await?;
+        }
+
+        "notification" => {
+            info!("Got a notification message!");
+            info!("Notification message: {data}");
+            notify(reqwest, cancel_token, sender, credentials, &data).await?;
+        }
+
+        other => {
+            error!("Unhandled message type: {other}");
+        }
+    };
+    Ok(None)
+}
+
+/// Prints a notification, see [`print_notification`]; Only fails on errors that stop the service,
+/// others are logged
+async fn notify(
+    reqwest: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    credentials: &mut Credentials,
+    data: &Value,
+) -> Result<()> {
+    match print_notification(reqwest, cancel_token, sender, credentials, data).await {
+        Ok(()) => Ok(()),
+        Err(e @ (Error::QueueClosed | Error::Unauthorized)) => Err(e),
+        Err(e) => {
+            error!("Unable to print Twitch notification: {e}");
+            Ok(())
+        }
+    }
+}
+
+/// Subscribes to every event over a transport, see [`subscribe`]; The subscriptions Twitch
+/// accepted, by their ID
+async fn subscribe_all<'a>(
+    reqwest: &Client,
+    client_id: &str,
+    token: &str,
+    transport: &Value,
+    subscriptions: &'a [Value],
+) -> Result<HashMap<String, &'a Value>> {
+    let mut active = HashMap::new();
+    for subscription in subscriptions {
+        if let Some(id) = subscribe(reqwest, client_id, token, transport, subscription).await? {
+            active.insert(id, subscription);
+        }
+    }
+    Ok(active)
+}
+
+/// Subscribes to an event of the session again once Twitch revoked it
+async fn resubscribe(
+    reqwest: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    credentials: &mut Credentials,
+    session_id: &str,
+    active: &mut HashMap<String, &Value>,
+    revoked: &Value,
+) -> Result<()> {
+    let status = str_at(revoked, "/status")?;
+    warn!("Twitch revoked {} subscription: {status}", revoked["type"]);
+    let Some(subscription) = active.remove(str_at(revoked, "/id")?) else {
+        return Ok(());
+    };
+    // Fails if its channel is gone, which is only logged; A revoked token is refreshed as usual
+    let token = credentials
+        .access_token(reqwest, cancel_token, sender)
+        .await?;
+    let transport = json!({ "method": "websocket", "session_id": session_id });
+    let subscribed = subscribe(
+        reqwest,
+        &credentials.client_id,
+        &token,
+        &transport,
+        subscription,
+    );
+    if let Some(id) = subscribed.await? {
+        info!("Subscribed to {} again", subscription["type"]);
+        active.insert(id, subscription);
+    }
+    Ok(())
+}
+
+/// Session ID & idle timeout of a connection, as told in its Welcome message; The timeout is
+/// padded by `keepalive_margin`, to be safe from latency
+fn welcomed(welcome_message: &Value, keepalive_margin: Duration) -> Result<(String, Duration)> {
+    let session_id = str_at(welcome_message, "/payload/session/id")?.to_string();
+    let idle_timeout = keepalive_timeout(welcome_message)? + keepalive_margin;
+    Ok((session_id, idle_timeout))
+}
+
+/// How long Twitch may go without sending anything, as told in a Welcome message
+fn keepalive_timeout(welcome_message: &Value) -> Result<Duration> {
+    const POINTER: &str = "/payload/session/keepalive_timeout_seconds";
+    welcome_message
+        .pointer(POINTER)
+        .and_then(Value::as_u64)
+        .map(Duration::from_secs)
+        .ok_or_else(|| Error::MissingField(POINTER.to_string()))
+}
+
+/// Subscribes to an event over a transport, see [`subscriptions`]; Its subscription ID, or None
+/// if Twitch refused it, e.g. for a missing scope or with the subscription limit reached, or it
+/// exists already
+async fn subscribe(
+    reqwest: &Client,
+    client_id: &str,
+    token: &str,
+    transport: &Value,
+    subscription: &Value,
+) -> Result<Option<String>> {
+    let mut subscription_body = subscription.clone();
+    subscription_body["transport"] = transport.clone();
+
+    let res = reqwest
+        .post(EVENT_SUBSCRIPTION_URL)
+        .header("Client-Id", client_id)
+        .bearer_auth(token)
+        .json(&subscription_body)
+        .send_retrying()
+        .await?;
+    let status = res.status();
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(Error::Unauthorized);
+    }
+    // Webhook subscriptions outlive the service
+    if status == StatusCode::CONFLICT {
+        debug!("Already subscribed to {}", subscription["type"]);
+        return Ok(None);
+    }
+    // Refusals are JSON too, with what went wrong
+    if status.is_client_error() {
+        let res = res.json::<Value>().await?;
+        let message = str_at(&res, "/message").unwrap_or_default();
+        warn!(
+            "Unable to subscribe to {} ({status}): {message}",
+            subscription["type"]
         );
-        let (mut stream, _response) = tokio_tungstenite::connect_async_tls_with_config(
-            client_request,
-            Some(WebSocketConfig {
-                accept_unmasked_frames: true,
-                ..Default::default()
-            }),
-            true,
-            Some(tokio_tungstenite::Connector::NativeTls(
-                native_tls::TlsConnector::new().unwrap(),
-            )),
-        )
-        .await
-        .unwrap();
-
-        // Skip 1, first message is Ping - Calling .skip() consumes the stream for some reason.
-        // Need to discover & refactor on how to do this properly
-        stream.next().await;
-        let Some(Ok(message)) = stream.next().await else {
-            // TODO: Handle this properly
-            panic!("Websocket instantly closed")
+        return Ok(None);
+    }
+    let res = res.error_for_status()?.json::<Value>().await?;
+    debug!("Subscription status for {subscription}: {status}\n{res}");
+    Ok(Some(str_at(&res, "/data/0/id")?.to_string()))
+}
+
+/// Connects to an EventSub WebSocket URL & waits for its Welcome message
+async fn connect(url: Uri) -> Result<(WsStream, Value)> {
+    let (mut stream, _response) = tokio_tungstenite::connect_async_tls_with_config(
+        ClientRequestBuilder::new(url),
+        Some(WebSocketConfig {
+            accept_unmasked_frames: true,
+            ..Default::default()
+        }),
+        true,
+        Some(tokio_tungstenite::Connector::NativeTls(
+            native_tls::TlsConnector::new()?,
+        )),
+    )
+    .await?;
+
+    // Pings may come first
+    loop {
+        let Some(message) = stream.next().await else {
+            return Err(tokio_tungstenite::tungstenite::Error::ConnectionClosed.into());
         };
+        if let Message::Text(welcome_text) = message? {
+            // info!("Welcome message: {welcome_text}");
+            return Ok((stream, serde_json::from_str(&welcome_text)?));
+        }
+    }
+}
 
-        // TODO: Handle this properly
-        let welcome_text = message.into_text().unwrap();
-        // info!("Welcome message: {welcome_text}");
-        let welcome_message = serde_json::from_str::<serde_json::Value>(&welcome_text)
-            .expect("Welcome message contains malformed JSON");
-
-        // Extract session id and subscribe to event
-        let session_id = &welcome_message["payload"]["session"]["id"];
-        info!("Session ID: {session_id}");
-        if custom_connect_url.is_none() {
-            // Default connect url = needs to (re)register subscriptions
-            for id in BROADCASTER_IDS {
-                let subscription_body = json!({
-                    "type": "stream.online",
-                    "version": "1",
-                    "condition": { "broadcaster_user_id": id },
-                    "transport": { "method": "websocket", "session_id": session_id }
-                });
-
-                let subscription_request = reqwest
-                    .post(EVENT_SUBSCRIPTION_URL)
-                    // https://twitchapps.com/tmi/
-                    .header("Client-Id", "q6batx0epp608isickayubi39itsckt")
-                    .bearer_auth(
-                        std::env::var("TWITCH_OAUTH_TOKEN").expect("Env var TWITCH_OAUTH_TOKEN is missing; Generate one on https://twitchapps.com/tmi/"),
+/// Prints a notification by its subscription type
+async fn print_notification(
+    reqwest: &Client,
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    credentials: &mut Credentials,
+    data: &Value,
+) -> Result<()> {
+    let event = data
+        .pointer("/payload/event")
+        .ok_or_else(|| Error::MissingField("/payload/event".to_string()))?;
+    let timestamp = DateTime::from_str(str_at(data, "/metadata/message_timestamp")?)?;
+    let base = PrintData {
+        service: Some("twitch".to_string()),
+        logo: Some("twitch".to_string()),
+        event_id: event["id"]
+            .as_str()
+            .or_else(|| data["metadata"]["message_id"].as_str())
+            .map(str::to_string),
+        timestamp,
+        ..Default::default()
+    };
+
+    let data = match str_at(data, "/metadata/subscription_type")? {
+        "stream.online" => {
+            let token = credentials
+                .access_token(reqwest, cancel_token, sender)
+                .await?;
+            return print_stream_online(reqwest, sender, &credentials.client_id, &token, data)
+                .await;
+        }
+        "channel.update" => {
+            let token = credentials
+                .access_token(reqwest, cancel_token, sender)
+                .await?;
+            let live = helix(
+                reqwest,
+                &credentials.client_id,
+                &token,
+                &format!("{STREAMS_URL}{}", str_at(event, "/broadcaster_user_id")?),
+            )
+            .await?
+            .pointer("/data/0")
+            .is_some();
+            channel_update_print_data(event, live, base)?
+        }
+        "stream.offline" => Some(stream_offline_print_data(event, base)?),
+        kind => channel_event_print_data(kind, event, base)?,
+    };
+    if let Some(data) = data {
+        sender.send(data).await?;
+    }
+    Ok(())
+}
+
+/// Compact receipt of a tracked channel switching category or changing its title mid-stream;
+/// None while offline, or if neither changed or they're not known yet
+///
+/// What they were before is kept in the state, as the event only has what they are now.
+fn channel_update_print_data(
+    event: &Value,
+    live: bool,
+    base: PrintData,
+) -> Result<Option<PrintData>> {
+    let id = str_at(event, "/broadcaster_user_id")?;
+    let name = str_at(event, "/broadcaster_user_name")?;
+    let title = str_at(event, "/title")?;
+    let category = str_at(event, "/category_name")?;
+    let previous_title = state::get("twitch", &format!("title:{id}"));
+    let previous_category = state::get("twitch", &format!("category:{id}"));
+    remember_channel(id, title, category);
+    if !live {
+        debug!("{name} updated their channel while offline");
+        return Ok(None);
+    }
+
+    let data = match previous_category {
+        Some(previous) if previous != category => PrintData {
+            title: format!("Twitch: {name} Switched Category"),
+            message: Some(format!("{previous} -> {category}\n{title}").into()),
+            ..base
+        },
+        _ if previous_title.is_some_and(|previous| previous != title) => PrintData {
+            title: format!("Twitch: {name} Changed Title"),
+            message: Some(format!("{title}\n\nCategory: {category}").into()),
+            ..base
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(PrintData {
+        priority: Priority::Low,
+        source: Some(name.to_string()),
+        ..data
+    }))
+}
+
+/// Receipt of a tracked channel going offline, with how long it was live if it's known when it
+/// went online, see [`print_stream_online`]
+fn stream_offline_print_data(event: &Value, base: PrintData) -> Result<PrintData> {
+    let id = str_at(event, "/broadcaster_user_id")?;
+    let name = str_at(event, "/broadcaster_user_name")?;
+    let started_at = state::get("twitch", &format!("started:{id}"))
+        .and_then(|started_at| DateTime::<Local>::from_str(&started_at).ok());
+    state::set("twitch", &format!("started:{id}"), None);
+
+    let mut message = format!("{name} ended their stream");
+    if let Some(started_at) = started_at {
+        let minutes = (base.timestamp - started_at).num_minutes().max(0);
+        let _ = match (minutes / 60, minutes % 60) {
+            (0, minutes) => write!(message, " (live for {minutes}m)"),
+            (hours, minutes) => write!(message, " (live for {hours}h {minutes}m)"),
+        };
+    }
+    Ok(PrintData {
+        priority: Priority::Low,
+        title: format!("Twitch: {name} is Offline"),
+        source: Some(name.to_string()),
+        message: Some(message.into()),
+        ..base
+    })
+}
+
+/// `████████░░░░░░░░░░░░`, full once the target is reached
+fn progress_bar(current: u64, target: u64) -> String {
+    let filled = usize::try_from(
+        (current * PROGRESS_BAR_WIDTH as u64)
+            .checked_div(target)
+            .unwrap_or_default(),
+    )
+    .map_or(PROGRESS_BAR_WIDTH, |filled| filled.min(PROGRESS_BAR_WIDTH));
+    format!(
+        "{}{}",
+        "█".repeat(filled),
+        "░".repeat(PROGRESS_BAR_WIDTH - filled)
+    )
+}
+
+/// Keeps a channel's title & category, to tell what changed on its next update
+fn remember_channel(id: &str, title: &str, category: &str) {
+    state::set("twitch", &format!("title:{id}"), Some(title));
+    state::set("twitch", &format!("category:{id}"), Some(category));
+}
+
+/// Subscription tier, e.g. `Tier 1` for Twitch's `1000`
+fn tier(event: &Value) -> Result<String> {
+    let tier = str_at(event, "/tier")?;
+    Ok(format!(
+        "Tier {}",
+        tier.parse::<u32>().map_or(0, |tier| tier / 1000)
+    ))
+}
+
+/// Receipt of an event of your own channel, see [`ChannelEvent`]; None for unknown ones
+fn channel_event_print_data(
+    kind: &str,
+    event: &Value,
+    base: PrintData,
+) -> Result<Option<PrintData>> {
+    // Anonymous gifts & cheers have no user
+    let user = str_at(event, "/user_name").unwrap_or("Anonymous");
+    let base = PrintData {
+        source: event["broadcaster_user_name"]
+            .as_str()
+            .or_else(|| event["to_broadcaster_user_name"].as_str())
+            .map(str::to_string),
+        ..base
+    };
+
+    let data = match kind {
+        "channel.follow" => PrintData {
+            priority: Priority::Low,
+            title: "Twitch: New Follower".to_string(),
+            message: Some(format!("{user} followed you").into()),
+            ..base
+        },
+
+        "channel.subscribe" => {
+            let gifted = event["is_gift"].as_bool().unwrap_or_default();
+            PrintData {
+                title: "Twitch: New Subscriber".to_string(),
+                message: Some(
+                    format!(
+                        "{user} subscribed at {}{}",
+                        tier(event)?,
+                        if gifted { ", gifted" } else { "" }
                     )
-                    .json(&subscription_body)
-                    .send()
-                    .await
-                    .expect("Unable to subscribe to Twitch Event");
-                debug!(
-                    "Subscription status for user {id}: {}",
-                    subscription_request.status()
-                );
-                let sub_res = subscription_request.text().await.unwrap();
-                debug!("{sub_res}");
+                    .into(),
+                ),
+                ..base
             }
         }
 
-        tokio::pin!(stream);
+        "channel.subscription.gift" => {
+            let total = event["total"].as_u64().unwrap_or_default();
+            let mut message = format!("{user} gifted {total} {} subs", tier(event)?);
+            if let Some(cumulative) = event["cumulative_total"].as_u64() {
+                let _ = write!(message, "\n{cumulative} gifted in total");
+            }
+            PrintData {
+                priority: Priority::High,
+                title: "Twitch: Gifted Subs".to_string(),
+                message: Some(message.into()),
+                ..base
+            }
+        }
 
-        loop {
-            tokio::select! {
-                () = cancel_token.cancelled() => {
-                    debug!("Cancel signal caught! Stopping service...");
-                    let _ = stream.close(None).await;
-                    break;
-                }
+        "channel.raid" => PrintData {
+            priority: Priority::High,
+            title: "Twitch: Raid".to_string(),
+            message: Some(
+                format!(
+                    "{} raided with {} viewers",
+                    str_at(event, "/from_broadcaster_user_name")?,
+                    event["viewers"].as_u64().unwrap_or_default()
+                )
+                .into(),
+            ),
+            ..base
+        },
 
-                // When client doesn't receive an event or keepalive message for longer
-                // than keepalive_timeout_seconds, Assume that the connection is lost
-                // They said 30s, but due to latency imma be safe and put it at 40s
-                () = tokio::time::sleep(Duration::from_secs(40)) => {
-                    error!("Didn't get any message for 40s, closing connection & reconnecting...");
-                    let _ = stream.close(None).await;
-                    // Also assume that session ID is gone
-                    custom_connect_url = None;
+        "channel.cheer" => cheer_print_data(user, event, base),
 
-                    break;
-                }
+        "user.whisper.message" => {
+            let from = str_at(event, "/from_user_name")?;
+            PrintData {
+                priority: Priority::High,
+                source: Some(from.to_string()),
+                title: "Twitch: Whisper".to_string(),
+                message: Some(
+                    vec![
+                        Span::bold(from),
+                        Span::plain(format!(":\n{}", str_at(event, "/whisper/text")?)),
+                    ]
+                    .into(),
+                ),
+                ..base
+            }
+        }
 
-                // Handle message normally
-                // Will be out of loop if stream is None or contains Err
-                // TODO: Handle if contains Err
-                Some(Ok(message)) = stream.next() => {
-                    match message {
-                        Message::Text(data) => {
-                            // info!("{data}");
-                            let data = serde_json::from_str::<serde_json::Value>(&data)
-                                .expect("Twitch stream did not return valid JSON");
-                            let String(message_type) = &data["metadata"]["message_type"] else {
-                                error!("Twitch message is missing message_type\n{data}\nSkipping...");
-                                continue;
-                            };
-                            match message_type.as_str() {
-                                "session_keepalive" => {
-                                    // debug!("Keepalive message got");
-                                }
-
-                                "session_reconnect" => {
-                                    info!("Twitch sent reconnecting message!");
-                                    // TODO: Twitch docs says "You should not close the old connection until you receive a Welcome message on the new connection"
-                                    // I cba to implement this with current code structure, so i'm just gonna remake the connection from scratch
-
-                                    // let reconnect_url = data["payload"]["session"]["reconnect_url"].as_str().unwrap();
-                                    // custom_connect_url = Some(reconnect_url.to_string().into_boxed_str());
-                                    break;
-                                }
-
-                                "notification" => {
-                                    info!("Got a notification message!");
-
-                                    // Directly assume that event will be `stream.online`
-                                    // Handle more events here when I do add more ws events
-                                    info!("Notification message: {data}");
-                                    let String(channel_id) = &data["payload"]["event"]["broadcaster_user_id"]
-                                    else {
-                                        error!("Twitch notification is missing `broadcaster_user_id`\n{data}\nSkipping...");
-                                        continue;
-                                    };
-
-                                    // Get channel info for stream title, category & game details
-                                    let channel_info_req = reqwest
-                                        .get(format!("{CHANNEL_INFO_URL}{channel_id}"))
-                                        .header("Client-Id", "q6batx0epp608isickayubi39itsckt")
-                                        .bearer_auth(std::env::var("TWITCH_OAUTH_TOKEN").unwrap())
-                                        .send()
-                                        .await
-                                        .expect("Unable to fetch more streamer detail");
-                                    let Ok(channel_info) = channel_info_req.json::<serde_json::Value>().await
-                                    else {
-                                        error!("Unable to parse Twitch Channel Info JSON");
-                                        continue;
-                                    };
-                                    info!("Channel info: {channel_info}");
-                                    let channel_info = channel_info["data"].as_array().unwrap().first().unwrap();
-
-                                    let stream_title = channel_info["title"].as_str().unwrap().to_string();
-                                    let game_name =  channel_info["game_name"].as_str().unwrap();
-
-                                    let tags = channel_info["tags"].as_array().unwrap();
-                                    let tags_stringified: Vec<&str> = tags.iter().map(|t| { t.as_str().unwrap() }).collect();
-                                    let tags_joined = tags_stringified.join(", ");
-
-                                    sender
-                                        .send(PrintData {
-                                            title: format!(
-                                                "Twitch: {} is Live",
-                                                channel_info["broadcaster_name"].as_str().unwrap()
-                                            ),
-                                            subtitle: None,
-                                            message: Some(format!("{stream_title}\n\nCategory: {game_name}\nTags: {tags_joined}")),
-                                            timestamp: DateTime::from_str(
-                                                data["metadata"]["message_timestamp"].as_str().unwrap(),
-                                            )
-                                            .unwrap(),
-                                        })
-                                        .await
-                                        .unwrap();
-                                }
-
-                                other => {
-                                    error!("Unhandled message type: {other}");
-                                }
-                            };
-
-                        },
-
-                        Message::Ping(_) |  Message::Pong(_) | Message::Frame(_) => {},
-                        Message::Binary(vec) => {
-                            info!("Twitch set binary message: {:?}", vec);
-                        },
-                        Message::Close(frame) => {
-                            error!("Twitch ended websocket connection");
-                            if let Some(frame) = frame {
-                                error!("Close frame: {frame:?}");
-                            }
-                            break;
-                        },
-                    }
+        "channel.hype_train.begin" | "channel.hype_train.progress" | "channel.hype_train.end" => {
+            return hype_train_print_data(kind, event, base)
+        }
+
+        "channel.goal.begin" | "channel.goal.progress" | "channel.goal.end" => {
+            return goal_print_data(kind, event, base);
+        }
+
+        other => {
+            error!("Unhandled Twitch subscription type: {other}");
+            return Ok(None);
+        }
+    };
+    Ok(Some(data))
+}
 
+/// Receipt of bits cheered, with the message they came with
+fn cheer_print_data(user: &str, event: &Value, base: PrintData) -> PrintData {
+    let bits = event["bits"].as_u64().unwrap_or_default();
+    let mut message = format!("{user} cheered {bits} bits");
+    if let Some(text) = event["message"].as_str().filter(|text| !text.is_empty()) {
+        let _ = write!(message, "\n\n{text}");
+    }
+    PrintData {
+        priority: if bits >= 1000 {
+            Priority::High
+        } else {
+            Priority::Normal
+        },
+        title: "Twitch: Cheer".to_string(),
+        message: Some(message.into()),
+        ..base
+    }
+}
+
+/// Receipt of a hype train starting, reaching another level or ending; None for progress within
+/// the same level
+fn hype_train_print_data(kind: &str, event: &Value, base: PrintData) -> Result<Option<PrintData>> {
+    if kind.ends_with("end") {
+        state::set("twitch", "hype_train_level", None);
+        return Ok(Some(PrintData {
+            event_id: Some(format!("{}:end", str_at(event, "/id")?)),
+            priority: Priority::High,
+            title: "Twitch: Hype Train Ended".to_string(),
+            message: Some(
+                format!(
+                    "*** Reached level {} ***\n{} points in total",
+                    event["level"].as_u64().unwrap_or_default(),
+                    event["total"].as_u64().unwrap_or_default()
+                )
+                .into(),
+            ),
+            ..base
+        }));
+    }
+
+    let level = event["level"].as_u64().unwrap_or(1);
+    // Progress is sent on every contribution, only new levels are printed
+    let last_level =
+        state::get("twitch", "hype_train_level").and_then(|level| level.parse::<u64>().ok());
+    if kind.ends_with("progress") && last_level.is_some_and(|last| last >= level) {
+        return Ok(None);
+    }
+    state::set("twitch", "hype_train_level", Some(&level.to_string()));
+    let progress = progress_bar(
+        event["progress"].as_u64().unwrap_or_default(),
+        event["goal"].as_u64().unwrap_or_default(),
+    );
+    Ok(Some(PrintData {
+        event_id: Some(format!("{}:level:{level}", str_at(event, "/id")?)),
+        priority: Priority::High,
+        title: if kind.ends_with("begin") {
+            "Twitch: Hype Train!".to_string()
+        } else {
+            format!("Twitch: Hype Train Level {level}!")
+        },
+        message: Some(format!("*** Level {level} ***\n{progress}").into()),
+        ..base
+    }))
+}
+
+/// Receipt of a goal starting, ending or reaching another quarter of its target; None for
+/// progress within the same quarter
+fn goal_print_data(kind: &str, event: &Value, base: PrintData) -> Result<Option<PrintData>> {
+    let id = str_at(event, "/id")?;
+    let current = event["current_amount"].as_u64().unwrap_or_default();
+    let target = event["target_amount"].as_u64().unwrap_or_default();
+    let quarter = (current * 4).checked_div(target).unwrap_or_default().min(4);
+    let milestone_key = format!("goal_quarter:{id}");
+    let (title, priority, event_id) = match kind {
+        "channel.goal.begin" => ("Twitch: New Goal", Priority::Low, "begin".to_string()),
+        // Progress is sent on every contribution, only new quarters are printed
+        "channel.goal.progress" => {
+            let last_quarter = state::get("twitch", &milestone_key)
+                .and_then(|quarter| quarter.parse::<u64>().ok());
+            if quarter == 0 || last_quarter.is_some_and(|last| last >= quarter) {
+                return Ok(None);
+            }
+            state::set("twitch", &milestone_key, Some(&quarter.to_string()));
+            (
+                "Twitch: Goal Progress",
+                Priority::Normal,
+                format!("quarter:{quarter}"),
+            )
+        }
+        _ if event["is_achieved"].as_bool().unwrap_or_default() => {
+            state::set("twitch", &milestone_key, None);
+            ("Twitch: Goal Reached!", Priority::High, "end".to_string())
+        }
+        _ => {
+            state::set("twitch", &milestone_key, None);
+            ("Twitch: Goal Ended", Priority::Low, "end".to_string())
+        }
+    };
+    let unit = match str_at(event, "/type")? {
+        "follow" => "followers",
+        "subscription" | "new_subscription" => "sub points",
+        "subscription_count" | "new_subscription_count" => "subs",
+        "new_bit" => "bits",
+        "new_cheerer" => "cheerers",
+        _ => "",
+    };
+    let mut message = format!(
+        "{}\n{current}/{target} {unit}",
+        progress_bar(current, target)
+    );
+    if let Some(description) = event["description"].as_str().filter(|d| !d.is_empty()) {
+        message = format!("{description}\n{message}");
+    }
+    Ok(Some(PrintData {
+        event_id: Some(format!("{id}:{event_id}")),
+        priority,
+        title: title.to_string(),
+        message: Some(message.into()),
+        ..base
+    }))
+}
+
+/// Events of your own channel (`TWITCH_CHANNEL_ID`) printed besides streams going live, listed
+/// in `TWITCH_CHANNEL_EVENTS`, e.g. `follow,raid`; Each needs its own scope, asked for when
+/// logging in, and whispers need a verified phone number too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelEvent {
+    Follow,
+    /// Gifted subs as well
+    Subscribe,
+    Raid,
+    Cheer,
+    /// Sent to you, rather than your channel
+    Whisper,
+    /// Its start, every level it reaches & its end
+    HypeTrain,
+    /// Creator goals starting, every quarter of the way & ending
+    Goal,
+}
+
+impl FromStr for ChannelEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "follow" => Ok(Self::Follow),
+            "subscribe" => Ok(Self::Subscribe),
+            "raid" => Ok(Self::Raid),
+            "cheer" => Ok(Self::Cheer),
+            "whisper" => Ok(Self::Whisper),
+            "hype_train" => Ok(Self::HypeTrain),
+            "goal" => Ok(Self::Goal),
+            other => Err(format!(
+                "Unknown event `{other}`; expected follow, subscribe, raid, cheer, whisper, \
+                hype_train or goal"
+            )),
+        }
+    }
+}
+
+impl ChannelEvent {
+    const fn scope(self) -> Option<&'static str> {
+        match self {
+            Self::Follow => Some("moderator:read:followers"),
+            Self::Subscribe => Some("channel:read:subscriptions"),
+            Self::Raid => None,
+            Self::Cheer => Some("bits:read"),
+            Self::Whisper => Some("user:read:whispers"),
+            Self::HypeTrain => Some("channel:read:hype_train"),
+            Self::Goal => Some("channel:read:goals"),
+        }
+    }
+
+    /// EventSub subscriptions of the event, without their transport
+    fn subscriptions(self, channel_id: &str) -> Vec<Value> {
+        let subscription = |kind: &str, version: &str, condition: Value| {
+            json!({
+                "type": kind,
+                "version": version,
+                "condition": condition,
+            })
+        };
+        let channel = json!({ "broadcaster_user_id": channel_id });
+        match self {
+            // Followers are only listed to moderators, which includes the broadcaster
+            Self::Follow => vec![subscription(
+                "channel.follow",
+                "2",
+                json!({ "broadcaster_user_id": channel_id, "moderator_user_id": channel_id }),
+            )],
+            Self::Subscribe => vec![
+                subscription("channel.subscribe", "1", channel.clone()),
+                subscription("channel.subscription.gift", "1", channel),
+            ],
+            Self::Raid => vec![subscription(
+                "channel.raid",
+                "1",
+                json!({ "to_broadcaster_user_id": channel_id }),
+            )],
+            Self::Cheer => vec![subscription("channel.cheer", "1", channel)],
+            Self::Whisper => vec![subscription(
+                "user.whisper.message",
+                "1",
+                json!({ "user_id": channel_id }),
+            )],
+            Self::HypeTrain => ["begin", "progress", "end"]
+                .map(|stage| {
+                    subscription(&format!("channel.hype_train.{stage}"), "2", channel.clone())
+                })
+                .into(),
+            Self::Goal => ["begin", "progress", "end"]
+                .map(|stage| subscription(&format!("channel.goal.{stage}"), "1", channel.clone()))
+                .into(),
+        }
+    }
+}
+
+/// EventSub subscriptions to make on every new session, without their transport
+fn subscriptions(
+    broadcaster_ids: &[String],
+    channel_id: Option<&str>,
+    channel_events: &[ChannelEvent],
+) -> Vec<Value> {
+    let mut subscriptions = broadcaster_ids
+        .iter()
+        .flat_map(|id| {
+            [
+                ("stream.online", "1"),
+                ("stream.offline", "1"),
+                ("channel.update", "2"),
+            ]
+            .map(|(kind, version)| {
+                json!({
+                    "type": kind,
+                    "version": version,
+                    "condition": { "broadcaster_user_id": id },
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    if let Some(channel_id) = channel_id {
+        for event in channel_events {
+            subscriptions.extend(event.subscriptions(channel_id));
+        }
+    }
+    subscriptions
+}
+
+/// Prints a `stream.online` event, with the channel's title, category & tags
+async fn print_stream_online(
+    reqwest: &Client,
+    sender: &Sender<PrintData>,
+    client_id: &str,
+    token: &str,
+    data: &Value,
+) -> Result<()> {
+    let channel_id = str_at(data, "/payload/event/broadcaster_user_id")?;
+    // For how long it was live once it goes offline
+    let started_at = str_at(data, "/payload/event/started_at")?;
+    state::set("twitch", &format!("started:{channel_id}"), Some(started_at));
+
+    // Get channel info for stream title, category & game details
+    let channel_info = helix(
+        reqwest,
+        client_id,
+        token,
+        &format!("{CHANNEL_INFO_URL}{channel_id}"),
+    )
+    .await?;
+    info!("Channel info: {channel_info}");
+    let channel_info = channel_info
+        .pointer("/data/0")
+        .ok_or_else(|| Error::MissingField("/data/0".to_string()))?;
+
+    let stream_title = str_at(channel_info, "/title")?;
+    let game_name = str_at(channel_info, "/game_name")?;
+    let broadcaster_name = str_at(channel_info, "/broadcaster_name")?;
+    remember_channel(channel_id, stream_title, game_name);
+    let tags_joined = channel_info["tags"]
+        .as_array()
+        .map(|tags| tags.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .join(", ");
+
+    sender
+        .send(PrintData {
+            service: Some("twitch".to_string()),
+            logo: Some("twitch".to_string()),
+            event_id: data["payload"]["event"]["id"].as_str().map(str::to_string),
+            title: format!("Twitch: {broadcaster_name} is Live"),
+            source: Some(broadcaster_name.to_string()),
+            subtitle: None,
+            message: Some(
+                format!("{stream_title}\n\nCategory: {game_name}\nTags: {tags_joined}").into(),
+            ),
+            timestamp: DateTime::from_str(str_at(data, "/metadata/message_timestamp")?)?,
+            ..Default::default()
+        })
+        .await?;
+    Ok(())
+}
+
+/// JSON at a Helix API URL
+async fn helix(reqwest: &Client, client_id: &str, token: &str, url: &str) -> Result<Value> {
+    let res = reqwest
+        .get(url)
+        .header("Client-Id", client_id)
+        .bearer_auth(token)
+        .send_retrying()
+        .await?;
+    if res.status() == StatusCode::UNAUTHORIZED {
+        return Err(Error::Unauthorized);
+    }
+    Ok(res.error_for_status()?.json().await?)
+}
+
+/// Token for the Helix API; Either `TWITCH_OAUTH_TOKEN` as is, or tokens of an app of your own
+/// (`TWITCH_CLIENT_ID`, with `TWITCH_CLIENT_SECRET` if it's confidential) refreshed as they
+/// expire
+///
+/// Refreshed tokens are kept in the state file, see [`state`]; Without one, you're asked to log in
+/// again on every start. Logging in is done through the device code flow, printing a receipt
+/// with the code & a QR code of the page to enter it on.
+struct Credentials {
+    client_id: String,
+    /// Asked for when logging in, space separated; subscriptions to tracked channels need none
+    scopes: String,
+    client_secret: Option<String>,
+    access_token: Option<String>,
+    /// When the access token has to be validated (or refreshed) again; None until validated
+    valid_until: Option<Instant>,
+    refresh_token: Option<String>,
+    /// Whether tokens are refreshed, rather than a static `TWITCH_OAUTH_TOKEN`
+    refreshable: bool,
+    /// Whether tokens are the app's own rather than a user's, see [`Webhook`]
+    app: bool,
+}
+
+#[derive(Deserialize)]
+struct AppTokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+impl Credentials {
+    fn from_env(scopes: String) -> Result<Self> {
+        if let Ok(client_id) = secrets::var("TWITCH_CLIENT_ID") {
+            return Ok(Self {
+                client_id,
+                scopes,
+                client_secret: secrets::var("TWITCH_CLIENT_SECRET").ok(),
+                access_token: state::get("twitch", "access_token"),
+                valid_until: None,
+                refresh_token: state::get("twitch", "refresh_token"),
+                refreshable: true,
+                app: false,
+            });
+        }
+
+        Ok(Self {
+            client_id: CLIENT_ID.to_string(),
+            scopes,
+            client_secret: None,
+            access_token: Some(error::env("TWITCH_OAUTH_TOKEN")?),
+            valid_until: None,
+            refresh_token: None,
+            refreshable: false,
+            app: false,
+        })
+    }
+
+    /// App of your own (`TWITCH_CLIENT_ID` & `TWITCH_CLIENT_SECRET`), as webhook subscriptions
+    /// can't be made by users
+    fn app_from_env() -> Result<Self> {
+        Ok(Self {
+            client_id: error::env("TWITCH_CLIENT_ID")?,
+            scopes: String::new(),
+            client_secret: Some(error::env("TWITCH_CLIENT_SECRET")?),
+            access_token: None,
+            valid_until: None,
+            refresh_token: None,
+            refreshable: true,
+            app: true,
+        })
+    }
+
+    /// Has the access token validated again before its next use, e.g. after it was rejected
+    const fn expire(&mut self) {
+        self.valid_until = None;
+    }
+
+    /// Returns a valid access token, refreshing it once expired & logging in if need be
+    async fn access_token(
+        &mut self,
+        reqwest: &Client,
+        cancel_token: &CancellationToken,
+        sender: &Sender<PrintData>,
+    ) -> Result<String> {
+        if let Some(token) = &self.access_token {
+            if self.valid_until.is_some_and(|until| until > Instant::now()) {
+                return Ok(token.clone());
+            }
+            if let Some(expires_in) = validate(reqwest, token).await? {
+                self.valid_until = Some(Instant::now() + valid_for(expires_in));
+                return Ok(token.clone());
+            }
+        }
+        if !self.refreshable {
+            return Err(Error::Unauthorized);
+        }
+        if self.app {
+            let tokens = self.app_token(reqwest).await?;
+            self.valid_until = Some(Instant::now() + valid_for(tokens.expires_in));
+            return Ok(self.access_token.insert(tokens.access_token).clone());
+        }
+
+        let refreshed = match &self.refresh_token {
+            Some(refresh_token) => match self.refresh(reqwest, refresh_token).await {
+                Ok(tokens) => Some(tokens),
+                Err(e) => {
+                    warn!("Unable to refresh Twitch token, logging in again: {e}");
+                    None
                 }
+            },
+            None => None,
+        };
+        let tokens = match refreshed {
+            Some(tokens) => tokens,
+            None => self.log_in(reqwest, cancel_token, sender).await?,
+        };
+
+        state::set("twitch", "access_token", Some(&tokens.access_token));
+        state::set("twitch", "refresh_token", Some(&tokens.refresh_token));
+        self.valid_until = Some(Instant::now() + valid_for(tokens.expires_in));
+        self.refresh_token = Some(tokens.refresh_token);
+        Ok(self.access_token.insert(tokens.access_token).clone())
+    }
+
+    /// Form fields identifying the app to the token endpoint
+    fn app_fields(&self) -> Vec<(&str, &str)> {
+        let mut fields = vec![("client_id", self.client_id.as_str())];
+        if let Some(secret) = &self.client_secret {
+            fields.push(("client_secret", secret));
+        }
+        fields
+    }
+
+    async fn refresh(&self, reqwest: &Client, refresh_token: &str) -> Result<TokenResponse> {
+        debug!("Refreshing Twitch access token");
+        let mut form = self.app_fields();
+        form.extend([
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ]);
+        let res = reqwest.post(TOKEN_URL).form(&form).send_retrying().await?;
+        match res.status() {
+            StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => Err(Error::Unauthorized),
+            _ => Ok(res.error_for_status()?.json().await?),
+        }
+    }
+
+    /// Gets an app access token through the client credentials flow; Never stored, as it's
+    /// quick to get again
+    async fn app_token(&self, reqwest: &Client) -> Result<AppTokenResponse> {
+        debug!("Getting Twitch app access token");
+        let mut form = self.app_fields();
+        form.push(("grant_type", "client_credentials"));
+        let res = reqwest.post(TOKEN_URL).form(&form).send_retrying().await?;
+        match res.status() {
+            StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err(Error::Unauthorized)
             }
+            _ => Ok(res.error_for_status()?.json().await?),
         }
+    }
 
-        // Check if we break out of loop because of cancel token
-        if cancel_token.is_cancelled() {
-            break;
+    /// Logs in through the device code flow, waiting for the code printed to be entered
+    async fn log_in(
+        &self,
+        reqwest: &Client,
+        cancel_token: &CancellationToken,
+        sender: &Sender<PrintData>,
+    ) -> Result<TokenResponse> {
+        let mut form = self.app_fields();
+        form.push(("scopes", &self.scopes));
+        let device: DeviceCode = reqwest
+            .post(DEVICE_URL)
+            .form(&form)
+            .send_retrying()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        info!(
+            "Log into Twitch at {} with code {}",
+            device.verification_uri, device.user_code
+        );
+        sender
+            .send(PrintData {
+                service: Some("twitch".to_string()),
+                logo: Some("twitch".to_string()),
+                priority: Priority::High,
+                title: "Twitch: Log in".to_string(),
+                subtitle: Some(format!("Code: {}", device.user_code)),
+                message: Some(
+                    format!(
+                        "Scan the code or open {} to let notifi-printer watch for streams",
+                        device.verification_uri
+                    )
+                    .into(),
+                ),
+                qr_codes: vec![QrCode {
+                    caption: None,
+                    data: device.verification_uri.clone(),
+                }],
+                timestamp: Local::now(),
+                ..Default::default()
+            })
+            .await?;
+
+        form.extend([
+            ("device_code", device.device_code.as_str()),
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+        ]);
+        let expires_at = Instant::now() + Duration::from_secs(device.expires_in);
+        while Instant::now() < expires_at {
+            tokio::select! {
+                () = cancel_token.cancelled() => return Err(Error::Cancelled),
+                () = tokio::time::sleep(Duration::from_secs(device.interval.max(1))) => {}
+            }
+
+            let res = reqwest.post(TOKEN_URL).form(&form).send_retrying().await?;
+            if res.status().is_success() {
+                info!("Logged into Twitch!");
+                return Ok(res.json().await?);
+            }
+            let status = res.status();
+            let body: Value = res.json().await?;
+            if body["message"].as_str() != Some("authorization_pending") {
+                error!("Unable to log into Twitch: {body}");
+                return Err(Error::Status(status));
+            }
         }
+
+        Err(Error::Unauthorized)
+    }
+}
+
+/// How long a token is valid for, per [`VALIDATE_INTERVAL`]; Refreshed a minute early so it
+/// never expires mid-request
+fn valid_for(expires_in: u64) -> Duration {
+    match expires_in {
+        // Tokens that never expire
+        0 => VALIDATE_INTERVAL,
+        _ => Duration::from_secs(expires_in.saturating_sub(60)).min(VALIDATE_INTERVAL),
+    }
+}
+
+/// Seconds until the token expires; None if it's invalid
+async fn validate(reqwest: &Client, token: &str) -> Result<Option<u64>> {
+    let res = reqwest
+        .get(VALIDATE_URL)
+        .header("Authorization", format!("OAuth {token}"))
+        .send_retrying()
+        .await?;
+    if res.status() == StatusCode::UNAUTHORIZED {
+        return Ok(None);
     }
+    let res: Value = res.error_for_status()?.json().await?;
+    Ok(res["expires_in"].as_u64())
 }
@@ -0,0 +1,268 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Local};
+use futures_util::{SinkExt, StreamExt};
+use tokio::sync::mpsc::Sender;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace};
+
+use crate::{
+    error::{self, Result},
+    printer::{PrintData, Priority, QrCode, Span},
+    retry::Backoff,
+    secrets, status,
+};
+
+const CHAT_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+
+/// Twitch pings about every 5 minutes; Without anything for longer, the connection is assumed lost
+const IDLE_TIMEOUT: Duration = Duration::from_mins(6);
+
+/// Wait before reconnecting after the connection failed, doubled on every failure in a row
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
+/// Watches the chat of the channels in `TWITCH_CHAT_CHANNELS`, printing messages that mention
+/// `TWITCH_CHAT_USERNAME` or have any of `TWITCH_CHAT_KEYWORDS` in them
+///
+/// Chat is read anonymously, so no token is needed.
+#[instrument(skip(cancel_token, sender))]
+pub async fn start_service(cancel_token: CancellationToken, sender: Sender<PrintData>) {
+    let channels = match error::env("TWITCH_CHAT_CHANNELS") {
+        Ok(channels) => list(&channels),
+        Err(e) => {
+            info!("{e}, Twitch chat service disabled");
+            return;
+        }
+    };
+    let watch = Watch {
+        username: secrets::var("TWITCH_CHAT_USERNAME")
+            .ok()
+            .map(|username| username.trim().to_lowercase()),
+        keywords: secrets::var("TWITCH_CHAT_KEYWORDS")
+            .map(|keywords| list(&keywords))
+            .unwrap_or_default(),
+    };
+    if watch.username.is_none() && watch.keywords.is_empty() {
+        info!("Neither TWITCH_CHAT_USERNAME nor TWITCH_CHAT_KEYWORDS set, Twitch chat disabled");
+        return;
+    }
+
+    let mut backoff = Backoff::new(RETRY_DELAY, MAX_RETRY_DELAY);
+    loop {
+        let delay = match session(&cancel_token, &sender, &channels, &watch).await {
+            Ok(()) => {
+                backoff.reset();
+                None
+            }
+            Err(e) if e.is_transient() => {
+                let delay = backoff.next_delay();
+                error!("Twitch chat connection failed, reconnecting in {delay:?}: {e}");
+                Some(delay)
+            }
+            Err(e) => {
+                error!("Stopping Twitch chat service: {e}");
+                break;
+            }
+        };
+        if let Some(delay) = delay {
+            tokio::select! {
+                () = cancel_token.cancelled() => {}
+                () = tokio::time::sleep(delay) => {}
+            }
+        }
+
+        if cancel_token.is_cancelled() {
+            info!("Stopping service due to cancel token...");
+            break;
+        }
+    }
+}
+
+/// Lowercased, comma separated list, e.g. of channel logins
+fn list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Joins the channels' chat & prints matching messages until cancelled or the connection is lost
+async fn session(
+    cancel_token: &CancellationToken,
+    sender: &Sender<PrintData>,
+    channels: &[String],
+    watch: &Watch,
+) -> Result<()> {
+    let (mut stream, _response) = tokio_tungstenite::connect_async(CHAT_URL).await?;
+    // Tags carry display names, message IDs & when they were sent
+    stream
+        .send(Message::Text("CAP REQ :twitch.tv/tags".to_string()))
+        .await?;
+    // `justinfan` users are anonymous, only able to read chat
+    let nick = format!("NICK justinfan{}", fastrand::u32(10_000..100_000));
+    stream.send(Message::Text(nick)).await?;
+    let join = channels
+        .iter()
+        .map(|channel| format!("#{channel}"))
+        .collect::<Vec<_>>()
+        .join(",");
+    stream.send(Message::Text(format!("JOIN {join}"))).await?;
+    info!("Watching Twitch chat of {}", channels.join(", "));
+    status::service_ok("twitch_chat");
+
+    loop {
+        tokio::select! {
+            () = cancel_token.cancelled() => {
+                debug!("Cancel signal caught! Stopping service...");
+                let _ = stream.close(None).await;
+                return Ok(());
+            }
+
+            () = tokio::time::sleep(IDLE_TIMEOUT) => {
+                error!("Didn't get anything from Twitch chat for {IDLE_TIMEOUT:?}, reconnecting");
+                let _ = stream.close(None).await;
+                return Ok(());
+            }
+
+            message = stream.next() => {
+                let Some(message) = message else {
+                    error!("Twitch chat stream ended");
+                    return Ok(());
+                };
+                let text = match message? {
+                    Message::Text(text) => text,
+                    Message::Close(frame) => {
+                        error!("Twitch ended chat connection: {frame:?}");
+                        return Ok(());
+                    }
+                    _ => continue,
+                };
+                // A message may hold several lines
+                for line in text.lines() {
+                    trace!("{line}");
+                    if let Some(server) = line.strip_prefix("PING ") {
+                        stream.send(Message::Text(format!("PONG {server}"))).await?;
+                        status::service_ok("twitch_chat");
+                    } else if let Some(message) = ChatMessage::parse(line) {
+                        if let Some(data) = watch.print_data(&message) {
+                            sender.send(data).await?;
+                        }
+                    } else if line.ends_with(" RECONNECT") {
+                        info!("Twitch chat asked to reconnect");
+                        let _ = stream.close(None).await;
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `PRIVMSG` of a channel's chat, e.g.
+/// `@display-name=Someone;id=... :someone!someone@someone.tmi.twitch.tv PRIVMSG #channel :hi`
+struct ChatMessage<'a> {
+    tags: HashMap<&'a str, &'a str>,
+    /// Sender's login, lowercase
+    login: &'a str,
+    channel: &'a str,
+    text: &'a str,
+}
+
+impl<'a> ChatMessage<'a> {
+    /// None for other kinds of lines, e.g. joins
+    fn parse(line: &'a str) -> Option<Self> {
+        let (tags, line) = match line.strip_prefix('@') {
+            Some(line) => line.split_once(' ')?,
+            None => ("", line),
+        };
+        let (prefix, line) = line.strip_prefix(':')?.split_once(' ')?;
+        let (channel, text) = line.strip_prefix("PRIVMSG #")?.split_once(" :")?;
+        // `/me` messages are wrapped as CTCP actions
+        let text = text
+            .strip_prefix("\u{1}ACTION ")
+            .and_then(|text| text.strip_suffix('\u{1}'))
+            .unwrap_or(text);
+        Some(Self {
+            tags: tags
+                .split(';')
+                .filter_map(|tag| tag.split_once('='))
+                .collect(),
+            login: prefix.split('!').next()?,
+            channel,
+            text,
+        })
+    }
+
+    fn tag(&self, key: &str) -> Option<&'a str> {
+        self.tags
+            .get(key)
+            .copied()
+            .filter(|value| !value.is_empty())
+    }
+}
+
+/// What chat messages are printed for
+struct Watch {
+    /// Lowercase
+    username: Option<String>,
+    /// Lowercase
+    keywords: Vec<String>,
+}
+
+impl Watch {
+    /// Receipt of a message mentioning you or with a keyword in it; None otherwise, or if it's
+    /// your own
+    fn print_data(&self, message: &ChatMessage) -> Option<PrintData> {
+        if self.username.as_deref() == Some(message.login) {
+            return None;
+        }
+        let text = message.text.to_lowercase();
+        // Whole words only, with or without an `@`
+        let mentioned = self.username.as_ref().is_some_and(|username| {
+            text.split(|c: char| !c.is_alphanumeric() && c != '_')
+                .any(|word| word == username)
+        });
+        let keyword = self
+            .keywords
+            .iter()
+            .find(|keyword| text.contains(keyword.as_str()));
+        let channel = message.channel;
+        let (title, priority) = match (mentioned, keyword) {
+            (true, _) => (format!("Twitch: Mentioned in {channel}"), Priority::High),
+            (false, Some(keyword)) => (
+                format!("Twitch: \"{keyword}\" in {channel}"),
+                Priority::Normal,
+            ),
+            (false, None) => return None,
+        };
+        let timestamp = message
+            .tag("tmi-sent-ts")
+            .and_then(|ms| ms.parse().ok())
+            .and_then(DateTime::from_timestamp_millis)
+            .map_or_else(Local::now, |sent| sent.with_timezone(&Local));
+
+        Some(PrintData {
+            service: Some("twitch_chat".to_string()),
+            logo: Some("twitch".to_string()),
+            event_id: message.tag("id").map(ToString::to_string),
+            source: Some(channel.to_string()),
+            priority,
+            title,
+            message: Some(
+                vec![
+                    Span::bold(message.tag("display-name").unwrap_or(message.login)),
+                    Span::plain(format!(":\n{}", message.text)),
+                ]
+                .into(),
+            ),
+            qr_codes: vec![QrCode {
+                caption: Some("Open".to_string()),
+                data: format!("https://www.twitch.tv/{channel}"),
+            }],
+            timestamp,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+use tokio::io::AsyncWriteExt;
+
+use super::{Delivery, Sink};
+use crate::{
+    printer::{PrintData, TIMESTAMP_FORMAT},
+    secrets,
+};
+
+/// Appends prints as plain text to the file at `SINK_FILE_PATH`, e.g. to `tail -f` or grep
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn from_env() -> Option<Self> {
+        let path = secrets::var("SINK_FILE_PATH").ok()?;
+        Some(Self { path: path.into() })
+    }
+}
+
+impl Sink for FileSink {
+    fn deliver<'a>(&'a self, data: &'a PrintData, _job: &'a [u8]) -> Delivery<'a> {
+        Box::pin(async move {
+            let entry = format!(
+                "[{}] {}\n{}\n\n",
+                data.timestamp.format(TIMESTAMP_FORMAT),
+                data.title,
+                super::body(data)
+            );
+            tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?
+                .write_all(entry.as_bytes())
+                .await?;
+            Ok(())
+        })
+    }
+}
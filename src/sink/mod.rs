@@ -0,0 +1,146 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::{
+    error::Result,
+    printer::{Message, PrintData, Priority},
+    secrets,
+};
+
+pub mod file;
+pub mod ntfy;
+pub mod spool;
+pub mod telegram;
+
+/// Sinks prints are fanned out to besides the printer, once [`open`]ed
+static SINKS: Mutex<Vec<Arc<Route>>> = Mutex::new(Vec::new());
+
+/// Delivery in progress; Boxed so sinks of different kinds can be kept together
+pub type Delivery<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// Destination prints are copied to besides the printer, e.g. a phone through ntfy
+pub trait Sink: Send + Sync {
+    /// Delivers a print that made it to the printer; `job` holds the commands it was sent
+    fn deliver<'a>(&'a self, data: &'a PrintData, job: &'a [u8]) -> Delivery<'a>;
+}
+
+/// A sink & which prints it gets; All of them unless `SINK_<NAME>_PRIORITY` (the lowest
+/// priority) or `SINK_<NAME>_SERVICES` (comma separated) are set
+struct Route {
+    name: &'static str,
+    sink: Box<dyn Sink>,
+    priority: Priority,
+    /// Services whose prints are delivered, see [`PrintData::service`]; All of them if None
+    services: Option<Vec<String>>,
+}
+
+impl Route {
+    fn from_env(name: &'static str, sink: impl Sink + 'static) -> Result<Self, String> {
+        let var = format!("SINK_{}", name.to_uppercase());
+        let priority = secrets::var(&format!("{var}_PRIORITY")).map_or(Ok(Priority::Low), |p| {
+            p.parse()
+                .map_err(|e| format!("Invalid {var}_PRIORITY! {e}"))
+        })?;
+        let services = secrets::var(&format!("{var}_SERVICES"))
+            .ok()
+            .map(|services| {
+                services
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            });
+        Ok(Self {
+            name,
+            sink: Box::new(sink),
+            priority,
+            services,
+        })
+    }
+
+    fn matches(&self, data: &PrintData) -> bool {
+        data.priority >= self.priority
+            && self.services.as_ref().is_none_or(|services| {
+                data.service
+                    .as_ref()
+                    .is_some_and(|service| services.contains(service))
+            })
+    }
+}
+
+/// Sets up the sinks configured through their env vars, replacing the previous ones
+///
+/// - `SINK_FILE_PATH`: Appends prints as plain text to a file
+/// - `SINK_SPOOL_DIR`: Writes the printer's commands to a file per print in a directory
+/// - `SINK_NTFY_TOPIC`: Publishes prints to an ntfy topic
+/// - `SINK_TELEGRAM_TOKEN`: Sends prints to a Telegram chat through a bot
+pub fn open() -> Result<(), String> {
+    let mut sinks = Vec::new();
+    if let Some(sink) = file::FileSink::from_env() {
+        sinks.push(Route::from_env("file", sink)?);
+    }
+    if let Some(sink) = spool::SpoolSink::from_env() {
+        sinks.push(Route::from_env("spool", sink)?);
+    }
+    if let Some(sink) = ntfy::NtfySink::from_env() {
+        sinks.push(Route::from_env("ntfy", sink)?);
+    }
+    if let Some(sink) = telegram::TelegramSink::from_env()? {
+        sinks.push(Route::from_env("telegram", sink)?);
+    }
+
+    if !sinks.is_empty() {
+        let names = sinks.iter().map(|route| route.name).collect::<Vec<_>>();
+        info!("Fanning prints out to {}", names.join(", "));
+    }
+    *SINKS.lock().unwrap() = sinks.into_iter().map(Arc::new).collect();
+    Ok(())
+}
+
+/// Delivers a print that made it to the printer to every sink it's routed to; In the
+/// background, so slow sinks don't hold up the printer
+pub fn fan_out(data: &PrintData, job: &[u8]) {
+    let routes = SINKS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|route| route.matches(data))
+        .cloned()
+        .collect::<Vec<_>>();
+    if routes.is_empty() {
+        return;
+    }
+
+    let span = info_span!(parent: &data.trace.0, "fan_out");
+    let data = data.clone();
+    let job = job.to_vec();
+    tokio::spawn(
+        async move {
+            for route in routes {
+                if let Err(e) = route.sink.deliver(&data, &job).await {
+                    warn!("Unable to deliver `{}` to {}: {e}", data.title, route.name);
+                }
+            }
+        }
+        .instrument(span),
+    );
+}
+
+/// Everything below the title as plain text, for sinks that aren't printers
+pub fn body(data: &PrintData) -> String {
+    let mut parts = Vec::new();
+    parts.extend(data.subtitle.clone());
+    parts.extend(data.message.as_ref().map(Message::text));
+    for qr_code in &data.qr_codes {
+        parts.push(match &qr_code.caption {
+            Some(caption) => format!("{caption}: {}", qr_code.data),
+            None => qr_code.data.clone(),
+        });
+    }
+    parts.join("\n\n")
+}
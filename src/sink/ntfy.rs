@@ -0,0 +1,68 @@
+use reqwest::Client;
+use serde_json::json;
+
+use super::{Delivery, Sink};
+use crate::{
+    http::{self, SendRetrying},
+    printer::{PrintData, Priority},
+    secrets,
+};
+
+/// Server topics are published on when `SINK_NTFY_SERVER` isn't set
+const DEFAULT_SERVER: &str = "https://ntfy.sh";
+
+/// Publishes prints to the ntfy topic `SINK_NTFY_TOPIC`, e.g. to get them on a phone too;
+/// Authenticated with `SINK_NTFY_TOKEN` if set
+pub struct NtfySink {
+    http_client: Client,
+    server: String,
+    topic: String,
+    token: Option<String>,
+}
+
+impl NtfySink {
+    pub fn from_env() -> Option<Self> {
+        let topic = secrets::var("SINK_NTFY_TOPIC").ok()?;
+        Some(Self {
+            http_client: http::client(),
+            server: secrets::var("SINK_NTFY_SERVER").unwrap_or_else(|_| DEFAULT_SERVER.to_string()),
+            topic,
+            token: secrets::var("SINK_NTFY_TOKEN").ok(),
+        })
+    }
+}
+
+impl Sink for NtfySink {
+    fn deliver<'a>(&'a self, data: &'a PrintData, _job: &'a [u8]) -> Delivery<'a> {
+        Box::pin(async move {
+            // ntfy priorities go from 1 (min) to 5 (max)
+            let priority = match data.priority {
+                Priority::Low => 2,
+                Priority::Normal => 3,
+                Priority::High => 4,
+                Priority::Urgent => 5,
+            };
+            // Tapping the notification opens the first link
+            let click = data
+                .qr_codes
+                .iter()
+                .map(|qr_code| qr_code.data.as_str())
+                .find(|data| data.starts_with("https://") || data.starts_with("http://"));
+
+            // Published as JSON, as headers can't hold every title
+            let mut req = self.http_client.post(&self.server).json(&json!({
+                "topic": self.topic,
+                "title": data.title,
+                "message": super::body(data),
+                "priority": priority,
+                "tags": data.logo.iter().collect::<Vec<_>>(),
+                "click": click,
+            }));
+            if let Some(token) = &self.token {
+                req = req.bearer_auth(token);
+            }
+            req.send_retrying().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+}
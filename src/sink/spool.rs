@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use chrono::Local;
+
+use super::{Delivery, Sink};
+use crate::{printer::PrintData, secrets};
+
+/// Writes the commands of every print to a file of its own in `SINK_SPOOL_DIR`, e.g. for a
+/// second printer behind a spooler
+pub struct SpoolSink {
+    dir: PathBuf,
+}
+
+impl SpoolSink {
+    pub fn from_env() -> Option<Self> {
+        let dir = secrets::var("SINK_SPOOL_DIR").ok()?;
+        Some(Self { dir: dir.into() })
+    }
+}
+
+impl Sink for SpoolSink {
+    fn deliver<'a>(&'a self, _data: &'a PrintData, job: &'a [u8]) -> Delivery<'a> {
+        Box::pin(async move {
+            // Named by when it was spooled, so spoolers pick them up in order
+            let name = Local::now().format("%Y%m%d-%H%M%S%.6f").to_string();
+            let path = self.dir.join(format!("{name}.escpos"));
+            // Written under another name first, so spoolers never pick up half a job
+            let tmp = self.dir.join(format!(".{name}.tmp"));
+            tokio::fs::create_dir_all(&self.dir).await?;
+            tokio::fs::write(&tmp, job).await?;
+            tokio::fs::rename(&tmp, &path).await?;
+            Ok(())
+        })
+    }
+}
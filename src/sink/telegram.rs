@@ -0,0 +1,63 @@
+use reqwest::{Client, Response};
+use serde_json::json;
+
+use super::{Delivery, Sink};
+use crate::{
+    http::{self, SendRetrying},
+    printer::{PrintData, Priority},
+    secrets,
+};
+
+/// Longest message Telegram accepts, in characters
+const MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Sends prints to the chat `SINK_TELEGRAM_CHAT_ID` through the bot `SINK_TELEGRAM_TOKEN`; Low
+/// priority ones silently
+pub struct TelegramSink {
+    http_client: Client,
+    token: String,
+    chat_id: String,
+}
+
+impl TelegramSink {
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(token) = secrets::var("SINK_TELEGRAM_TOKEN") else {
+            return Ok(None);
+        };
+        let chat_id = secrets::var("SINK_TELEGRAM_CHAT_ID")
+            .map_err(|_| "Env `SINK_TELEGRAM_CHAT_ID` not set!".to_string())?;
+        Ok(Some(Self {
+            http_client: http::client(),
+            token,
+            chat_id,
+        }))
+    }
+}
+
+impl Sink for TelegramSink {
+    fn deliver<'a>(&'a self, data: &'a PrintData, _job: &'a [u8]) -> Delivery<'a> {
+        Box::pin(async move {
+            let text = format!("{}\n\n{}", data.title, super::body(data))
+                .chars()
+                .take(MAX_MESSAGE_LENGTH)
+                .collect::<String>();
+            let res = self
+                .http_client
+                .post(format!(
+                    "https://api.telegram.org/bot{}/sendMessage",
+                    self.token
+                ))
+                .json(&json!({
+                    "chat_id": self.chat_id,
+                    "text": text,
+                    "disable_notification": data.priority == Priority::Low,
+                }))
+                .send_retrying()
+                .await;
+            // Errors name the URL, which holds the token
+            res.and_then(Response::error_for_status)
+                .map_err(reqwest::Error::without_url)?;
+            Ok(())
+        })
+    }
+}
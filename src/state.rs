@@ -0,0 +1,84 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use tracing::warn;
+
+/// Cursors, etags & tokens services pick up from after a restart, see [`get`] & [`set`]
+static STATE: Mutex<State> = Mutex::new(State {
+    path: None,
+    values: BTreeMap::new(),
+});
+
+/// Values keyed on `<service>.<key>`, persisted as a JSON file once [`open`]ed; In memory only
+/// until then
+struct State {
+    path: Option<PathBuf>,
+    values: BTreeMap<String, String>,
+}
+
+impl State {
+    /// Rewrites the whole file, through a temporary one so a crash can't leave it half written
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let tmp = path.with_extension("tmp");
+        let saved = serde_json::to_vec_pretty(&self.values)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(&tmp, json))
+            .and_then(|()| std::fs::rename(&tmp, path));
+        if let Err(e) = saved {
+            warn!("Unable to write state file {}: {e}", path.display());
+        }
+    }
+}
+
+/// Persists the state to the file at `path`, loading what was saved last run
+pub fn open(path: impl AsRef<Path>) -> std::io::Result<()> {
+    let path = path.as_ref().to_path_buf();
+    let values = match std::fs::read(&path) {
+        Ok(bytes) => match serde_json::from_slice(&bytes) {
+            Ok(values) => Some(values),
+            Err(e) => {
+                warn!("Ignoring unreadable state file {}: {e}", path.display());
+                None
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let mut state = STATE.lock().unwrap();
+    if let Some(values) = values {
+        state.values = values;
+    }
+    state.path = Some(path);
+    drop(state);
+    Ok(())
+}
+
+pub fn get(service: &str, key: &str) -> Option<String> {
+    STATE
+        .lock()
+        .unwrap()
+        .values
+        .get(&format!("{service}.{key}"))
+        .cloned()
+}
+
+/// Sets or, with None, removes a value & saves the state
+pub fn set(service: &str, key: &str, value: Option<&str>) {
+    let mut state = STATE.lock().unwrap();
+    let key = format!("{service}.{key}");
+    let changed = match value {
+        Some(value) => state.values.insert(key, value.to_string()).as_deref() != Some(value),
+        None => state.values.remove(&key).is_some(),
+    };
+    if changed {
+        state.save();
+    }
+}
@@ -0,0 +1,108 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use chrono::{DateTime, Local};
+
+use crate::{logo, printer::PrintData, profile::Profile};
+
+/// Thermal printers print 203 dpi, i.e. 8 dots per mm
+const DOTS_PER_MM: u64 = 8;
+/// Default line spacing of 1/6"
+const LINE_DOTS: u64 = 34;
+/// A QR code with its caption, roughly
+const QR_CODE_DOTS: u64 = 240;
+/// Feeds around the title & timestamp, and before the cut
+const RECEIPT_DOTS: u64 = 160;
+
+/// Prints since the last daily summary, fed by the print loop; See
+/// [`service::summary`](crate::service::summary)
+static STATS: Mutex<Stats> = Mutex::new(Stats {
+    since: None,
+    receipts: 0,
+    paper_dots: 0,
+    services: BTreeMap::new(),
+    sources: BTreeMap::new(),
+});
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// When the last summary was taken; None since startup
+    pub since: Option<DateTime<Local>>,
+    pub receipts: u32,
+    /// Estimated length of paper printed, see [`Self::paper_mm`]
+    pub paper_dots: u64,
+    /// Receipts per service, keyed on [`PrintData::service`]
+    pub services: BTreeMap<String, u32>,
+    /// Receipts per service & [`PrintData::source`]
+    pub sources: BTreeMap<(String, String), u32>,
+}
+
+impl Stats {
+    pub const fn paper_mm(&self) -> u64 {
+        self.paper_dots / DOTS_PER_MM
+    }
+
+    /// Up to `n` of the service's sources with the most receipts, most first
+    pub fn top_sources(&self, service: &str, n: usize) -> Vec<(&str, u32)> {
+        let mut sources = self
+            .sources
+            .iter()
+            .filter(|((s, _), _)| s == service)
+            .map(|((_, source), count)| (source.as_str(), *count))
+            .collect::<Vec<_>>();
+        sources.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        sources.truncate(n);
+        sources
+    }
+}
+
+/// Counts a print that made it to the printer
+pub fn printed(data: &PrintData, profile: &Profile) {
+    let service = data.service.clone().unwrap_or_else(|| "other".to_string());
+    let paper_dots = paper_dots(data, profile);
+
+    let mut stats = STATS.lock().unwrap();
+    stats.receipts += 1;
+    stats.paper_dots += paper_dots;
+    if let Some(source) = &data.source {
+        *stats
+            .sources
+            .entry((service.clone(), source.clone()))
+            .or_default() += 1;
+    }
+    *stats.services.entry(service).or_default() += 1;
+}
+
+/// Stats since the last call, starting the count over
+pub fn take() -> Stats {
+    let mut stats = STATS.lock().unwrap();
+    std::mem::replace(
+        &mut *stats,
+        Stats {
+            since: Some(Local::now()),
+            ..Default::default()
+        },
+    )
+}
+
+/// Rough length of paper the print takes, from its lines of text, images & QR codes; Templates
+/// & styles are left out
+fn paper_dots(data: &PrintData, profile: &Profile) -> u64 {
+    let lines = |text: &str| textwrap::wrap(text, profile.columns.max(1)).len() as u64;
+    let mut text_lines = lines(&data.title);
+    if let Some(subtitle) = &data.subtitle {
+        text_lines += lines(subtitle);
+    }
+    if let Some(message) = &data.message {
+        text_lines += lines(&message.text());
+    }
+
+    let logo = data.logo.as_deref().and_then(logo::get);
+    let images = logo
+        .as_deref()
+        .into_iter()
+        .chain(data.image.as_ref())
+        .map(|image| u64::from(image.printed_height(profile.dots)))
+        .sum::<u64>();
+
+    RECEIPT_DOTS + text_lines * LINE_DOTS + images + data.qr_codes.len() as u64 * QR_CODE_DOTS
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::printer::PrintData;
+
+/// How many of the latest prints are kept in [`Status::recent`]
+const RECENT_PRINTS: usize = 20;
+
+/// Health of the daemon, updated as it goes & reported by `GET /healthz`
+static STATUS: Mutex<Status> = Mutex::new(Status {
+    printer_connected: true,
+    last_print: None,
+    recent: VecDeque::new(),
+    queue_depth: 0,
+    paused: false,
+    services: BTreeMap::new(),
+});
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    /// False from a failed write until the printer is reconnected
+    pub printer_connected: bool,
+    pub last_print: Option<DateTime<Local>>,
+    /// Latest prints, newest first
+    pub recent: VecDeque<Printed>,
+    /// Prints waiting in the queue & channel
+    pub queue_depth: usize,
+    /// Whether printing is paused through the admin API
+    pub paused: bool,
+    pub services: BTreeMap<&'static str, ServiceStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Printed {
+    pub title: String,
+    pub logo: Option<String>,
+    pub printed_at: DateTime<Local>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceState {
+    Running,
+    /// Waiting to be restarted after a crash
+    Crashed,
+    /// Returned on its own, e.g. when left unconfigured
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub state: ServiceState,
+    pub restarts: u32,
+    /// Last time the service reached its API, or did its scheduled work
+    pub last_success: Option<DateTime<Local>>,
+}
+
+/// Copy of the current status
+pub fn get() -> Status {
+    STATUS.lock().unwrap().clone()
+}
+
+pub fn set_printer_connected(connected: bool) {
+    STATUS.lock().unwrap().printer_connected = connected;
+}
+
+pub fn printed(data: &PrintData) {
+    let mut status = STATUS.lock().unwrap();
+    let now = Local::now();
+    status.last_print = Some(now);
+    status.recent.push_front(Printed {
+        title: data.title.clone(),
+        logo: data.logo.clone(),
+        printed_at: now,
+    });
+    status.recent.truncate(RECENT_PRINTS);
+}
+
+pub fn set_paused(paused: bool) {
+    STATUS.lock().unwrap().paused = paused;
+}
+
+pub fn set_queue_depth(depth: usize) {
+    STATUS.lock().unwrap().queue_depth = depth;
+}
+
+pub fn set_service_state(name: &'static str, state: ServiceState) {
+    STATUS
+        .lock()
+        .unwrap()
+        .services
+        .entry(name)
+        .and_modify(|service| {
+            if service.state == ServiceState::Crashed && state == ServiceState::Running {
+                service.restarts += 1;
+            }
+            service.state = state;
+        })
+        .or_insert(ServiceStatus {
+            state,
+            restarts: 0,
+            last_success: None,
+        });
+}
+
+/// Marks a successful poll of the service named, see [`ServiceStatus::last_success`]
+pub fn service_ok(name: &'static str) {
+    if let Some(service) = STATUS.lock().unwrap().services.get_mut(name) {
+        service.last_success = Some(Local::now());
+    }
+}
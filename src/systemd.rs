@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use sd_notify::NotifyState;
+use tracing::warn;
+
+use crate::status;
+
+/// How often the status line is updated when the unit has no watchdog
+const STATUS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Tells systemd the daemon is up, for `Type=notify` units; No-op outside systemd
+pub fn ready() {
+    notify(&[NotifyState::Ready]);
+}
+
+pub fn stopping() {
+    notify(&[NotifyState::Stopping]);
+}
+
+/// How often [`keepalive`] should run; Half of the unit's `WatchdogSec`, if it has one
+pub fn keepalive_interval() -> Duration {
+    let mut usec = 0;
+    if sd_notify::watchdog_enabled(false, &mut usec) {
+        Duration::from_micros(usec) / 2
+    } else {
+        STATUS_INTERVAL
+    }
+}
+
+/// Pets the watchdog & reports queue depth & the last print as the unit's status
+pub fn keepalive() {
+    let status = status::get();
+    let last_print = status.last_print.map_or_else(
+        || "never".to_string(),
+        |t| t.format("%Y-%m-%d %H:%M").to_string(),
+    );
+    let mut line = format!("Queue: {}, last print: {last_print}", status.queue_depth);
+    if status.paused {
+        line.push_str(", paused");
+    }
+    if !status.printer_connected {
+        line.push_str(", printer disconnected");
+    }
+
+    let mut usec = 0;
+    if sd_notify::watchdog_enabled(false, &mut usec) {
+        notify(&[NotifyState::Watchdog, NotifyState::Status(&line)]);
+    } else {
+        notify(&[NotifyState::Status(&line)]);
+    }
+}
+
+fn notify(state: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, state) {
+        warn!("Unable to notify systemd: {e}");
+    }
+}
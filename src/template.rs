@@ -0,0 +1,102 @@
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tera::{Context, Tera};
+use tracing::{info, warn};
+
+use crate::printer::{Message, PrintData, TIMESTAMP_FORMAT};
+
+/// Templates in `TEMPLATE_DIR`, parsed once on first use; `None` when there are none
+static TEMPLATES: OnceLock<Option<Tera>> = OnceLock::new();
+
+/// Placeholders available to templates
+#[derive(Serialize)]
+struct Receipt<'a> {
+    /// Name of the service the receipt is from, see [`PrintData::service`]
+    service: Option<&'a str>,
+    title: &'a str,
+    subtitle: Option<&'a str>,
+    /// Message without styles; Print it with `@message` to keep them
+    message: Option<String>,
+    timestamp: String,
+    /// Whether the profile prints this receipt compactly, see
+    /// [`Profile::compact`](crate::profile::Profile::compact)
+    compact: bool,
+}
+
+/// Renders the receipt layout of a print, see [`PrintData::layout`] for its directives
+///
+/// Layouts are [Tera](https://keats.github.io/tera/docs/) templates read from
+/// `<TEMPLATE_DIR>/<service>.txt`, falling back to `<TEMPLATE_DIR>/default.txt`. `TEMPLATE_DIR`
+/// defaults to `templates`. Prints without a template use the built-in layout, which roughly is:
+///
+/// ```text
+/// @font small
+/// @center
+/// @logo
+/// @size 2 2
+/// {{ title }}
+/// @image
+/// @font normal
+/// @size 1 1
+/// @left
+/// {% if subtitle -%}
+/// {{ subtitle }}
+/// @divider
+/// {% endif %}
+/// @message
+/// @center
+/// @qr_codes
+/// @left
+///
+/// Timestamp: {{ timestamp }}
+/// ```
+pub fn render(data: &PrintData, compact: bool) -> Option<String> {
+    let templates = TEMPLATES.get_or_init(load).as_ref()?;
+    let name = [data.service.as_deref(), Some("default")]
+        .into_iter()
+        .flatten()
+        .map(|name| format!("{name}.txt"))
+        .find(|name| templates.get_template_names().any(|t| t == name))?;
+
+    let receipt = Receipt {
+        service: data.service.as_deref(),
+        title: &data.title,
+        subtitle: data.subtitle.as_deref(),
+        message: data.message.as_ref().map(Message::text),
+        timestamp: data.timestamp.format(TIMESTAMP_FORMAT).to_string(),
+        compact,
+    };
+    let context = Context::from_serialize(receipt).expect("Receipt is always a map");
+    match templates.render(&name, &context) {
+        Ok(layout) => Some(layout),
+        Err(e) => {
+            warn!("Unable to render template {name}, using the built-in layout: {e:?}");
+            None
+        }
+    }
+}
+
+fn load() -> Option<Tera> {
+    let dir = std::env::var("TEMPLATE_DIR").unwrap_or_else(|_| "templates".to_string());
+    let mut templates = match Tera::new(&format!("{dir}/*.txt")) {
+        Ok(templates) => templates,
+        Err(e) => {
+            warn!("Unable to load templates from {dir}: {e:?}");
+            return None;
+        }
+    };
+    if templates.get_template_names().next().is_none() {
+        return None;
+    }
+    templates.autoescape_on(Vec::new()); // Printed, not HTML
+    info!(
+        "Loaded receipt templates: {}",
+        templates
+            .get_template_names()
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    Some(templates)
+}
@@ -0,0 +1,116 @@
+use chrono::Local;
+use image::{DynamicImage, GrayImage, Luma};
+
+use crate::{
+    printer::{PrintData, Priority, QrCode},
+    profile::Profile,
+    raster::{Raster, MAX_IMAGE_WIDTH},
+};
+
+/// Service test pages are printed as, see [`PrintData::service`]
+const SERVICE: &str = "test-page";
+
+/// Whether the print is a test page, laid out by [`layout`] rather than a template
+pub fn is_test_page(data: &PrintData) -> bool {
+    data.service.as_deref() == Some(SERVICE)
+}
+
+/// Page showing off every font, size, style & code, to check what a new printer supports
+pub fn print_data() -> PrintData {
+    // Left to right gradient, showing off dithering
+    let gradient = GrayImage::from_fn(256, 48, |x, _| Luma([u8::try_from(x).unwrap_or(255)]));
+
+    PrintData {
+        service: Some(SERVICE.to_string()),
+        logo: Some(SERVICE.to_string()),
+        priority: Priority::Urgent,
+        title: "Test page".to_string(),
+        subtitle: None,
+        image: Some(Raster::from_image(
+            &DynamicImage::ImageLuma8(gradient),
+            MAX_IMAGE_WIDTH,
+        )),
+        message: Some(
+            [
+                "Accents: café, naïve, Ærøskøbing",
+                "Symbols: £ ¥ € ° ± ½ « »",
+                "Emoji: 👋 🖨️",
+            ]
+            .join("\n")
+            .into(),
+        ),
+        qr_codes: vec![QrCode {
+            caption: Some("QR code".to_string()),
+            data: "https://github.com/angeloanan/notifi-printer".to_string(),
+        }],
+        timestamp: Local::now(),
+        ..Default::default()
+    }
+}
+
+/// Layout of the test page, see [`PrintData::layout`]; Describes the profile it's printed with
+pub fn layout(profile: &Profile) -> String {
+    // Digits across a whole line, to check the paper width
+    let ruler = |columns: usize| -> String {
+        (1..=columns)
+            .map(|i| char::from(b'0' + u8::try_from(i % 10).unwrap_or(0)))
+            .collect()
+    };
+
+    format!(
+        "@center
+@size 2 2
+Test page
+@size 1 1
+{protocol:?}, {code_page:?}, {dots} dots
+@divider
+@left
+{ruler}
+@font small
+{small_ruler}
+@font normal
+@divider
+Left
+@center
+Center
+@right
+Right
+@left
+@divider
+@style bold
+Bold
+@style underline
+Underline
+@style invert
+Inverted
+@style
+@font small
+Small font
+@font normal
+@size 1 2
+Tall
+@size 2 1
+Wide
+@size 2 2
+Big
+@size 3 3
+Huge
+@size 1 1
+@divider
+@message
+@divider
+@center
+@image
+@feed 0
+@barcode notifi-printer
+@qr_codes
+@left
+@divider
+Cut below",
+        protocol = profile.protocol,
+        code_page = profile.code_page,
+        dots = profile.dots,
+        ruler = ruler(profile.columns),
+        small_ruler = ruler(profile.small_columns),
+    )
+}
@@ -0,0 +1,103 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::Local;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::printer::PrintData;
+
+/// Per-service rate limit, so a follow-bot wave or a busy thread doesn't spew dozens of
+/// receipts in a minute
+///
+/// Each service may print `burst` receipts at once, regaining one every `60 / per_minute`
+/// seconds. Prints over the limit are suppressed, and reported with a single receipt once the
+/// service has calmed down.
+pub struct Throttle {
+    /// Receipts regained per second
+    rate: f64,
+    burst: f64,
+    /// Buckets by service, see [`PrintData::service`]
+    buckets: BTreeMap<String, Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    suppressed: usize,
+}
+
+impl Throttle {
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            rate: f64::from(per_minute.max(1)) / 60.0,
+            burst: f64::from(burst.max(1)),
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Whether the print's service may print right now; Counts it as suppressed otherwise
+    pub fn allow(&mut self, data: &PrintData) -> bool {
+        let now = Instant::now();
+        let (rate, burst) = (self.rate, self.burst);
+        let bucket = self
+            .buckets
+            .entry(data.service.clone().unwrap_or_default())
+            .or_insert(Bucket {
+                tokens: burst,
+                updated: now,
+                suppressed: 0,
+            });
+        bucket.tokens = (bucket.tokens + (now - bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return true;
+        }
+        warn!("Rate limited, suppressing print `{}`", data.title);
+        bucket.suppressed += 1;
+        false
+    }
+
+    /// When the next report of suppressed prints is due, once a service's limit is fully regained
+    pub fn due(&self) -> Option<Instant> {
+        self.buckets
+            .values()
+            .filter(|bucket| bucket.suppressed > 0)
+            .map(|bucket| refilled(bucket, self.rate, self.burst))
+            .min()
+    }
+
+    /// Reports of suppressed prints which are due
+    pub fn take_due(&mut self) -> Vec<PrintData> {
+        let now = Instant::now();
+        let mut reports = Vec::new();
+        for (service, bucket) in &mut self.buckets {
+            if bucket.suppressed == 0 || now < refilled(bucket, self.rate, self.burst) {
+                continue;
+            }
+
+            let suppressed = std::mem::take(&mut bucket.suppressed);
+            reports.push(PrintData {
+                title: "NOTIFI-PRINTER".to_string(),
+                subtitle: Some(format!("Rate limited {service}")),
+                message: Some(
+                    format!(
+                        "{suppressed} more notification{} suppressed",
+                        if suppressed == 1 { " was" } else { "s were" }
+                    )
+                    .into(),
+                ),
+                timestamp: Local::now(),
+                ..Default::default()
+            });
+        }
+        reports
+    }
+}
+
+/// When the bucket is full again
+fn refilled(bucket: &Bucket, rate: f64, burst: f64) -> Instant {
+    let missing = (burst - bucket.tokens).max(0.0);
+    bucket.updated + Duration::from_secs_f64(missing / rate)
+}